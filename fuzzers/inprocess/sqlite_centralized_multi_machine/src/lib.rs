@@ -124,6 +124,13 @@ struct Opt {
     )]
     parent_addr: Option<String>,
 
+    #[arg(
+        long,
+        help = "Other nodes to attach to if the parent is lost, tried in order",
+        name = "FALLBACK_PARENT_ADDR"
+    )]
+    fallback_parent_addr: Vec<String>,
+
     #[arg(
         long,
         help = "The port on which the node will listen on, if children are to be expected",
@@ -284,7 +291,16 @@ pub extern "C" fn libafl_main() {
         .parent_addr
         .map(|parent_str| SocketAddr::from_str(parent_str.as_str()).expect("Wrong parent address"));
 
-    let mut node_description = NodeDescriptor::builder().parent_addr(parent_addr).build();
+    let fallback_parents: Vec<SocketAddr> = opt
+        .fallback_parent_addr
+        .iter()
+        .map(|addr_str| SocketAddr::from_str(addr_str.as_str()).expect("Wrong fallback address"))
+        .collect();
+
+    let mut node_description = NodeDescriptor::builder()
+        .parent_addr(parent_addr)
+        .fallback_parents(fallback_parents)
+        .build();
 
     if opt.node_listening_port.is_some() {
         node_description.node_listening_port = opt.node_listening_port;
@@ -1,22 +1,16 @@
 use alloc::vec::Vec;
 use core::{fmt::Debug, marker::PhantomData};
 
-#[cfg(feature = "llmp_compression")]
-use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
 use libafl_bolts::{
     llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag},
     ClientId, Error,
 };
 use serde::de::DeserializeOwned;
 
-#[cfg(feature = "llmp_compression")]
-use crate::events::COMPRESS_THRESHOLD;
 use crate::events::{BrokerEventResult, Event, _LLMP_TAG_TO_MAIN};
 
 /// An LLMP-backed event manager for scalable multi-processed fuzzing
 pub struct CentralizedLlmpHook<I> {
-    #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
     phantom: PhantomData<I>,
 }
 
@@ -34,18 +28,21 @@ where
         _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
     ) -> Result<LlmpMsgHookResult, Error> {
         if *msg_tag == _LLMP_TAG_TO_MAIN {
-            #[cfg(feature = "llmp_compression")]
-            let compressor = &self.compressor;
             #[cfg(not(feature = "llmp_compression"))]
             let event_bytes = msg;
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if *_msg_flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = compressor.decompress(msg)?;
-                &compressed
-            } else {
-                &*msg
+            let event_bytes = match _msg_flags.compression_codec() {
+                Ok(Some(codec)) => {
+                    compressed = codec.decompress(msg)?;
+                    &compressed
+                }
+                Ok(None) => &*msg,
+                Err(e) => {
+                    log::warn!("Dropping an event from {client_id:?}: {e}");
+                    return Ok(LlmpMsgHookResult::Handled);
+                }
             };
             let event: Event<I> = postcard::from_bytes(event_bytes)?;
             match Self::handle_in_broker(client_id, &event)? {
@@ -60,12 +57,7 @@ where
 
 impl<I> Debug for CentralizedLlmpHook<I> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let mut debug_struct = f.debug_struct("CentralizedLlmpHook");
-
-        #[cfg(feature = "llmp_compression")]
-        let debug_struct = debug_struct.field("compressor", &self.compressor);
-
-        debug_struct
+        f.debug_struct("CentralizedLlmpHook")
             .field("phantom", &self.phantom)
             .finish_non_exhaustive()
     }
@@ -75,8 +67,6 @@ impl<I> CentralizedLlmpHook<I> {
     /// Create an event broker from a raw broker.
     pub fn new() -> Result<Self, Error> {
         Ok(Self {
-            #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
             phantom: PhantomData,
         })
     }
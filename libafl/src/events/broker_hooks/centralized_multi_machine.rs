@@ -7,7 +7,7 @@ use std::{
 };
 
 #[cfg(feature = "llmp_compression")]
-use libafl_bolts::llmp::LLMP_FLAG_COMPRESSED;
+use libafl_bolts::compress::Compressor;
 use libafl_bolts::{
     llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag, LLMP_FLAG_FROM_MM},
     ownedref::OwnedRef,
@@ -133,8 +133,9 @@ where
     ) -> Result<(Flags, Vec<u8>), Error> {
         let serialized = postcard::to_allocvec(&event)?;
 
-        match state_lock.compressor().maybe_compress(&serialized) {
-            Some(comp_buf) => Ok((LLMP_FLAG_COMPRESSED, comp_buf)),
+        let compressor = state_lock.compressor();
+        match compressor.maybe_compress(&serialized) {
+            Some(comp_buf) => Ok((Flags::compressed_with(compressor.codec()), comp_buf)),
             None => Ok((Flags(0), serialized)),
         }
     }
@@ -242,10 +243,12 @@ where
                     MultiMachineMsg::LlmpMsg(msg) => {
                         let msg = msg.into_owned().unwrap().into_vec();
                         #[cfg(feature = "llmp_compression")]
-                        match state_wr_lock.compressor().maybe_compress(msg.as_ref()) {
+                        let compressor = state_wr_lock.compressor();
+                        #[cfg(feature = "llmp_compression")]
+                        match compressor.maybe_compress(msg.as_ref()) {
                             Some(comp_buf) => Ok((
                                 _LLMP_TAG_TO_MAIN,
-                                LLMP_FLAG_COMPRESSED | LLMP_FLAG_FROM_MM,
+                                Flags::compressed_with(compressor.codec()) | LLMP_FLAG_FROM_MM,
                                 comp_buf,
                             )),
                             None => Ok((_LLMP_TAG_TO_MAIN, LLMP_FLAG_FROM_MM, msg)),
@@ -0,0 +1,150 @@
+//! A broker hook dropping testcases that were already forwarded.
+//!
+//! Clients fuzzing the same target often find the same inputs at roughly the same time.
+//! Each of them then sends an [`Event::NewTestcase`], including the serialized observers,
+//! and the broker forwards all of them to all other clients.
+//! The [`LlmpDedupHook`] keeps the hashes of recently forwarded inputs, and drops the duplicates.
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::hash::Hash;
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    generic_hash_std,
+    llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag},
+    ClientId,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    events::{llmp::LLMP_TAG_EVENT_TO_BOTH, Event},
+    Error,
+};
+
+/// A broker hook that drops [`Event::NewTestcase`] events for inputs it forwarded before.
+///
+/// It remembers the hashes of the last `capacity` inputs.
+/// Add it after the hook that updates the monitor, so that the duplicates still count
+/// towards the corpus sizes of their clients.
+///
+/// The input type is only needed to create the hook, so the event managers taking an optional
+/// [`LlmpDedupHook`] only require their inputs to be [`Hash`] when deduplicating.
+/// The default hook forwards all testcases.
+#[derive(Debug, Clone)]
+pub struct LlmpDedupHook {
+    capacity: usize,
+    seen: HashSet<u64>,
+    /// The hashes in `seen`, oldest first
+    order: VecDeque<u64>,
+    /// Hashes the input of a serialized [`Event::NewTestcase`], `None` for other events
+    input_hash: fn(&[u8]) -> Result<Option<u64>, Error>,
+}
+
+impl Default for LlmpDedupHook {
+    fn default() -> Self {
+        Self::new::<()>(0)
+    }
+}
+
+impl LlmpDedupHook {
+    /// Creates a new [`LlmpDedupHook`] for events of inputs of type `I`,
+    /// remembering the last `capacity` inputs.
+    /// A `capacity` of `0` disables the deduplication.
+    #[must_use]
+    pub fn new<I>(capacity: usize) -> Self
+    where
+        I: DeserializeOwned + Hash,
+    {
+        Self {
+            capacity,
+            seen: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            input_hash: input_hash::<I>,
+        }
+    }
+
+    /// Remembers `hash`, and returns `true` if it was seen before
+    fn check_and_insert(&mut self, hash: u64) -> bool {
+        if !self.seen.insert(hash) {
+            return true;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(hash);
+        false
+    }
+}
+
+/// The hash of the input of a serialized [`Event::NewTestcase`]
+fn input_hash<I>(event_bytes: &[u8]) -> Result<Option<u64>, Error>
+where
+    I: DeserializeOwned + Hash,
+{
+    let event: Event<I> = postcard::from_bytes(event_bytes)?;
+    Ok(match &event {
+        Event::NewTestcase { input, .. } => Some(generic_hash_std(input)),
+        _ => None,
+    })
+}
+
+impl<SHM, SP> LlmpHook<SHM, SP> for LlmpDedupHook {
+    fn on_new_message(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SHM, SP>,
+        _client_id: ClientId,
+        msg_tag: &mut Tag,
+        #[cfg(feature = "llmp_compression")] msg_flags: &mut Flags,
+        #[cfg(not(feature = "llmp_compression"))] _msg_flags: &mut Flags,
+        msg: &mut [u8],
+        _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+    ) -> Result<LlmpMsgHookResult, Error> {
+        if self.capacity == 0 || *msg_tag != LLMP_TAG_EVENT_TO_BOTH {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        }
+
+        #[cfg(not(feature = "llmp_compression"))]
+        let event_bytes = msg;
+        #[cfg(feature = "llmp_compression")]
+        let compressed;
+        #[cfg(feature = "llmp_compression")]
+        let event_bytes = match msg_flags.compression_codec() {
+            Ok(Some(codec)) => {
+                compressed = codec.decompress(msg)?;
+                &compressed
+            }
+            Ok(None) => &*msg,
+            Err(e) => {
+                log::warn!("Dropping an event: {e}");
+                return Ok(LlmpMsgHookResult::Handled);
+            }
+        };
+
+        if let Some(hash) = (self.input_hash)(event_bytes)? {
+            if self.check_and_insert(hash) {
+                log::debug!("Dropping a duplicate testcase in the broker");
+                return Ok(LlmpMsgHookResult::Handled);
+            }
+        }
+        Ok(LlmpMsgHookResult::ForwardToClients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::LlmpDedupHook;
+
+    #[test]
+    fn test_dedup_eviction() {
+        let mut hook = LlmpDedupHook::new::<()>(2);
+        assert!(!hook.check_and_insert(1));
+        assert!(hook.check_and_insert(1));
+        assert!(!hook.check_and_insert(2));
+        assert!(!hook.check_and_insert(3));
+        // 1 was evicted by 3
+        assert!(!hook.check_and_insert(1));
+        assert!(hook.check_and_insert(3));
+    }
+}
@@ -2,16 +2,12 @@
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-#[cfg(feature = "llmp_compression")]
-use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
 use libafl_bolts::{
     llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag},
     ClientId,
 };
 use serde::de::DeserializeOwned;
 
-#[cfg(feature = "llmp_compression")]
-use crate::events::llmp::COMPRESS_THRESHOLD;
 use crate::{
     events::{llmp::LLMP_TAG_EVENT_TO_BOTH, BrokerEventResult, Event},
     monitors::{stats::ClientStatsManager, Monitor},
@@ -24,6 +20,16 @@ pub mod centralized;
 #[cfg(all(unix, feature = "std"))]
 pub use centralized::*;
 
+/// Record and replay the broker traffic
+#[cfg(feature = "std")]
+pub mod record;
+#[cfg(feature = "std")]
+pub use record::*;
+
+/// Drop duplicate testcases in the broker
+pub mod dedup;
+pub use dedup::*;
+
 /// Multi-machine hook
 #[cfg(all(unix, feature = "multi_machine"))]
 pub mod centralized_multi_machine;
@@ -34,8 +40,6 @@ pub use centralized_multi_machine::*;
 #[derive(Debug)]
 pub struct StdLlmpEventHook<I, MT> {
     monitor: MT,
-    #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
     phantom: PhantomData<I>,
     client_stats_manager: ClientStatsManager,
 }
@@ -56,8 +60,6 @@ where
         _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
     ) -> Result<LlmpMsgHookResult, Error> {
         let monitor = &mut self.monitor;
-        #[cfg(feature = "llmp_compression")]
-        let compressor = &self.compressor;
 
         if *msg_tag == LLMP_TAG_EVENT_TO_BOTH {
             #[cfg(not(feature = "llmp_compression"))]
@@ -65,11 +67,16 @@ where
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if *msg_flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = compressor.decompress(msg)?;
-                &compressed
-            } else {
-                &*msg
+            let event_bytes = match msg_flags.compression_codec() {
+                Ok(Some(codec)) => {
+                    compressed = codec.decompress(msg)?;
+                    &compressed
+                }
+                Ok(None) => &*msg,
+                Err(e) => {
+                    log::warn!("Dropping an event from {client_id:?}: {e}");
+                    return Ok(LlmpMsgHookResult::Handled);
+                }
             };
             let event: Event<I> = postcard::from_bytes(event_bytes)?;
             match Self::handle_in_broker(
@@ -104,8 +111,6 @@ where
     pub fn new(monitor: MT) -> Result<Self, Error> {
         Ok(Self {
             monitor,
-            #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
             client_stats_manager: ClientStatsManager::default(),
             phantom: PhantomData,
         })
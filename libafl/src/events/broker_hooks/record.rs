@@ -0,0 +1,330 @@
+//! Record the traffic of an [`LlmpBroker`], and replay it later.
+//!
+//! The [`LlmpRecordHook`] writes every message passing through the broker to a compact log.
+//! A [`LlmpRecordReader`] reads the log back, to feed it into a fresh broker with
+//! [`replay_into_broker`], or into an [`crate::events::LlmpEventConverter`] with
+//! [`crate::events::LlmpEventConverter::replay`].
+//! This makes distributed issues, such as lost objectives or duplicate testcases, reproducible.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+use libafl_bolts::{
+    current_time,
+    llmp::{Flags, LlmpBroker, LlmpBrokerInner, LlmpHook, LlmpHookTuple, LlmpMsgHookResult, Tag},
+    shmem::{ShMem, ShMemProvider},
+    ClientId, Error,
+};
+
+/// The magic at the start of each recording, including the format version
+const LLMP_RECORD_MAGIC: [u8; 8] = *b"LLMPREC1";
+
+/// A message recorded by the [`LlmpRecordHook`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlmpRecord {
+    /// When the broker received the message
+    pub time: Duration,
+    /// The client that sent the message
+    pub client_id: ClientId,
+    /// The tag of the message
+    pub tag: Tag,
+    /// The flags of the message, for example if it is compressed
+    pub flags: Flags,
+    /// The raw payload
+    pub payload: Vec<u8>,
+}
+
+/// A broker hook recording all messages it sees.
+///
+/// Add it as the first hook, to record the messages before other hooks modify them.
+/// It never filters messages.
+/// Each message is flushed once recorded, so a killed broker leaves a complete recording.
+#[derive(Debug)]
+pub struct LlmpRecordHook<W>
+where
+    W: Write,
+{
+    writer: W,
+}
+
+impl LlmpRecordHook<BufWriter<File>> {
+    /// Records to a new file at `path`
+    pub fn to_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W> LlmpRecordHook<W>
+where
+    W: Write,
+{
+    /// Records to `writer`
+    pub fn new(mut writer: W) -> Result<Self, Error> {
+        writer.write_all(&LLMP_RECORD_MAGIC)?;
+        Ok(Self { writer })
+    }
+
+    /// Writes a single message to the recording, and flushes it
+    pub fn record(&mut self, record: &LlmpRecord) -> Result<(), Error> {
+        let payload_len = u32::try_from(record.payload.len())
+            .map_err(|_| Error::illegal_argument("Message too large to record"))?;
+        self.writer
+            .write_all(&record.time.as_secs().to_le_bytes())?;
+        self.writer
+            .write_all(&record.time.subsec_nanos().to_le_bytes())?;
+        self.writer.write_all(&record.client_id.0.to_le_bytes())?;
+        self.writer.write_all(&record.tag.0.to_le_bytes())?;
+        self.writer.write_all(&record.flags.0.to_le_bytes())?;
+        self.writer.write_all(&payload_len.to_le_bytes())?;
+        self.writer.write_all(&record.payload)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W, SHM, SP> LlmpHook<SHM, SP> for LlmpRecordHook<W>
+where
+    W: Write,
+{
+    fn on_new_message(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SHM, SP>,
+        client_id: ClientId,
+        msg_tag: &mut Tag,
+        msg_flags: &mut Flags,
+        msg: &mut [u8],
+        _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+    ) -> Result<LlmpMsgHookResult, Error> {
+        self.record(&LlmpRecord {
+            time: current_time(),
+            client_id,
+            tag: *msg_tag,
+            flags: *msg_flags,
+            payload: msg.to_vec(),
+        })?;
+        Ok(LlmpMsgHookResult::ForwardToClients)
+    }
+}
+
+/// Reads the messages recorded by a [`LlmpRecordHook`], in order
+#[derive(Debug)]
+pub struct LlmpRecordReader<R>
+where
+    R: Read,
+{
+    reader: R,
+}
+
+impl LlmpRecordReader<BufReader<File>> {
+    /// Reads the recording at `path`
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R> LlmpRecordReader<R>
+where
+    R: Read,
+{
+    /// Reads a recording from `reader`
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; LLMP_RECORD_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != LLMP_RECORD_MAGIC {
+            return Err(Error::illegal_argument("Not an llmp recording"));
+        }
+        Ok(Self { reader })
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Reads the next message, `None` at the end of the recording
+    pub fn next_record(&mut self) -> Result<Option<LlmpRecord>, Error> {
+        let mut secs = [0; 8];
+        match self.reader.read_exact(&mut secs) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let nanos = self.read_u32()?;
+        let client_id = ClientId(self.read_u32()?);
+        let tag = Tag(self.read_u32()?);
+        let flags = Flags(self.read_u32()?);
+        // Don't trust the length for the allocation, a truncated or corrupt recording may lie
+        let payload_len = self.read_u32()?;
+        let mut payload = Vec::new();
+        (&mut self.reader)
+            .take(payload_len.into())
+            .read_to_end(&mut payload)?;
+        if payload.len() != payload_len as usize {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(Some(LlmpRecord {
+            time: Duration::new(u64::from_le_bytes(secs), nanos),
+            client_id,
+            tag,
+            flags,
+            payload,
+        }))
+    }
+}
+
+impl<R> Iterator for LlmpRecordReader<R>
+where
+    R: Read,
+{
+    type Item = Result<LlmpRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Feeds recorded messages into `broker`, as if its clients had sent them, in order.
+///
+/// The messages go through the hooks of the broker, and are broadcast to its clients if the hooks
+/// let them through. Returns the number of replayed messages.
+pub fn replay_into_broker<HT, SHM, SP, I>(
+    broker: &mut LlmpBroker<HT, SHM, SP>,
+    records: I,
+) -> Result<usize, Error>
+where
+    HT: LlmpHookTuple<SHM, SP>,
+    SHM: ShMem,
+    SP: ShMemProvider<ShMem = SHM>,
+    I: IntoIterator<Item = Result<LlmpRecord, Error>>,
+{
+    let mut count = 0;
+    for record in records {
+        let record = record?;
+        broker.inject_msg(record.client_id, record.tag, record.flags, &record.payload)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use libafl_bolts::{
+        llmp::{Flags, LlmpBroker, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag},
+        shmem::{ShMemProvider, StdShMemProvider},
+        tuples::tuple_list,
+        ClientId, Error,
+    };
+
+    use crate::events::broker_hooks::record::{
+        replay_into_broker, LlmpRecord, LlmpRecordHook, LlmpRecordReader,
+    };
+
+    /// Remembers the messages it sees
+    #[derive(Debug, Default)]
+    struct SeenHook {
+        seen: Vec<(ClientId, Tag, Vec<u8>)>,
+    }
+
+    impl<SHM, SP> LlmpHook<SHM, SP> for SeenHook {
+        fn on_new_message(
+            &mut self,
+            _broker_inner: &mut LlmpBrokerInner<SHM, SP>,
+            client_id: ClientId,
+            msg_tag: &mut Tag,
+            _msg_flags: &mut Flags,
+            msg: &mut [u8],
+            _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+        ) -> Result<LlmpMsgHookResult, Error> {
+            self.seen.push((client_id, *msg_tag, msg.to_vec()));
+            Ok(LlmpMsgHookResult::ForwardToClients)
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_llmp_record_replay() {
+        let dir =
+            std::env::temp_dir().join(format!("libafl_test_llmp_record_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("recording");
+
+        let mut broker = LlmpBroker::new(
+            StdShMemProvider::new().unwrap(),
+            tuple_list!(LlmpRecordHook::to_file(&path).unwrap()),
+        )
+        .unwrap();
+        broker
+            .inject_msg(ClientId(1), Tag(0x1337), Flags(0), b"first")
+            .unwrap();
+        broker
+            .inject_msg(ClientId(2), Tag(0x1338), Flags(1), b"second")
+            .unwrap();
+        drop(broker);
+
+        let records: Vec<_> = LlmpRecordReader::from_file(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].client_id, ClientId(2));
+        assert_eq!(records[1].tag, Tag(0x1338));
+        assert_eq!(records[1].flags, Flags(1));
+        assert_eq!(records[1].payload, b"second");
+        assert!(records[0].time <= records[1].time);
+
+        let mut broker = LlmpBroker::new(
+            StdShMemProvider::new().unwrap(),
+            tuple_list!(SeenHook::default()),
+        )
+        .unwrap();
+        let replayed =
+            replay_into_broker(&mut broker, LlmpRecordReader::from_file(&path).unwrap()).unwrap();
+        assert_eq!(replayed, 2);
+        assert_eq!(
+            broker.hooks().0.seen,
+            [
+                (ClientId(1), Tag(0x1337), b"first".to_vec()),
+                (ClientId(2), Tag(0x1338), b"second".to_vec())
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_llmp_record_truncated() {
+        let mut recording = Vec::new();
+        LlmpRecordHook::new(&mut recording)
+            .unwrap()
+            .record(&LlmpRecord {
+                time: Duration::from_secs(1),
+                client_id: ClientId(1),
+                tag: Tag(0x1337),
+                flags: Flags(0),
+                payload: b"payload".to_vec(),
+            })
+            .unwrap();
+
+        // The length of the payload claims 4 GiB
+        let len_offset = recording.len() - b"payload".len() - 4;
+        recording[len_offset..len_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = LlmpRecordReader::new(recording.as_slice()).unwrap();
+        assert!(reader.next_record().is_err());
+    }
+}
@@ -13,8 +13,8 @@ use std::process;
 
 #[cfg(feature = "llmp_compression")]
 use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
+    compress::{CodecCompressor, CompressionCodec, Compressor},
+    llmp::{Flags, LLMP_FLAG_INITIALIZED},
 };
 use libafl_bolts::{
     llmp::{LlmpClient, LlmpClientDescription, Tag},
@@ -50,7 +50,7 @@ pub struct CentralizedEventManager<EM, I, S, SHM, SP> {
     /// The centralized LLMP client for inter process communication
     client: LlmpClient<SHM, SP>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CodecCompressor,
     time_ref: Option<Handle<TimeObserver>>,
     is_main: bool,
     phantom: PhantomData<(I, S)>,
@@ -68,6 +68,8 @@ impl CentralizedEventManager<(), (), (), (), ()> {
 #[derive(Debug)]
 pub struct CentralizedEventManagerBuilder {
     is_main: bool,
+    #[cfg(feature = "llmp_compression")]
+    compression_codec: CompressionCodec,
 }
 
 impl Default for CentralizedEventManagerBuilder {
@@ -80,13 +82,28 @@ impl CentralizedEventManagerBuilder {
     /// The constructor
     #[must_use]
     pub fn new() -> Self {
-        Self { is_main: false }
+        Self {
+            is_main: false,
+            #[cfg(feature = "llmp_compression")]
+            compression_codec: CompressionCodec::default(),
+        }
     }
 
     /// Make this a main evaluator node
     #[must_use]
     pub fn is_main(self, is_main: bool) -> Self {
-        Self { is_main }
+        Self { is_main, ..self }
+    }
+
+    /// Sets the codec used to compress outgoing events, if the broker agreed to it when attaching,
+    /// else gzip is used. Incoming events are decompressed with whatever codec their sender used.
+    #[must_use]
+    #[cfg(feature = "llmp_compression")]
+    pub fn compression_codec(self, compression_codec: CompressionCodec) -> Self {
+        Self {
+            compression_codec,
+            ..self
+        }
     }
 
     /// Creates a new [`CentralizedEventManager`].
@@ -101,9 +118,14 @@ impl CentralizedEventManagerBuilder {
     {
         Ok(CentralizedEventManager {
             inner,
-            client,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CodecCompressor::with_threshold(
+                client
+                    .compression_codecs()
+                    .pick_codec(self.compression_codec),
+                COMPRESS_THRESHOLD,
+            ),
+            client,
             time_ref: time_obs,
             is_main: self.is_main,
             phantom: PhantomData,
@@ -416,7 +438,7 @@ where
             Some(comp_buf) => {
                 self.client.send_buf_with_flags(
                     _LLMP_TAG_TO_MAIN,
-                    flags | LLMP_FLAG_COMPRESSED,
+                    flags | Flags::compressed_with(self.compressor.codec()),
                     &comp_buf,
                 )?;
             }
@@ -451,11 +473,16 @@ where
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
-                &compressed
-            } else {
-                msg
+            let event_bytes = match _flags.compression_codec() {
+                Ok(Some(codec)) => {
+                    compressed = codec.decompress(msg)?;
+                    &compressed
+                }
+                Ok(None) => msg,
+                Err(e) => {
+                    log::warn!("Skipping an event from {client_id:?}: {e}");
+                    continue;
+                }
             };
             let event: Event<I> = postcard::from_bytes(event_bytes)?;
             log::debug!("Processor received message {}", event.name_detailed());
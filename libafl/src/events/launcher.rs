@@ -53,12 +53,16 @@ use crate::events::multi_machine::{NodeDescriptor, TcpMultiMachineHooks};
 use crate::{
     events::{
         llmp::{LlmpRestartingEventManager, LlmpShouldSaveState, ManagerKind, RestartingMgr},
-        EventConfig, EventManagerHooksTuple,
+        EventConfig, EventManagerHooksTuple, LlmpDedupHook,
     },
     monitors::Monitor,
     observers::TimeObserver,
     Error,
 };
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::compress::CompressionCodec;
+#[cfg(feature = "llmp_encryption")]
+use libafl_bolts::secure_link::LinkKey;
 
 /// The (internal) `env` that indicates we're running as client.
 const _AFL_LAUNCHER_CLIENT: &str = "AFL_LAUNCHER_CLIENT";
@@ -171,6 +175,19 @@ pub struct Launcher<'a, CF, MT, SP> {
     /// clusters.
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The pre-shared key authenticating and encrypting the link to and from other brokers.
+    /// If set, brokers without the key can neither connect to us, nor can we connect to them.
+    #[cfg(feature = "llmp_encryption")]
+    #[builder(default = None)]
+    link_key: Option<LinkKey>,
+    /// The codec the clients use to compress their events
+    #[cfg(feature = "llmp_compression")]
+    #[builder(default)]
+    compression_codec: CompressionCodec,
+    /// The hook of the broker dropping duplicate testcases found by several clients.
+    /// The default hook forwards all testcases.
+    #[builder(default)]
+    dedup_hook: LlmpDedupHook,
     /// The time observer for addaptive serialization
     #[builder(default = None)]
     time_ref: Option<Handle<TimeObserver>>,
@@ -322,6 +339,8 @@ where
                                 .serialize_state(self.serialize_state)
                                 .hooks(hooks);
                             let builder = builder.time_ref(self.time_ref.clone());
+                            #[cfg(feature = "llmp_compression")]
+                            let builder = builder.compression_codec(self.compression_codec);
                             let (state, mgr) = builder.build().launch()?;
 
                             return (self.run_client.take().unwrap())(
@@ -345,12 +364,15 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .dedup_hook(self.dedup_hook.clone())
                 .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
                 .hooks(hooks);
 
             let builder = builder.time_ref(self.time_ref.clone());
+            #[cfg(feature = "llmp_encryption")]
+            let builder = builder.link_key(self.link_key.clone());
 
             builder.build().launch()?;
 
@@ -412,6 +434,8 @@ where
                     .hooks(hooks);
 
                 let builder = builder.time_ref(self.time_ref.clone());
+                #[cfg(feature = "llmp_compression")]
+                let builder = builder.compression_codec(self.compression_codec);
 
                 let (state, mgr) = builder.build().launch()?;
 
@@ -508,12 +532,15 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .dedup_hook(self.dedup_hook.clone())
                 .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
                 .hooks(hooks);
 
             let builder = builder.time_ref(self.time_ref.clone());
+            #[cfg(feature = "llmp_encryption")]
+            let builder = builder.link_key(self.link_key.clone());
 
             builder.build().launch()?;
 
@@ -590,6 +617,15 @@ pub struct CentralizedLauncher<'a, CF, MF, MT, SP> {
     /// clusters.
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The pre-shared key authenticating and encrypting the link to and from other brokers.
+    /// If set, brokers without the key can neither connect to us, nor can we connect to them.
+    #[cfg(feature = "llmp_encryption")]
+    #[builder(default = None)]
+    link_key: Option<LinkKey>,
+    /// The codec the clients use to compress their events
+    #[cfg(feature = "llmp_compression")]
+    #[builder(default)]
+    compression_codec: CompressionCodec,
     #[cfg(feature = "multi_machine")]
     multi_machine_node_descriptor: NodeDescriptor<SocketAddr>,
     /// If this launcher should spawn a new `broker` on `[Self::broker_port]` (default).
@@ -668,6 +704,8 @@ where
                     .hooks(tuple_list!());
 
                 let builder = builder.time_ref(centralized_launcher.time_obs.clone());
+                #[cfg(feature = "llmp_compression")]
+                let builder = builder.compression_codec(centralized_launcher.compression_codec);
 
                 builder.build().launch()
             };
@@ -782,6 +820,12 @@ where
                                     CentralizedEventManager::builder();
                                 centralized_event_manager_builder =
                                     centralized_event_manager_builder.is_main(true);
+                                #[cfg(feature = "llmp_compression")]
+                                {
+                                    centralized_event_manager_builder =
+                                        centralized_event_manager_builder
+                                            .compression_codec(self.compression_codec);
+                                }
 
                                 let c_mgr = centralized_event_manager_builder.build_on_port(
                                     mgr,
@@ -809,6 +853,9 @@ where
                                 )?;
 
                                 let centralized_builder = CentralizedEventManager::builder();
+                                #[cfg(feature = "llmp_compression")]
+                                let centralized_builder =
+                                    centralized_builder.compression_codec(self.compression_codec);
 
                                 let c_mgr = centralized_builder.build_on_port(
                                     mgr,
@@ -895,6 +942,11 @@ where
                 self.broker_port,
             )?;
 
+            #[cfg(feature = "llmp_encryption")]
+            if let Some(link_key) = &self.link_key {
+                broker.inner_mut().set_link_key(link_key.clone());
+            }
+
             if let Some(remote_broker_addr) = self.remote_broker_addr {
                 log::info!("B2b: Connecting to {:?}", &remote_broker_addr);
                 broker.inner_mut().connect_b2b(remote_broker_addr)?;
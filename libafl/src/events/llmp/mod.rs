@@ -4,8 +4,8 @@ use core::{fmt::Debug, marker::PhantomData, time::Duration};
 
 #[cfg(feature = "llmp_compression")]
 use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
+    compress::{CodecCompressor, CompressionCodec, Compressor},
+    llmp::{Flags, LLMP_FLAG_INITIALIZED},
 };
 use libafl_bolts::{
     llmp::{LlmpClient, LlmpClientDescription, Tag},
@@ -14,6 +14,8 @@ use libafl_bolts::{
 };
 use serde::{de::DeserializeOwned, Serialize};
 
+#[cfg(feature = "std")]
+use crate::events::LlmpRecord;
 use crate::{
     events::{Event, EventFirer},
     fuzzer::EvaluatorObservers,
@@ -83,7 +85,7 @@ pub struct LlmpEventConverter<I, IC, ICB, S, SHM, SP> {
     llmp: LlmpClient<SHM, SP>,
     last_sent: Duration,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CodecCompressor,
     converter: Option<IC>,
     converter_back: Option<ICB>,
     phantom: PhantomData<(I, S)>,
@@ -110,13 +112,19 @@ impl
 #[derive(Debug, Clone, Default)]
 pub struct LlmpEventConverterBuilder {
     throttle: Option<Duration>,
+    #[cfg(feature = "llmp_compression")]
+    compression_codec: CompressionCodec,
 }
 
 impl LlmpEventConverterBuilder {
     #[must_use]
     /// Constructor
     pub fn new() -> Self {
-        Self { throttle: None }
+        Self {
+            throttle: None,
+            #[cfg(feature = "llmp_compression")]
+            compression_codec: CompressionCodec::default(),
+        }
     }
 
     #[must_use]
@@ -124,6 +132,18 @@ impl LlmpEventConverterBuilder {
     pub fn throttle(self, throttle: Duration) -> Self {
         Self {
             throttle: Some(throttle),
+            ..self
+        }
+    }
+
+    #[must_use]
+    /// Sets the codec used to compress outgoing events, if the broker agreed to it when attaching,
+    /// else gzip is used. Incoming events are decompressed with whatever codec their sender used.
+    #[cfg(feature = "llmp_compression")]
+    pub fn compression_codec(self, compression_codec: CompressionCodec) -> Self {
+        Self {
+            compression_codec,
+            ..self
         }
     }
 
@@ -137,9 +157,12 @@ impl LlmpEventConverterBuilder {
         Ok(LlmpEventConverter {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
-            llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CodecCompressor::with_threshold(
+                llmp.compression_codecs().pick_codec(self.compression_codec),
+                COMPRESS_THRESHOLD,
+            ),
+            llmp,
             converter,
             converter_back,
             phantom: PhantomData,
@@ -163,9 +186,12 @@ impl LlmpEventConverterBuilder {
         Ok(LlmpEventConverter {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
-            llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CodecCompressor::with_threshold(
+                llmp.compression_codecs().pick_codec(self.compression_codec),
+                COMPRESS_THRESHOLD,
+            ),
+            llmp,
             converter,
             converter_back,
             phantom: PhantomData,
@@ -189,9 +215,12 @@ impl LlmpEventConverterBuilder {
         Ok(LlmpEventConverter {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
-            llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CodecCompressor::with_threshold(
+                llmp.compression_codecs().pick_codec(self.compression_codec),
+                COMPRESS_THRESHOLD,
+            ),
+            llmp,
             converter,
             converter_back,
             phantom: PhantomData,
@@ -346,11 +375,16 @@ where
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
-                &compressed
-            } else {
-                msg
+            let event_bytes = match _flags.compression_codec() {
+                Ok(Some(codec)) => {
+                    compressed = codec.decompress(msg)?;
+                    &compressed
+                }
+                Ok(None) => msg,
+                Err(e) => {
+                    log::warn!("Skipping an event from {client_id:?}: {e}");
+                    continue;
+                }
             };
 
             let event: Event<DI> = postcard::from_bytes(event_bytes)?;
@@ -360,6 +394,57 @@ where
         }
         Ok(count)
     }
+
+    /// Handle recorded events, see [`crate::events::LlmpRecordHook`], as if they arrived in
+    /// the client, in order.
+    ///
+    /// Messages meant for the broker only are skipped. Returns the number of handled events.
+    #[cfg(feature = "std")]
+    pub fn replay<DI, E, EM, R, Z>(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        executor: &mut E,
+        manager: &mut EM,
+        records: R,
+    ) -> Result<usize, Error>
+    where
+        ICB: InputConverter<To = I, From = DI>,
+        DI: DeserializeOwned + Input,
+        R: IntoIterator<Item = Result<LlmpRecord, Error>>,
+        S: HasCurrentTestcase<I> + HasSolutions<I>,
+        Z: EvaluatorObservers<E, EM, I, S>,
+    {
+        let mut count = 0;
+        for record in records {
+            let record = record?;
+            if record.tag == _LLMP_TAG_EVENT_TO_BROKER {
+                continue;
+            }
+            #[cfg(not(feature = "llmp_compression"))]
+            let event_bytes = &record.payload;
+            #[cfg(feature = "llmp_compression")]
+            let compressed;
+            #[cfg(feature = "llmp_compression")]
+            let event_bytes = match record.flags.compression_codec() {
+                Ok(Some(codec)) => {
+                    compressed = codec.decompress(&record.payload)?;
+                    &compressed
+                }
+                Ok(None) => &record.payload,
+                Err(e) => {
+                    log::warn!("Skipping a recorded event: {e}");
+                    continue;
+                }
+            };
+
+            let event: Event<DI> = postcard::from_bytes(event_bytes)?;
+            log::debug!("Replaying message {}", event.name_detailed());
+            self.handle_in_client(fuzzer, executor, state, manager, record.client_id, event)?;
+            count += 1;
+        }
+        Ok(count)
+    }
 }
 
 impl<I, IC, ICB, S, SHM, SP> EventFirer<I, S> for LlmpEventConverter<I, IC, ICB, S, SHM, SP>
@@ -417,7 +502,7 @@ where
             Some(comp_buf) => {
                 self.llmp.send_buf_with_flags(
                     LLMP_TAG_EVENT_TO_BOTH,
-                    flags | LLMP_FLAG_COMPRESSED,
+                    flags | Flags::compressed_with(self.compressor.codec()),
                     &comp_buf,
                 )?;
             }
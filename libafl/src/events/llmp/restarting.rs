@@ -8,6 +8,7 @@ use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::{
     marker::PhantomData,
+    mem,
     num::NonZeroUsize,
     sync::atomic::{compiler_fence, Ordering},
     time::Duration,
@@ -22,10 +23,12 @@ use libafl_bolts::os::startable_self;
 use libafl_bolts::os::unix_signals::setup_signal_handler;
 #[cfg(all(feature = "fork", unix))]
 use libafl_bolts::os::{fork, ForkResult};
+#[cfg(feature = "llmp_encryption")]
+use libafl_bolts::secure_link::LinkKey;
 #[cfg(feature = "llmp_compression")]
 use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
+    compress::{CodecCompressor, CompressionCodec, Compressor},
+    llmp::{Flags, LLMP_FLAG_INITIALIZED},
 };
 use libafl_bolts::{
     core_affinity::CoreId,
@@ -56,9 +59,9 @@ use crate::{
         launcher::ClientDescription, serialize_observers_adaptive, std_maybe_report_progress,
         std_report_progress, AdaptiveSerializer, AwaitRestartSafe, CanSerializeObserver, Event,
         EventConfig, EventFirer, EventManagerHooksTuple, EventManagerId, EventReceiver,
-        EventRestarter, HasEventManagerId, LlmpShouldSaveState, ProgressReporter,
-        RecordSerializationTime, SendExiting, StdLlmpEventHook, LLMP_TAG_EVENT_TO_BOTH,
-        _LLMP_TAG_EVENT_TO_BROKER,
+        EventRestarter, HasEventManagerId, LlmpDedupHook, LlmpShouldSaveState, ProgressReporter,
+        RecordSerializationTime, SendExiting, StdLlmpEventHook, _LLMP_TAG_EVENT_TO_BROKER,
+        LLMP_TAG_EVENT_TO_BOTH,
     },
     inputs::Input,
     monitors::Monitor,
@@ -82,7 +85,7 @@ pub struct LlmpRestartingEventManager<EMH, I, S, SHM, SP> {
     /// The LLMP client for inter process communication
     llmp: LlmpClient<SHM, SP>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CodecCompressor,
     /// The configuration defines this specific fuzzer.
     /// A node will not re-use the observer values sent over LLMP
     /// from nodes with other configurations.
@@ -199,7 +202,7 @@ where
                 Some(comp_buf) => {
                     self.llmp.send_buf_with_flags(
                         LLMP_TAG_EVENT_TO_BOTH,
-                        flags | LLMP_FLAG_COMPRESSED,
+                        flags | Flags::compressed_with(self.compressor.codec()),
                         &comp_buf,
                     )?;
                 }
@@ -336,11 +339,16 @@ where
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
-                &compressed
-            } else {
-                msg
+            let event_bytes = match flags.compression_codec() {
+                Ok(Some(codec)) => {
+                    compressed = codec.decompress(msg)?;
+                    &compressed
+                }
+                Ok(None) => msg,
+                Err(e) => {
+                    log::warn!("Skipping an event from {client_id:?}: {e}");
+                    continue;
+                }
             };
 
             let event: Event<I> = postcard::from_bytes(event_bytes)?;
@@ -422,6 +430,8 @@ const _ENV_FUZZER_BROKER_CLIENT_INITIAL: &str = "_AFL_ENV_FUZZER_BROKER_CLIENT";
 pub struct LlmpEventManagerBuilder<EMH> {
     throttle: Option<Duration>,
     save_state: LlmpShouldSaveState,
+    #[cfg(feature = "llmp_compression")]
+    compression_codec: CompressionCodec,
     hooks: EMH,
 }
 
@@ -438,6 +448,8 @@ impl LlmpEventManagerBuilder<()> {
         Self {
             throttle: None,
             save_state: LlmpShouldSaveState::OnRestart,
+            #[cfg(feature = "llmp_compression")]
+            compression_codec: CompressionCodec::default(),
             hooks: (),
         }
     }
@@ -449,6 +461,8 @@ impl LlmpEventManagerBuilder<()> {
         LlmpEventManagerBuilder {
             throttle: self.throttle,
             save_state: self.save_state,
+            #[cfg(feature = "llmp_compression")]
+            compression_codec: self.compression_codec,
             hooks,
         }
     }
@@ -469,6 +483,15 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         self
     }
 
+    /// Sets the codec used to compress outgoing events, if the broker agreed to it when attaching,
+    /// else gzip is used. Incoming events are decompressed with whatever codec their sender used.
+    #[must_use]
+    #[cfg(feature = "llmp_compression")]
+    pub fn compression_codec(mut self, compression_codec: CompressionCodec) -> Self {
+        self.compression_codec = compression_codec;
+        self
+    }
+
     /// Create a manager from a raw LLMP client
     /// If staterestorer is some then this restarting manager restarts
     /// Otherwise this restarting manager does not restart
@@ -483,9 +506,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
-            llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CodecCompressor::with_threshold(
+                llmp.compression_codecs().pick_codec(self.compression_codec),
+                COMPRESS_THRESHOLD,
+            ),
+            llmp,
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -734,9 +760,21 @@ pub struct RestartingMgr<EMH, I, MT, S, SP> {
     /// The address to connect to
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The pre-shared key for the links to and from other brokers
+    #[cfg(feature = "llmp_encryption")]
+    #[builder(default = None)]
+    link_key: Option<LinkKey>,
     /// The type of manager to build
     #[builder(default = ManagerKind::Any)]
     kind: ManagerKind,
+    /// The codec the fuzzer clients use to compress their events
+    #[cfg(feature = "llmp_compression")]
+    #[builder(default)]
+    compression_codec: CompressionCodec,
+    /// The hook of the broker dropping duplicate testcases sent by several clients.
+    /// The default hook forwards all testcases.
+    #[builder(default)]
+    dedup_hook: LlmpDedupHook,
     /// The amount of external clients that should have connected (not counting our own tcp client)
     /// before this broker quits _after the last client exited_.
     /// If `None`, the broker will never quit when the last client exits, but run forever.
@@ -780,6 +818,11 @@ where
             .is_err()
         {
             let broker_things = |mut broker: LlmpBroker<_, SP::ShMem, SP>, remote_broker_addr| {
+                #[cfg(feature = "llmp_encryption")]
+                if let Some(link_key) = &self.link_key {
+                    broker.inner_mut().set_link_key(link_key.clone());
+                }
+
                 if let Some(remote_broker_addr) = remote_broker_addr {
                     log::info!("B2b: Connecting to {:?}", &remote_broker_addr);
                     broker.inner_mut().connect_b2b(remote_broker_addr)?;
@@ -805,6 +848,7 @@ where
                         LlmpConnection::IsBroker { broker } => {
                             let llmp_hook =
                                 StdLlmpEventHook::<I, MT>::new(self.monitor.take().unwrap())?;
+                            let dedup_hook = mem::take(&mut self.dedup_hook);
 
                             // Yep, broker. Just loop here.
                             log::info!(
@@ -812,7 +856,7 @@ where
                             );
 
                             broker_things(
-                                broker.add_hooks(tuple_list!(llmp_hook)),
+                                broker.add_hooks(tuple_list!(llmp_hook, dedup_hook)),
                                 self.remote_broker_addr,
                             )?;
 
@@ -834,10 +878,11 @@ where
                 }
                 ManagerKind::Broker => {
                     let llmp_hook = StdLlmpEventHook::new(self.monitor.take().unwrap())?;
+                    let dedup_hook = mem::take(&mut self.dedup_hook);
 
                     let broker = LlmpBroker::create_attach_to_tcp(
                         self.shmem_provider.clone(),
-                        tuple_list!(llmp_hook),
+                        tuple_list!(llmp_hook, dedup_hook),
                         self.broker_port,
                     )?;
 
@@ -974,37 +1019,37 @@ where
             core_id.set_affinity()?;
         }
 
+        let builder = LlmpEventManagerBuilder::builder()
+            .hooks(self.hooks)
+            .save_state(self.serialize_state);
+        #[cfg(feature = "llmp_compression")]
+        let builder = builder.compression_codec(self.compression_codec);
+
         // If we're restarting, deserialize the old state.
         let (state, mut mgr) =
             if let Some((state_opt, mgr_description)) = staterestorer.restore()? {
                 (
                     state_opt,
-                    LlmpEventManagerBuilder::builder()
-                        .hooks(self.hooks)
-                        .save_state(self.serialize_state)
-                        .build_existing_client_from_description(
-                            new_shmem_provider,
-                            &mgr_description,
-                            self.configuration,
-                            self.time_ref.clone(),
-                            Some(staterestorer),
-                        )?,
+                    builder.build_existing_client_from_description(
+                        new_shmem_provider,
+                        &mgr_description,
+                        self.configuration,
+                        self.time_ref.clone(),
+                        Some(staterestorer),
+                    )?,
                 )
             } else {
                 log::info!("First run. Let's set it all up");
                 // Mgr to send and receive msgs from/to all other fuzzer instances
                 (
                     None,
-                    LlmpEventManagerBuilder::builder()
-                        .hooks(self.hooks)
-                        .save_state(self.serialize_state)
-                        .build_existing_client_from_env(
-                            new_shmem_provider,
-                            _ENV_FUZZER_BROKER_CLIENT_INITIAL,
-                            self.configuration,
-                            self.time_ref.clone(),
-                            Some(staterestorer),
-                        )?,
+                    builder.build_existing_client_from_env(
+                        new_shmem_provider,
+                        _ENV_FUZZER_BROKER_CLIENT_INITIAL,
+                        self.configuration,
+                        self.time_ref.clone(),
+                        Some(staterestorer),
+                    )?,
                 )
             };
         // We reset the staterestorer, the next staterestorer and receiver (after crash) will reuse the page from the initial message.
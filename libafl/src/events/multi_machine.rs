@@ -1,9 +1,11 @@
-use core::fmt::Display;
+use core::{fmt::Display, mem};
 use std::{
     boxed::Box,
-    collections::HashMap,
-    io::ErrorKind,
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, ErrorKind},
+    net::SocketAddr,
     process,
+    string::{String, ToString},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc, OnceLock,
@@ -14,8 +16,13 @@ use std::{
 
 use enumflags2::{bitflags, BitFlags};
 #[cfg(feature = "llmp_compression")]
-use libafl_bolts::compress::GzipCompressor;
-use libafl_bolts::{current_time, ownedref::OwnedRef, Error};
+use libafl_bolts::compress::CodecCompressor;
+#[cfg(feature = "llmp_encryption")]
+use libafl_bolts::secure_link::{
+    LinkInitiator, LinkKey, LinkResponder, SecureChannel, LINK_CHALLENGE_SIZE, LINK_FINISH_SIZE,
+    LINK_HELLO_SIZE,
+};
+use libafl_bolts::{current_time, hash_std, ownedref::OwnedRef, Error};
 use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -46,6 +53,25 @@ pub enum NodePolicy {
 }
 
 const DUMMY_BYTE: u8 = 0x14;
+/// Starts a [`NodeControl`] message instead of a testcase
+const CONTROL_BYTE: u8 = 0x15;
+
+/// The maximum number of redirections followed when joining the tree
+const MAX_REDIRECTS: usize = 8;
+
+/// The time another node gets to connect, authenticate and say hello
+const LINK_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The error of a link handshake that timed out, retried like a failed connection
+fn handshake_timeout(msg: String) -> Error {
+    Error::os_error(io::Error::from(ErrorKind::TimedOut), msg)
+}
+
+/// The largest [`NodeControl`] frame accepted. Those are read before the peer is known.
+const MAX_CONTROL_FRAME_SIZE: usize = 1 << 20;
+
+/// The maximum number of hashes in a [`NodeControl::Hello`], so that it fits in a control frame
+const MAX_KNOWN_TESTCASES: usize = 1 << 16;
 
 /// Use `OwnedRef` as much as possible here to avoid useless copies.
 /// An owned TCP message for multi machine
@@ -114,25 +140,341 @@ impl NodeId {
     }
 }
 
+/// Messages maintaining the topology of the tree, sent between a parent and its children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum NodeControl {
+    /// Sent by a joining child, with the port it listens on for its own children
+    Hello {
+        /// The listening port of the child
+        listening_port: Option<u16>,
+        /// The parent the child lost, if it is re-attaching. It won't be redirected there.
+        lost_parent: Option<String>,
+        /// The hashes of the testcases the child knows already, so that the parent only sends
+        /// the ones it misses. At most [`MAX_KNOWN_TESTCASES`].
+        known: Vec<u64>,
+    },
+    /// Sent by the parent when a child joined, and whenever the parent itself was re-attached.
+    /// This is the node the child should attach to if the parent is lost.
+    Ancestor {
+        /// The address of the parent's own parent
+        addr: Option<String>,
+    },
+    /// Sent by a parent that has enough children already: join this node instead.
+    Redirect {
+        /// The address of the node to join
+        addr: String,
+    },
+}
+
+/// A connection to another node.
+/// Messages are encrypted and authenticated if the [`NodeDescriptor`] has a link key.
+#[derive(Debug)]
+struct NodeLink {
+    stream: TcpStream,
+    #[cfg(feature = "llmp_encryption")]
+    channel: Option<SecureChannel>,
+    /// The address other nodes can reach the node on the other end with, if known
+    addr: Option<String>,
+    /// For the link to our parent, the last ancestor the parent announced
+    ancestor: Option<String>,
+}
+
+impl NodeLink {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            #[cfg(feature = "llmp_encryption")]
+            channel: None,
+            addr: None,
+            ancestor: None,
+        }
+    }
+
+    /// Joins the parent at `addr`, following its redirections to other nodes of the tree.
+    ///
+    /// A parent that does not answer in time is an [`Error::OsError`] of kind
+    /// [`ErrorKind::TimedOut`], like a parent that is not up yet, so that it can be retried.
+    async fn join<A>(
+        mut addr: String,
+        lost_parent: Option<String>,
+        known: &[u64],
+        node_descriptor: &NodeDescriptor<A>,
+    ) -> Result<Self, Error> {
+        for _ in 0..MAX_REDIRECTS {
+            let mut link = time::timeout(LINK_HANDSHAKE_TIMEOUT, async {
+                let stream = TcpStream::connect(addr.as_str())
+                    .await
+                    .map_err(|e| Error::os_error(e, format!("Unable to connect to {addr}")))?;
+                Self::connect(stream, node_descriptor).await
+            })
+            .await
+            .map_err(|_| handshake_timeout(format!("Connecting to {addr} timed out")))??;
+            link.write_control(&NodeControl::Hello {
+                listening_port: node_descriptor.node_listening_port,
+                lost_parent: lost_parent.clone(),
+                known: known.to_vec(),
+            })
+            .await?;
+
+            match time::timeout(LINK_HANDSHAKE_TIMEOUT, link.read_control())
+                .await
+                .map_err(|_| handshake_timeout(format!("{addr} did not welcome us in time")))??
+            {
+                NodeControl::Ancestor { addr: ancestor } => {
+                    link.addr = Some(addr);
+                    link.ancestor = ancestor;
+                    return Ok(link);
+                }
+                NodeControl::Redirect { addr: next } => {
+                    log::info!("{addr} has enough children, redirected to {next}");
+                    addr = next;
+                }
+                NodeControl::Hello { .. } => {
+                    return Err(Error::illegal_state(format!(
+                        "{addr} answered with an unexpected hello"
+                    )));
+                }
+            }
+        }
+
+        Err(Error::illegal_state(format!(
+            "Too many redirections while joining the tree, last one to {addr}"
+        )))
+    }
+
+    /// Welcomes a joining child, waiting for its [`NodeControl::Hello`].
+    /// Returns the link, the parent the child lost, if any, and the testcases it knows.
+    async fn welcome<A>(
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        node_descriptor: &NodeDescriptor<A>,
+    ) -> Result<(Self, Option<String>, HashSet<u64>), Error> {
+        let (mut link, hello) = time::timeout(LINK_HANDSHAKE_TIMEOUT, async {
+            let mut link = Self::accept(stream, node_descriptor).await?;
+            let hello = link.read_control().await?;
+            Ok::<_, Error>((link, hello))
+        })
+        .await
+        .map_err(|_| Error::illegal_state("The child did not say hello in time"))??;
+        let NodeControl::Hello {
+            listening_port,
+            lost_parent,
+            known,
+        } = hello
+        else {
+            return Err(Error::illegal_state("Expected a hello from the child"));
+        };
+        if known.len() > MAX_KNOWN_TESTCASES {
+            return Err(Error::illegal_state(format!(
+                "The child claims to know {} testcases, the limit is {MAX_KNOWN_TESTCASES}",
+                known.len()
+            )));
+        }
+        link.addr = listening_port.map(|port| SocketAddr::new(peer_addr.ip(), port).to_string());
+        Ok((link, lost_parent, known.into_iter().collect()))
+    }
+
+    /// Connects to the parent, running the `secure_link` handshake if a [`LinkKey`] is set.
+    #[cfg(feature = "llmp_encryption")]
+    async fn connect<A>(
+        mut stream: TcpStream,
+        node_descriptor: &NodeDescriptor<A>,
+    ) -> Result<Self, Error> {
+        let Some(link_key) = &node_descriptor.link_key else {
+            return Ok(Self::new(stream));
+        };
+
+        let initiator = LinkInitiator::new(link_key)?;
+        stream.write_all(&initiator.hello()).await?;
+        let mut challenge = [0; LINK_CHALLENGE_SIZE];
+        stream.read_exact(&mut challenge).await?;
+        let (finish, channel) = initiator.finish(&challenge)?;
+        stream.write_all(&finish).await?;
+
+        let mut link = Self::new(stream);
+        link.channel = Some(channel);
+        Ok(link)
+    }
+
+    /// Connects to the parent
+    #[cfg(not(feature = "llmp_encryption"))]
+    #[expect(clippy::unused_async)]
+    async fn connect<A>(
+        stream: TcpStream,
+        _node_descriptor: &NodeDescriptor<A>,
+    ) -> Result<Self, Error> {
+        Ok(Self::new(stream))
+    }
+
+    /// Accepts a child, running the `secure_link` handshake if a [`LinkKey`] is set.
+    #[cfg(feature = "llmp_encryption")]
+    async fn accept<A>(
+        mut stream: TcpStream,
+        node_descriptor: &NodeDescriptor<A>,
+    ) -> Result<Self, Error> {
+        let Some(link_key) = &node_descriptor.link_key else {
+            return Ok(Self::new(stream));
+        };
+
+        let mut hello = [0; LINK_HELLO_SIZE];
+        stream.read_exact(&mut hello).await?;
+        let responder = LinkResponder::new(link_key, &hello)?;
+        stream.write_all(&responder.challenge()).await?;
+        let mut finish = [0; LINK_FINISH_SIZE];
+        stream.read_exact(&mut finish).await?;
+
+        let mut link = Self::new(stream);
+        link.channel = Some(responder.finish(&finish)?);
+        Ok(link)
+    }
+
+    /// Accepts a child
+    #[cfg(not(feature = "llmp_encryption"))]
+    #[expect(clippy::unused_async)]
+    async fn accept<A>(
+        stream: TcpStream,
+        _node_descriptor: &NodeDescriptor<A>,
+    ) -> Result<Self, Error> {
+        Ok(Self::new(stream))
+    }
+
+    /// Writes a frame starting with `marker`. The body is sealed if the link is encrypted.
+    async fn write_frame(&mut self, marker: u8, body: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "llmp_encryption")]
+        let sealed;
+        #[cfg(feature = "llmp_encryption")]
+        let body = match &mut self.channel {
+            Some(channel) => {
+                sealed = channel.seal(body)?;
+                sealed.as_slice()
+            }
+            None => body,
+        };
+        let body_len = u32::to_le_bytes(body.len() as u32);
+
+        // 0. Write the marker byte
+        log::debug!("Sending marker byte...");
+        self.stream.write_all(&[marker]).await?;
+        log::debug!("marker byte sent.");
+
+        // 1. Write msg size
+        log::debug!("Sending msg len...");
+        self.stream.write_all(&body_len).await?;
+        log::debug!("msg len sent.");
+
+        // 2. Write msg
+        log::debug!("Sending msg...");
+        self.stream.write_all(body).await?;
+        log::debug!("msg sent.");
+
+        Ok(())
+    }
+
+    /// Reads the body of a frame of at most `max_len` bytes, once its marker byte was read.
+    async fn read_frame_body(&mut self, max_len: usize) -> Result<Vec<u8>, Error> {
+        // 1. Read msg size
+        let mut body_len: [u8; 4] = [0; 4];
+        log::debug!("Receiving msg len...");
+        self.stream.read_exact(&mut body_len).await?;
+        log::debug!("msg len received.");
+        let body_len = u32::from_le_bytes(body_len) as usize;
+        if body_len > max_len {
+            return Err(Error::illegal_state(format!(
+                "Received a frame of {body_len} bytes, the limit is {max_len} bytes"
+            )));
+        }
+
+        // 2. Read msg
+        // do not store msg on the stack to avoid overflow issues
+        let mut body = vec![0; body_len];
+        log::debug!("Receiving msg...");
+        self.stream.read_exact(body.as_mut_slice()).await?;
+        log::debug!("msg received.");
+
+        #[cfg(feature = "llmp_encryption")]
+        if let Some(channel) = &mut self.channel {
+            return channel.open(&body);
+        }
+        Ok(body)
+    }
+
+    async fn write_control(&mut self, control: &NodeControl) -> Result<(), Error> {
+        self.write_frame(CONTROL_BYTE, &postcard::to_allocvec(control)?)
+            .await
+    }
+
+    /// Waits for the next frame, which has to be a [`NodeControl`] message.
+    async fn read_control(&mut self) -> Result<NodeControl, Error> {
+        let mut marker = [0u8];
+        self.stream.read_exact(&mut marker).await?;
+        if marker[0] != CONTROL_BYTE {
+            return Err(Error::illegal_state(
+                "Expected a control message from another node. Is the same link key set on all nodes?",
+            ));
+        }
+        Ok(postcard::from_bytes(
+            &self.read_frame_body(MAX_CONTROL_FRAME_SIZE).await?,
+        )?)
+    }
+}
+
 /// The state of the hook shared between the background threads and the main thread.
 #[derive(Debug)]
 pub struct TcpMultiMachineState<A> {
     node_descriptor: NodeDescriptor<A>,
     /// the parent to which the testcases should be forwarded when deemed interesting
-    parent: Option<TcpStream>,
+    parent: Option<NodeLink>,
     /// The children who connected during the fuzzing session.
-    children: HashMap<NodeId, TcpStream>, // The children who connected during the fuzzing session.
+    children: HashMap<NodeId, NodeLink>, // The children who connected during the fuzzing session.
+    /// The node to attach to if the parent is lost, as announced by the last parent
+    ancestor: Option<String>,
+    /// The address of the last parent we lost
+    lost_parent: Option<String>,
+    /// Our testcases the parent missed while we had none, sent once we are attached again
+    missed_by_parent: VecDeque<Vec<u8>>,
+    /// The number of nodes redirected to our children so far
+    nb_redirected: usize,
     old_msgs: Vec<Vec<u8>>,
+    /// The hashes of all the testcases we know, sent or received
+    known: HashSet<u64>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CodecCompressor,
 }
 
 /// The tree descriptor for the
+///
+/// The tree is not fixed: if the parent is lost, the node tries to attach to the parent again,
+/// then to the last ancestor the parent announced, then to the [`NodeDescriptor::fallback_parents`].
+/// Once attached, our testcases the parent missed in the meantime are sent to it.
+///
+/// When joining, a node tells its parent which testcases it knows already. The parent then sends
+/// all the past testcases the node misses, a batch at a time, without stalling its own fuzzing.
+/// Nodes can join a running tree at any time, using any node of the tree as parent.
 #[derive(Debug, Clone, TypedBuilder)]
 pub struct NodeDescriptor<A> {
     /// The parent address, if there is one.
     pub parent_addr: Option<A>,
 
+    /// Other nodes to attach to if the parent is lost, tried in order
+    #[builder(default = Vec::new())]
+    pub fallback_parents: Vec<A>,
+
+    /// The delay between two attempts to attach to a new parent, once the parent is lost
+    #[builder(default = Duration::from_secs(5))]
+    pub reconnect_interval: Duration,
+
+    /// How many of our testcases are kept for the parent while no parent is attached
+    #[builder(default = 1024)]
+    pub replay_capacity: usize,
+
+    /// The maximum number of children. Further joining nodes are redirected to our children.
+    #[builder(default = None)]
+    pub max_children: Option<usize>,
+
+    /// How many past testcases are sent at once to a joining child, before letting fuzzing go on
+    #[builder(default = 64)]
+    pub sync_batch_size: usize,
+
     /// The node listening port. Defaults to 50000
     #[builder(default = Some(50000))]
     pub node_listening_port: Option<u16>,
@@ -144,6 +486,12 @@ pub struct NodeDescriptor<A> {
     /// Node flags
     #[builder(default_code = "BitFlags::default()")]
     pub flags: BitFlags<NodePolicy>, // The policy for shared messages between nodes.
+
+    /// The pre-shared key authenticating and encrypting the links to the parent and children.
+    /// If set, all nodes of the tree need the same key.
+    #[cfg(feature = "llmp_encryption")]
+    #[builder(default = None)]
+    pub link_key: Option<LinkKey>,
 }
 
 /// A set of multi-machine `broker_hooks`.
@@ -224,20 +572,13 @@ where
 
         // Create the state of the hook. This will be shared with the background server, so we wrap
         // it with concurrent-safe objects
-        let state = Arc::new(RwLock::new(TcpMultiMachineState {
-            node_descriptor,
-            parent: None,
-            children: HashMap::default(),
-            old_msgs: Vec::new(),
-            #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::new(),
-        }));
+        let state = Arc::new(RwLock::new(TcpMultiMachineState::new(node_descriptor)));
 
         let rt =
             Arc::new(Runtime::new().map_err(|_| Error::unknown("Tokio runtime spawning failed"))?);
 
         unsafe {
-            TcpMultiMachineState::init::<I>(&state.clone(), &rt.clone())?;
+            TcpMultiMachineState::init(&state.clone(), &rt.clone())?;
         }
 
         Ok(TcpMultiMachineHooks {
@@ -251,16 +592,29 @@ impl<A> TcpMultiMachineState<A>
 where
     A: Clone + Display + ToSocketAddrs + Send + Sync + 'static,
 {
+    fn new(node_descriptor: NodeDescriptor<A>) -> Self {
+        Self {
+            node_descriptor,
+            parent: None,
+            children: HashMap::default(),
+            ancestor: None,
+            lost_parent: None,
+            missed_by_parent: VecDeque::new(),
+            nb_redirected: 0,
+            old_msgs: Vec::new(),
+            known: HashSet::new(),
+            #[cfg(feature = "llmp_compression")]
+            compressor: CodecCompressor::default(),
+        }
+    }
+
     /// Initializes the Multi-Machine state.
     ///
     /// # Safety
     ///
     /// This should be run **only once**, in the same process as the llmp hooks, and before the hooks
     /// are effectively used.
-    unsafe fn init<I: Input>(
-        self_mutex: &Arc<RwLock<Self>>,
-        rt: &Arc<Runtime>,
-    ) -> Result<(), Error> {
+    unsafe fn init(self_mutex: &Arc<RwLock<Self>>, rt: &Arc<Runtime>) -> Result<(), Error> {
         let node_descriptor =
             rt.block_on(async { self_mutex.read().await.node_descriptor.clone() });
 
@@ -274,17 +628,29 @@ where
 
                 parent_lock.parent = loop {
                     log::debug!("Trying to connect to parent @ {}..", parent_addr);
-                    match TcpStream::connect(parent_addr).await {
-                        Ok(stream) => {
+                    match NodeLink::join(
+                        parent_addr.to_string(),
+                        None,
+                        &parent_lock.known_testcases(),
+                        &parent_lock.node_descriptor,
+                    )
+                    .await
+                    {
+                        Ok(link) => {
                             log::debug!("Connected to parent @ {}", parent_addr);
 
-                            break Some(stream);
+                            break Some(link);
                         }
-                        Err(e) => {
+                        Err(Error::OsError(e, ..)) => {
                             if current_time() > timeout {
                                 return Err(Error::os_error(e, "Unable to connect to parent"));
                             }
                         }
+                        Err(e) => {
+                            return Err(Error::illegal_state(format!(
+                                "Unable to establish a link to parent @ {parent_addr}: {e}"
+                            )));
+                        }
                     }
 
                     time::sleep(Duration::from_secs(1)).await;
@@ -294,6 +660,51 @@ where
             Ok(())
         })?;
 
+        // Re-attach to the tree whenever the parent is lost
+        if node_descriptor.parent_addr.is_some() {
+            let bg_state = self_mutex.clone();
+            let reconnect_interval = node_descriptor.reconnect_interval;
+            let node_descriptor = node_descriptor.clone();
+            let _handle: JoinHandle<()> = rt.spawn(async move {
+                let state = bg_state;
+                loop {
+                    time::sleep(reconnect_interval).await;
+
+                    let (candidates, lost_parent, known) = {
+                        let state_guard = state.read().await;
+                        if state_guard.parent.is_some() {
+                            continue;
+                        }
+                        (
+                            state_guard.parent_candidates(),
+                            state_guard.lost_parent.clone(),
+                            state_guard.known_testcases(),
+                        )
+                    };
+
+                    for candidate in candidates {
+                        log::info!("Trying to re-attach to {candidate}...");
+                        match NodeLink::join(
+                            candidate.clone(),
+                            lost_parent.clone(),
+                            &known,
+                            &node_descriptor,
+                        )
+                        .await
+                        {
+                            Ok(link) => {
+                                Self::reattach(&state, link).await;
+                                break;
+                            }
+                            Err(e) => {
+                                log::debug!("Could not re-attach to {candidate}: {e}");
+                            }
+                        }
+                    }
+                }
+            });
+        }
+
         // Now, setup the background tasks for the children to connect to
         if let Some(listening_port) = node_descriptor.node_listening_port {
             let bg_state = self_mutex.clone();
@@ -306,28 +717,18 @@ where
                 let state = bg_state;
 
                 // The main listening loop. Should never fail.
-                'listening: loop {
+                loop {
                     log::debug!("listening for children on {:?}...", listener);
                     match listener.accept().await {
-                        Ok((mut stream, addr)) => {
+                        Ok((stream, addr)) => {
                             log::debug!("{} joined the children.", addr);
-                            let mut state_guard = state.write().await;
-
-                            if let Err(e) = state_guard
-                                .send_old_events_to_stream::<I>(&mut stream)
-                                .await
-                            {
-                                log::error!("Error while send old messages: {e:?}.");
-                                log::error!("The loop will resume");
-                                continue 'listening;
-                            }
-
-                            state_guard.children.insert(NodeId::new(), stream);
-                            log::debug!(
-                                "[pid {}]{addr} added the child. nb children: {}",
-                                process::id(),
-                                state_guard.children.len()
-                            );
+                            // Welcome the child in the background, the listener goes on
+                            let child_state = state.clone();
+                            let node_descriptor = node_descriptor.clone();
+                            let _child_handle: JoinHandle<()> = tokio::spawn(async move {
+                                Self::welcome_child(&child_state, stream, addr, &node_descriptor)
+                                    .await;
+                            });
                         }
                         Err(e) => {
                             log::error!("Error while accepting child {e:?}.");
@@ -340,114 +741,313 @@ where
         Ok(())
     }
 
+    /// Welcomes a joining child, then redirects it to one of our children if we have enough of
+    /// them already, or tells it about its ancestor and syncs it.
+    ///
+    /// The state is not locked while writing to the child.
+    async fn welcome_child(
+        state: &RwLock<Self>,
+        stream: TcpStream,
+        addr: SocketAddr,
+        node_descriptor: &NodeDescriptor<A>,
+    ) {
+        let (mut link, lost_parent, known) =
+            match NodeLink::welcome(stream, addr, node_descriptor).await {
+                Ok(welcomed) => welcomed,
+                Err(e) => {
+                    log::error!("Rejected child {addr}: {e}");
+                    return;
+                }
+            };
+
+        let control = {
+            let mut state_guard = state.write().await;
+            match state_guard.redirect_target(lost_parent.as_deref()) {
+                Some(redirect_addr) => NodeControl::Redirect {
+                    addr: redirect_addr,
+                },
+                None => NodeControl::Ancestor {
+                    addr: state_guard
+                        .parent
+                        .as_ref()
+                        .and_then(|parent| parent.addr.clone()),
+                },
+            }
+        };
+
+        if let NodeControl::Redirect {
+            addr: redirect_addr,
+        } = &control
+        {
+            log::info!("Enough children, redirecting {addr} to {redirect_addr}");
+            if let Err(e) = link.write_control(&control).await {
+                log::error!("Error while redirecting {addr}: {e:?}.");
+            }
+            return;
+        }
+        if let Err(e) = link.write_control(&control).await {
+            log::error!("Error while welcoming {addr}: {e:?}.");
+            return;
+        }
+
+        // Send the past testcases
+        if let Err(e) = Self::sync_child(state, link, &known).await {
+            log::error!("Error while syncing {addr}: {e:?}.");
+            return;
+        }
+        log::debug!(
+            "[pid {}]{addr} added the child. nb children: {}",
+            process::id(),
+            state.read().await.children.len()
+        );
+    }
+
     /// Add an event as past event.
     pub fn add_past_msg(&mut self, msg: &[u8]) {
+        self.known.insert(hash_std(msg));
         self.old_msgs.push(msg.to_vec());
     }
 
-    /// The compressor
-    #[cfg(feature = "llmp_compression")]
-    pub fn compressor(&mut self) -> &GzipCompressor {
-        &self.compressor
+    /// The hashes of the testcases we know, to send to a new parent.
+    /// Beyond [`MAX_KNOWN_TESTCASES`], the parent sends some testcases we know again.
+    fn known_testcases(&self) -> Vec<u64> {
+        self.known
+            .iter()
+            .copied()
+            .take(MAX_KNOWN_TESTCASES)
+            .collect()
     }
 
-    /// Read a [`TcpMultiMachineMsg`] from a stream.
-    /// Expects a message written by [`TcpMultiMachineState::write_msg`].
-    /// If there is nothing to read from the stream, return asap with Ok(None).
-    #[expect(clippy::uninit_vec)]
-    async fn read_msg<'a, I: Input + 'a>(
-        stream: &mut TcpStream,
-    ) -> Result<Option<MultiMachineMsg<'a, I>>, Error> {
-        // 0. Check if we should try to fetch something from the stream
-        let mut dummy_byte: [u8; 1] = [0u8];
-        log::debug!("Starting read msg...");
-
-        let n_read = match stream.try_read(&mut dummy_byte) {
-            Ok(n) => n,
-            Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                return Ok(None);
+    /// Sends the past testcases a joining child does not know, then adds it to the children.
+    ///
+    /// The state is only locked to pick the next batch, and the writes wait for the child to
+    /// keep up, so the fuzzing goes on meanwhile.
+    /// Testcases found while syncing are sent in a later batch, until the child caught up.
+    async fn sync_child(
+        state: &RwLock<Self>,
+        mut link: NodeLink,
+        known: &HashSet<u64>,
+    ) -> Result<(), Error> {
+        let mut next = 0;
+        let mut nb_sent = 0;
+        loop {
+            let batch: Vec<Vec<u8>> = {
+                let mut state_guard = state.write().await;
+                if next >= state_guard.old_msgs.len() {
+                    log::debug!("Sent {nb_sent} of {next} past testcases to the new child.");
+                    state_guard.children.insert(NodeId::new(), link);
+                    return Ok(());
+                }
+                let end = state_guard
+                    .old_msgs
+                    .len()
+                    .min(next + state_guard.node_descriptor.sync_batch_size.max(1));
+                let batch = state_guard.old_msgs[next..end]
+                    .iter()
+                    .filter(|msg| !known.contains(&hash_std(msg)))
+                    .cloned()
+                    .collect();
+                next = end;
+                batch
+            };
+
+            for msg in &batch {
+                link.write_frame(DUMMY_BYTE, msg).await?;
             }
-            Err(e) => return Err(Error::os_error(e, "try read failed")),
-        };
+            nb_sent += batch.len();
+        }
+    }
 
-        log::debug!("msg read.");
+    /// The nodes to try to attach to, once the parent is lost
+    fn parent_candidates(&self) -> Vec<String> {
+        let mut candidates: Vec<String> = self
+            .node_descriptor
+            .parent_addr
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        candidates.extend(self.ancestor.clone());
+        candidates.extend(
+            self.node_descriptor
+                .fallback_parents
+                .iter()
+                .map(ToString::to_string),
+        );
+        candidates
+    }
 
-        if n_read == 0 {
-            log::debug!("No dummy byte received...");
-            return Ok(None); // Nothing to read from this stream
+    /// Drops the link to the parent, remembering the ancestor it announced.
+    fn lose_parent(&mut self) {
+        if let Some(parent) = self.parent.take() {
+            self.ancestor = parent.ancestor;
+            self.lost_parent = parent.addr;
         }
+    }
 
-        log::debug!("Received dummy byte!");
+    /// Keeps a testcase for the parent while we have none.
+    fn keep_for_parent(&mut self, msg: &[u8]) {
+        if self.node_descriptor.replay_capacity == 0 {
+            return;
+        }
+        if self.missed_by_parent.len() >= self.node_descriptor.replay_capacity {
+            self.missed_by_parent.pop_front();
+        }
+        self.missed_by_parent.push_back(msg.to_vec());
+    }
 
-        // we should always read the dummy byte at this point.
-        assert_eq!(u8::from_le_bytes(dummy_byte), DUMMY_BYTE);
+    /// Uses `link` as the new parent. Replays the testcases it missed, and tells our children
+    /// about their new ancestor.
+    ///
+    /// The state is not locked while replaying, testcases found meanwhile are replayed afterwards.
+    async fn reattach(state: &RwLock<Self>, mut link: NodeLink) {
+        log::info!(
+            "Re-attached to parent @ {}",
+            link.addr.as_deref().unwrap_or("?")
+        );
 
-        // 1. Read msg size
-        let mut node_msg_len: [u8; 4] = [0; 4];
-        log::debug!("Receiving msg len...");
-        stream.read_exact(&mut node_msg_len).await?;
-        log::debug!("msg len received.");
-        let node_msg_len = u32::from_le_bytes(node_msg_len) as usize;
+        let mut nb_replayed = 0;
+        loop {
+            let mut missed = {
+                let mut state_guard = state.write().await;
+                if state_guard.missed_by_parent.is_empty() {
+                    state_guard.adopt_parent(link).await;
+                    log::debug!("Replayed {nb_replayed} testcases.");
+                    return;
+                }
+                mem::take(&mut state_guard.missed_by_parent)
+            };
+
+            while let Some(msg) = missed.pop_front() {
+                if let Err(e) = link.write_frame(DUMMY_BYTE, &msg).await {
+                    log::error!("Lost the new parent while replaying testcases: {e:?}");
+                    missed.push_front(msg);
+                    state.write().await.requeue_for_parent(missed, link);
+                    return;
+                }
+                nb_replayed += 1;
+            }
+        }
+    }
 
-        // 2. Read msg
-        // do not store msg on the stack to avoid overflow issues
-        // TODO: optimize with less allocations...
-        let mut node_msg: Vec<u8> = Vec::with_capacity(node_msg_len);
-        unsafe {
-            node_msg.set_len(node_msg_len);
+    /// Sets `link` as the parent, and tells our children about their new ancestor.
+    async fn adopt_parent(&mut self, link: NodeLink) {
+        let ancestor = link.addr.clone();
+        for (child_id, child) in &mut self.children {
+            if let Err(e) = child
+                .write_control(&NodeControl::Ancestor {
+                    addr: ancestor.clone(),
+                })
+                .await
+            {
+                log::debug!("Could not update the ancestor of child {child_id:?}: {e:?}");
+            }
         }
-        log::debug!("Receiving msg...");
-        stream.read_exact(node_msg.as_mut_slice()).await?;
-        log::debug!("msg received.");
-        let node_msg = node_msg.into_boxed_slice();
 
-        Ok(Some(MultiMachineMsg::from_llmp_msg(node_msg)))
+        self.parent = Some(link);
     }
 
-    /// Write an [`OwnedTcpMultiMachineMsg`] to a stream.
-    /// Can be read back using [`TcpMultiMachineState::read_msg`].
-    async fn write_msg<'a, I: Input>(
-        stream: &mut TcpStream,
-        msg: &MultiMachineMsg<'a, I>,
-    ) -> Result<(), Error> {
-        let serialized_msg = msg.serialize_as_ref();
-        let msg_len = u32::to_le_bytes(serialized_msg.len() as u32);
+    /// Keeps the testcases `link` did not get before the ones found since, and forgets `link`.
+    fn requeue_for_parent(&mut self, mut missed: VecDeque<Vec<u8>>, link: NodeLink) {
+        missed.append(&mut self.missed_by_parent);
+        let excess = missed
+            .len()
+            .saturating_sub(self.node_descriptor.replay_capacity);
+        missed.drain(..excess);
+        self.missed_by_parent = missed;
+        self.ancestor = link.ancestor;
+        self.lost_parent = link.addr;
+    }
 
-        // 0. Write the dummy byte
-        log::debug!("Sending dummy byte...");
-        stream.write_all(&[DUMMY_BYTE]).await?;
-        log::debug!("dummy byte sent.");
+    /// If we have enough children, the child a joining node should be redirected to.
+    /// Never redirects to the parent the joining node just lost.
+    fn redirect_target(&mut self, lost_parent: Option<&str>) -> Option<String> {
+        let max_children = self.node_descriptor.max_children?;
+        if self.children.len() < max_children {
+            return None;
+        }
+        let candidates: Vec<&String> = self
+            .children
+            .values()
+            .filter_map(|child| child.addr.as_ref())
+            .filter(|addr| Some(addr.as_str()) != lost_parent)
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        self.nb_redirected += 1;
+        Some(candidates[self.nb_redirected % candidates.len()].clone())
+    }
 
-        // 1. Write msg size
-        log::debug!("Sending msg len...");
-        stream.write_all(&msg_len).await?;
-        log::debug!("msg len sent.");
+    /// The compressor
+    #[cfg(feature = "llmp_compression")]
+    pub fn compressor(&mut self) -> &CodecCompressor {
+        &self.compressor
+    }
 
-        // 2. Write msg
-        log::debug!("Sending msg...");
-        stream.write_all(serialized_msg).await?;
-        log::debug!("msg sent.");
+    /// Read a [`TcpMultiMachineMsg`] from a stream.
+    /// Expects a message written by [`TcpMultiMachineState::write_msg`].
+    /// [`NodeControl`] messages on the way are handled here.
+    /// If there is nothing to read from the stream, return asap with Ok(None).
+    async fn read_msg<'a, I: Input + 'a>(
+        link: &mut NodeLink,
+    ) -> Result<Option<MultiMachineMsg<'a, I>>, Error> {
+        loop {
+            // 0. Check if we should try to fetch something from the stream
+            let mut marker_byte: [u8; 1] = [0u8];
+            log::debug!("Starting read msg...");
+
+            let n_read = match link.stream.try_read(&mut marker_byte) {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(Error::os_error(e, "try read failed")),
+            };
 
-        Ok(())
-    }
+            log::debug!("msg read.");
 
-    pub(crate) async fn send_old_events_to_stream<I: Input>(
-        &mut self,
-        stream: &mut TcpStream,
-    ) -> Result<(), Error> {
-        log::debug!("Send old events to new child...");
-
-        for old_msg in &self.old_msgs {
-            let event_ref: MultiMachineMsg<I> =
-                MultiMachineMsg::llmp_msg(OwnedRef::Ref(old_msg.as_slice()));
-            log::debug!("Sending an old message...");
-            Self::write_msg(stream, &event_ref).await?;
-            log::debug!("Old message sent.");
-        }
+            if n_read == 0 {
+                // The other node closed the connection
+                return Err(Error::os_error(
+                    io::Error::from(ErrorKind::UnexpectedEof),
+                    "The other node disconnected",
+                ));
+            }
 
-        log::debug!("Sent {} old messages.", self.old_msgs.len());
+            match u8::from_le_bytes(marker_byte) {
+                DUMMY_BYTE => {
+                    log::debug!("Received dummy byte!");
+                    // Testcases are only read once the link was set up, and authenticated if a key is set
+                    let node_msg = link
+                        .read_frame_body(u32::MAX as usize)
+                        .await?
+                        .into_boxed_slice();
+                    return Ok(Some(MultiMachineMsg::from_llmp_msg(node_msg)));
+                }
+                CONTROL_BYTE => {
+                    let control: NodeControl =
+                        postcard::from_bytes(&link.read_frame_body(MAX_CONTROL_FRAME_SIZE).await?)?;
+                    log::debug!("Received control message {control:?}");
+                    if let NodeControl::Ancestor { addr } = control {
+                        link.ancestor = addr;
+                    }
+                }
+                _ => {
+                    return Err(Error::illegal_state(
+                        "Received garbage from another node. Is the same link key set on all nodes?",
+                    ));
+                }
+            }
+        }
+    }
 
-        Ok(())
+    /// Write an [`OwnedTcpMultiMachineMsg`] to a stream.
+    /// Can be read back using [`TcpMultiMachineState::read_msg`].
+    async fn write_msg<'a, I: Input>(
+        link: &mut NodeLink,
+        msg: &MultiMachineMsg<'a, I>,
+    ) -> Result<(), Error> {
+        link.write_frame(DUMMY_BYTE, msg.serialize_as_ref()).await
     }
 
     pub(crate) async fn send_interesting_event_to_nodes<'a, I: Input>(
@@ -464,12 +1064,13 @@ where
             if let Some(parent) = &mut self.parent {
                 log::debug!("Sending to parent...");
                 if let Err(e) = Self::write_msg(parent, msg).await {
-                    log::error!(
-                        "The parent disconnected. We won't try to communicate with it again."
-                    );
+                    log::error!("The parent disconnected. We will try to attach to a new parent.");
                     log::error!("Error: {e:?}");
-                    self.parent.take();
+                    self.lose_parent();
+                    self.keep_for_parent(msg.serialize_as_ref());
                 }
+            } else if self.node_descriptor.parent_addr.is_some() {
+                self.keep_for_parent(msg.serialize_as_ref());
             }
         }
 
@@ -524,6 +1125,7 @@ where
                     Ok(Some(msg)) => {
                         log::debug!("Received event from parent");
                         // The parent has something for us, we store it
+                        self.known.insert(hash_std(msg.serialize_as_ref()));
                         msgs.push(msg);
                         // nb_received += 1;
                     }
@@ -537,9 +1139,16 @@ where
                     Err(Error::OsError(_, _, _)) => {
                         // most likely the parent disconnected. drop the connection
                         log::debug!(
-                            "The parent disconnected. We won't try to communicate with it again."
+                            "The parent disconnected. We will try to attach to a new parent."
                         );
-                        self.parent.take();
+                        self.lose_parent();
+                        break;
+                    }
+
+                    Err(e @ Error::IllegalState(..)) => {
+                        // the parent sent something we could not authenticate, don't trust it anymore
+                        log::error!("Dropping the link to the parent: {e}");
+                        self.lose_parent();
                         break;
                     }
 
@@ -570,6 +1179,7 @@ where
                     Ok(Some(msg)) => {
                         // The parent has something for us, we store it
                         log::debug!("Received event from child!");
+                        self.known.insert(hash_std(msg.serialize_as_ref()));
                         msgs.push(msg);
                         // nb_received += 1;
                     }
@@ -590,6 +1200,13 @@ where
                         break;
                     }
 
+                    Err(e @ Error::IllegalState(..)) => {
+                        // the child sent something we could not authenticate, don't trust it anymore
+                        log::error!("Dropping the link to child {child_id:?}: {e}");
+                        ids_to_remove.push(*child_id);
+                        break;
+                    }
+
                     Err(e) => {
                         // Other error
                         log::debug!("An error occurred and was not expected.");
@@ -608,3 +1225,224 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        string::{String, ToString},
+        vec,
+    };
+    use std::collections::{HashSet, VecDeque};
+
+    use libafl_bolts::{hash_std, Error};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        runtime::Runtime,
+        sync::RwLock,
+    };
+
+    use super::{
+        NodeControl, NodeDescriptor, NodeId, NodeLink, TcpMultiMachineState, CONTROL_BYTE,
+        DUMMY_BYTE,
+    };
+
+    /// Both ends of a loopback connection
+    async fn link_pair() -> (NodeLink, NodeLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (connected, accepted) = tokio::join!(
+            TcpStream::connect(listener.local_addr().unwrap()),
+            listener.accept()
+        );
+        (
+            NodeLink::new(connected.unwrap()),
+            NodeLink::new(accepted.unwrap().0),
+        )
+    }
+
+    /// Welcomes one child on `listener`, as the listening loop does
+    async fn welcome_one(
+        listener: &TcpListener,
+        node_descriptor: &NodeDescriptor<String>,
+    ) -> Result<(), Error> {
+        let (stream, addr) = listener.accept().await?;
+        let (mut link, _, _) = NodeLink::welcome(stream, addr, node_descriptor).await?;
+        link.write_control(&NodeControl::Ancestor { addr: None })
+            .await
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_parent_loss() {
+        let descriptor = NodeDescriptor::builder()
+            .parent_addr(Some("10.0.0.1:50000".to_string()))
+            .fallback_parents(vec!["10.0.0.3:50000".to_string()])
+            .replay_capacity(2)
+            .build();
+        let mut state = TcpMultiMachineState::new(descriptor);
+
+        Runtime::new().unwrap().block_on(async {
+            let (mut parent, _other) = link_pair().await;
+            parent.addr = Some("10.0.0.1:50000".to_string());
+            parent.ancestor = Some("10.0.0.2:50000".to_string());
+            state.parent = Some(parent);
+            state.lose_parent();
+            assert!(state.parent.is_none());
+            assert_eq!(state.lost_parent.as_deref(), Some("10.0.0.1:50000"));
+            assert_eq!(
+                state.parent_candidates(),
+                ["10.0.0.1:50000", "10.0.0.2:50000", "10.0.0.3:50000"]
+            );
+
+            // Only the last testcases are kept for the next parent
+            for msg in [b"a", b"b", b"c"] {
+                state.keep_for_parent(msg);
+            }
+            assert_eq!(state.missed_by_parent, [b"b".to_vec(), b"c".to_vec()]);
+
+            // A parent lost while replaying keeps the testcases it did not get first
+            let (mut parent, _other) = link_pair().await;
+            parent.addr = Some("10.0.0.2:50000".to_string());
+            state.missed_by_parent = VecDeque::from([b"d".to_vec()]);
+            state.requeue_for_parent(VecDeque::from([b"b".to_vec(), b"c".to_vec()]), parent);
+            assert_eq!(state.missed_by_parent, [b"c".to_vec(), b"d".to_vec()]);
+            assert_eq!(state.lost_parent.as_deref(), Some("10.0.0.2:50000"));
+        });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_reattach() {
+        let descriptor = NodeDescriptor::builder()
+            .parent_addr(Some("10.0.0.1:50000".to_string()))
+            .build();
+        let state = RwLock::new(TcpMultiMachineState::new(descriptor));
+
+        Runtime::new().unwrap().block_on(async {
+            let (mut parent, mut parent_end) = link_pair().await;
+            let (child, mut child_end) = link_pair().await;
+            {
+                let mut state_guard = state.write().await;
+                state_guard.children.insert(NodeId::new(), child);
+                state_guard.keep_for_parent(b"a");
+                state_guard.keep_for_parent(b"b");
+            }
+
+            parent.addr = Some("10.0.0.2:50000".to_string());
+            TcpMultiMachineState::reattach(&state, parent).await;
+
+            // The new parent gets the testcases it missed
+            for expected in [b"a", b"b"] {
+                let mut marker = [0];
+                parent_end.stream.read_exact(&mut marker).await.unwrap();
+                assert_eq!(marker[0], DUMMY_BYTE);
+                assert_eq!(
+                    parent_end.read_frame_body(usize::MAX).await.unwrap(),
+                    expected
+                );
+            }
+
+            // The children learn about their new ancestor
+            let NodeControl::Ancestor { addr } = child_end.read_control().await.unwrap() else {
+                panic!("Expected the new ancestor");
+            };
+            assert_eq!(addr.as_deref(), Some("10.0.0.2:50000"));
+
+            let state_guard = state.read().await;
+            assert!(state_guard.parent.is_some());
+            assert!(state_guard.missed_by_parent.is_empty());
+        });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_sync_child() {
+        let descriptor = NodeDescriptor::<String>::builder()
+            .parent_addr(None)
+            .sync_batch_size(2)
+            .build();
+        let state = RwLock::new(TcpMultiMachineState::new(descriptor));
+
+        Runtime::new().unwrap().block_on(async {
+            {
+                let mut state_guard = state.write().await;
+                for msg in [b"a", b"b", b"c"] {
+                    state_guard.add_past_msg(msg);
+                }
+            }
+            let known = HashSet::from([hash_std(b"b")]);
+
+            let (child, mut child_end) = link_pair().await;
+            TcpMultiMachineState::sync_child(&state, child, &known)
+                .await
+                .unwrap();
+            assert_eq!(state.read().await.children.len(), 1);
+
+            // Only the testcases the child misses are sent
+            for expected in [b"a", b"c"] {
+                let mut marker = [0];
+                child_end.stream.read_exact(&mut marker).await.unwrap();
+                assert_eq!(marker[0], DUMMY_BYTE);
+                assert_eq!(
+                    child_end.read_frame_body(usize::MAX).await.unwrap(),
+                    expected
+                );
+            }
+            state.write().await.children.clear();
+            let mut marker = [0];
+            assert!(child_end.stream.read_exact(&mut marker).await.is_err());
+        });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_node_link_oversized_hello() {
+        Runtime::new().unwrap().block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let parent = NodeDescriptor::builder().parent_addr(None).build();
+
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(&[CONTROL_BYTE]).await.unwrap();
+            stream.write_all(&u32::MAX.to_le_bytes()).await.unwrap();
+            assert!(welcome_one(&listener, &parent).await.is_err());
+        });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(feature = "llmp_encryption")]
+    fn test_node_link_key() {
+        use libafl_bolts::secure_link::LinkKey;
+
+        let descriptor = |key: &str| {
+            NodeDescriptor::<String>::builder()
+                .parent_addr(None)
+                .node_listening_port(None)
+                .link_key(Some(LinkKey::from_passphrase(key)))
+                .build()
+        };
+        let parent = descriptor("correct horse battery staple");
+
+        Runtime::new().unwrap().block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+
+            let child = descriptor("correct horse battery staple");
+            let (welcomed, joined) = tokio::join!(
+                welcome_one(&listener, &parent),
+                NodeLink::join(addr.clone(), None, &[], &child)
+            );
+            welcomed.unwrap();
+            assert!(joined.unwrap().channel.is_some());
+
+            let child = descriptor("incorrect horse battery staple");
+            let (welcomed, joined) = tokio::join!(
+                welcome_one(&listener, &parent),
+                NodeLink::join(addr.clone(), None, &[], &child)
+            );
+            assert!(welcomed.is_err());
+            assert!(joined.is_err());
+        });
+    }
+}
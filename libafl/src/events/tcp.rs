@@ -14,14 +14,17 @@ use std::{
     sync::Arc,
 };
 
-#[cfg(feature = "tcp_compression")]
-use libafl_bolts::compress::GzipCompressor;
 #[cfg(any(windows, not(feature = "fork")))]
 use libafl_bolts::os::startable_self;
 #[cfg(all(unix, not(miri)))]
 use libafl_bolts::os::unix_signals::setup_signal_handler;
 #[cfg(all(feature = "fork", unix))]
 use libafl_bolts::os::{fork, ForkResult};
+#[cfg(feature = "tcp_compression")]
+use libafl_bolts::{
+    compress::{CodecCompressor, CompressionCodec, Compressor},
+    llmp::Flags,
+};
 use libafl_bolts::{
     core_affinity::CoreId,
     os::CTRL_C_EXIT,
@@ -32,13 +35,16 @@ use libafl_bolts::{
 };
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf},
     sync::{broadcast, broadcast::error::RecvError, mpsc},
     task::{spawn, JoinHandle},
+    time::timeout,
 };
 use typed_builder::TypedBuilder;
 
 use super::{std_maybe_report_progress, std_report_progress, AwaitRestartSafe, SendExiting};
+#[cfg(feature = "tcp_compression")]
+use crate::events::LogSeverity;
 #[cfg(all(unix, not(miri)))]
 use crate::events::EVENTMGR_SIGHANDLER_STATE;
 use crate::{
@@ -82,6 +88,326 @@ where
 
 const UNDEFINED_CLIENT_ID: ClientId = ClientId(0xffffffff);
 
+/// The time the broker waits for a new client to send its id, before dropping the connection
+const TCP_CLIENT_ID_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The codec handshake, exchanged after the client ids.
+///
+/// Each step is a gzip-compressed [`Event::Log`] in the old framing, so that brokers not knowing
+/// about codecs just log the offer, and the client then keeps sending and receiving gzip events.
+/// Once both ends agreed on codecs, each event is prefixed with its [`Flags`].
+#[cfg(feature = "tcp_compression")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CodecsHandshake {
+    /// Sent by the client with the codecs it can decompress, see [`Flags::supported_codecs`]
+    Offer(Flags),
+    /// Sent by the broker with the codecs both ends can decompress.
+    /// The events the broker sends afterwards are prefixed with their [`Flags`].
+    Answer(Flags),
+    /// Sent by the client once it got the answer.
+    /// The events the client sends afterwards are prefixed with their [`Flags`].
+    Ack,
+}
+
+#[cfg(feature = "tcp_compression")]
+impl CodecsHandshake {
+    /// The start of the log message of each step
+    const PREFIX: &'static str = "LibAFL tcp codecs ";
+    /// Handshake frames are way smaller than this, larger frames are not checked for them
+    const MAX_FRAME_LEN: usize = 256;
+
+    /// Serializes this step to a frame payload
+    fn to_frame(self) -> Result<Vec<u8>, Error> {
+        let message = match self {
+            Self::Offer(codecs) => format!("{}offer {}", Self::PREFIX, codecs.0),
+            Self::Answer(codecs) => format!("{}answer {}", Self::PREFIX, codecs.0),
+            Self::Ack => format!("{}ack", Self::PREFIX),
+        };
+        let event = Event::<()>::Log {
+            severity_level: LogSeverity::Debug,
+            message,
+            phantom: PhantomData,
+        };
+        Ok(CompressionCodec::Gzip.compress(&postcard::to_allocvec(&event)?))
+    }
+
+    /// Parses a frame payload sent in the old framing, if it is a step of the handshake
+    fn from_frame(buf: &[u8]) -> Option<Self> {
+        if buf.len() > Self::MAX_FRAME_LEN {
+            return None;
+        }
+        let event_bytes = CompressionCodec::Gzip.decompress(buf).ok()?;
+        let Event::Log { message, .. } = postcard::from_bytes::<Event<()>>(&event_bytes).ok()?
+        else {
+            return None;
+        };
+        let step = message.strip_prefix(Self::PREFIX)?;
+        let (kind, codecs) = step.split_once(' ').unwrap_or((step, ""));
+        match kind {
+            "offer" => Some(Self::Offer(Flags(codecs.parse().ok()?))),
+            "answer" => Some(Self::Answer(Flags(codecs.parse().ok()?))),
+            "ack" => Some(Self::Ack),
+            _ => None,
+        }
+    }
+}
+
+/// The clients a broker knows about, indexed by their [`ClientId`]
+#[derive(Debug, Default)]
+struct TcpBrokerClients {
+    recv_handles: Vec<JoinHandle<()>>,
+    receivers: Vec<Arc<tokio::sync::Mutex<broadcast::Receiver<Vec<u8>>>>>,
+}
+
+/// The sending end of a client connection in the broker, shared by the task forwarding events
+/// to the client, and the one answering its codec handshake.
+#[derive(Debug)]
+struct TcpClientWriter {
+    write: WriteHalf<tokio::net::TcpStream>,
+    /// The codecs the client agreed on, `None` as long as it gets events in the old framing
+    #[cfg(feature = "tcp_compression")]
+    codecs: Option<Flags>,
+}
+
+impl TcpClientWriter {
+    /// Writes a frame, with the message length first.
+    /// `buf` starts with the id of the client that sent the message, which is not counted.
+    async fn write_frame(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        // subtract 4 since the client_id isn't part of the actual message.
+        let len = u32::try_from(buf.len() - 4).unwrap();
+        // Write message length
+        self.write.write_all(&len.to_le_bytes()).await?;
+        // Write the rest
+        self.write.write_all(buf).await
+    }
+}
+
+/// Handshakes with a new client, then spawns the tasks receiving from and forwarding to it.
+async fn serve_tcp_client(
+    socket: tokio::net::TcpStream,
+    clients: Arc<tokio::sync::Mutex<TcpBrokerClients>>,
+    exit_cleanly_after: Option<NonZeroUsize>,
+    tx: mpsc::Sender<Vec<u8>>,
+    rx: broadcast::Receiver<Vec<u8>>,
+) {
+    let (mut read, write) = tokio::io::split(socket);
+
+    // Protocol: the new client communicate its old ClientId or -1 if new
+    let mut this_client_id = [0; 4];
+    match timeout(TCP_CLIENT_ID_TIMEOUT, read.read_exact(&mut this_client_id)).await {
+        Ok(Ok(_)) => (),
+        Ok(Err(e)) => {
+            log::warn!("TCP Manager - A new client closed its socket: {e}");
+            return;
+        }
+        Err(_) => {
+            log::warn!("TCP Manager - Dropping a new client that did not send its id");
+            return;
+        }
+    }
+    let this_client_id = ClientId(u32::from_le_bytes(this_client_id));
+
+    let mut clients = clients.lock().await;
+
+    // we waited for all the clients we wanted to see attached. Now wait for them to close their tcp connections.
+    let reached_max = exit_cleanly_after
+        .is_some_and(|max_clients| max_clients.get() <= clients.recv_handles.len());
+
+    let (this_client_id, is_old) = if this_client_id == UNDEFINED_CLIENT_ID {
+        if reached_max {
+            (UNDEFINED_CLIENT_ID, false) // Dumb id
+        } else {
+            // ClientIds for this broker start at 0.
+            (
+                ClientId(clients.recv_handles.len().try_into().unwrap()),
+                false,
+            )
+        }
+    } else {
+        (this_client_id, true)
+    };
+
+    let client_idx = this_client_id.0 as usize;
+    if is_old && client_idx >= clients.recv_handles.len() {
+        log::warn!(
+            "TCP Manager - Dropping a client reconnecting with unknown id {this_client_id:?}"
+        );
+        return;
+    }
+
+    let this_client_id_bytes = this_client_id.0.to_le_bytes();
+
+    let mut writer = TcpClientWriter {
+        write,
+        #[cfg(feature = "tcp_compression")]
+        codecs: None,
+    };
+
+    // Protocol: Send the client id for this node;
+    if writer.write.write_all(&this_client_id_bytes).await.is_err() {
+        log::info!("Socket closed, client restarting");
+        return;
+    }
+
+    if !is_old && reached_max {
+        return;
+    }
+
+    let writer = Arc::new(tokio::sync::Mutex::new(writer));
+    let handle = receive_from_tcp_client(read, writer.clone(), tx);
+
+    // Keep all handles around.
+    if is_old {
+        clients.recv_handles[client_idx].abort();
+        clients.recv_handles[client_idx] = spawn(handle);
+    } else {
+        clients.recv_handles.push(spawn(handle));
+        // Get old messages only if new
+        clients
+            .receivers
+            .push(Arc::new(tokio::sync::Mutex::new(rx)));
+    }
+
+    let rx_inner = clients.receivers[client_idx].clone();
+    drop(clients);
+
+    // The forwarding end. No need to keep a handle to this (TODO: unless they don't quit/get stuck?)
+    spawn(async move {
+        // In a loop, read data from the socket and write the data back.
+        loop {
+            let buf: Vec<u8> = match rx_inner.lock().await.recv().await {
+                Ok(buf) => buf,
+                Err(RecvError::Lagged(num)) => {
+                    log::error!("Receiver lagged, skipping {num} messages");
+                    continue;
+                }
+                _ => panic!("Could not receive"),
+            };
+
+            log::debug!("TCP Manager - {buf:?}");
+
+            if buf.len() <= 4 {
+                log::warn!("We got no contents (or only the length) in a broadcast");
+                continue;
+            }
+
+            if buf[..4] == this_client_id_bytes {
+                log::debug!(
+                    "TCP Manager - Not forwarding message from this very client ({this_client_id:?})."
+                );
+                continue;
+            }
+
+            let mut writer = writer.lock().await;
+
+            #[cfg(feature = "tcp_compression")]
+            let buf = match frame_for_client(writer.codecs, &buf) {
+                Ok(buf) => buf,
+                Err(e) => {
+                    log::warn!("TCP Manager - Not forwarding an event to {this_client_id:?}: {e}");
+                    continue;
+                }
+            };
+
+            if writer.write_frame(&buf).await.is_err() {
+                // The socket is closed, the client is restarting
+                log::info!("Socket closed, client restarting");
+                return;
+            }
+        }
+    });
+}
+
+/// Reads the events of a client, and passes them to the broker.
+/// With `tcp_compression`, the events are passed on prefixed with their [`Flags`], whatever the client sent.
+async fn receive_from_tcp_client(
+    mut read: ReadHalf<tokio::net::TcpStream>,
+    writer: Arc<tokio::sync::Mutex<TcpClientWriter>>,
+    tx: mpsc::Sender<Vec<u8>>,
+) {
+    // Set once the client acknowledged the codecs
+    #[cfg(feature = "tcp_compression")]
+    let mut prefixed = false;
+    // Only needed to answer the codec handshake
+    #[cfg(not(feature = "tcp_compression"))]
+    drop(writer);
+
+    // In a loop, read data from the socket and write the data back.
+    loop {
+        let mut len_buf = [0; 4];
+
+        if read.read_exact(&mut len_buf).await.is_err() {
+            // The socket is closed, the client is restarting
+            log::info!("Socket closed, client restarting");
+            return;
+        }
+
+        let mut len = u32::from_le_bytes(len_buf);
+        // we forward the sender id as well, so we add 4 bytes to the message length
+        len += 4;
+
+        log::debug!("TCP Manager - len +4 = {len:?}");
+
+        let mut buf = vec![0; len as usize];
+
+        if read
+            .read_exact(&mut buf)
+            .await
+            // .expect("Failed to read data from socket"); // TODO verify if we have to handle this error
+            .is_err()
+        {
+            // The socket is closed, the client is restarting
+            log::info!("Socket closed, client restarting");
+            return;
+        }
+
+        log::debug!("TCP Manager - len: {len:?} - {buf:?}");
+
+        #[cfg(feature = "tcp_compression")]
+        if !prefixed {
+            match CodecsHandshake::from_frame(&buf[4..]) {
+                Some(CodecsHandshake::Offer(codecs)) => {
+                    let codecs = codecs & Flags::supported_codecs();
+                    let mut answer = UNDEFINED_CLIENT_ID.0.to_le_bytes().to_vec();
+                    answer.extend(
+                        CodecsHandshake::Answer(codecs)
+                            .to_frame()
+                            .expect("Could not serialize the codecs"),
+                    );
+                    let mut writer = writer.lock().await;
+                    if writer.write_frame(&answer).await.is_err() {
+                        log::info!("Socket closed, client restarting");
+                        return;
+                    }
+                    // Protocol: from now on, events to this client are prefixed with their flags
+                    writer.codecs = Some(codecs);
+                    continue;
+                }
+                Some(CodecsHandshake::Ack) => {
+                    // Protocol: from now on, events from this client are prefixed with their flags
+                    prefixed = true;
+                    continue;
+                }
+                Some(CodecsHandshake::Answer(_)) => {
+                    log::warn!("TCP Manager - A client sent a codecs answer, ignoring it");
+                    continue;
+                }
+                None => {
+                    let event_bytes = buf.split_off(4);
+                    buf.extend(
+                        Flags::compressed_with(CompressionCodec::Gzip)
+                            .0
+                            .to_le_bytes(),
+                    );
+                    buf.extend(event_bytes);
+                }
+            }
+        }
+
+        tx.send(buf).await.expect("Could not send");
+    }
+}
+
 impl<I, MT> TcpEventBroker<I, MT>
 where
     I: Input,
@@ -114,7 +440,6 @@ where
     /// Run in the broker until all clients exit
     // TODO: remove expect(clippy::needless_return) when clippy is fixed
     #[tokio::main(flavor = "current_thread")]
-    #[expect(clippy::too_many_lines)]
     pub async fn broker_loop(&mut self) -> Result<(), Error> {
         let (tx_bc, rx) = broadcast::channel(65536);
         let (tx, mut rx_mpsc) = mpsc::channel(65536);
@@ -128,152 +453,21 @@ where
         let listener = tokio::net::TcpListener::from_std(listener)?;
 
         let tokio_broker = spawn(async move {
-            let mut recv_handles: Vec<JoinHandle<_>> = vec![];
-            let mut receivers: Vec<Arc<tokio::sync::Mutex<broadcast::Receiver<_>>>> = vec![];
+            let clients = Arc::new(tokio::sync::Mutex::new(TcpBrokerClients::default()));
 
             loop {
-                let mut reached_max = false;
-                if let Some(max_clients) = exit_cleanly_after {
-                    if max_clients.get() <= recv_handles.len() {
-                        // we waited for all the clients we wanted to see attached. Now wait for them to close their tcp connections.
-                        reached_max = true;
-                    }
-                }
-
                 // Asynchronously wait for an inbound socket.
                 let (socket, _) = listener.accept().await.expect("Accept failed");
-                let (mut read, mut write) = tokio::io::split(socket);
-
-                // Protocol: the new client communicate its old ClientId or -1 if new
-                let mut this_client_id = [0; 4];
-                read.read_exact(&mut this_client_id)
-                    .await
-                    .expect("Socket closed?");
-                let this_client_id = ClientId(u32::from_le_bytes(this_client_id));
-
-                let (this_client_id, is_old) = if this_client_id == UNDEFINED_CLIENT_ID {
-                    if reached_max {
-                        (UNDEFINED_CLIENT_ID, false) // Dumb id
-                    } else {
-                        // ClientIds for this broker start at 0.
-                        (ClientId(recv_handles.len().try_into().unwrap()), false)
-                    }
-                } else {
-                    (this_client_id, true)
-                };
-
-                let this_client_id_bytes = this_client_id.0.to_le_bytes();
-
-                // Protocol: Send the client id for this node;
-                write.write_all(&this_client_id_bytes).await.unwrap();
-
-                if !is_old && reached_max {
-                    continue;
-                }
-
-                let tx_inner = tx.clone();
-
-                let handle = async move {
-                    // In a loop, read data from the socket and write the data back.
-                    loop {
-                        let mut len_buf = [0; 4];
-
-                        if read.read_exact(&mut len_buf).await.is_err() {
-                            // The socket is closed, the client is restarting
-                            log::info!("Socket closed, client restarting");
-                            return;
-                        }
-
-                        let mut len = u32::from_le_bytes(len_buf);
-                        // we forward the sender id as well, so we add 4 bytes to the message length
-                        len += 4;
-
-                        log::debug!("TCP Manager - len +4 = {len:?}");
-
-                        let mut buf = vec![0; len as usize];
-
-                        if read
-                            .read_exact(&mut buf)
-                            .await
-                            // .expect("Failed to read data from socket"); // TODO verify if we have to handle this error
-                            .is_err()
-                        {
-                            // The socket is closed, the client is restarting
-                            log::info!("Socket closed, client restarting");
-                            return;
-                        }
-
-                        log::debug!("TCP Manager - len: {len:?} - {buf:?}");
-                        tx_inner.send(buf).await.expect("Could not send");
-                    }
-                };
-
-                let client_idx = this_client_id.0 as usize;
-
-                // Keep all handles around.
-                if is_old {
-                    recv_handles[client_idx].abort();
-                    recv_handles[client_idx] = spawn(handle);
-                } else {
-                    recv_handles.push(spawn(handle));
-                    // Get old messages only if new
-                    let rx_inner = Arc::new(tokio::sync::Mutex::new(rx.resubscribe()));
-                    receivers.push(rx_inner.clone());
-                }
-
-                let rx_inner = receivers[client_idx].clone();
-
-                // The forwarding end. No need to keep a handle to this (TODO: unless they don't quit/get stuck?)
-                spawn(async move {
-                    // In a loop, read data from the socket and write the data back.
-                    loop {
-                        let buf: Vec<u8> = match rx_inner.lock().await.recv().await {
-                            Ok(buf) => buf,
-                            Err(RecvError::Lagged(num)) => {
-                                log::error!("Receiver lagged, skipping {num} messages");
-                                continue;
-                            }
-                            _ => panic!("Could not receive"),
-                        };
-
-                        log::debug!("TCP Manager - {buf:?}");
-
-                        if buf.len() <= 4 {
-                            log::warn!("We got no contents (or only the length) in a broadcast");
-                            continue;
-                        }
-
-                        if buf[..4] == this_client_id_bytes {
-                            log::debug!("TCP Manager - Not forwarding message from this very client ({this_client_id:?})."
-                        );
-                            continue;
-                        }
 
-                        // subtract 4 since the client_id isn't part of the actual message.
-                        let len = u32::try_from(buf.len() - 4).unwrap();
-                        let len_buf: [u8; 4] = len.to_le_bytes();
-
-                        // Write message length
-                        if write.write_all(&len_buf).await.is_err() {
-                            // The socket is closed, the client is restarting
-                            log::info!("Socket closed, client restarting");
-                            return;
-                        }
-                        // Write the rest
-                        if write.write_all(&buf).await.is_err() {
-                            // The socket is closed, the client is restarting
-                            log::info!("Socket closed, client restarting");
-                            return;
-                        }
-                    }
-                });
+                // Handshake in a task of its own, so that a stuck client does not hold up the others
+                spawn(serve_tcp_client(
+                    socket,
+                    clients.clone(),
+                    exit_cleanly_after,
+                    tx.clone(),
+                    rx.resubscribe(),
+                ));
             }
-
-            /*log::info!("Joining handles..");
-            // wait for all clients to exit/error out
-            for recv_handle in recv_handles {
-                drop(recv_handle.await);
-            }*/
         });
 
         loop {
@@ -289,7 +483,19 @@ where
             let event_bytes = &buf[4..];
 
             #[cfg(feature = "tcp_compression")]
-            let event_bytes = &GzipCompressor::new().decompress(event_bytes)?;
+            let decompressed;
+            #[cfg(feature = "tcp_compression")]
+            let event_bytes = match event_codec(event_bytes, true) {
+                Ok((Some(codec), compressed)) => {
+                    decompressed = codec.decompress(compressed)?;
+                    &decompressed
+                }
+                Ok((None, event_bytes)) => event_bytes,
+                Err(e) => {
+                    log::warn!("TCP Manager - Skipping an event from {client_id:?}: {e}");
+                    continue;
+                }
+            };
 
             let event: Event<I> = postcard::from_bytes(event_bytes)?;
             match Self::handle_in_broker(
@@ -414,6 +620,67 @@ where
     }
 }
 
+/// Compresses a serialized event.
+/// Once the codecs are agreed on, the event is `prefixed` with the [`Flags`] telling the receiver
+/// which codec to decompress it with. Before that, the compressor uses gzip.
+#[cfg(feature = "tcp_compression")]
+fn compress_event(compressor: &CodecCompressor, prefixed: bool, event_bytes: &[u8]) -> Vec<u8> {
+    let mut buf = if prefixed {
+        Flags::compressed_with(compressor.codec())
+            .0
+            .to_le_bytes()
+            .to_vec()
+    } else {
+        Vec::new()
+    };
+    buf.extend(compressor.compress(event_bytes));
+    buf
+}
+
+/// The codec an event compressed by [`compress_event`] was compressed with, and its compressed bytes.
+/// Fails if the sender used a codec that is not enabled in this build.
+#[cfg(feature = "tcp_compression")]
+fn event_codec(buf: &[u8], prefixed: bool) -> Result<(Option<CompressionCodec>, &[u8]), Error> {
+    if !prefixed {
+        return Ok((Some(CompressionCodec::Gzip), buf));
+    }
+    let Some((flags, event_bytes)) = buf.split_first_chunk::<4>() else {
+        return Err(Error::illegal_state("Received a truncated event"));
+    };
+    Ok((
+        Flags(u32::from_le_bytes(*flags)).compression_codec()?,
+        event_bytes,
+    ))
+}
+
+/// Converts a frame received by the broker, with a prefixed event, for a client that agreed on `codecs`.
+/// Clients that did not agree on codecs get gzip events without prefix,
+/// others get the event as is if they can decompress it, else recompressed with gzip.
+#[cfg(feature = "tcp_compression")]
+fn frame_for_client(codecs: Option<Flags>, buf: &[u8]) -> Result<Vec<u8>, Error> {
+    let (client_id, event_bytes) = buf.split_at(4);
+    let (codec, compressed) = event_codec(event_bytes, true)?;
+    let flags = Flags(u32::from_le_bytes(event_bytes[..4].try_into().unwrap()));
+    let gzip = Flags::compressed_with(CompressionCodec::Gzip);
+
+    let mut frame = client_id.to_vec();
+    match codecs {
+        Some(codecs) if flags.readable_with(codecs) => frame.extend(event_bytes),
+        None if flags == gzip => frame.extend(compressed),
+        _ => {
+            if codecs.is_some() {
+                frame.extend(gzip.0.to_le_bytes());
+            }
+            let recompressed = match codec {
+                Some(codec) => CompressionCodec::Gzip.compress(&codec.decompress(compressed)?),
+                None => CompressionCodec::Gzip.compress(compressed),
+            };
+            frame.extend(recompressed);
+        }
+    }
+    Ok(frame)
+}
+
 /// An `EventManager` that forwards all events to other attached via tcp.
 pub struct TcpEventManager<EMH, I, S> {
     /// We send message every `throttle` second
@@ -426,7 +693,13 @@ pub struct TcpEventManager<EMH, I, S> {
     /// Our `CientId`
     client_id: ClientId,
     #[cfg(feature = "tcp_compression")]
-    compressor: GzipCompressor,
+    compressor: CodecCompressor,
+    /// The codec to compress events with, if the broker agrees to it
+    #[cfg(feature = "tcp_compression")]
+    compression_codec: CompressionCodec,
+    /// The codecs the broker agreed on, `None` as long as events are sent in the old framing
+    #[cfg(feature = "tcp_compression")]
+    compression_codecs: Option<Flags>,
     /// The configuration defines this specific fuzzer.
     /// A node will not re-use the observer values sent over TCP
     /// from nodes with other configurations.
@@ -446,6 +719,8 @@ impl<I, S> TcpEventManager<(), I, S> {
 #[derive(Debug, Copy, Clone)]
 pub struct TcpEventManagerBuilder<EMH, I, S> {
     throttle: Option<Duration>,
+    #[cfg(feature = "tcp_compression")]
+    compression_codec: CompressionCodec,
     hooks: EMH,
     phantom: PhantomData<(I, S)>,
 }
@@ -462,6 +737,8 @@ impl<I, S> TcpEventManagerBuilder<(), I, S> {
     pub fn new() -> Self {
         Self {
             throttle: None,
+            #[cfg(feature = "tcp_compression")]
+            compression_codec: CompressionCodec::default(),
             hooks: (),
             phantom: PhantomData,
         }
@@ -472,6 +749,8 @@ impl<I, S> TcpEventManagerBuilder<(), I, S> {
     pub fn hooks<EMH>(self, hooks: EMH) -> TcpEventManagerBuilder<EMH, I, S> {
         TcpEventManagerBuilder {
             throttle: self.throttle,
+            #[cfg(feature = "tcp_compression")]
+            compression_codec: self.compression_codec,
             hooks,
             phantom: PhantomData,
         }
@@ -486,6 +765,15 @@ impl<EMH, I, S> TcpEventManagerBuilder<EMH, I, S> {
         self
     }
 
+    /// Set the codec used to compress outgoing events.
+    /// Incoming events are decompressed with whatever codec their sender used.
+    #[must_use]
+    #[cfg(feature = "tcp_compression")]
+    pub fn compression_codec(mut self, compression_codec: CompressionCodec) -> Self {
+        self.compression_codec = compression_codec;
+        self
+    }
+
     /// Create a manager from a raw TCP client with hooks
     pub fn build_from_client<A: ToSocketAddrs>(
         self,
@@ -505,14 +793,28 @@ impl<EMH, I, S> TcpEventManagerBuilder<EMH, I, S> {
 
         log::info!("Our client id: {client_id:?}");
 
+        // Protocol: offer our codecs, the broker answers if it knows about codecs
+        #[cfg(feature = "tcp_compression")]
+        {
+            let offer = CodecsHandshake::Offer(Flags::supported_codecs()).to_frame()?;
+            tcp.write_all(&u32::try_from(offer.len())?.to_le_bytes())?;
+            tcp.write_all(&client_id.0.to_le_bytes())?;
+            tcp.write_all(&offer)?;
+        }
+
         Ok(TcpEventManager {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
             tcp,
             client_id,
+            // Until the broker agreed on codecs, events are sent with gzip
+            #[cfg(feature = "tcp_compression")]
+            compressor: CodecCompressor::new(CompressionCodec::Gzip),
+            #[cfg(feature = "tcp_compression")]
+            compression_codec: self.compression_codec,
             #[cfg(feature = "tcp_compression")]
-            compressor: GzipCompressor::new(),
+            compression_codecs: None,
             configuration,
             phantom: PhantomData,
         })
@@ -579,6 +881,15 @@ where
 }
 
 impl<EMH, I, S> TcpEventManager<EMH, I, S> {
+    /// Sends a message to the broker, with its length and our client id first
+    fn send_frame(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let size = u32::try_from(buf.len())?;
+        self.tcp.write_all(&size.to_le_bytes())?;
+        self.tcp.write_all(&self.client_id.0.to_le_bytes())?;
+        self.tcp.write_all(buf)?;
+        Ok(())
+    }
+
     /// Send information that this client is exiting.
     /// The other side may free up all allocated memory.
     /// We are no longer allowed to send anything afterwards.
@@ -606,12 +917,13 @@ where
         let serialized = postcard::to_allocvec(&event)?;
 
         #[cfg(feature = "tcp_compression")]
-        let serialized = self.compressor.compress(&serialized);
+        let serialized = compress_event(
+            &self.compressor,
+            self.compression_codecs.is_some(),
+            &serialized,
+        );
 
-        let size = u32::try_from(serialized.len())?;
-        self.tcp.write_all(&size.to_le_bytes())?;
-        self.tcp.write_all(&self.client_id.0.to_le_bytes())?;
-        self.tcp.write_all(&serialized)?;
+        self.send_frame(&serialized)?;
 
         self.last_sent = libafl_bolts::current_time();
         Ok(())
@@ -656,6 +968,23 @@ where
                     let mut buf = vec![0_u8; 4_usize + len as usize];
                     self.tcp.read_exact(&mut buf)?;
 
+                    #[cfg(feature = "tcp_compression")]
+                    if self.compression_codecs.is_none()
+                        && buf[..4] == UNDEFINED_CLIENT_ID.0.to_le_bytes()
+                    {
+                        if let Some(CodecsHandshake::Answer(codecs)) =
+                            CodecsHandshake::from_frame(&buf[4..])
+                        {
+                            // Protocol: acknowledge the codecs, our events are prefixed with their flags afterwards
+                            self.send_frame(&CodecsHandshake::Ack.to_frame()?)?;
+                            self.compressor
+                                .set_codec(codecs.pick_codec(self.compression_codec));
+                            self.compression_codecs = Some(codecs);
+                            self.tcp.set_nonblocking(true).expect("set to non-blocking");
+                            continue;
+                        }
+                    }
+
                     let mut client_id_buf = [0_u8; 4];
                     client_id_buf.copy_from_slice(&buf[..4]);
 
@@ -669,7 +998,19 @@ where
 
                         let buf = &buf[4..];
                         #[cfg(feature = "tcp_compression")]
-                        let buf = &self.compressor.decompress(buf)?;
+                        let decompressed;
+                        #[cfg(feature = "tcp_compression")]
+                        let buf = match event_codec(buf, self.compression_codecs.is_some()) {
+                            Ok((Some(codec), compressed)) => {
+                                decompressed = codec.decompress(compressed)?;
+                                &decompressed
+                            }
+                            Ok((None, buf)) => buf,
+                            Err(e) => {
+                                log::warn!("Skipping an event from {other_client_id:?}: {e}");
+                                continue;
+                            }
+                        };
 
                         // make decompressed vec and slice compatible
                         let event = postcard::from_bytes(buf)?;
@@ -1022,6 +1363,10 @@ pub struct TcpRestartingMgr<EMH, I, MT, S, SP> {
     /// The type of manager to build
     #[builder(default = TcpManagerKind::Any)]
     kind: TcpManagerKind,
+    /// The codec the fuzzer clients use to compress their events
+    #[cfg(feature = "tcp_compression")]
+    #[builder(default)]
+    compression_codec: CompressionCodec,
     /// The amount of external clients that should have connected (not counting our own tcp client)
     /// before this broker quits _after the last client exited_.
     /// If `None`, the broker will never quit when the last client exits, but run forever.
@@ -1239,14 +1584,16 @@ where
             core_id.set_affinity()?;
         }
 
+        let builder = TcpEventManagerBuilder::new().hooks(self.hooks);
+        #[cfg(feature = "tcp_compression")]
+        let builder = builder.compression_codec(self.compression_codec);
+
         // If we're restarting, deserialize the old state.
         let (state, mut mgr) = if let Some((state_opt, this_id)) = staterestorer.restore()? {
             (
                 state_opt,
                 TcpRestartingEventManager::with_save_state(
-                    TcpEventManagerBuilder::new()
-                        .hooks(self.hooks)
-                        .build_on_port(self.broker_port, this_id, self.configuration)?,
+                    builder.build_on_port(self.broker_port, this_id, self.configuration)?,
                     staterestorer,
                     self.serialize_state,
                 ),
@@ -1254,13 +1601,11 @@ where
         } else {
             log::info!("First run. Let's set it all up");
             // Mgr to send and receive msgs from/to all other fuzzer instances
-            let mgr = TcpEventManagerBuilder::new()
-                .hooks(self.hooks)
-                .build_existing_from_env(
-                    &("127.0.0.1", self.broker_port),
-                    _ENV_FUZZER_BROKER_CLIENT_INITIAL,
-                    self.configuration,
-                )?;
+            let mgr = builder.build_existing_from_env(
+                &("127.0.0.1", self.broker_port),
+                _ENV_FUZZER_BROKER_CLIENT_INITIAL,
+                self.configuration,
+            )?;
 
             (
                 None,
@@ -1283,3 +1628,55 @@ where
         Ok((state, mgr))
     }
 }
+
+#[cfg(test)]
+#[cfg(feature = "tcp_compression")]
+mod tests {
+    use libafl_bolts::{
+        compress::CompressionCodec,
+        llmp::{Flags, LLMP_FLAG_LZ4, LLMP_FLAG_ZSTD},
+    };
+
+    use super::{frame_for_client, CodecsHandshake};
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_codecs_handshake() {
+        let supported = Flags::supported_codecs();
+        for step in [
+            CodecsHandshake::Offer(supported),
+            CodecsHandshake::Answer(supported),
+            CodecsHandshake::Ack,
+        ] {
+            let frame = step.to_frame().unwrap();
+            assert_eq!(CodecsHandshake::from_frame(&frame), Some(step));
+        }
+
+        // Events are no handshake steps
+        let event = CompressionCodec::Gzip.compress(b"no log event");
+        assert_eq!(CodecsHandshake::from_frame(&event), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_frame_for_client() {
+        let gzip = Flags::compressed_with(CompressionCodec::Gzip);
+        let compressed = CompressionCodec::Gzip.compress(b"event");
+        let mut buf = 3_u32.to_le_bytes().to_vec();
+        buf.extend(gzip.0.to_le_bytes());
+        buf.extend(&compressed);
+
+        // Clients that agreed on codecs get the prefixed event
+        assert_eq!(frame_for_client(Some(gzip), &buf).unwrap(), buf);
+
+        // Others get the gzip event only
+        let mut old_frame = 3_u32.to_le_bytes().to_vec();
+        old_frame.extend(&compressed);
+        assert_eq!(frame_for_client(None, &buf).unwrap(), old_frame);
+
+        // Events that cannot be decompressed are not forwarded
+        let mut unknown = buf.clone();
+        unknown[4..8].copy_from_slice(&(gzip | LLMP_FLAG_ZSTD | LLMP_FLAG_LZ4).0.to_le_bytes());
+        assert!(frame_for_client(None, &unknown).is_err());
+    }
+}
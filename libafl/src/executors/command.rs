@@ -1,4 +1,6 @@
 //! The command executor executes a sub program for each run
+#[cfg(feature = "regex")]
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::{
     fmt::{self, Debug, Formatter},
@@ -13,7 +15,7 @@ use std::{
 use std::{
     ffi::{OsStr, OsString},
     io::{Read, Write},
-    os::unix::ffi::OsStrExt,
+    os::unix::{ffi::OsStrExt, process::ExitStatusExt},
     path::{Path, PathBuf},
     process::{Child, Command, Stdio},
     time::Duration,
@@ -23,7 +25,7 @@ use std::{
 use libafl_bolts::core_affinity::CoreId;
 use libafl_bolts::{
     fs::{get_unique_std_input_file, InputFile},
-    tuples::{Handle, MatchName, RefIndexable},
+    tuples::{Handle, MatchName, MatchNameRef, RefIndexable},
     AsSlice,
 };
 #[cfg(all(feature = "intel_pt", target_os = "linux"))]
@@ -46,12 +48,22 @@ use nix::{
 use typed_builder::TypedBuilder;
 
 use super::HasTimeout;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+use super::Sandbox;
 #[cfg(target_os = "linux")]
 use crate::executors::hooks::ExecutorHooksTuple;
+#[cfg(feature = "regex")]
+use crate::observers::SanitizerReportObserver;
 use crate::{
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{persistent_command::PersistentCommandExecutor, Executor, ExitKind, HasObservers},
     inputs::HasTargetBytes,
-    observers::{ObserversTuple, StdErrObserver, StdOutObserver},
+    observers::{
+        HangBacktraceObserver, ObserversTuple, SandboxViolationObserver, StdErrObserver,
+        StdOutObserver,
+    },
     state::HasExecutions,
     std::borrow::ToOwned,
     Error,
@@ -88,6 +100,15 @@ pub struct StdCommandConfigurator {
     debug_child: bool,
     stdout_observer: Option<Handle<StdOutObserver>>,
     stderr_observer: Option<Handle<StdErrObserver>>,
+    hang_observer: Option<Handle<HangBacktraceObserver>>,
+    #[cfg(feature = "regex")]
+    sanitizer_observer: Option<Handle<SanitizerReportObserver>>,
+    sandbox_observer: Option<Handle<SandboxViolationObserver>>,
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    sandbox: Option<Sandbox>,
     timeout: Duration,
     /// true: input gets delivered via stdink
     input_location: InputLocation,
@@ -95,6 +116,17 @@ pub struct StdCommandConfigurator {
     command: Command,
 }
 
+impl StdCommandConfigurator {
+    /// If the `stderr` of the child has to be captured
+    fn pipes_stderr(&self) -> bool {
+        #[cfg(feature = "regex")]
+        if self.sanitizer_observer.is_some() {
+            return true;
+        }
+        self.stderr_observer.is_some()
+    }
+}
+
 impl<I> CommandConfigurator<I> for StdCommandConfigurator
 where
     I: HasTargetBytes,
@@ -107,7 +139,29 @@ where
         self.stderr_observer.clone()
     }
 
+    fn hang_observer(&self) -> Option<Handle<HangBacktraceObserver>> {
+        self.hang_observer.clone()
+    }
+
+    #[cfg(feature = "regex")]
+    fn sanitizer_observer(&self) -> Option<Handle<SanitizerReportObserver>> {
+        self.sanitizer_observer.clone()
+    }
+
+    fn sandbox_observer(&self) -> Option<Handle<SandboxViolationObserver>> {
+        self.sandbox_observer.clone()
+    }
+
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    fn sandbox_violations(&self) -> u64 {
+        self.sandbox.as_ref().map_or(0, Sandbox::violations)
+    }
+
     fn spawn_child(&mut self, input: &I) -> Result<Child, Error> {
+        let pipes_stderr = self.pipes_stderr();
         match &mut self.input_location {
             InputLocation::Arg { argnum } => {
                 let args = self.command.get_args();
@@ -121,7 +175,7 @@ where
                 if self.stdout_observer.is_some() {
                     cmd.stdout(Stdio::piped());
                 }
-                if self.stderr_observer.is_some() {
+                if pipes_stderr {
                     cmd.stderr(Stdio::piped());
                 }
 
@@ -146,6 +200,13 @@ where
                 if let Some(cwd) = self.command.get_current_dir() {
                     cmd.current_dir(cwd);
                 }
+                #[cfg(all(
+                    target_os = "linux",
+                    any(target_arch = "x86_64", target_arch = "aarch64")
+                ))]
+                if let Some(sandbox) = &self.sandbox {
+                    sandbox.apply(&mut cmd)?;
+                }
                 Ok(cmd.spawn()?)
             }
             InputLocation::StdIn => {
@@ -341,20 +402,45 @@ where
         *state.executions_mut() += 1;
         self.observers.pre_exec_child_all(state, input)?;
 
+        let violations = self.configurer.sandbox_violations();
         let mut child = self.configurer.spawn_child(input)?;
 
-        let exit_kind = child
+        let exit_kind = if let Some(status) = child
             .wait_timeout(self.configurer.exec_timeout())
             .expect("waiting on child failed")
-            .map(|status| self.configurer.exit_kind_from_status(&status))
-            .unwrap_or_else(|| {
-                // if this fails, there is not much we can do. let's hope it failed because the process finished
-                // in the meantime.
-                drop(child.kill());
-                // finally, try to wait to properly clean up system resources.
-                drop(child.wait());
-                ExitKind::Timeout
-            });
+        {
+            if status.signal() == Some(libc::SIGSYS)
+                || self.configurer.sandbox_violations() != violations
+            {
+                if let Some(h) = self.configurer.sandbox_observer() {
+                    if let Some(obs) = self.observers.get_mut(&h) {
+                        obs.set_violation();
+                    }
+                }
+            }
+            self.configurer.exit_kind_from_status(&status)
+        } else {
+            #[cfg(all(
+                target_os = "linux",
+                target_env = "gnu",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            if let Some(h) = self.configurer.hang_observer() {
+                if let Some(obs) = self.observers.get_mut(&h) {
+                    #[expect(clippy::cast_possible_wrap)]
+                    let pid = Pid::from_raw(child.id() as i32);
+                    if let Err(err) = obs.sample_process(pid) {
+                        log::warn!("Could not sample the stack of the timed-out child: {err:?}");
+                    }
+                }
+            }
+            // if this fails, there is not much we can do. let's hope it failed because the process finished
+            // in the meantime.
+            drop(child.kill());
+            // finally, try to wait to properly clean up system resources.
+            drop(child.wait());
+            ExitKind::Timeout
+        };
 
         self.observers
             .post_exec_child_all(state, input, &exit_kind)?;
@@ -381,6 +467,28 @@ where
             let obs = observers.index_mut(h);
             obs.observe(&stderr);
         }
+        #[cfg(feature = "regex")]
+        if let Some(h) = self.configurer.sanitizer_observer() {
+            let stderr = if let Some(stderr_h) = self.configurer.stderr_observer() {
+                let mut observers = self.observers_mut();
+                observers
+                    .index_mut(&stderr_h)
+                    .output
+                    .clone()
+                    .unwrap_or_default()
+            } else {
+                let mut stderr = Vec::new();
+                child.stderr.as_mut().ok_or_else(|| {
+                     Error::illegal_state(
+                         "SanitizerReportObserver tries to read stderr, but stderr was not `Stdio::pipe` in CommandExecutor",
+                     )
+                 })?.read_to_end(&mut stderr)?;
+                stderr
+            };
+            let mut observers = self.observers_mut();
+            let obs = observers.index_mut(&h);
+            obs.parse_output(&String::from_utf8_lossy(&stderr));
+        }
         Ok(exit_kind)
     }
 }
@@ -514,6 +622,15 @@ where
 pub struct CommandExecutorBuilder {
     stdout: Option<Handle<StdOutObserver>>,
     stderr: Option<Handle<StdErrObserver>>,
+    hang: Option<Handle<HangBacktraceObserver>>,
+    #[cfg(feature = "regex")]
+    sanitizer: Option<Handle<SanitizerReportObserver>>,
+    sandbox_violation: Option<Handle<SandboxViolationObserver>>,
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    sandbox: Option<Sandbox>,
     debug_child: bool,
     program: Option<OsString>,
     args: Vec<OsString>,
@@ -536,6 +653,15 @@ impl CommandExecutorBuilder {
         CommandExecutorBuilder {
             stdout: None,
             stderr: None,
+            hang: None,
+            #[cfg(feature = "regex")]
+            sanitizer: None,
+            sandbox_violation: None,
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            sandbox: None,
             program: None,
             args: vec![],
             input_location: InputLocation::StdIn,
@@ -592,6 +718,38 @@ impl CommandExecutorBuilder {
         self
     }
 
+    /// Sets the observer collecting the backtrace of the child when it times out
+    pub fn hang_observer(&mut self, hang: Handle<HangBacktraceObserver>) -> &mut Self {
+        self.hang = Some(hang);
+        self
+    }
+
+    /// Sets the observer parsing the sanitizer reports in the stderr of the child
+    #[cfg(feature = "regex")]
+    pub fn sanitizer_observer(&mut self, sanitizer: Handle<SanitizerReportObserver>) -> &mut Self {
+        self.sanitizer = Some(sanitizer);
+        self
+    }
+
+    /// Sets the observer reporting violations of the sandbox policy
+    pub fn sandbox_observer(
+        &mut self,
+        sandbox_violation: Handle<SandboxViolationObserver>,
+    ) -> &mut Self {
+        self.sandbox_violation = Some(sandbox_violation);
+        self
+    }
+
+    /// Runs the child in the given [`Sandbox`]
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    pub fn sandbox(&mut self, sandbox: Sandbox) -> &mut Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
     /// Sets the input mode to [`InputLocation::File`]
     /// and adds the filename as arg to at the current position.
     /// Uses a default filename.
@@ -719,10 +877,35 @@ impl CommandExecutorBuilder {
             command.stderr(Stdio::piped());
         }
 
+        #[cfg(feature = "regex")]
+        if self.sanitizer.is_some() {
+            command.stderr(Stdio::piped());
+        }
+
+        // With an `Arg` input location, the sandbox is applied to each spawned command
+        #[cfg(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if let Some(sandbox) = &self.sandbox {
+            if !matches!(self.input_location, InputLocation::Arg { .. }) {
+                sandbox.apply(&mut command)?;
+            }
+        }
+
         let configurator = StdCommandConfigurator {
             debug_child: self.debug_child,
             stdout_observer: self.stdout.clone(),
             stderr_observer: self.stderr.clone(),
+            hang_observer: self.hang.clone(),
+            #[cfg(feature = "regex")]
+            sanitizer_observer: self.sanitizer.clone(),
+            sandbox_observer: self.sandbox_violation.clone(),
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            sandbox: self.sandbox.clone(),
             input_location: self.input_location.clone(),
             timeout: self.timeout,
             command,
@@ -734,6 +917,63 @@ impl CommandExecutorBuilder {
             ),
         )
     }
+
+    /// Builds a [`PersistentCommandExecutor`], keeping the program alive across executions.
+    ///
+    /// The program has to speak the protocol described in [`crate::executors::persistent_command`].
+    /// Inputs are always delivered through this protocol, so the input location must not be set,
+    /// and `stdout` cannot be observed.
+    pub fn build_persistent<I, OT, S>(
+        &self,
+        observers: OT,
+    ) -> Result<PersistentCommandExecutor<I, OT, S>, Error>
+    where
+        I: HasTargetBytes,
+        OT: MatchName + ObserversTuple<I, S>,
+    {
+        let Some(program) = &self.program else {
+            return Err(Error::illegal_argument(
+                "CommandExecutor::builder: no program set!",
+            ));
+        };
+        if self.input_location != InputLocation::StdIn {
+            return Err(Error::illegal_argument(
+                "CommandExecutor::builder: persistent executors receive the input through the protocol, no input location may be set",
+            ));
+        }
+        if self.stdout.is_some() || self.stderr.is_some() {
+            return Err(Error::illegal_argument(
+                "CommandExecutor::builder: persistent executors do not support stdout or stderr observers",
+            ));
+        }
+        #[cfg(feature = "regex")]
+        if self.sanitizer.is_some() {
+            return Err(Error::illegal_argument(
+                "CommandExecutor::builder: persistent executors do not support sanitizer observers",
+            ));
+        }
+        #[cfg(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if self.sandbox.is_some() {
+            return Err(Error::illegal_argument(
+                "CommandExecutor::builder: persistent executors do not support sandboxes",
+            ));
+        }
+
+        let mut command = Command::new(program);
+        command.args(&self.args);
+        command.envs(
+            self.envs
+                .iter()
+                .map(|(k, v)| (k.as_os_str(), v.as_os_str())),
+        );
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        PersistentCommandExecutor::new(command, observers, self.timeout, self.debug_child)
+    }
 }
 
 /// A `CommandConfigurator` takes care of creating and spawning a [`Command`] for the [`CommandExecutor`].
@@ -786,6 +1026,23 @@ pub trait CommandConfigurator<I, C = Child>: Sized {
     fn stderr_observer(&self) -> Option<Handle<StdErrObserver>> {
         None
     }
+    /// Get the observer collecting the backtrace of the child when it times out
+    fn hang_observer(&self) -> Option<Handle<HangBacktraceObserver>> {
+        None
+    }
+    /// Get the observer parsing the sanitizer reports in the stderr of the child
+    #[cfg(feature = "regex")]
+    fn sanitizer_observer(&self) -> Option<Handle<SanitizerReportObserver>> {
+        None
+    }
+    /// Get the observer reporting violations of the sandbox policy, i.e. children killed by `SIGSYS`
+    fn sandbox_observer(&self) -> Option<Handle<SandboxViolationObserver>> {
+        None
+    }
+    /// The number of sandbox violations so far, including those the children survived
+    fn sandbox_violations(&self) -> u64 {
+        0
+    }
 
     /// Spawns a new process with the given configuration.
     fn spawn_child(&mut self, input: &I) -> Result<C, Error>;
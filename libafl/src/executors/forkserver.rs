@@ -38,15 +38,24 @@ use nix::{
 };
 
 use super::HasTimeout;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+use super::Sandbox;
 #[cfg(feature = "regex")]
 use crate::observers::{
-    get_asan_runtime_flags, get_asan_runtime_flags_with_log_path, AsanBacktraceObserver,
+    get_asan_runtime_flags, get_asan_runtime_flags_with_log_path,
+    sanitizer::{merge_sanitizer_options, sanitizer_runtime_envs},
+    AsanBacktraceObserver, SanitizerReportObserver,
 };
 use crate::{
     executors::{Executor, ExitKind, HasObservers},
     inputs::{BytesInput, HasTargetBytes, Input, NopTargetBytesConverter, TargetBytesConverter},
     mutators::Tokens,
-    observers::{MapObserver, Observer, ObserversTuple},
+    observers::{
+        HangBacktraceObserver, MapObserver, Observer, ObserversTuple, SandboxViolationObserver,
+    },
     state::HasExecutions,
     Error,
 };
@@ -364,6 +373,87 @@ impl Forkserver {
         debug_output: bool,
         kill_signal: Signal,
     ) -> Result<Self, Error> {
+        Self::spawn(
+            target,
+            args,
+            envs,
+            input_filefd,
+            use_stdin,
+            memlimit,
+            is_persistent,
+            is_deferred_frksrv,
+            dump_asan_logs,
+            coverage_map_size,
+            debug_output,
+            kill_signal,
+            |_| Ok(()),
+        )
+    }
+
+    /// Create a new [`Forkserver`] running in the given [`Sandbox`].
+    /// The forked children inherit the namespaces and the `seccomp` policy of the forkserver.
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[expect(clippy::too_many_arguments)]
+    pub fn with_sandbox(
+        target: OsString,
+        args: Vec<OsString>,
+        envs: Vec<(OsString, OsString)>,
+        input_filefd: RawFd,
+        use_stdin: bool,
+        memlimit: u64,
+        is_persistent: bool,
+        is_deferred_frksrv: bool,
+        dump_asan_logs: bool,
+        coverage_map_size: Option<usize>,
+        debug_output: bool,
+        kill_signal: Signal,
+        sandbox: &Sandbox,
+    ) -> Result<Self, Error> {
+        if sandbox.has_pid_namespace() {
+            return Err(Error::illegal_argument(
+                "The forkserver cannot run in a new pid namespace, as it reports the pids of its children",
+            ));
+        }
+        Self::spawn(
+            target,
+            args,
+            envs,
+            input_filefd,
+            use_stdin,
+            memlimit,
+            is_persistent,
+            is_deferred_frksrv,
+            dump_asan_logs,
+            coverage_map_size,
+            debug_output,
+            kill_signal,
+            |command| sandbox.apply(command),
+        )
+    }
+
+    /// Spawns the forkserver, `configure` is called on the command right before spawning it.
+    #[expect(clippy::too_many_arguments)]
+    fn spawn<F>(
+        target: OsString,
+        args: Vec<OsString>,
+        envs: Vec<(OsString, OsString)>,
+        input_filefd: RawFd,
+        use_stdin: bool,
+        memlimit: u64,
+        is_persistent: bool,
+        is_deferred_frksrv: bool,
+        dump_asan_logs: bool,
+        coverage_map_size: Option<usize>,
+        debug_output: bool,
+        kill_signal: Signal,
+        configure: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnOnce(&mut Command) -> Result<(), Error>,
+    {
         let Some(coverage_map_size) = coverage_map_size else {
             return Err(Error::unknown("Coverage map size unknown. Use coverage_map_size() to tell the forkserver about the map size."));
         };
@@ -423,7 +513,7 @@ impl Forkserver {
             command.env("ASAN_OPTIONS", asan_options);
         }
 
-        let fsrv_handle = match command
+        command
             .env("LD_BIND_NOW", "1")
             .envs(envs)
             .setlimit(memlimit)
@@ -435,9 +525,10 @@ impl Forkserver {
                 st_pipe.write_end().unwrap(),
                 ctl_pipe.read_end().unwrap(),
                 ctl_pipe.write_end().unwrap(),
-            )
-            .spawn()
-        {
+            );
+        configure(&mut command)?;
+
+        let fsrv_handle = match command.spawn() {
             Ok(fsrv_handle) => fsrv_handle,
             Err(err) => {
                 return Err(Error::illegal_state(format!(
@@ -621,6 +712,16 @@ pub struct ForkserverExecutor<I, OT, S, SHM, TC> {
     max_input_size: usize,
     #[cfg(feature = "regex")]
     asan_obs: Handle<AsanBacktraceObserver>,
+    hang_obs: Handle<HangBacktraceObserver>,
+    #[cfg(feature = "regex")]
+    sanitizer_obs: Option<Handle<SanitizerReportObserver>>,
+    sandbox_obs: Handle<SandboxViolationObserver>,
+    /// The sandbox of the forkserver, counting the violations that did not kill a child
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    sandbox: Option<Sandbox>,
     timeout: TimeSpec,
     crash_exitcode: Option<i8>,
 }
@@ -763,6 +864,12 @@ where
 
         self.forkserver.set_child_pid(Pid::from_raw(pid));
 
+        #[cfg(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        let violations = self.sandbox.as_ref().map_or(0, Sandbox::violations);
+
         if let Some(status) = self.forkserver.read_st_timed(&self.timeout)? {
             self.forkserver.set_status(status);
             let exitcode_is_crash = if let Some(crash_exitcode) = self.crash_exitcode {
@@ -772,6 +879,13 @@ where
             };
             if libc::WIFSIGNALED(self.forkserver().status()) || exitcode_is_crash {
                 exit_kind = ExitKind::Crash;
+                if libc::WIFSIGNALED(self.forkserver().status())
+                    && libc::WTERMSIG(self.forkserver().status()) == libc::SIGSYS
+                {
+                    if let Some(sandbox_observer) = self.observers.get_mut(&self.sandbox_obs) {
+                        sandbox_observer.set_violation();
+                    }
+                }
                 #[cfg(feature = "regex")]
                 if let Some(asan_observer) = self.observers.get_mut(&self.asan_obs) {
                     asan_observer.parse_asan_output_from_asan_log_file(pid)?;
@@ -780,6 +894,20 @@ where
         } else {
             self.forkserver.set_last_run_timed_out(true);
 
+            #[cfg(all(
+                target_os = "linux",
+                target_env = "gnu",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            {
+                let child_pid = self.forkserver().child_pid();
+                if let Some(hang_observer) = self.observers.get_mut(&self.hang_obs) {
+                    if let Err(err) = hang_observer.sample_process(child_pid) {
+                        log::warn!("Could not sample the stack of the timed-out child: {err:?}");
+                    }
+                }
+            }
+
             // We need to kill the child in case he has timed out, or we can't get the correct pid in the next call to self.executor.forkserver_mut().read_st()?
             let _ = kill(self.forkserver().child_pid(), self.forkserver.kill_signal);
             if let Err(err) = self.forkserver.read_st() {
@@ -790,6 +918,24 @@ where
             exit_kind = ExitKind::Timeout;
         }
 
+        // Violations the child survived, answered with an `errno`
+        #[cfg(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if self.sandbox.as_ref().map_or(0, Sandbox::violations) != violations {
+            if let Some(sandbox_observer) = self.observers.get_mut(&self.sandbox_obs) {
+                sandbox_observer.set_violation();
+            }
+        }
+
+        #[cfg(feature = "regex")]
+        if let Some(sanitizer_obs) = &self.sanitizer_obs {
+            if let Some(sanitizer_observer) = self.observers.get_mut(sanitizer_obs) {
+                sanitizer_observer.parse_log_file(pid)?;
+            }
+        }
+
         if !libc::WIFSTOPPED(self.forkserver().status()) {
             self.forkserver.reset_child_pid();
         }
@@ -820,6 +966,15 @@ pub struct ForkserverExecutorBuilder<'a, TC, SP> {
     timeout: Option<Duration>,
     #[cfg(feature = "regex")]
     asan_obs: Option<Handle<AsanBacktraceObserver>>,
+    hang_obs: Option<Handle<HangBacktraceObserver>>,
+    #[cfg(feature = "regex")]
+    sanitizer_obs: Option<Handle<SanitizerReportObserver>>,
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    sandbox: Option<Sandbox>,
+    sandbox_obs: Option<Handle<SandboxViolationObserver>>,
     crash_exitcode: Option<i8>,
     target_bytes_converter: TC,
 }
@@ -891,6 +1046,21 @@ where
                 .asan_obs
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
+            hang_obs: self
+                .hang_obs
+                .clone()
+                .unwrap_or(HangBacktraceObserver::default().handle()),
+            #[cfg(feature = "regex")]
+            sanitizer_obs: self.sanitizer_obs.clone(),
+            sandbox_obs: self
+                .sandbox_obs
+                .clone()
+                .unwrap_or(SandboxViolationObserver::default().handle()),
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            sandbox: self.sandbox.clone(),
             crash_exitcode: self.crash_exitcode,
             target_bytes_converter: self.target_bytes_converter,
         })
@@ -955,6 +1125,21 @@ where
                 .asan_obs
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
+            hang_obs: self
+                .hang_obs
+                .clone()
+                .unwrap_or(HangBacktraceObserver::default().handle()),
+            #[cfg(feature = "regex")]
+            sanitizer_obs: self.sanitizer_obs.clone(),
+            sandbox_obs: self
+                .sandbox_obs
+                .clone()
+                .unwrap_or(SandboxViolationObserver::default().handle()),
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            sandbox: self.sandbox.clone(),
             crash_exitcode: self.crash_exitcode,
             target_bytes_converter: self.target_bytes_converter,
         })
@@ -985,7 +1170,49 @@ where
             }
         };
 
+        #[cfg(feature = "regex")]
+        if self.sanitizer_obs.is_some() {
+            for (key, value) in sanitizer_runtime_envs() {
+                // With an `AsanBacktraceObserver`, the ASan reports go to its own log file
+                if key == "ASAN_OPTIONS" && self.has_asan_obs() {
+                    continue;
+                }
+                // Keep the options of the user, set for the target or inherited from the fuzzer
+                if let Some((_, options)) = self.envs.iter_mut().find(|(k, _)| k == key) {
+                    *options = merge_sanitizer_options(options.to_str(), &value).into();
+                } else {
+                    // The forkserver replaces the inherited ASan options with its own
+                    let inherited = if key == "ASAN_OPTIONS" {
+                        Some(get_asan_runtime_flags())
+                    } else {
+                        env::var(key).ok()
+                    };
+                    let options = merge_sanitizer_options(inherited.as_deref(), &value);
+                    self.envs.push((key.into(), options.into()));
+                }
+            }
+        }
+
         let mut forkserver = match &self.program {
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            Some(t) if self.sandbox.is_some() => Forkserver::with_sandbox(
+                t.clone(),
+                self.arguments.clone(),
+                self.envs.clone(),
+                input_file.as_raw_fd(),
+                self.use_stdin,
+                0,
+                self.is_persistent,
+                self.is_deferred_frksrv,
+                self.has_asan_obs(),
+                self.map_size,
+                self.debug_child,
+                self.kill_signal.unwrap_or(KILL_SIGNAL_DEFAULT),
+                self.sandbox.as_ref().unwrap(),
+            )?,
             Some(t) => Forkserver::with_kill_signal(
                 t.clone(),
                 self.arguments.clone(),
@@ -1448,6 +1675,52 @@ where
         self
     }
 
+    /// Use a [`HangBacktraceObserver`] with a custom name to collect the backtrace of timed-out children.
+    ///
+    /// A [`HangBacktraceObserver`] with the default name is picked up without calling this.
+    #[must_use]
+    pub fn hang_observer(mut self, hang_observer: Handle<HangBacktraceObserver>) -> Self {
+        self.hang_obs = Some(hang_observer);
+        self
+    }
+
+    /// Collect the reports of `UBSan`, `MSan`, `TSan` and `LSan` in the given [`SanitizerReportObserver`].
+    ///
+    /// The sanitizers are configured to write their reports to log files, unless their options
+    /// are already set with [`Self::env`].
+    #[cfg(feature = "regex")]
+    #[must_use]
+    pub fn sanitizer_observer(
+        mut self,
+        sanitizer_observer: Handle<SanitizerReportObserver>,
+    ) -> Self {
+        self.sanitizer_obs = Some(sanitizer_observer);
+        self
+    }
+
+    /// Run the forkserver, and thereby all children, in the given [`Sandbox`].
+    ///
+    /// The forkserver cannot run in a new pid namespace, as it reports the pids of its children.
+    /// Children killed by the `seccomp` policy are reported to the [`SandboxViolationObserver`].
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[must_use]
+    pub fn sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Use a [`SandboxViolationObserver`] with a custom name to report violations of the sandbox policy.
+    ///
+    /// A [`SandboxViolationObserver`] with the default name is picked up without calling this.
+    #[must_use]
+    pub fn sandbox_observer(mut self, sandbox_observer: Handle<SandboxViolationObserver>) -> Self {
+        self.sandbox_obs = Some(sandbox_observer);
+        self
+    }
+
     /// Determine if the asan observer is present (always false if feature "regex" is disabled)
     #[cfg(feature = "regex")]
     pub fn has_asan_obs(&self) -> bool {
@@ -1490,6 +1763,15 @@ impl<'a> ForkserverExecutorBuilder<'a, NopTargetBytesConverter<BytesInput>, Unix
             timeout: None,
             #[cfg(feature = "regex")]
             asan_obs: None,
+            hang_obs: None,
+            #[cfg(feature = "regex")]
+            sanitizer_obs: None,
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            sandbox: None,
+            sandbox_obs: None,
             crash_exitcode: None,
             target_bytes_converter: NopTargetBytesConverter::new(),
         }
@@ -1523,6 +1805,15 @@ impl<'a, TC> ForkserverExecutorBuilder<'a, TC, UnixShMemProvider> {
             timeout: self.timeout,
             #[cfg(feature = "regex")]
             asan_obs: self.asan_obs,
+            hang_obs: self.hang_obs,
+            #[cfg(feature = "regex")]
+            sanitizer_obs: self.sanitizer_obs,
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            sandbox: self.sandbox,
+            sandbox_obs: self.sandbox_obs,
             crash_exitcode: self.crash_exitcode,
             target_bytes_converter: self.target_bytes_converter,
         }
@@ -1556,6 +1847,15 @@ impl<'a, TC, SP> ForkserverExecutorBuilder<'a, TC, SP> {
             timeout: self.timeout,
             #[cfg(feature = "regex")]
             asan_obs: self.asan_obs,
+            hang_obs: self.hang_obs,
+            #[cfg(feature = "regex")]
+            sanitizer_obs: self.sanitizer_obs,
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            sandbox: self.sandbox,
+            sandbox_obs: self.sandbox_obs,
             crash_exitcode: self.crash_exitcode,
             target_bytes_converter,
         }
@@ -19,7 +19,7 @@ pub mod unix_signal_handler {
         feedbacks::Feedback,
         fuzzer::HasObjective,
         inputs::Input,
-        observers::ObserversTuple,
+        observers::{hang_backtrace::record_hang_pc, ObserversTuple},
         state::{HasCurrentTestcase, HasExecutions, HasSolutions},
     };
 
@@ -126,6 +126,33 @@ pub mod unix_signal_handler {
         }));
     }
 
+    /// The program counter interrupted by a signal, if it can be read on this platform
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        target_arch = "x86_64"
+    ))]
+    fn interrupted_pc(context: &ucontext_t) -> Option<usize> {
+        usize::try_from(context.uc_mcontext.gregs[libc::REG_RIP as usize]).ok()
+    }
+
+    /// The program counter interrupted by a signal, if it can be read on this platform
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        target_arch = "aarch64"
+    ))]
+    fn interrupted_pc(context: &ucontext_t) -> Option<usize> {
+        usize::try_from(context.uc_mcontext.pc).ok()
+    }
+
+    /// The program counter interrupted by a signal, if it can be read on this platform
+    #[cfg(not(all(
+        any(target_os = "linux", target_os = "android"),
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    fn interrupted_pc(_context: &ucontext_t) -> Option<usize> {
+        None
+    }
+
     /// Timeout-Handler for in-process fuzzing.
     /// It will store the current State to shmem, then exit.
     ///
@@ -136,7 +163,7 @@ pub mod unix_signal_handler {
     pub unsafe fn inproc_timeout_handler<E, EM, I, OF, S, Z>(
         _signal: Signal,
         _info: &mut siginfo_t,
-        _context: Option<&mut ucontext_t>,
+        context: Option<&mut ucontext_t>,
         data: &mut InProcessExecutorHandlerData,
     ) where
         E: HasInProcessHooks<I, S> + HasObservers,
@@ -170,6 +197,11 @@ pub mod unix_signal_handler {
 
         log::error!("Timeout in fuzz run.");
 
+        // Let the `HangBacktraceObserver` know where the target was hanging.
+        if let Some(pc) = context.and_then(|context| interrupted_pc(context)) {
+            record_hang_pc(pc);
+        }
+
         run_observers_and_save_state::<E, EM, I, OF, S, Z>(
             executor,
             state,
@@ -16,6 +16,17 @@ pub use inprocess_fork::InProcessForkExecutor;
 #[cfg(unix)]
 use libafl_bolts::os::unix_signals::Signal;
 use libafl_bolts::tuples::RefIndexable;
+pub use multi_differential::MultiDiffExecutor;
+#[cfg(all(feature = "otlp_monitor", feature = "introspection"))]
+pub use otlp_span::OtlpSpanExecutor;
+#[cfg(all(feature = "std", unix))]
+pub use persistent_command::PersistentCommandExecutor;
+#[cfg(all(
+    feature = "std",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub use sandbox::{Sandbox, SeccompAction, SeccompPolicy};
 use serde::{Deserialize, Serialize};
 pub use shadow::ShadowExecutor;
 pub use with_observers::WithObservers;
@@ -34,6 +45,21 @@ pub mod inprocess;
 #[cfg(all(feature = "std", unix))]
 pub mod inprocess_fork;
 
+pub mod multi_differential;
+
+#[cfg(all(feature = "otlp_monitor", feature = "introspection"))]
+pub mod otlp_span;
+
+#[cfg(all(feature = "std", unix))]
+pub mod persistent_command;
+
+#[cfg(all(
+    feature = "std",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub mod sandbox;
+
 pub mod shadow;
 
 pub mod with_observers;
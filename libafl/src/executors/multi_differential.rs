@@ -0,0 +1,735 @@
+//! Executor for N-way differential fuzzing.
+//!
+//! It wraps any number of executors that will be run after each other with the same input.
+//! The executors are either a [`Vec`] of executors of the same type, or a `tuple_list!` of executors of different types.
+//! Use it instead of nesting [`crate::executors::DiffExecutor`]s when comparing more than two implementations.
+//! To compare the outputs of the executors, add a [`crate::observers::DiffOutputsObserver`] to its differential
+//! observers, and use a [`crate::feedbacks::differential::MultiDiffFeedback`].
+//!
+use alloc::vec::Vec;
+use core::{
+    cell::UnsafeCell,
+    fmt::Debug,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr,
+    time::Duration,
+};
+
+use libafl_bolts::{
+    ownedref::OwnedMutPtr,
+    tuples::{MatchName, RefIndexable},
+};
+use serde::{Deserialize, Serialize};
+
+use super::HasTimeout;
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    observers::{MultiDifferentialObserversTuple, ObserversTuple},
+    Error,
+};
+
+/// The executors wrapped by a [`MultiDiffExecutor`]
+///
+/// Like [`ObserversTuple`], it is implemented for `tuple_list!`s, here of executors of different types.
+/// It is also implemented for a [`Vec`] of executors of the same type.
+pub trait ExecutorsTuple {
+    /// Pointers to the observers of each executor, in order
+    type ObserversPtrs;
+
+    /// The number of executors
+    fn count(&self) -> usize;
+
+    /// Pointers to the current observers of each executor
+    fn observers_ptrs(&self) -> Self::ObserversPtrs;
+
+    /// Point `ptrs` to the current observers of each executor
+    fn update_observers_ptrs(&self, ptrs: &mut Self::ObserversPtrs);
+
+    /// Point `ptrs` to the current observers of each executor, obtained through
+    /// [`HasObservers::observers_mut`]
+    fn update_observers_ptrs_mut(&mut self, ptrs: &mut Self::ObserversPtrs);
+}
+
+/// Runs all executors of an [`ExecutorsTuple`] after each other with the same input
+pub trait RunExecutorsTuple<DOT, EM, I, S, Z>: ExecutorsTuple {
+    /// Runs the executors in order, the first one having the index `idx`, and pushes their exit kinds to `exit_kinds`.
+    ///
+    /// The observers of each executor are `pre_exec`'d and `post_exec`'d around its run, and passed to the
+    /// differential observers `differential`.
+    #[expect(clippy::too_many_arguments)]
+    fn run_target_all(
+        &mut self,
+        idx: usize,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+        differential: &mut DOT,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error>;
+}
+
+/// Sets and gets the timeout of all executors of an [`ExecutorsTuple`]
+pub trait TimeoutExecutorsTuple {
+    /// Set the timeout of all executors
+    fn set_timeout_all(&mut self, timeout: Duration);
+
+    /// The timeout shared by all executors, `None` if there are no executors
+    ///
+    /// # Panics
+    /// Panics if the executors have different timeouts.
+    fn timeout_all(&self) -> Option<Duration>;
+}
+
+/// Pointers to the observers of the executors of an [`ExecutorsTuple`], looked up in order by name
+pub trait ObserversPtrsTuple {
+    /// Returns the first observer named `name` of the type `T`
+    fn match_observer<T>(&self, name: &str) -> Option<&T>;
+
+    /// Returns the first observer named `name` of the type `T` (mutable)
+    fn match_observer_mut<T>(&mut self, name: &str) -> Option<&mut T>;
+}
+
+fn run_target_one<DOT, E, EM, I, S, Z>(
+    executor: &mut E,
+    idx: usize,
+    fuzzer: &mut Z,
+    state: &mut S,
+    mgr: &mut EM,
+    input: &I,
+    differential: &mut DOT,
+) -> Result<ExitKind, Error>
+where
+    E: Executor<EM, I, S, Z> + HasObservers,
+    E::Observers: ObserversTuple<I, S>,
+    DOT: MultiDifferentialObserversTuple<E::Observers, I, S>,
+{
+    {
+        let mut observers = executor.observers_mut();
+        differential.pre_observe_all(idx, &mut *observers)?;
+        observers.pre_exec_all(state, input)?;
+    }
+    let ret = executor.run_target(fuzzer, state, mgr, input)?;
+    let mut observers = executor.observers_mut();
+    observers.post_exec_all(state, input, &ret)?;
+    differential.post_observe_all(idx, &mut *observers, &ret)?;
+    Ok(ret)
+}
+
+impl<E> ExecutorsTuple for Vec<E>
+where
+    E: HasObservers,
+{
+    type ObserversPtrs = Vec<OwnedMutPtr<E::Observers>>;
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn observers_ptrs(&self) -> Self::ObserversPtrs {
+        self.iter()
+            .map(|executor| OwnedMutPtr::Ptr(ptr::from_ref(&*executor.observers()).cast_mut()))
+            .collect()
+    }
+
+    fn update_observers_ptrs(&self, ptrs: &mut Self::ObserversPtrs) {
+        for (slot, executor) in ptrs.iter_mut().zip(self.iter()) {
+            *slot = OwnedMutPtr::Ptr(ptr::from_ref(&*executor.observers()).cast_mut());
+        }
+    }
+
+    fn update_observers_ptrs_mut(&mut self, ptrs: &mut Self::ObserversPtrs) {
+        for (slot, executor) in ptrs.iter_mut().zip(self.iter_mut()) {
+            *slot = OwnedMutPtr::Ptr(ptr::from_mut(&mut *executor.observers_mut()));
+        }
+    }
+}
+
+impl<DOT, E, EM, I, S, Z> RunExecutorsTuple<DOT, EM, I, S, Z> for Vec<E>
+where
+    E: Executor<EM, I, S, Z> + HasObservers,
+    E::Observers: ObserversTuple<I, S>,
+    DOT: MultiDifferentialObserversTuple<E::Observers, I, S>,
+{
+    fn run_target_all(
+        &mut self,
+        idx: usize,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+        differential: &mut DOT,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        for (offset, executor) in self.iter_mut().enumerate() {
+            let ret = run_target_one(
+                executor,
+                idx + offset,
+                fuzzer,
+                state,
+                mgr,
+                input,
+                differential,
+            )?;
+            exit_kinds.push(ret);
+        }
+        Ok(())
+    }
+}
+
+impl<E> TimeoutExecutorsTuple for Vec<E>
+where
+    E: HasTimeout,
+{
+    fn set_timeout_all(&mut self, timeout: Duration) {
+        for executor in self {
+            executor.set_timeout(timeout);
+        }
+    }
+
+    fn timeout_all(&self) -> Option<Duration> {
+        let timeout = self.first()?.timeout();
+        assert!(
+            self.iter().all(|e| e.timeout() == timeout),
+            "The wrapped Executors have different timeouts!"
+        );
+        Some(timeout)
+    }
+}
+
+impl<OT> ObserversPtrsTuple for Vec<OwnedMutPtr<OT>>
+where
+    OT: MatchName,
+{
+    #[expect(deprecated)]
+    fn match_observer<T>(&self, name: &str) -> Option<&T> {
+        self.iter()
+            .find_map(|inner| inner.as_ref().match_name::<T>(name))
+    }
+
+    #[expect(deprecated)]
+    fn match_observer_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        self.iter_mut()
+            .find_map(|inner| inner.as_mut().match_name_mut::<T>(name))
+    }
+}
+
+impl ExecutorsTuple for () {
+    type ObserversPtrs = ();
+
+    fn count(&self) -> usize {
+        0
+    }
+
+    fn observers_ptrs(&self) -> Self::ObserversPtrs {}
+
+    fn update_observers_ptrs(&self, _ptrs: &mut Self::ObserversPtrs) {}
+
+    fn update_observers_ptrs_mut(&mut self, _ptrs: &mut Self::ObserversPtrs) {}
+}
+
+impl<DOT, EM, I, S, Z> RunExecutorsTuple<DOT, EM, I, S, Z> for () {
+    fn run_target_all(
+        &mut self,
+        _idx: usize,
+        _fuzzer: &mut Z,
+        _state: &mut S,
+        _mgr: &mut EM,
+        _input: &I,
+        _differential: &mut DOT,
+        _exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl TimeoutExecutorsTuple for () {
+    fn set_timeout_all(&mut self, _timeout: Duration) {}
+
+    fn timeout_all(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl ObserversPtrsTuple for () {
+    fn match_observer<T>(&self, _name: &str) -> Option<&T> {
+        None
+    }
+
+    fn match_observer_mut<T>(&mut self, _name: &str) -> Option<&mut T> {
+        None
+    }
+}
+
+impl<Head, Tail> ExecutorsTuple for (Head, Tail)
+where
+    Head: HasObservers,
+    Tail: ExecutorsTuple,
+{
+    type ObserversPtrs = (OwnedMutPtr<Head::Observers>, Tail::ObserversPtrs);
+
+    fn count(&self) -> usize {
+        1 + self.1.count()
+    }
+
+    fn observers_ptrs(&self) -> Self::ObserversPtrs {
+        (
+            OwnedMutPtr::Ptr(ptr::from_ref(&*self.0.observers()).cast_mut()),
+            self.1.observers_ptrs(),
+        )
+    }
+
+    fn update_observers_ptrs(&self, ptrs: &mut Self::ObserversPtrs) {
+        ptrs.0 = OwnedMutPtr::Ptr(ptr::from_ref(&*self.0.observers()).cast_mut());
+        self.1.update_observers_ptrs(&mut ptrs.1);
+    }
+
+    fn update_observers_ptrs_mut(&mut self, ptrs: &mut Self::ObserversPtrs) {
+        ptrs.0 = OwnedMutPtr::Ptr(ptr::from_mut(&mut *self.0.observers_mut()));
+        self.1.update_observers_ptrs_mut(&mut ptrs.1);
+    }
+}
+
+impl<DOT, EM, Head, I, S, Tail, Z> RunExecutorsTuple<DOT, EM, I, S, Z> for (Head, Tail)
+where
+    Head: Executor<EM, I, S, Z> + HasObservers,
+    Head::Observers: ObserversTuple<I, S>,
+    Tail: RunExecutorsTuple<DOT, EM, I, S, Z>,
+    DOT: MultiDifferentialObserversTuple<Head::Observers, I, S>,
+{
+    fn run_target_all(
+        &mut self,
+        idx: usize,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+        differential: &mut DOT,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        let ret = run_target_one(&mut self.0, idx, fuzzer, state, mgr, input, differential)?;
+        exit_kinds.push(ret);
+        self.1
+            .run_target_all(idx + 1, fuzzer, state, mgr, input, differential, exit_kinds)
+    }
+}
+
+impl<Head, Tail> TimeoutExecutorsTuple for (Head, Tail)
+where
+    Head: HasTimeout,
+    Tail: TimeoutExecutorsTuple,
+{
+    fn set_timeout_all(&mut self, timeout: Duration) {
+        self.0.set_timeout(timeout);
+        self.1.set_timeout_all(timeout);
+    }
+
+    fn timeout_all(&self) -> Option<Duration> {
+        let timeout = self.0.timeout();
+        if let Some(other) = self.1.timeout_all() {
+            assert_eq!(
+                timeout, other,
+                "The wrapped Executors have different timeouts!"
+            );
+        }
+        Some(timeout)
+    }
+}
+
+impl<Head, Tail> ObserversPtrsTuple for (OwnedMutPtr<Head>, Tail)
+where
+    Head: MatchName,
+    Tail: ObserversPtrsTuple,
+{
+    #[expect(deprecated)]
+    fn match_observer<T>(&self, name: &str) -> Option<&T> {
+        self.0
+            .as_ref()
+            .match_name::<T>(name)
+            .or_else(|| self.1.match_observer::<T>(name))
+    }
+
+    #[expect(deprecated)]
+    fn match_observer_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        match self.0.as_mut().match_name_mut::<T>(name) {
+            Some(t) => Some(t),
+            None => self.1.match_observer_mut::<T>(name),
+        }
+    }
+}
+
+/// A [`MultiDiffExecutor`] wraps an [`ExecutorsTuple`] and runs each of its executors on every input
+///
+/// If all executors agree on the [`ExitKind`], it is returned.
+/// Otherwise, [`ExitKind::Diff`] is returned, with the exit kind of the first executor as `primary`,
+/// and the first exit kind that differs from it as `secondary`.
+/// The exit kinds of all executors are available through [`MultiDiffExecutor::exit_kinds`].
+#[derive(Debug)]
+pub struct MultiDiffExecutor<ET, DOT, I, OP, S> {
+    executors: ET,
+    observers: UnsafeCell<MultiProxyObserversTuple<OP, DOT>>,
+    exit_kinds: Vec<ExitKind>,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<ET, DOT, I, S> MultiDiffExecutor<ET, DOT, I, ET::ObserversPtrs, S>
+where
+    ET: ExecutorsTuple,
+{
+    /// Create a new `MultiDiffExecutor`, wrapping the given `executors`.
+    ///
+    /// # Panics
+    /// Panics if `executors` is empty.
+    pub fn new(executors: ET, observers: DOT) -> Self {
+        assert!(
+            executors.count() > 0,
+            "MultiDiffExecutor needs at least one executor"
+        );
+        Self {
+            observers: UnsafeCell::new(MultiProxyObserversTuple {
+                inner: executors.observers_ptrs(),
+                differential: observers,
+            }),
+            executors,
+            exit_kinds: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<ET, DOT, I, OP, S> MultiDiffExecutor<ET, DOT, I, OP, S> {
+    /// Retrieve the wrapped `Executor`s.
+    pub fn executors(&mut self) -> &mut ET {
+        &mut self.executors
+    }
+
+    /// The exit kinds of the last run, one per executor
+    #[must_use]
+    pub fn exit_kinds(&self) -> &[ExitKind] {
+        &self.exit_kinds
+    }
+}
+
+impl<DOT, EM, ET, I, S, Z> Executor<EM, I, S, Z>
+    for MultiDiffExecutor<ET, DOT, I, ET::ObserversPtrs, S>
+where
+    ET: RunExecutorsTuple<DOT, EM, I, S, Z>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        self.exit_kinds.clear();
+        self.executors.run_target_all(
+            0,
+            fuzzer,
+            state,
+            mgr,
+            input,
+            &mut self.observers.get_mut().differential,
+            &mut self.exit_kinds,
+        )?;
+
+        let first = self.exit_kinds[0];
+        match self.exit_kinds.iter().find(|ret| **ret != first) {
+            None => Ok(first),
+            // We found a diff in the exit codes!
+            Some(other) => Ok(ExitKind::Diff {
+                primary: first.into(),
+                secondary: (*other).into(),
+            }),
+        }
+    }
+}
+
+impl<ET, DOT, I, OP, S> HasTimeout for MultiDiffExecutor<ET, DOT, I, OP, S>
+where
+    ET: TimeoutExecutorsTuple,
+{
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executors.set_timeout_all(timeout);
+    }
+
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executors
+            .timeout_all()
+            .expect("MultiDiffExecutor needs at least one executor")
+    }
+}
+
+/// Proxy the observers of the inner executors
+///
+/// Observers are looked up in the observers of the inner executors first, in order, then in the differential observers.
+/// If inner executors share observer names, a lookup by name finds the observer of the first executor.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(
+    bound = "OP: serde::Serialize + serde::de::DeserializeOwned, DOT: serde::Serialize + serde::de::DeserializeOwned"
+)]
+pub struct MultiProxyObserversTuple<OP, DOT> {
+    inner: OP,
+    differential: DOT,
+}
+
+impl<OP, DOT, I, S> ObserversTuple<I, S> for MultiProxyObserversTuple<OP, DOT>
+where
+    OP: ObserversPtrsTuple,
+    DOT: ObserversTuple<I, S>,
+{
+    fn pre_exec_all(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.differential.pre_exec_all(state, input)
+    }
+
+    fn post_exec_all(
+        &mut self,
+        state: &mut S,
+        input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.differential.post_exec_all(state, input, exit_kind)
+    }
+
+    fn pre_exec_child_all(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.differential.pre_exec_child_all(state, input)
+    }
+
+    fn post_exec_child_all(
+        &mut self,
+        state: &mut S,
+        input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.differential
+            .post_exec_child_all(state, input, exit_kind)
+    }
+}
+
+impl<OP, DOT> Deref for MultiProxyObserversTuple<OP, DOT> {
+    type Target = DOT;
+
+    fn deref(&self) -> &Self::Target {
+        &self.differential
+    }
+}
+
+impl<OP, DOT> DerefMut for MultiProxyObserversTuple<OP, DOT> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.differential
+    }
+}
+
+impl<OP, DOT> MatchName for MultiProxyObserversTuple<OP, DOT>
+where
+    OP: ObserversPtrsTuple,
+    DOT: MatchName,
+{
+    #[expect(deprecated)]
+    fn match_name<T>(&self, name: &str) -> Option<&T> {
+        self.inner
+            .match_observer::<T>(name)
+            .or_else(|| self.differential.match_name::<T>(name))
+    }
+
+    #[expect(deprecated)]
+    fn match_name_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        match self.inner.match_observer_mut::<T>(name) {
+            Some(t) => Some(t),
+            None => self.differential.match_name_mut::<T>(name),
+        }
+    }
+}
+
+impl<ET, DOT, I, S> HasObservers for MultiDiffExecutor<ET, DOT, I, ET::ObserversPtrs, S>
+where
+    ET: ExecutorsTuple,
+{
+    type Observers = MultiProxyObserversTuple<ET::ObserversPtrs, DOT>;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        unsafe {
+            self.executors
+                .update_observers_ptrs(&mut self.observers.get().as_mut().unwrap().inner);
+            RefIndexable::from(self.observers.get().as_ref().unwrap())
+        }
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.executors
+            .update_observers_ptrs_mut(&mut self.observers.get_mut().inner);
+        RefIndexable::from(self.observers.get_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use libafl_bolts::{
+        ownedref::OwnedRef,
+        tuples::{tuple_list, tuple_list_type, RefIndexable},
+        Error, HasLen,
+    };
+
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers, MultiDiffExecutor},
+        feedbacks::{
+            differential::{MajorityVoteComparator, MultiDiffFeedback},
+            Feedback,
+        },
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        observers::{DiffOutputsObserver, ObserversTuple, ValueObserver},
+        state::NopState,
+    };
+
+    /// An executor whose observer always reports the same value
+    #[derive(Debug)]
+    struct ConstExecutor {
+        value: u32,
+        observers: tuple_list_type!(ValueObserver<'static, u32>),
+    }
+
+    impl ConstExecutor {
+        fn new(value: u32) -> Self {
+            Self {
+                value,
+                observers: tuple_list!(ValueObserver::new("value", OwnedRef::Owned(Box::new(0)))),
+            }
+        }
+    }
+
+    impl<EM, I, S, Z> Executor<EM, I, S, Z> for ConstExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            _input: &I,
+        ) -> Result<ExitKind, Error> {
+            self.observers.0.set(self.value);
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers for ConstExecutor {
+        type Observers = tuple_list_type!(ValueObserver<'static, u32>);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    /// An executor whose observer reports the length of the input
+    #[derive(Debug)]
+    struct LenExecutor {
+        observers: tuple_list_type!(ValueObserver<'static, u32>),
+    }
+
+    impl LenExecutor {
+        fn new() -> Self {
+            Self {
+                observers: tuple_list!(ValueObserver::new("value", OwnedRef::Owned(Box::new(0)))),
+            }
+        }
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for LenExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            self.observers.0.set(input.len() as u32);
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers for LenExecutor {
+        type Observers = tuple_list_type!(ValueObserver<'static, u32>);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    #[test]
+    fn test_multi_diff_executor_tuple() {
+        let mut state: NopState<BytesInput> = NopState::new();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![0, 0]);
+
+        let executors = tuple_list!(
+            ConstExecutor::new(2),
+            LenExecutor::new(),
+            ConstExecutor::new(3)
+        );
+        let outputs = DiffOutputsObserver::new("outputs", &executors.0.observers.0);
+        let mut feedback = MultiDiffFeedback::new("diff", &outputs, MajorityVoteComparator);
+        let mut executor = MultiDiffExecutor::new(executors, tuple_list!(outputs));
+
+        executor
+            .observers_mut()
+            .pre_exec_all(&mut state, &input)
+            .unwrap();
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(executor.exit_kinds(), &[ExitKind::Ok; 3]);
+
+        let observers = executor.observers();
+        assert_eq!(observers.0.outputs(), &[2, 2, 3]);
+        assert!(feedback
+            .is_interesting(&mut state, &mut mgr, &input, &*observers, &exit_kind)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_multi_diff_executor() {
+        let mut state: NopState<BytesInput> = NopState::new();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![0]);
+
+        let executors = vec![
+            ConstExecutor::new(1),
+            ConstExecutor::new(2),
+            ConstExecutor::new(1),
+        ];
+        let outputs = DiffOutputsObserver::new("outputs", &executors[0].observers.0);
+        let mut feedback = MultiDiffFeedback::new("diff", &outputs, MajorityVoteComparator);
+        let mut executor = MultiDiffExecutor::new(executors, tuple_list!(outputs));
+
+        executor
+            .observers_mut()
+            .pre_exec_all(&mut state, &input)
+            .unwrap();
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+
+        let observers = executor.observers();
+        assert_eq!(observers.0.outputs(), &[1, 2, 1]);
+        assert!(feedback
+            .is_interesting(&mut state, &mut mgr, &input, &*observers, &exit_kind)
+            .unwrap());
+    }
+}
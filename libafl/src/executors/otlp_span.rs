@@ -0,0 +1,94 @@
+//! An executor wrapper recording a span around each execution, for an [`OtlpTracer`].
+
+use alloc::{format, string::ToString};
+use core::time::Duration;
+
+use libafl_bolts::tuples::RefIndexable;
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    monitors::{otlp::string_attribute, OtlpTracer},
+    Error,
+};
+
+/// Wraps an [`Executor`], recording a span named `execution` around each run of the target.
+///
+/// The span carries the [`ExitKind`], and has an error status for anything but [`ExitKind::Ok`].
+/// Inside an [`crate::stages::OtlpSpanStage`] sharing the same tracer, the executions become
+/// children of the span of the stage.
+#[derive(Debug)]
+pub struct OtlpSpanExecutor<E> {
+    executor: E,
+    tracer: OtlpTracer,
+}
+
+impl<E> OtlpSpanExecutor<E> {
+    /// Wraps `executor`, recording its executions to `tracer`
+    pub fn new(executor: E, tracer: OtlpTracer) -> Self {
+        Self { executor, tracer }
+    }
+
+    /// The wrapped executor
+    pub fn inner(&self) -> &E {
+        &self.executor
+    }
+
+    /// The wrapped executor, mutable
+    pub fn inner_mut(&mut self) -> &mut E {
+        &mut self.executor
+    }
+}
+
+impl<E, EM, I, S, Z> Executor<EM, I, S, Z> for OtlpSpanExecutor<E>
+where
+    E: Executor<EM, I, S, Z>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        self.tracer.start_span();
+        let ret = self.executor.run_target(fuzzer, state, mgr, input);
+        let (exit_kind, error) = match &ret {
+            Ok(exit_kind) => (format!("{exit_kind:?}"), *exit_kind != ExitKind::Ok),
+            Err(_) => ("Error".to_string(), true),
+        };
+        self.tracer.end_span(
+            "execution",
+            &[string_attribute("exit_kind", &exit_kind)],
+            error,
+        );
+        ret
+    }
+}
+
+impl<E> HasObservers for OtlpSpanExecutor<E>
+where
+    E: HasObservers,
+{
+    type Observers = E::Observers;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.executor.observers()
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.executor.observers_mut()
+    }
+}
+
+impl<E> HasTimeout for OtlpSpanExecutor<E>
+where
+    E: HasTimeout,
+{
+    fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executor.set_timeout(timeout);
+    }
+}
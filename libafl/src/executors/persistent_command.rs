@@ -0,0 +1,745 @@
+//! A [`PersistentCommandExecutor`] keeps a single child process alive across many executions.
+//!
+//! This is meant for targets that cannot link a forkserver, such as interpreters (Python, the JVM, Node),
+//! where restarting the process for every input is prohibitively slow.
+//! The fuzzer talks to the child over its `stdin` and `stdout` using a small framed protocol.
+//! All integers are little endian.
+//!
+//! 1. On startup, the child writes an 8 byte hello: the magic [`PERSISTENT_CMD_MAGIC`] (`u32`),
+//!    followed by the protocol version [`PERSISTENT_CMD_VERSION`] (`u32`).
+//! 2. For each input, the fuzzer writes the input length (`u32`), followed by the input bytes.
+//! 3. After running the input, the child answers with a status (`i32`), the coverage length (`u32`),
+//!    and that many coverage bytes. The coverage may be empty, and may not be larger than the
+//!    coverage map (or [`PERSISTENT_CMD_MAX_COVERAGE`] bytes without a coverage map).
+//!    A status of `0` is reported as [`ExitKind::Ok`], any other status as [`ExitKind::Crash`].
+//!    The whole answer must arrive within the timeout.
+//!
+//! The child is restarted whenever it reports a crash, dies, times out,
+//! or after a configurable number of iterations.
+//! The child must not write anything else to `stdout`, use `stderr` for logging instead.
+//!
+//! A minimal Python harness may look like this:
+//! ```python
+//! import struct, sys
+//! inp, out = sys.stdin.buffer, sys.stdout.buffer
+//! out.write(b"LAFP" + struct.pack("<I", 1)); out.flush()
+//! while hdr := inp.read(4):
+//!     data = inp.read(struct.unpack("<I", hdr)[0])
+//!     try:
+//!         target(data); status = 0
+//!     except Exception:
+//!         status = 1
+//!     out.write(struct.pack("<iI", status, 0)); out.flush()
+//! ```
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
+};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    os::{
+        fd::{AsRawFd, BorrowedFd},
+        unix::{io::RawFd, process::CommandExt, process::ExitStatusExt},
+    },
+    process::{Child, Command, ExitStatus, Stdio},
+    time::Instant,
+};
+
+use libafl_bolts::{
+    os::{dup2, pipes::Pipe},
+    tuples::{Handle, MatchName, RefIndexable},
+    AsSlice,
+};
+use nix::sys::{
+    select::{pselect, FdSet},
+    signal::SigSet,
+    time::TimeSpec,
+};
+
+use super::HasTimeout;
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::{MapObserver, ObserversTuple, StdMapObserver},
+    state::HasExecutions,
+    Error,
+};
+
+/// The magic the child sends as the first part of its hello message (`b"LAFP"` in little endian)
+pub const PERSISTENT_CMD_MAGIC: u32 = 0x5046414C;
+/// The protocol version the child sends as the second part of its hello message
+pub const PERSISTENT_CMD_VERSION: u32 = 1;
+/// The largest coverage the child may report if no coverage map is set
+pub const PERSISTENT_CMD_MAX_COVERAGE: usize = 1 << 24;
+
+/// The default time a freshly spawned child has to send its hello message
+const STARTUP_TIMEOUT_DEFAULT: Duration = Duration::from_secs(30);
+
+/// A [`PersistentCommandExecutor`] runs many inputs in a single, long-lived child process.
+///
+/// Inputs are delivered, and results are received, using the framed protocol described in the
+/// [module documentation](self).
+/// Construct it using [`crate::executors::command::CommandExecutorBuilder::build_persistent`].
+///
+/// If the child reports coverage, it will be copied to the map observer set via
+/// [`PersistentCommandExecutor::with_coverage_observer`].
+pub struct PersistentCommandExecutor<I, OT, S, M = StdMapObserver<'static, u8, false>> {
+    /// The command used to (re)spawn the child
+    command: Command,
+    /// The pipe ends the `pre_exec` hook of `command` connects the next child to
+    fds: Arc<ChildFds>,
+    /// If set to true, the child's `stderr` will remain visible
+    debug_child: bool,
+    /// The running child, if any
+    child: Option<PersistentChild>,
+    /// The observers used by this executor
+    observers: OT,
+    /// The observer the reported coverage is written to
+    coverage_observer: Option<Handle<M>>,
+    /// The timeout for a single execution
+    timeout: Duration,
+    /// The time a freshly spawned child has to send its hello message
+    startup_timeout: Duration,
+    /// Restart the child after this many executions, if set
+    max_iterations: Option<u64>,
+    phantom: PhantomData<(I, S)>,
+}
+
+/// The live child process of a [`PersistentCommandExecutor`], together with its pipes
+#[derive(Debug)]
+struct PersistentChild {
+    handle: Child,
+    /// Fuzzer -> child, connected to the child's `stdin`
+    ctl_pipe: Pipe,
+    /// Child -> fuzzer, connected to the child's `stdout`
+    st_pipe: Pipe,
+    /// The number of inputs this child has executed
+    iterations: u64,
+}
+
+/// The pipe ends a spawning child connects to its `stdin` and `stdout`, updated before each spawn
+#[derive(Debug)]
+struct ChildFds {
+    ctl_read: AtomicI32,
+    ctl_write: AtomicI32,
+    st_read: AtomicI32,
+    st_write: AtomicI32,
+}
+
+impl ChildFds {
+    /// Installs the single `pre_exec` hook on `command` that connects the child to the pipes.
+    ///
+    /// `pre_exec` hooks accumulate, so the hook is installed once, and reads the fds of the current
+    /// spawn from the returned [`ChildFds`]. This way, the same `command` is used for every spawn,
+    /// including its environment removals and clearing.
+    fn install(command: &mut Command) -> Arc<Self> {
+        let fds = Arc::new(Self {
+            ctl_read: AtomicI32::new(-1),
+            ctl_write: AtomicI32::new(-1),
+            st_read: AtomicI32::new(-1),
+            st_write: AtomicI32::new(-1),
+        });
+        let child_fds = fds.clone();
+        let func = move || {
+            let ctl_read = child_fds.ctl_read.load(Ordering::Relaxed);
+            let ctl_write = child_fds.ctl_write.load(Ordering::Relaxed);
+            let st_read = child_fds.st_read.load(Ordering::Relaxed);
+            let st_write = child_fds.st_write.load(Ordering::Relaxed);
+            dup2(ctl_read, libc::STDIN_FILENO).map_err(|_| io::Error::last_os_error())?;
+            dup2(st_write, libc::STDOUT_FILENO).map_err(|_| io::Error::last_os_error())?;
+            unsafe {
+                libc::close(ctl_read);
+                libc::close(ctl_write);
+                libc::close(st_read);
+                libc::close(st_write);
+            }
+            Ok(())
+        };
+        // # Safety
+        // The closure only does atomic loads and calls async-signal-safe libc functions.
+        unsafe { command.pre_exec(func) };
+        fds
+    }
+}
+
+impl PersistentChild {
+    /// Spawns the child from the `command` and waits for its hello message.
+    fn spawn(
+        command: &mut Command,
+        fds: &ChildFds,
+        startup_timeout: Duration,
+    ) -> Result<Self, Error> {
+        let mut ctl_pipe = Pipe::new()?;
+        let mut st_pipe = Pipe::new()?;
+
+        fds.ctl_read
+            .store(ctl_pipe.read_end().unwrap(), Ordering::Relaxed);
+        fds.ctl_write
+            .store(ctl_pipe.write_end().unwrap(), Ordering::Relaxed);
+        fds.st_read
+            .store(st_pipe.read_end().unwrap(), Ordering::Relaxed);
+        fds.st_write
+            .store(st_pipe.write_end().unwrap(), Ordering::Relaxed);
+
+        let handle = match command.spawn() {
+            Ok(handle) => handle,
+            Err(err) => {
+                return Err(Error::illegal_state(format!(
+                    "Could not spawn the persistent child: {err:#?}"
+                )))
+            }
+        };
+
+        // The child's ends of the pipes are unnecessary for the parent, so we'll close them
+        ctl_pipe.close_read_end();
+        st_pipe.close_write_end();
+
+        // Writes must not block, so a child that stops reading is a timeout, not a hang
+        let ctl_write = ctl_pipe.write_end().unwrap();
+        let flags = unsafe { libc::fcntl(ctl_write, libc::F_GETFL) };
+        if flags == -1
+            || unsafe { libc::fcntl(ctl_write, libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1
+        {
+            return Err(Error::last_os_error(
+                "Could not make the ctl pipe non-blocking",
+            ));
+        }
+
+        let mut child = Self {
+            handle,
+            ctl_pipe,
+            st_pipe,
+            iterations: 0,
+        };
+
+        let mut hello = [0_u8; 8];
+        if !child.read_st_timed(&mut hello, Instant::now() + startup_timeout)? {
+            return Err(Error::illegal_state(format!(
+                "The persistent child did not send a hello message within {startup_timeout:?}"
+            )));
+        }
+        let magic = u32::from_le_bytes(hello[0..4].try_into().unwrap());
+        let version = u32::from_le_bytes(hello[4..8].try_into().unwrap());
+        if magic != PERSISTENT_CMD_MAGIC {
+            return Err(Error::illegal_state(format!(
+                "The persistent child sent an invalid hello magic {magic:#x}, expected {PERSISTENT_CMD_MAGIC:#x}"
+            )));
+        }
+        if version != PERSISTENT_CMD_VERSION {
+            return Err(Error::illegal_state(format!(
+                "The persistent child speaks protocol version {version}, but we only support version {PERSISTENT_CMD_VERSION}"
+            )));
+        }
+        Ok(child)
+    }
+
+    /// Sends an input to the child, giving up if it was not fully written by the `deadline`.
+    /// Returns `false` on timeout.
+    fn write_input(&mut self, input: &[u8], deadline: Instant) -> Result<bool, Error> {
+        let len = u32::try_from(input.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Input too large"))?;
+        Ok(self.write_ctl_timed(&len.to_le_bytes(), deadline)?
+            && self.write_ctl_timed(input, deadline)?)
+    }
+
+    /// Writes all of `buf` to the ctl pipe, giving up if it was not fully written by the `deadline`.
+    /// Returns `false` on timeout.
+    fn write_ctl_timed(&mut self, buf: &[u8], deadline: Instant) -> Result<bool, Error> {
+        let Some(ctl_write) = self.ctl_pipe.write_end() else {
+            return Err(Error::os_error(
+                io::Error::new(ErrorKind::BrokenPipe, "Write pipe end was already closed"),
+                "write_ctl_timed failed",
+            ));
+        };
+
+        // # Safety
+        // The FDs are valid as this point in time.
+        let ctl_write = unsafe { BorrowedFd::borrow_raw(ctl_write) };
+
+        let mut written = 0;
+        while written < buf.len() {
+            match self.ctl_pipe.write(&buf[written..]) {
+                Ok(0) => return Err(io::Error::from(ErrorKind::WriteZero).into()),
+                Ok(wrote) => {
+                    written += wrote;
+                    continue;
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => (),
+                Err(err) => return Err(err.into()),
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let mut writefds = FdSet::new();
+            writefds.insert(ctl_write);
+            let sret = pselect(
+                Some(writefds.highest().unwrap().as_raw_fd() + 1),
+                None,
+                &mut writefds,
+                None,
+                Some(&TimeSpec::from_duration(timeout)),
+                Some(&SigSet::empty()),
+            )?;
+            if sret == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reads exactly `buf.len()` bytes from the st pipe, giving up if they did not all arrive by the
+    /// `deadline`. Returns `false` on timeout.
+    fn read_st_timed(&mut self, buf: &mut [u8], deadline: Instant) -> Result<bool, Error> {
+        let Some(st_read) = self.st_pipe.read_end() else {
+            return Err(Error::os_error(
+                io::Error::new(ErrorKind::BrokenPipe, "Read pipe end was already closed"),
+                "read_st_timed failed",
+            ));
+        };
+
+        // # Safety
+        // The FDs are valid as this point in time.
+        let st_read = unsafe { BorrowedFd::borrow_raw(st_read) };
+
+        let mut filled = 0;
+        while filled < buf.len() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let mut readfds = FdSet::new();
+            readfds.insert(st_read);
+            let sret = pselect(
+                Some(readfds.highest().unwrap().as_raw_fd() + 1),
+                &mut readfds,
+                None,
+                None,
+                Some(&TimeSpec::from_duration(timeout)),
+                Some(&SigSet::empty()),
+            )?;
+            if sret == 0 {
+                return Ok(false);
+            }
+            match self.st_pipe.read(&mut buf[filled..]) {
+                Ok(0) => return Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reads the reported coverage, after the status has been received.
+    /// Returns `None` on timeout.
+    fn read_coverage(
+        &mut self,
+        deadline: Instant,
+        max_len: usize,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let mut len = [0_u8; 4];
+        if !self.read_st_timed(&mut len, deadline)? {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > max_len {
+            return Err(Error::illegal_state(format!(
+                "The persistent child reported {len} bytes of coverage, but at most {max_len} fit"
+            )));
+        }
+        let mut coverage = vec![0; len];
+        if !self.read_st_timed(&mut coverage, deadline)? {
+            return Ok(None);
+        }
+        Ok(Some(coverage))
+    }
+
+    /// Waits for the child to exit and returns its exit status
+    fn wait(mut self) -> Result<ExitStatus, Error> {
+        Ok(self.handle.wait()?)
+    }
+}
+
+impl Drop for PersistentChild {
+    fn drop(&mut self) {
+        // if this fails, there is not much we can do. let's hope it failed because the process finished
+        // in the meantime.
+        drop(self.handle.kill());
+        // finally, try to wait to properly clean up system resources.
+        drop(self.handle.wait());
+    }
+}
+
+impl<I, OT, S, M> Debug for PersistentCommandExecutor<I, OT, S, M>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersistentCommandExecutor")
+            .field("command", &self.command)
+            .field("debug_child", &self.debug_child)
+            .field("child", &self.child)
+            .field("observers", &self.observers)
+            .field("timeout", &self.timeout)
+            .field("startup_timeout", &self.startup_timeout)
+            .field("max_iterations", &self.max_iterations)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, OT, S> PersistentCommandExecutor<I, OT, S> {
+    /// Creates a new [`PersistentCommandExecutor`] and spawns the child.
+    ///
+    /// The `command` is used as is for every (re)spawn, with the child's `stdin` and `stdout`
+    /// connected to the protocol pipes. Its `stderr` is hidden unless `debug_child` is set.
+    pub fn new(
+        mut command: Command,
+        observers: OT,
+        timeout: Duration,
+        debug_child: bool,
+    ) -> Result<Self, Error> {
+        if !debug_child {
+            command.stderr(Stdio::null());
+        }
+        let fds = ChildFds::install(&mut command);
+        let child = PersistentChild::spawn(&mut command, &fds, STARTUP_TIMEOUT_DEFAULT)?;
+        Ok(Self {
+            command,
+            fds,
+            debug_child,
+            child: Some(child),
+            observers,
+            coverage_observer: None,
+            timeout,
+            startup_timeout: STARTUP_TIMEOUT_DEFAULT,
+            max_iterations: None,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<I, OT, S, M> PersistentCommandExecutor<I, OT, S, M> {
+    /// Copy the coverage reported by the child into the given map observer after each run.
+    /// Coverage entries that do not fit into the map are dropped.
+    pub fn with_coverage_observer<M2>(
+        self,
+        coverage_observer: Handle<M2>,
+    ) -> PersistentCommandExecutor<I, OT, S, M2> {
+        PersistentCommandExecutor {
+            command: self.command,
+            fds: self.fds,
+            debug_child: self.debug_child,
+            child: self.child,
+            observers: self.observers,
+            coverage_observer: Some(coverage_observer),
+            timeout: self.timeout,
+            startup_timeout: self.startup_timeout,
+            max_iterations: self.max_iterations,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Restart the child after it executed `max_iterations` inputs.
+    /// `None` (the default) only restarts the child after crashes and timeouts.
+    #[must_use]
+    pub fn with_max_iterations(mut self, max_iterations: Option<u64>) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Sets the time a freshly (re)spawned child has to send its hello message.
+    /// This is only applied to children spawned after this call.
+    #[must_use]
+    pub fn with_startup_timeout(mut self, startup_timeout: Duration) -> Self {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+
+    /// The pid of the currently running child, if any
+    #[must_use]
+    pub fn child_pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|child| child.handle.id())
+    }
+
+    /// The raw fd the fuzzer writes inputs to, if a child is running
+    #[must_use]
+    pub fn ctl_fd(&self) -> Option<RawFd> {
+        self.child
+            .as_ref()
+            .and_then(|child| child.ctl_pipe.write_end())
+    }
+}
+
+impl<I, OT, S, M> PersistentCommandExecutor<I, OT, S, M>
+where
+    I: HasTargetBytes,
+    OT: MatchName,
+    M: MapObserver<Entry = u8>,
+{
+    /// Sends the input to the child and waits for the result.
+    fn run_in_child(&mut self, input: &I) -> Result<ExitKind, Error> {
+        if self
+            .child
+            .as_ref()
+            .zip(self.max_iterations)
+            .is_some_and(|(child, max)| child.iterations >= max)
+        {
+            self.child = None;
+        }
+        let mut child = match self.child.take() {
+            Some(child) => child,
+            None => PersistentChild::spawn(&mut self.command, &self.fds, self.startup_timeout)?,
+        };
+        child.iterations += 1;
+
+        let deadline = Instant::now() + self.timeout;
+        match child.write_input(input.target_bytes().as_slice(), deadline) {
+            Ok(true) => (),
+            // The child stopped reading
+            Ok(false) => return Ok(ExitKind::Timeout),
+            Err(Error::OsError(err, _, _)) if err.kind() == ErrorKind::BrokenPipe => {
+                // The child died before reading the input
+                return Ok(Self::exit_kind_from_status(child.wait()?));
+            }
+            Err(err) => return Err(err),
+        }
+
+        let mut status = [0_u8; 4];
+        match child.read_st_timed(&mut status, deadline) {
+            Ok(true) => (),
+            Ok(false) => return Ok(ExitKind::Timeout),
+            Err(Error::OsError(err, _, _)) if err.kind() == ErrorKind::UnexpectedEof => {
+                return Ok(Self::exit_kind_from_status(child.wait()?));
+            }
+            Err(err) => return Err(err),
+        }
+
+        let max_len = self
+            .coverage_observer
+            .as_ref()
+            .map_or(PERSISTENT_CMD_MAX_COVERAGE, |h| {
+                let observers = RefIndexable::from(&self.observers);
+                let map: &M = &observers[h];
+                map.len()
+            });
+        let coverage = match child.read_coverage(deadline, max_len) {
+            Ok(Some(coverage)) => coverage,
+            Ok(None) => return Ok(ExitKind::Timeout),
+            Err(Error::OsError(err, _, _)) if err.kind() == ErrorKind::UnexpectedEof => {
+                return Ok(Self::exit_kind_from_status(child.wait()?));
+            }
+            Err(err) => return Err(err),
+        };
+        if let Some(h) = &self.coverage_observer {
+            let mut observers = RefIndexable::from(&mut self.observers);
+            let map: &mut M = &mut observers[h];
+            for (i, val) in coverage.into_iter().take(map.usable_count()).enumerate() {
+                map.set(i, val);
+            }
+        }
+
+        if i32::from_le_bytes(status) == 0 {
+            self.child = Some(child);
+            Ok(ExitKind::Ok)
+        } else {
+            // The child's state may be corrupted, dropping it kills it and we start afresh on the next run
+            Ok(ExitKind::Crash)
+        }
+    }
+
+    /// Maps the exit status of a child that died mid-execution to an [`ExitKind`]
+    fn exit_kind_from_status(status: ExitStatus) -> ExitKind {
+        match status.signal() {
+            // for reference: https://www.man7.org/linux/man-pages/man7/signal.7.html
+            Some(9) => ExitKind::Oom,
+            _ => ExitKind::Crash,
+        }
+    }
+}
+
+impl<EM, I, OT, S, M, Z> Executor<EM, I, S, Z> for PersistentCommandExecutor<I, OT, S, M>
+where
+    I: HasTargetBytes,
+    S: HasExecutions,
+    OT: MatchName + ObserversTuple<I, S>,
+    M: MapObserver<Entry = u8>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        self.observers.pre_exec_child_all(state, input)?;
+
+        let exit_kind = self.run_in_child(input)?;
+
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<I, OT, S, M> HasTimeout for PersistentCommandExecutor<I, OT, S, M> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<I, OT, S, M> HasObservers for PersistentCommandExecutor<I, OT, S, M>
+where
+    OT: ObserversTuple<I, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process::Command, time::Duration};
+
+    use crate::{
+        executors::{
+            persistent_command::{PersistentCommandExecutor, PERSISTENT_CMD_MAGIC},
+            Executor, ExitKind,
+        },
+        fuzzer::NopFuzzer,
+        inputs::{BytesInput, NopInput},
+        state::NopState,
+    };
+
+    /// A shell script that speaks the protocol, but ignores the input contents,
+    /// and answers each input with `answer`.
+    fn sh_target_answering(answer: &str) -> Command {
+        let magic = PERSISTENT_CMD_MAGIC.to_le_bytes();
+        let script = format!(
+            r#"printf '\{:03o}\{:03o}\{:03o}\{:03o}\001\000\000\000'
+n=0
+while [ "$(dd bs=1 count=5 2>/dev/null | wc -c)" -eq 5 ]; do
+  n=$((n+1))
+  {answer}
+done"#,
+            magic[0], magic[1], magic[2], magic[3]
+        );
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    /// A target crashing on the third input.
+    fn sh_target() -> Command {
+        sh_target_answering(
+            r"if [ $n -eq 3 ]; then exit 1; fi
+  printf '\000\000\000\000\000\000\000\000'",
+        )
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_persistent_command() {
+        let mut executor: PersistentCommandExecutor<BytesInput, (), NopState<NopInput>> =
+            PersistentCommandExecutor::new(sh_target(), (), Duration::from_secs(5), false).unwrap();
+        let pid = executor.child_pid().unwrap();
+
+        let mut state = NopState::<NopInput>::new();
+        let input = BytesInput::new(b"a".to_vec());
+        for expected in [ExitKind::Ok, ExitKind::Ok, ExitKind::Crash, ExitKind::Ok] {
+            let exit_kind = executor
+                .run_target(&mut NopFuzzer::new(), &mut state, &mut (), &input)
+                .unwrap();
+            assert_eq!(exit_kind, expected);
+        }
+        // The child got restarted after the crash
+        assert_ne!(executor.child_pid().unwrap(), pid);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_persistent_command_stalled_coverage() {
+        // The status arrives, but the coverage never does
+        let target = sh_target_answering(r"printf '\000\000\000\000\001'; sleep 10");
+        let mut executor: PersistentCommandExecutor<BytesInput, (), NopState<NopInput>> =
+            PersistentCommandExecutor::new(target, (), Duration::from_millis(500), false).unwrap();
+
+        let mut state = NopState::<NopInput>::new();
+        let input = BytesInput::new(b"a".to_vec());
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut (), &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Timeout);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_persistent_command_stalled_input() {
+        // The child never reads the input, which does not fit into the pipe buffer
+        let magic = PERSISTENT_CMD_MAGIC.to_le_bytes();
+        let script = format!(
+            r"printf '\{:03o}\{:03o}\{:03o}\{:03o}\001\000\000\000'; sleep 10",
+            magic[0], magic[1], magic[2], magic[3]
+        );
+        let mut target = Command::new("sh");
+        target.arg("-c").arg(script);
+        let mut executor: PersistentCommandExecutor<BytesInput, (), NopState<NopInput>> =
+            PersistentCommandExecutor::new(target, (), Duration::from_millis(500), false).unwrap();
+
+        let mut state = NopState::<NopInput>::new();
+        let input = BytesInput::new(vec![0; 1 << 20]);
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut (), &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Timeout);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_persistent_command_env_remove() {
+        // Removed variables stay removed in every spawned child, not only the first one
+        let mut target = sh_target_answering(
+            r#"if [ -n "${HOME+set}" ]; then exit 1; fi
+  printf '\000\000\000\000\000\000\000\000'"#,
+        );
+        target.env_remove("HOME");
+        let mut executor: PersistentCommandExecutor<BytesInput, (), NopState<NopInput>> =
+            PersistentCommandExecutor::new(target, (), Duration::from_secs(5), false)
+                .unwrap()
+                .with_max_iterations(Some(1));
+
+        let mut state = NopState::<NopInput>::new();
+        let input = BytesInput::new(b"a".to_vec());
+        for _ in 0..2 {
+            let exit_kind = executor
+                .run_target(&mut NopFuzzer::new(), &mut state, &mut (), &input)
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Ok);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_persistent_command_coverage_too_large() {
+        let target = sh_target_answering(r"printf '\000\000\000\000\377\377\377\377'");
+        let mut executor: PersistentCommandExecutor<BytesInput, (), NopState<NopInput>> =
+            PersistentCommandExecutor::new(target, (), Duration::from_secs(5), false).unwrap();
+
+        let mut state = NopState::<NopInput>::new();
+        let input = BytesInput::new(b"a".to_vec());
+        assert!(executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut (), &input)
+            .is_err());
+    }
+}
@@ -0,0 +1,936 @@
+//! Run out-of-process targets in a sandbox, built from Linux namespaces and a `seccomp` policy.
+//!
+//! A [`Sandbox`] can be passed to the [`crate::executors::ForkserverExecutorBuilder`] and the
+//! [`crate::executors::CommandExecutorBuilder`].
+//! The namespaces are created unprivileged, in a new user namespace mapping the current user to itself.
+//! Violations of the [`SeccompPolicy`] are reported to a [`crate::observers::SandboxViolationObserver`],
+//! so that a [`crate::feedbacks::SandboxViolationFeedback`] can turn them into objectives.
+
+use alloc::{ffi::CString, format, string::ToString, sync::Arc, vec::Vec};
+use core::{
+    ffi::{c_int, CStr},
+    mem::{size_of, zeroed},
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    ffi::OsStr,
+    io,
+    os::unix::{ffi::OsStrExt, process::CommandExt},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use nix::sys::statvfs::statvfs;
+
+use crate::Error;
+
+// BPF instructions, see `linux/filter.h`
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_JMP_JGE_K: u16 = 0x35;
+const BPF_JMP_JSET_K: u16 = 0x45;
+const BPF_RET_K: u16 = 0x06;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// The x32 ABI shares `AUDIT_ARCH_X86_64`, its syscall numbers have this bit set.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// `fchmodat2`, the same number on all targets, but not in `libc` for all of them yet
+const SYS_FCHMODAT2: i64 = 452;
+
+/// Set by the supervisor before forking the target.
+/// The target has the same value at the same address until it executes the command.
+static EXEC_MARKER: AtomicU64 = AtomicU64::new(0);
+
+// Offsets in `struct seccomp_data`, arguments are 64 bit little endian
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+const fn seccomp_data_arg(idx: u32) -> u32 {
+    16 + 8 * idx
+}
+
+const fn stmt(code: u16, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// What happens to a syscall denied by a [`SeccompPolicy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompAction {
+    /// Kill the target with `SIGSYS`, the execution is reported as a sandbox violation
+    KillProcess,
+    /// Let the syscall fail with the given `errno`, the execution is still reported as a sandbox
+    /// violation
+    Errno(u16),
+}
+
+impl SeccompAction {
+    /// The filter's return value for denied syscalls.
+    /// The supervisor answers with the `errno`, so that it can count the violation.
+    fn ret(self) -> u32 {
+        match self {
+            Self::KillProcess => libc::SECCOMP_RET_KILL_PROCESS,
+            Self::Errno(_) => libc::SECCOMP_RET_USER_NOTIF,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SeccompRule {
+    /// Deny the syscall
+    Deny(i64),
+    /// Deny the syscall if the lower 32 bit of argument `arg` equal `value`
+    DenyIfEq { nr: i64, arg: u32, value: u32 },
+    /// Deny the syscall if the lower 32 bit of argument `arg` have any bit of `flags` set
+    DenyIfFlags { nr: i64, arg: u32, flags: u32 },
+}
+
+/// A `seccomp` policy denying a set of syscalls to the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeccompPolicy {
+    action: SeccompAction,
+    rules: Vec<SeccompRule>,
+    deny_exec: bool,
+}
+
+impl SeccompPolicy {
+    /// Creates a new [`SeccompPolicy`], denying nothing yet.
+    #[must_use]
+    pub fn new(action: SeccompAction) -> Self {
+        Self {
+            action,
+            rules: Vec::new(),
+            deny_exec: false,
+        }
+    }
+
+    /// Kills the target on any attempt to execute a program, open a network socket or write a file.
+    #[must_use]
+    pub fn strict() -> Self {
+        Self::new(SeccompAction::KillProcess)
+            .deny_exec()
+            .deny_network()
+            .deny_file_writes()
+    }
+
+    /// Denies the syscall with the given number (see `libc::SYS_*`)
+    #[must_use]
+    pub fn deny_syscall(mut self, nr: i64) -> Self {
+        self.rules.push(SeccompRule::Deny(nr));
+        self
+    }
+
+    /// Denies executing other programs. Starting the target itself is still allowed.
+    #[must_use]
+    pub fn deny_exec(mut self) -> Self {
+        self.deny_exec = true;
+        self.rules.push(SeccompRule::Deny(libc::SYS_execveat));
+        self
+    }
+
+    /// Denies opening `IPv4`, `IPv6` and raw packet sockets. Unix sockets are still allowed.
+    ///
+    /// `io_uring` is denied as well, as its operations bypass the syscall filter.
+    #[must_use]
+    pub fn deny_network(mut self) -> Self {
+        for domain in [libc::AF_INET, libc::AF_INET6, libc::AF_PACKET] {
+            self.rules.push(SeccompRule::DenyIfEq {
+                nr: libc::SYS_socket,
+                arg: 0,
+                #[expect(clippy::cast_sign_loss)]
+                value: domain as u32,
+            });
+        }
+        self.deny_io_uring()
+    }
+
+    /// Denies opening files for writing, creating, removing, renaming, linking, truncating,
+    /// allocating space for files, and changing the mode, owner, timestamps and extended
+    /// attributes of files and directories.
+    ///
+    /// `io_uring` is denied as well, as its operations bypass the syscall filter.
+    ///
+    /// Note that sanitizers writing their reports to log files will be denied as well.
+    #[must_use]
+    pub fn deny_file_writes(mut self) -> Self {
+        let flags =
+            (libc::O_WRONLY | libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC | libc::O_APPEND) as u32;
+        self.rules.push(SeccompRule::DenyIfFlags {
+            nr: libc::SYS_openat,
+            arg: 2,
+            flags,
+        });
+        #[cfg(target_arch = "x86_64")]
+        {
+            self.rules.push(SeccompRule::DenyIfFlags {
+                nr: libc::SYS_open,
+                arg: 1,
+                flags,
+            });
+            for nr in [
+                libc::SYS_creat,
+                libc::SYS_unlink,
+                libc::SYS_rmdir,
+                libc::SYS_rename,
+                libc::SYS_renameat,
+                libc::SYS_mkdir,
+                libc::SYS_link,
+                libc::SYS_symlink,
+                libc::SYS_chmod,
+                libc::SYS_mknod,
+                libc::SYS_chown,
+                libc::SYS_lchown,
+                libc::SYS_utimes,
+            ] {
+                self.rules.push(SeccompRule::Deny(nr));
+            }
+        }
+        // `openat2` passes the flags in a struct, which `seccomp` cannot look into
+        for nr in [
+            libc::SYS_openat2,
+            libc::SYS_unlinkat,
+            libc::SYS_renameat2,
+            libc::SYS_mkdirat,
+            libc::SYS_linkat,
+            libc::SYS_symlinkat,
+            libc::SYS_truncate,
+            libc::SYS_ftruncate,
+            libc::SYS_fchmod,
+            libc::SYS_fchmodat,
+            SYS_FCHMODAT2,
+            libc::SYS_open_by_handle_at,
+            libc::SYS_mknodat,
+            libc::SYS_fchown,
+            libc::SYS_fchownat,
+            libc::SYS_utimensat,
+            libc::SYS_setxattr,
+            libc::SYS_fsetxattr,
+            libc::SYS_lsetxattr,
+            libc::SYS_fallocate,
+        ] {
+            self.rules.push(SeccompRule::Deny(nr));
+        }
+        self.deny_io_uring()
+    }
+
+    /// Denies setting up and entering `io_uring`s, once per policy
+    fn deny_io_uring(mut self) -> Self {
+        let rule = SeccompRule::Deny(libc::SYS_io_uring_setup);
+        if !self.rules.contains(&rule) {
+            self.rules.push(rule);
+            self.rules.push(SeccompRule::Deny(libc::SYS_io_uring_enter));
+        }
+        self
+    }
+
+    /// The action taken on denied syscalls
+    #[must_use]
+    pub fn action(&self) -> SeccompAction {
+        self.action
+    }
+
+    /// If the filter needs a supervisor answering its notifications
+    fn supervised(&self) -> bool {
+        self.deny_exec || matches!(self.action, SeccompAction::Errno(_))
+    }
+
+    /// Compiles the policy to a BPF program.
+    /// With [`Self::deny_exec`], the supervisor decides on each `execve`.
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn compile(&self) -> Vec<libc::sock_filter> {
+        let deny = self.action.ret();
+        let mut filter = vec![
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+            jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET_K, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+        ];
+        // Otherwise, the x32 numbers of the denied syscalls would bypass the rules
+        #[cfg(target_arch = "x86_64")]
+        {
+            filter.push(jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1));
+            filter.push(stmt(BPF_RET_K, libc::SECCOMP_RET_KILL_PROCESS));
+        }
+        for rule in &self.rules {
+            match *rule {
+                SeccompRule::Deny(nr) => {
+                    filter.push(jump(BPF_JMP_JEQ_K, nr as u32, 0, 1));
+                    filter.push(stmt(BPF_RET_K, deny));
+                }
+                SeccompRule::DenyIfEq { nr, arg, value } => {
+                    filter.push(jump(BPF_JMP_JEQ_K, nr as u32, 0, 4));
+                    filter.push(stmt(BPF_LD_W_ABS, seccomp_data_arg(arg)));
+                    filter.push(jump(BPF_JMP_JEQ_K, value, 0, 1));
+                    filter.push(stmt(BPF_RET_K, deny));
+                    filter.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR));
+                }
+                SeccompRule::DenyIfFlags { nr, arg, flags } => {
+                    filter.push(jump(BPF_JMP_JEQ_K, nr as u32, 0, 4));
+                    filter.push(stmt(BPF_LD_W_ABS, seccomp_data_arg(arg)));
+                    filter.push(jump(BPF_JMP_JSET_K, flags, 0, 1));
+                    filter.push(stmt(BPF_RET_K, deny));
+                    filter.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR));
+                }
+            }
+        }
+        if self.deny_exec {
+            filter.push(jump(BPF_JMP_JEQ_K, libc::SYS_execve as u32, 0, 1));
+            filter.push(stmt(BPF_RET_K, libc::SECCOMP_RET_USER_NOTIF));
+        }
+        filter.push(stmt(BPF_RET_K, libc::SECCOMP_RET_ALLOW));
+        filter
+    }
+}
+
+/// The number of violations the supervisors of the targets answered, in memory shared with them
+#[derive(Debug)]
+struct Violations(NonNull<AtomicU64>);
+
+// The counter is only accessed atomically
+unsafe impl Send for Violations {}
+unsafe impl Sync for Violations {}
+
+impl Violations {
+    fn new() -> Result<Self, Error> {
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size_of::<AtomicU64>(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(Error::last_os_error(
+                "Sandbox: could not map the violation counter",
+            ));
+        }
+        // The mapping is zeroed, and page aligned
+        Ok(Self(NonNull::new(map.cast()).unwrap()))
+    }
+
+    fn counter(&self) -> &AtomicU64 {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Drop for Violations {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.0.as_ptr().cast(), size_of::<AtomicU64>()) };
+    }
+}
+
+/// The configuration of the sandbox the target runs in
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    net_namespace: bool,
+    pid_namespace: bool,
+    read_only: Vec<PathBuf>,
+    tmpfs: Vec<PathBuf>,
+    seccomp: Option<SeccompPolicy>,
+    /// Shared with the supervisors of the targets, and the clones of this sandbox
+    violations: Arc<OnceLock<Violations>>,
+}
+
+impl Sandbox {
+    /// Creates a new, empty, [`Sandbox`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the target in a new network namespace, with only an unconfigured loopback device.
+    #[must_use]
+    pub fn net_namespace(mut self) -> Self {
+        self.net_namespace = true;
+        self
+    }
+
+    /// Runs the target in a new pid namespace.
+    ///
+    /// Only supported by the [`crate::executors::CommandExecutor`], as the forkserver reports the
+    /// pids of its children.
+    #[must_use]
+    pub fn pid_namespace(mut self) -> Self {
+        self.pid_namespace = true;
+        self
+    }
+
+    /// Bind mounts `path` read-only onto itself, in a new mount namespace.
+    #[must_use]
+    pub fn read_only<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.read_only.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Mounts a private `tmpfs` on `path`, in a new mount namespace.
+    #[must_use]
+    pub fn tmpfs<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.tmpfs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Restricts the syscalls of the target with the given [`SeccompPolicy`]
+    #[must_use]
+    pub fn seccomp(mut self, policy: SeccompPolicy) -> Self {
+        self.seccomp = Some(policy);
+        self
+    }
+
+    /// If the target runs in a new pid namespace
+    #[must_use]
+    pub fn has_pid_namespace(&self) -> bool {
+        self.pid_namespace
+    }
+
+    /// The number of violations answered by the supervisors of all targets run in this sandbox,
+    /// or its clones, so far.
+    ///
+    /// Violations killing the target are reported by its `SIGSYS` as well, the others only here.
+    #[must_use]
+    pub fn violations(&self) -> u64 {
+        self.violations
+            .get()
+            .map_or(0, |violations| violations.counter().load(Ordering::Relaxed))
+    }
+
+    /// Sets up the sandbox for `command`, using a `pre_exec` hook.
+    ///
+    /// The `seccomp` filter is installed as the last step of the hook, so this has to be called
+    /// after all other `pre_exec` hooks of the command are set. The command is then executed by
+    /// `std` as usual.
+    ///
+    /// If the [`SeccompPolicy`] denies executing programs, or answers with an `errno`, a supervisor
+    /// stays behind in place of the target, answering the notifications of the filter. It only
+    /// allows the first `execve` of the target, which is the one executing the command.
+    /// The target is killed with the supervisor, and the supervisor exits the same way the target
+    /// does. The supervisor needs Linux 5.5 or later.
+    pub fn apply(&self, command: &mut Command) -> Result<(), Error> {
+        let mount_namespace = !self.read_only.is_empty() || !self.tmpfs.is_empty();
+        let mut clone_flags = 0;
+        if mount_namespace {
+            clone_flags |= libc::CLONE_NEWNS;
+        }
+        if self.net_namespace {
+            clone_flags |= libc::CLONE_NEWNET;
+        }
+        if self.pid_namespace {
+            clone_flags |= libc::CLONE_NEWPID;
+        }
+        if clone_flags != 0 {
+            clone_flags |= libc::CLONE_NEWUSER;
+        }
+
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let uid_map = format!("{uid} {uid} 1").into_bytes();
+        let gid_map = format!("{gid} {gid} 1").into_bytes();
+
+        // Mount flags of the original mounts that must be kept when remounting read-only
+        let locked_flags = libc::MS_NOSUID
+            | libc::MS_NODEV
+            | libc::MS_NOEXEC
+            | libc::MS_NOATIME
+            | libc::MS_NODIRATIME
+            | libc::MS_RELATIME;
+        let read_only = self
+            .read_only
+            .iter()
+            .map(|path| {
+                let flags = statvfs(path)?.flags().bits() & locked_flags;
+                Ok((path_to_cstring(path)?, flags))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let tmpfs = self
+            .tmpfs
+            .iter()
+            .map(|path| path_to_cstring(path))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let pid_namespace = self.pid_namespace;
+        let seccomp = match &self.seccomp {
+            Some(policy) => {
+                if policy.supervised() && self.violations.get().is_none() {
+                    // Another clone may have been faster, either counter is fine
+                    drop(self.violations.set(Violations::new()?));
+                }
+                Some(SeccompFilter {
+                    filter: policy.compile(),
+                    action: policy.action(),
+                    supervised: policy.supervised(),
+                    violations: self.violations.clone(),
+                })
+            }
+            None => None,
+        };
+
+        let func = move || unsafe {
+            if clone_flags != 0 {
+                check(libc::unshare(clone_flags))?;
+                write_proc_file(c"/proc/self/setgroups", b"deny")?;
+                write_proc_file(c"/proc/self/uid_map", &uid_map)?;
+                write_proc_file(c"/proc/self/gid_map", &gid_map)?;
+            }
+            if mount_namespace {
+                check(libc::mount(
+                    ptr::null(),
+                    c"/".as_ptr(),
+                    ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    ptr::null(),
+                ))?;
+                for (path, flags) in &read_only {
+                    check(libc::mount(
+                        path.as_ptr(),
+                        path.as_ptr(),
+                        ptr::null(),
+                        libc::MS_BIND | libc::MS_REC,
+                        ptr::null(),
+                    ))?;
+                    check(libc::mount(
+                        ptr::null(),
+                        path.as_ptr(),
+                        ptr::null(),
+                        libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+                        ptr::null(),
+                    ))?;
+                }
+                for path in &tmpfs {
+                    check(libc::mount(
+                        c"tmpfs".as_ptr(),
+                        path.as_ptr(),
+                        c"tmpfs".as_ptr(),
+                        libc::MS_NOSUID | libc::MS_NODEV,
+                        ptr::null(),
+                    ))?;
+                }
+            }
+            if pid_namespace {
+                enter_pid_namespace()?;
+            }
+            if let Some(seccomp) = &seccomp {
+                seccomp.install()?;
+            }
+            Ok(())
+        };
+        unsafe { command.pre_exec(func) };
+        Ok(())
+    }
+}
+
+/// The compiled `seccomp` filter, installed in the `pre_exec` hook
+#[derive(Debug)]
+struct SeccompFilter {
+    filter: Vec<libc::sock_filter>,
+    action: SeccompAction,
+    supervised: bool,
+    violations: Arc<OnceLock<Violations>>,
+}
+
+impl SeccompFilter {
+    /// Installs the filter, and forks the supervisor if needed.
+    /// Returns in the target, which is executed next.
+    unsafe fn install(&self) -> io::Result<()> {
+        unsafe {
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            let prog = libc::sock_fprog {
+                #[expect(clippy::cast_possible_truncation)]
+                len: self.filter.len() as u16,
+                filter: self.filter.as_ptr().cast_mut(),
+            };
+            if !self.supervised {
+                return check(libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &raw const prog,
+                ));
+            }
+            let listener = libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &raw const prog,
+            );
+            if listener == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            #[expect(clippy::cast_possible_truncation)]
+            self.supervise(listener as c_int)
+        }
+    }
+
+    /// Forks the target, and stays behind in its place, answering the notifications of the filter
+    /// on `listener` until the target exits.
+    unsafe fn supervise(&self, listener: c_int) -> io::Result<()> {
+        unsafe {
+            // Never 0, which is what a new image of the same program has
+            #[expect(clippy::cast_sign_loss)]
+            EXEC_MARKER.store((libc::getpid() as u64) << 1 | 1, Ordering::Relaxed);
+            let child = match libc::fork() {
+                -1 => return Err(io::Error::last_os_error()),
+                0 => {
+                    libc::close(listener);
+                    return check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL));
+                }
+                child => child,
+            };
+            reset_signal_handlers();
+            close_fds_except(listener);
+            #[expect(clippy::cast_possible_truncation)]
+            let pidfd = libc::syscall(libc::SYS_pidfd_open, child, 0) as c_int;
+            if pidfd == -1 {
+                libc::kill(child, libc::SIGKILL);
+                exit_like(child);
+            }
+            loop {
+                let mut fds = [
+                    libc::pollfd {
+                        fd: listener,
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                    libc::pollfd {
+                        fd: pidfd,
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                ];
+                if libc::poll(fds.as_mut_ptr(), 2, -1) == -1 {
+                    if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    break;
+                }
+                // The target exited
+                if fds[1].revents != 0 {
+                    break;
+                }
+                if fds[0].revents & libc::POLLIN != 0 {
+                    self.answer(listener, child);
+                }
+            }
+            exit_like(child)
+        }
+    }
+
+    /// Answers the next notification of the filter.
+    ///
+    /// The `execve`s of the `target` before it executed the command are `std` executing it,
+    /// possibly trying several paths, and are allowed. All other notified syscalls are violations.
+    unsafe fn answer(&self, listener: c_int, target: libc::pid_t) {
+        unsafe {
+            let mut req: libc::seccomp_notif = zeroed();
+            // Fails if the caller was killed in the meantime
+            if libc::ioctl(listener, libc::SECCOMP_IOCTL_NOTIF_RECV, &raw mut req) == -1 {
+                return;
+            }
+            let mut resp: libc::seccomp_notif_resp = zeroed();
+            resp.id = req.id;
+            #[expect(clippy::cast_possible_wrap)]
+            let pid = req.pid as libc::pid_t;
+            if i64::from(req.data.nr) == libc::SYS_execve && pid == target && !executed(target) {
+                #[expect(clippy::cast_possible_truncation)]
+                {
+                    resp.flags = libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32;
+                }
+            } else {
+                if let Some(violations) = self.violations.get() {
+                    violations.counter().fetch_add(1, Ordering::Relaxed);
+                }
+                resp.error = match self.action {
+                    SeccompAction::KillProcess => {
+                        libc::kill(pid, libc::SIGSYS);
+                        -libc::EPERM
+                    }
+                    SeccompAction::Errno(errno) => -i32::from(errno),
+                };
+            }
+            // Fails if the caller was killed in the meantime, which is fine
+            libc::ioctl(listener, libc::SECCOMP_IOCTL_NOTIF_SEND, &raw const resp);
+        }
+    }
+}
+
+fn os_str_to_cstring(s: &OsStr) -> Result<CString, Error> {
+    CString::new(s.as_bytes()).map_err(|err| Error::illegal_argument(err.to_string()))
+}
+
+fn path_to_cstring(path: &Path) -> Result<CString, Error> {
+    os_str_to_cstring(path.as_os_str())
+}
+
+fn check(ret: c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Writes `content` to a file in `/proc`, without allocating
+unsafe fn write_proc_file(path: &CStr, content: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let written = libc::write(fd, content.as_ptr().cast(), content.len());
+        libc::close(fd);
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Forks after `unshare(CLONE_NEWPID)`, so that the target becomes the init of the new pid namespace.
+///
+/// The parent stays behind in place of the target and exits the same way it does.
+/// The target is killed with the parent, e.g. when the executor kills it on a timeout.
+unsafe fn enter_pid_namespace() -> io::Result<()> {
+    unsafe {
+        match libc::fork() {
+            -1 => Err(io::Error::last_os_error()),
+            0 => {
+                check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+                Ok(())
+            }
+            child => {
+                reset_signal_handlers();
+                close_fds_except(-1);
+                exit_like(child)
+            }
+        }
+    }
+}
+
+/// If `target` executed the command, i.e. it lost the [`EXEC_MARKER`] it inherited from the supervisor.
+///
+/// The target is stopped in the notified syscall, so its memory does not change meanwhile.
+unsafe fn executed(target: libc::pid_t) -> bool {
+    let mut marker = 0_u64;
+    let local = libc::iovec {
+        iov_base: (&raw mut marker).cast(),
+        iov_len: size_of::<u64>(),
+    };
+    let remote = libc::iovec {
+        iov_base: EXEC_MARKER.as_ptr().cast(),
+        iov_len: size_of::<u64>(),
+    };
+    let read =
+        unsafe { libc::process_vm_readv(target, &raw const local, 1, &raw const remote, 1, 0) };
+    #[expect(clippy::cast_sign_loss)]
+    let complete = read as usize == size_of::<u64>();
+    !complete || marker != EXEC_MARKER.load(Ordering::Relaxed)
+}
+
+/// Closes all fds but `keep`, including the pipe `std` uses to report exec errors,
+/// so that spawning returns as soon as the target is executed.
+unsafe fn close_fds_except(keep: c_int) {
+    for (first, last) in [(0, keep - 1), (keep + 1, c_int::MAX)] {
+        if first > last {
+            continue;
+        }
+        unsafe {
+            #[expect(clippy::cast_sign_loss)]
+            if libc::syscall(libc::SYS_close_range, first as u32, last as u32, 0) == -1 {
+                for fd in first..=last.min(1023) {
+                    libc::close(fd);
+                }
+            }
+        }
+    }
+}
+
+/// Restores the default action of all signals, in the processes staying behind in place of the target.
+///
+/// They never execute anything, so they would keep the handlers of the fuzzer,
+/// e.g. the `SIGCHLD` handler of `wait_timeout`, which writes to an fd closed by [`close_fds_except`].
+unsafe fn reset_signal_handlers() {
+    for signal in 1..libc::SIGRTMIN() {
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+}
+
+/// Waits for `child`, and exits the same way it did
+unsafe fn exit_like(child: libc::pid_t) -> ! {
+    unsafe {
+        let mut status = 0;
+        while libc::waitpid(child, &raw mut status, 0) == -1 {
+            if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                libc::_exit(1);
+            }
+        }
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{
+        io,
+        os::unix::process::{CommandExt, ExitStatusExt},
+        process::Command,
+    };
+
+    use wait_timeout::ChildExt;
+
+    use super::{Sandbox, SeccompAction, SeccompPolicy};
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_seccomp_deny_exec() {
+        let sandbox =
+            Sandbox::new().seccomp(SeccompPolicy::new(SeccompAction::KillProcess).deny_exec());
+
+        // The target itself may start
+        let mut command = Command::new("sh");
+        command.args(["-c", "exit 3"]);
+        sandbox.apply(&mut command).unwrap();
+        assert_eq!(command.status().unwrap().code(), Some(3));
+
+        // But it may not execute anything else
+        let mut command = Command::new("sh");
+        command.args(["-c", "exec true"]);
+        sandbox.apply(&mut command).unwrap();
+        assert_eq!(command.status().unwrap().signal(), Some(libc::SIGSYS));
+        assert_eq!(sandbox.violations(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_seccomp_deny_file_writes() {
+        let sandbox = Sandbox::new().seccomp(
+            SeccompPolicy::new(SeccompAction::Errno(libc::EPERM as u16)).deny_file_writes(),
+        );
+        let dir = std::env::temp_dir().join(format!("libafl_sandbox_{}", std::process::id()));
+
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "mkdir \"$0\" || ln -s / \"$0\"",
+            dir.to_str().unwrap(),
+        ]);
+        sandbox.apply(&mut command).unwrap();
+        assert_ne!(command.status().unwrap().code(), Some(0));
+        assert!(!dir.exists());
+        // The target went on, but the violations were still counted
+        let violations = sandbox.violations();
+        assert!(violations > 0);
+
+        // Changing the mode of an existing file is a write as well
+        let mut command = Command::new("sh");
+        command.args(["-c", "chmod 700 \"$0\"", "/tmp"]);
+        sandbox.apply(&mut command).unwrap();
+        assert_ne!(command.status().unwrap().code(), Some(0));
+        assert!(sandbox.violations() > violations);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_seccomp_deny_io_uring() {
+        for policy in [SeccompPolicy::deny_file_writes, SeccompPolicy::deny_network] {
+            let sandbox = Sandbox::new().seccomp(policy(SeccompPolicy::new(SeccompAction::Errno(
+                libc::EPERM as u16,
+            ))));
+
+            let mut command = Command::new("true");
+            sandbox.apply(&mut command).unwrap();
+            // Runs in the target, once the filter is installed, and exits with the `errno`
+            unsafe {
+                command.pre_exec(|| {
+                    let mut params = [0u8; 120];
+                    let ret = libc::syscall(libc::SYS_io_uring_setup, 1, params.as_mut_ptr());
+                    let errno = if ret == -1 {
+                        io::Error::last_os_error().raw_os_error().unwrap_or(0)
+                    } else {
+                        0
+                    };
+                    libc::_exit(errno)
+                });
+            }
+            let code = command.status().unwrap().code();
+            assert!(
+                code == Some(libc::EPERM) || code == Some(libc::ENOSYS),
+                "io_uring_setup was not denied: {code:?}"
+            );
+            assert_eq!(sandbox.violations(), 1);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_seccomp_signal_handlers() {
+        // Installs the `SIGCHLD` handler of `wait_timeout`, like the `CommandExecutor` does
+        let mut child = Command::new("true").spawn().unwrap();
+        child
+            .wait_timeout(Duration::from_secs(10))
+            .unwrap()
+            .unwrap();
+
+        // The supervisor does not run it when the target exits
+        let sandbox =
+            Sandbox::new().seccomp(SeccompPolicy::new(SeccompAction::KillProcess).deny_exec());
+        let mut command = Command::new("sh");
+        command.args(["-c", "exit 3"]);
+        sandbox.apply(&mut command).unwrap();
+        let status = command
+            .spawn()
+            .unwrap()
+            .wait_timeout(Duration::from_secs(10))
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.code(), Some(3));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_seccomp_env() {
+        let sandbox =
+            Sandbox::new().seccomp(SeccompPolicy::new(SeccompAction::KillProcess).deny_exec());
+
+        // Inherited, with the variables of the command
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "test -n \"$PATH\" && test \"$SANDBOX_VAR\" = 1"])
+            .env("SANDBOX_VAR", "1");
+        sandbox.apply(&mut command).unwrap();
+        assert_eq!(command.status().unwrap().code(), Some(0));
+
+        // Cleared
+        let mut command = Command::new("/bin/sh");
+        command
+            .args(["-c", "test -z \"${HOME+set}\" && test \"$SANDBOX_VAR\" = 1"])
+            .env_clear()
+            .env("SANDBOX_VAR", "1");
+        sandbox.apply(&mut command).unwrap();
+        assert_eq!(command.status().unwrap().code(), Some(0));
+    }
+}
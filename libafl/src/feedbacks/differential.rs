@@ -1,10 +1,13 @@
 //! Diff Feedback, comparing the content of two observers of the same type.
 //!
+//! For N-way differential fuzzing, [`MultiDiffFeedback`] compares the outputs collected by a
+//! [`DiffOutputsObserver`].
 
-use alloc::borrow::Cow;
+use alloc::{borrow::Cow, vec::Vec};
 use core::fmt::{self, Debug, Formatter};
 
 use libafl_bolts::{
+    impl_serdeany,
     tuples::{Handle, Handled, MatchName, MatchNameRef},
     Named,
 };
@@ -13,9 +16,11 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "track_hit_feedbacks")]
 use crate::feedbacks::premature_last_result_err;
 use crate::{
+    corpus::Testcase,
     executors::ExitKind,
     feedbacks::{Feedback, FeedbackFactory, StateInitializer},
-    Error,
+    observers::{DiffOutput, DiffOutputsObserver},
+    Error, HasMetadata,
 };
 
 /// The result of a differential test between two observers.
@@ -176,6 +181,217 @@ where
     }
 }
 
+/// Compares the outputs of all executors of a [`crate::executors::MultiDiffExecutor`],
+/// as collected by a [`DiffOutputsObserver`], to see if the result should be denoted as equal
+pub trait MultiDiffComparator<T> {
+    /// Performs the comparison between the outputs, given in the order of the executors
+    fn compare(&mut self, outputs: &[T]) -> DiffResult;
+}
+
+impl<F, T> MultiDiffComparator<T> for F
+where
+    F: Fn(&[T]) -> DiffResult,
+{
+    fn compare(&mut self, outputs: &[T]) -> DiffResult {
+        self(outputs)
+    }
+}
+
+/// A [`MultiDiffComparator`] reporting a [`DiffResult::Diff`] if any output differs from the others
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AnyDiffComparator;
+
+impl<T> MultiDiffComparator<T> for AnyDiffComparator
+where
+    T: PartialEq,
+{
+    fn compare(&mut self, outputs: &[T]) -> DiffResult {
+        if outputs.windows(2).all(|w| w[0] == w[1]) {
+            DiffResult::Equal
+        } else {
+            DiffResult::Diff
+        }
+    }
+}
+
+/// A [`MultiDiffComparator`] reporting a [`DiffResult::Diff`] if a strict majority of the outputs agree,
+/// but at least one output differs from the majority.
+///
+/// Without a strict majority, no single implementation can be blamed, so the outputs are denoted as equal.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct MajorityVoteComparator;
+
+impl<T> MultiDiffComparator<T> for MajorityVoteComparator
+where
+    T: PartialEq,
+{
+    fn compare(&mut self, outputs: &[T]) -> DiffResult {
+        match majority(outputs) {
+            Some((_, count)) if count < outputs.len() => DiffResult::Diff,
+            _ => DiffResult::Equal,
+        }
+    }
+}
+
+/// Returns the index of an output agreed upon by a strict majority of the outputs, and the size of that majority
+fn majority<T>(outputs: &[T]) -> Option<(usize, usize)>
+where
+    T: PartialEq,
+{
+    outputs
+        .iter()
+        .enumerate()
+        .map(|(idx, output)| (idx, outputs.iter().filter(|o| *o == output).count()))
+        .find(|(_, count)| *count * 2 > outputs.len())
+}
+
+/// Metadata added to testcases found by a [`MultiDiffFeedback`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiDiffMetadata {
+    /// The indices of the executors whose output differs from the majority.
+    /// Without a strict majority, this contains all executors.
+    pub outliers: Vec<usize>,
+}
+
+impl_serdeany!(MultiDiffMetadata);
+
+/// A [`MultiDiffFeedback`] compares the outputs collected by a [`DiffOutputsObserver`]
+/// during an N-way differential execution, using the given [`MultiDiffComparator`].
+///
+/// Use [`AnyDiffComparator`] to report any disagreement, [`MajorityVoteComparator`] to report
+/// executors that disagree with the majority, or a closure for custom comparisons.
+#[derive(Serialize, Deserialize)]
+pub struct MultiDiffFeedback<C, O>
+where
+    O: DiffOutput,
+{
+    /// This feedback's name
+    name: Cow<'static, str>,
+    /// The observer collecting the outputs
+    o_ref: Handle<DiffOutputsObserver<O>>,
+    // The previous run's result of `Self::is_interesting`
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+    /// The comparator used to compare the outputs
+    comparator: C,
+}
+
+impl<C, O> MultiDiffFeedback<C, O>
+where
+    O: DiffOutput,
+{
+    /// Create a new [`MultiDiffFeedback`] using a [`DiffOutputsObserver`] and a comparator.
+    pub fn new(name: &'static str, observer: &DiffOutputsObserver<O>, comparator: C) -> Self {
+        Self {
+            name: Cow::from(name),
+            o_ref: observer.handle(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            comparator,
+        }
+    }
+
+    fn observer<'a, OT>(&self, observers: &'a OT) -> Result<&'a DiffOutputsObserver<O>, Error>
+    where
+        OT: MatchName,
+    {
+        observers.get(&self.o_ref).ok_or_else(|| {
+            Error::illegal_argument(format!(
+                "MultiDiffFeedback: observer {} not found",
+                self.o_ref.name()
+            ))
+        })
+    }
+}
+
+impl<C, O, T> FeedbackFactory<MultiDiffFeedback<C, O>, T> for MultiDiffFeedback<C, O>
+where
+    C: Clone,
+    O: DiffOutput,
+{
+    fn create_feedback(&self, _ctx: &T) -> MultiDiffFeedback<C, O> {
+        Self {
+            name: self.name.clone(),
+            o_ref: self.o_ref.clone(),
+            comparator: self.comparator.clone(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl<C, O> Named for MultiDiffFeedback<C, O>
+where
+    O: DiffOutput,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, O> Debug for MultiDiffFeedback<C, O>
+where
+    O: DiffOutput,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiDiffFeedback")
+            .field("name", self.name())
+            .field("o", &self.o_ref)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, O, S> StateInitializer<S> for MultiDiffFeedback<C, O> where O: DiffOutput {}
+
+impl<C, EM, I, O, OT, S> Feedback<EM, I, OT, S> for MultiDiffFeedback<C, O>
+where
+    OT: MatchName,
+    C: MultiDiffComparator<O::Output>,
+    O: DiffOutput,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = self.observer(observers)?;
+        let res = self.comparator.compare(observer.outputs()) == DiffResult::Diff;
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let outputs = self.observer(observers)?.outputs();
+        let outliers = match majority(outputs) {
+            Some((idx, _)) => (0..outputs.len())
+                .filter(|i| outputs[*i] != outputs[idx])
+                .collect(),
+            None => (0..outputs.len()).collect(),
+        };
+        testcase
+            .metadata_map_mut()
+            .insert(MultiDiffMetadata { outliers });
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::borrow::Cow;
@@ -185,7 +401,12 @@ mod tests {
     use crate::{
         events::NopEventManager,
         executors::ExitKind,
-        feedbacks::{differential::DiffResult, DiffFeedback, Feedback},
+        feedbacks::{
+            differential::{
+                AnyDiffComparator, DiffResult, MajorityVoteComparator, MultiDiffComparator,
+            },
+            DiffFeedback, Feedback,
+        },
         inputs::BytesInput,
         observers::Observer,
         state::NopState,
@@ -255,4 +476,29 @@ mod tests {
     fn test_diff_neq() {
         test_diff(false);
     }
+
+    #[test]
+    fn test_multi_diff_comparators() {
+        let agree = [1, 1, 1];
+        let outlier = [1, 2, 1];
+        let split = [1, 2, 3];
+
+        assert!(AnyDiffComparator.compare(&agree).is_equal());
+        assert!(AnyDiffComparator.compare(&outlier).is_diff());
+        assert!(AnyDiffComparator.compare(&split).is_diff());
+
+        assert!(MajorityVoteComparator.compare(&agree).is_equal());
+        assert!(MajorityVoteComparator.compare(&outlier).is_diff());
+        assert!(MajorityVoteComparator.compare(&split).is_equal());
+
+        let mut custom = |outputs: &[i32]| {
+            if outputs.contains(&3) {
+                DiffResult::Diff
+            } else {
+                DiffResult::Equal
+            }
+        };
+        assert!(custom.compare(&outlier).is_equal());
+        assert!(custom.compare(&split).is_diff());
+    }
 }
@@ -0,0 +1,85 @@
+//! Feedback and metadata for the backtrace of hangs.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    feedbacks::{Feedback, StateInitializer},
+    observers::{HangBacktraceObserver, HangFrame, ObserverWithHashField},
+    Error, HasMetadata,
+};
+
+/// Metadata for [`HangBacktraceToMetadataFeedback`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HangBacktraceMetadata {
+    /// The frames of the hang, innermost first
+    pub frames: Vec<HangFrame>,
+    /// The hash of the hang location, as reported by the [`HangBacktraceObserver`]
+    pub hash: u64,
+}
+
+impl_serdeany!(HangBacktraceMetadata);
+
+/// Nop feedback that annotates the backtrace of a hang in the new testcase.
+/// The testcase is never interesting (use with an OR).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HangBacktraceToMetadataFeedback {
+    o_ref: Handle<HangBacktraceObserver>,
+}
+
+impl<S> StateInitializer<S> for HangBacktraceToMetadataFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for HangBacktraceToMetadataFeedback
+where
+    OT: MatchName,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Append the backtrace of the hang to the testcase, if there is one.
+    #[inline]
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("HangBacktraceObserver is missing"))?;
+        if let Some(hash) = observer.hash() {
+            testcase.metadata_map_mut().insert(HangBacktraceMetadata {
+                frames: observer.frames().to_vec(),
+                hash,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Named for HangBacktraceToMetadataFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl HangBacktraceToMetadataFeedback {
+    /// Creates a new [`HangBacktraceToMetadataFeedback`].
+    #[must_use]
+    pub fn new(observer: &HangBacktraceObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
@@ -40,6 +40,8 @@ pub mod concolic;
 /// The module for `CustomFilenameToTestcaseFeedback`
 pub mod custom_filename;
 pub mod differential;
+#[cfg(feature = "std")]
+pub mod hang_backtrace;
 /// The module for list feedback
 pub mod list;
 pub mod map;
@@ -48,11 +50,21 @@ pub mod nautilus;
 #[cfg(feature = "std")]
 pub mod new_hash_feedback;
 #[cfg(feature = "std")]
+pub mod sandbox;
+#[cfg(feature = "regex")]
+pub mod sanitizer;
+#[cfg(feature = "std")]
 pub mod stdio;
 pub mod transferred;
 
 #[cfg(feature = "std")]
 pub use capture_feedback::CaptureTimeoutFeedback;
+#[cfg(feature = "std")]
+pub use hang_backtrace::HangBacktraceToMetadataFeedback;
+#[cfg(feature = "std")]
+pub use sandbox::SandboxViolationFeedback;
+#[cfg(feature = "regex")]
+pub use sanitizer::SanitizerReportFeedback;
 
 #[cfg(feature = "introspection")]
 use crate::state::HasClientPerfMonitor;
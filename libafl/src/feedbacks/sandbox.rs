@@ -0,0 +1,88 @@
+//! The [`SandboxViolationFeedback`] turns violations of the sandbox policy into objectives.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    observers::SandboxViolationObserver,
+    Error,
+};
+
+/// A [`Feedback`] that is interesting if the target violated the policy of its sandbox,
+/// for example if a parser tried to execute another program.
+/// Use it as an objective, next to the crash feedback.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SandboxViolationFeedback {
+    o_ref: Handle<SandboxViolationObserver>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl SandboxViolationFeedback {
+    /// Creates a new [`SandboxViolationFeedback`]
+    #[must_use]
+    pub fn new(observer: &SandboxViolationObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl<S> StateInitializer<S> for SandboxViolationFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for SandboxViolationFeedback
+where
+    OT: MatchName,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("SandboxViolationObserver is missing"))?;
+        let res = observer.violation();
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
+impl Named for SandboxViolationFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl HasObserverHandle for SandboxViolationFeedback {
+    type Observer = SandboxViolationObserver;
+
+    #[inline]
+    fn observer_handle(&self) -> &Handle<SandboxViolationObserver> {
+        &self.o_ref
+    }
+}
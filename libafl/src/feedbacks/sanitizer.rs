@@ -0,0 +1,174 @@
+//! The [`SanitizerReportFeedback`] turns sanitizer reports into objectives, whatever the exit of the target.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    observers::{SanitizerKind, SanitizerReport, SanitizerReportObserver},
+    Error, HasMetadata,
+};
+
+/// Metadata for [`SanitizerReportFeedback`], the sanitizer reports of a testcase.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SanitizerReportMetadata {
+    /// The reports, in the order they were emitted
+    pub reports: Vec<SanitizerReport>,
+}
+
+impl_serdeany!(SanitizerReportMetadata);
+
+/// A [`Feedback`] that is interesting if a [`SanitizerReportObserver`] found a sanitizer report.
+///
+/// The exit kind of the target is not considered, so findings of sanitizers running with
+/// `halt_on_error=0` (or of `UBSan`, which does not abort by default) are caught, too.
+/// Use it as an objective. The reports are stored in a [`SanitizerReportMetadata`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SanitizerReportFeedback {
+    o_ref: Handle<SanitizerReportObserver>,
+    /// Only reports of these sanitizers are considered, all if empty
+    sanitizers: Vec<SanitizerKind>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl SanitizerReportFeedback {
+    /// Creates a new [`SanitizerReportFeedback`], considering the reports of all sanitizers.
+    #[must_use]
+    pub fn new(observer: &SanitizerReportObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+            sanitizers: Vec::new(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Only consider the reports of the given sanitizers
+    #[must_use]
+    pub fn with_sanitizers(mut self, sanitizers: &[SanitizerKind]) -> Self {
+        self.sanitizers = sanitizers.to_vec();
+        self
+    }
+
+    fn matches(&self, report: &SanitizerReport) -> bool {
+        self.sanitizers.is_empty() || self.sanitizers.contains(&report.sanitizer)
+    }
+}
+
+impl<S> StateInitializer<S> for SanitizerReportFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for SanitizerReportFeedback
+where
+    OT: MatchName,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("SanitizerReportObserver is missing"))?;
+        let res = observer.reports().iter().any(|report| self.matches(report));
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("SanitizerReportObserver is missing"))?;
+        let reports: Vec<_> = observer
+            .reports()
+            .iter()
+            .filter(|report| self.matches(report))
+            .cloned()
+            .collect();
+        if !reports.is_empty() {
+            testcase
+                .metadata_map_mut()
+                .insert(SanitizerReportMetadata { reports });
+        }
+        Ok(())
+    }
+}
+
+impl Named for SanitizerReportFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl HasObserverHandle for SanitizerReportFeedback {
+    type Observer = SanitizerReportObserver;
+
+    #[inline]
+    fn observer_handle(&self) -> &Handle<SanitizerReportObserver> {
+        &self.o_ref
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::tuples::tuple_list;
+
+    use crate::{
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{Feedback, SanitizerReportFeedback},
+        inputs::BytesInput,
+        observers::{SanitizerKind, SanitizerReportObserver},
+        state::NopState,
+    };
+
+    #[test]
+    fn test_sanitizer_report_feedback() {
+        let mut state: NopState<BytesInput> = NopState::new();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![0]);
+
+        let mut observer = SanitizerReportObserver::default();
+        let mut feedback = SanitizerReportFeedback::new(&observer);
+        let mut thread_feedback =
+            SanitizerReportFeedback::new(&observer).with_sanitizers(&[SanitizerKind::Thread]);
+
+        observer.parse_output("test.c:5:12: runtime error: load of null pointer of type 'int'\n");
+        let observers = tuple_list!(observer);
+        assert!(feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        assert!(!thread_feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+    }
+}
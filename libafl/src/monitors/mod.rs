@@ -20,22 +20,34 @@ pub mod tui;
 #[cfg(all(feature = "tui_monitor", feature = "std"))]
 pub use tui::TuiMonitor;
 
+#[cfg(feature = "otlp_monitor")]
+pub mod otlp;
+
 #[cfg(feature = "prometheus_monitor")]
 pub mod prometheus;
 
 #[cfg(feature = "statsd_monitor")]
 pub mod statsd;
 
+#[cfg(feature = "web_monitor")]
+pub mod web;
+
 use alloc::fmt::Debug;
 #[cfg(feature = "std")]
 use alloc::vec::Vec;
 use core::{fmt, fmt::Write, time::Duration};
 
 use libafl_bolts::ClientId;
+#[cfg(feature = "otlp_monitor")]
+pub use otlp::OtlpMonitor;
+#[cfg(all(feature = "otlp_monitor", feature = "introspection"))]
+pub use otlp::OtlpTracer;
 #[cfg(feature = "prometheus_monitor")]
 pub use prometheus::PrometheusMonitor;
 #[cfg(feature = "statsd_monitor")]
 pub use statsd::StatsdMonitor;
+#[cfg(feature = "web_monitor")]
+pub use web::WebMonitor;
 
 use crate::monitors::stats::ClientStatsManager;
 
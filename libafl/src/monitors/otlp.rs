@@ -0,0 +1,755 @@
+//! OpenTelemetry monitor and tracer.
+//!
+//! Pushes the fuzzing statistics to an OpenTelemetry collector, using the
+//! [OTLP/HTTP](https://opentelemetry.io/docs/specs/otlp/#otlphttp) protocol with JSON payloads.
+//!
+//! All global stats, per-client stats and numeric user stats are exported as metrics by the
+//! [`OtlpMonitor`]. With the `introspection` feature, the share of each part of the fuzz loop in
+//! the [`ClientPerfStats`] is exported as a metric as well.
+//!
+//! With the `introspection` feature, spans are recorded in the fuzzer itself, by an `OtlpTracer`
+//! shared between an `OtlpSpanStage`, for a span around each run of a stage, and an
+//! `OtlpSpanExecutor`, for a span around each execution.
+//!
+//! Exports happen on a background thread, so a slow or missing collector never stalls the fuzzer.
+//! If the collector can't keep up, exports are dropped.
+//! Only plain HTTP is supported. To export over TLS, run a local collector that forwards to the
+//! remote one.
+
+// Use this since clippy thinks we should use `OpenTelemetry` instead of OpenTelemetry.
+#![allow(clippy::doc_markdown)]
+
+#[cfg(feature = "introspection")]
+use alloc::rc::Rc;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "introspection")]
+use core::cell::RefCell;
+use core::time::Duration;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread,
+};
+
+use libafl_bolts::{current_time, ClientId};
+#[cfg(feature = "introspection")]
+use libafl_bolts::{
+    current_nanos,
+    rands::{Rand, StdRand},
+};
+use serde_json::{json, Value};
+
+#[cfg(feature = "introspection")]
+use super::stats::perf_stats::{ClientPerfStats, PerfFeature};
+use super::{
+    stats::{manager::GlobalStats, ClientStatsManager, UserStatsValue},
+    Monitor,
+};
+
+/// The prefix of all exported metric names
+const METRIC_PREFIX: &str = "libafl";
+
+/// The default OTLP/HTTP endpoint of a local collector
+pub const DEFAULT_OTLP_ENDPOINT: &str = "127.0.0.1:4318";
+
+/// The path the collector receives metrics on
+const METRICS_PATH: &str = "/v1/metrics";
+
+/// The path the collector receives traces on
+#[cfg(feature = "introspection")]
+const TRACES_PATH: &str = "/v1/traces";
+
+/// How long to wait for the collector, before giving up on an export
+const COLLECTOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many exports may wait for the collector, before new ones are dropped
+const EXPORT_QUEUE_LEN: usize = 16;
+
+/// How many spans an [`OtlpTracer`] keeps between two exports, before new ones are dropped
+#[cfg(feature = "introspection")]
+const MAX_BATCHED_SPANS: usize = 1 << 14;
+
+/// Sends requests to the collector from a background thread, dropping them if it falls behind
+#[derive(Debug, Clone)]
+struct OtlpExporter {
+    requests: SyncSender<(&'static str, String)>,
+}
+
+impl OtlpExporter {
+    fn new(endpoint: &str) -> Self {
+        let (requests, receiver) = mpsc::sync_channel::<(&'static str, String)>(EXPORT_QUEUE_LEN);
+        let endpoint = endpoint.to_string();
+        // The thread exits once all senders are gone
+        thread::spawn(move || {
+            for (path, body) in receiver {
+                match post_json(&endpoint, path, &body) {
+                    Ok(status) if (200..300).contains(&status) => {}
+                    Ok(status) => {
+                        log::warn!("OTLP collector at {endpoint} answered {path} with {status}");
+                    }
+                    Err(err) => {
+                        log::warn!("OTLP monitor failed to export to {endpoint}: {err}");
+                    }
+                }
+            }
+        });
+        Self { requests }
+    }
+
+    fn export(&self, path: &'static str, request: &Value) {
+        match self.requests.try_send((path, request.to_string())) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                log::debug!("OTLP collector is falling behind, dropping an export to {path}");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                log::warn!("OTLP exporter thread is gone, dropping an export to {path}");
+            }
+        }
+    }
+}
+
+/// The `resource` of all exports
+fn resource(service_name: &str) -> Value {
+    json!({ "attributes": [string_attribute("service.name", service_name)] })
+}
+
+/// OpenTelemetry monitor, pushing metrics to a collector via OTLP/HTTP
+#[derive(Debug)]
+pub struct OtlpMonitor {
+    service_name: String,
+    export_interval: Duration,
+    last_export: Option<Duration>,
+    exporter: OtlpExporter,
+}
+
+impl OtlpMonitor {
+    /// Create a new OpenTelemetry monitor, which pushes metrics to the collector at `endpoint`,
+    /// given as `host:port`, such as [`DEFAULT_OTLP_ENDPOINT`].
+    ///
+    /// If the collector is down, the exports are dropped, and the fuzzer continues as usual.
+    #[must_use]
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            service_name: METRIC_PREFIX.to_string(),
+            export_interval: Duration::from_secs(10),
+            last_export: None,
+            exporter: OtlpExporter::new(endpoint),
+        }
+    }
+
+    /// Sets the `service.name` resource attribute of all exports, `libafl` by default.
+    #[must_use]
+    pub fn with_service_name(mut self, service_name: &str) -> Self {
+        self.service_name = service_name.to_string();
+        self
+    }
+
+    /// Sets the minimum time between two exports, 10 seconds by default.
+    #[must_use]
+    pub fn with_export_interval(mut self, export_interval: Duration) -> Self {
+        self.export_interval = export_interval;
+        self
+    }
+
+    /// Builds the `ExportMetricsServiceRequest` for the current stats
+    #[expect(clippy::cast_precision_loss)]
+    fn metrics_request(
+        &self,
+        client_stats_manager: &mut ClientStatsManager,
+        now: Duration,
+    ) -> Value {
+        let mut metrics = Metrics::default();
+
+        let start_time = client_stats_manager.start_time();
+        let GlobalStats {
+            run_time,
+            client_stats_count,
+            corpus_size,
+            objective_size,
+            total_execs,
+            execs_per_sec,
+            ..
+        } = client_stats_manager.global_stats();
+        let global = || vec![string_attribute("client", "global")];
+        metrics.gauge("run_time", "s", global(), json!(run_time.as_secs_f64()));
+        metrics.gauge(
+            "clients",
+            "1",
+            global(),
+            int_value(*client_stats_count as u64),
+        );
+        metrics.gauge("corpus", "1", global(), int_value(*corpus_size));
+        metrics.gauge("objectives", "1", global(), int_value(*objective_size));
+        metrics.counter("executions", global(), *total_execs, start_time);
+        metrics.gauge("execs_per_sec", "1/s", global(), json!(*execs_per_sec));
+
+        for idx in 0..client_stats_manager.client_stats().len() {
+            let client_id = ClientId(idx as u32);
+            if !client_stats_manager.client_stats_for(client_id).enabled() {
+                continue;
+            }
+            let execs_per_sec = client_stats_manager
+                .update_client_stats_for(client_id, |client| client.execs_per_sec(now));
+            let client = client_stats_manager.client_stats_for(client_id);
+            let attributes = || vec![string_attribute("client", &idx.to_string())];
+
+            metrics.gauge("corpus", "1", attributes(), int_value(client.corpus_size()));
+            metrics.gauge(
+                "objectives",
+                "1",
+                attributes(),
+                int_value(client.objective_size()),
+            );
+            metrics.counter(
+                "executions",
+                attributes(),
+                client.executions(),
+                client.start_time(),
+            );
+            metrics.gauge("execs_per_sec", "1/s", attributes(), json!(execs_per_sec));
+            if let Some(edges) = client.edges_coverage() {
+                metrics.gauge("edges.hit", "1", attributes(), int_value(edges.edges_hit));
+                metrics.gauge(
+                    "edges.total",
+                    "1",
+                    attributes(),
+                    int_value(edges.edges_total),
+                );
+            }
+
+            for (name, stat) in client.user_stats() {
+                let value = match stat.value() {
+                    UserStatsValue::Number(n) => int_value(*n),
+                    UserStatsValue::Float(f) | UserStatsValue::Percent(f) => json!(*f),
+                    UserStatsValue::Ratio(_, 0) => json!(0.0),
+                    UserStatsValue::Ratio(a, b) => json!(*a as f64 / *b as f64),
+                    UserStatsValue::String(_) => continue,
+                };
+                let mut attributes = attributes();
+                attributes.push(string_attribute("stat", name));
+                metrics.gauge("user_stat", "1", attributes, value);
+            }
+
+            #[cfg(feature = "introspection")]
+            for (part, share) in perf_shares(&client.introspection_stats) {
+                let mut attributes = attributes();
+                attributes.push(string_attribute("part", &part));
+                metrics.gauge("perf.share", "1", attributes, json!(share));
+            }
+        }
+
+        json!({
+            "resourceMetrics": [{
+                "resource": resource(&self.service_name),
+                "scopeMetrics": [{
+                    "scope": { "name": METRIC_PREFIX, "version": env!("CARGO_PKG_VERSION") },
+                    "metrics": metrics.finish(now),
+                }],
+            }],
+        })
+    }
+}
+
+impl Monitor for OtlpMonitor {
+    fn display(
+        &mut self,
+        client_stats_manager: &mut ClientStatsManager,
+        _event_msg: &str,
+        _sender_id: ClientId,
+    ) {
+        let now = current_time();
+        if let Some(last_export) = self.last_export {
+            if now.saturating_sub(last_export) < self.export_interval {
+                return;
+            }
+        }
+
+        let metrics = self.metrics_request(client_stats_manager, now);
+        self.exporter.export(METRICS_PATH, &metrics);
+        self.last_export = Some(now);
+    }
+}
+
+/// A data point of a metric: its attributes, its value, and for sums, when it started counting
+type DataPoint = (Vec<Value>, Value, Option<Duration>);
+
+/// The metrics of one export, grouped by name
+#[derive(Debug, Default)]
+struct Metrics {
+    /// Name, unit, whether it is a monotonic sum, and the data points
+    metrics: Vec<(&'static str, &'static str, bool, Vec<DataPoint>)>,
+}
+
+impl Metrics {
+    fn add(&mut self, name: &'static str, unit: &'static str, monotonic: bool, point: DataPoint) {
+        if let Some((_, _, _, points)) = self.metrics.iter_mut().find(|(n, ..)| *n == name) {
+            points.push(point);
+        } else {
+            self.metrics.push((name, unit, monotonic, vec![point]));
+        }
+    }
+
+    fn gauge(
+        &mut self,
+        name: &'static str,
+        unit: &'static str,
+        attributes: Vec<Value>,
+        value: Value,
+    ) {
+        self.add(name, unit, false, (attributes, value, None));
+    }
+
+    /// A cumulative count since `start_time`
+    fn counter(
+        &mut self,
+        name: &'static str,
+        attributes: Vec<Value>,
+        value: u64,
+        start_time: Duration,
+    ) {
+        self.add(
+            name,
+            "1",
+            true,
+            (attributes, int_value(value), Some(start_time)),
+        );
+    }
+
+    /// The OTLP JSON representation of all metrics, sampled at `time`
+    fn finish(self, time: Duration) -> Vec<Value> {
+        let time_nanos = time.as_nanos().to_string();
+        self.metrics
+            .into_iter()
+            .map(|(name, unit, monotonic, points)| {
+                let data_points: Vec<Value> = points
+                    .into_iter()
+                    .map(|(attributes, value, start_time)| {
+                        let key = if value.is_string() {
+                            "asInt"
+                        } else {
+                            "asDouble"
+                        };
+                        let mut point = json!({
+                            "attributes": attributes, "timeUnixNano": time_nanos, key: value,
+                        });
+                        if let Some(start_time) = start_time {
+                            point["startTimeUnixNano"] = start_time.as_nanos().to_string().into();
+                        }
+                        point
+                    })
+                    .collect();
+                let name = format!("{METRIC_PREFIX}.{name}");
+                if monotonic {
+                    // Cumulative aggregation temporality
+                    json!({ "name": name, "unit": unit, "sum": {
+                        "dataPoints": data_points, "aggregationTemporality": 2, "isMonotonic": true,
+                    } })
+                } else {
+                    json!({ "name": name, "unit": unit, "gauge": { "dataPoints": data_points } })
+                }
+            })
+            .collect()
+    }
+}
+
+/// A span that was started, but not ended yet
+#[cfg(feature = "introspection")]
+#[derive(Debug)]
+struct OpenSpan {
+    trace_id: String,
+    span_id: String,
+    start: Duration,
+}
+
+#[cfg(feature = "introspection")]
+#[derive(Debug)]
+struct OtlpTracerInner {
+    service_name: String,
+    export_interval: Duration,
+    last_export: Duration,
+    exporter: OtlpExporter,
+    rand: StdRand,
+    open: Vec<OpenSpan>,
+    spans: Vec<Value>,
+    dropped: u64,
+}
+
+#[cfg(feature = "introspection")]
+impl OtlpTracerInner {
+    fn flush(&mut self) {
+        self.last_export = current_time();
+        if self.dropped != 0 {
+            log::debug!(
+                "OTLP tracer dropped {} spans, the export interval is too long",
+                self.dropped
+            );
+            self.dropped = 0;
+        }
+        if self.spans.is_empty() {
+            return;
+        }
+        let request = json!({
+            "resourceSpans": [{
+                "resource": resource(&self.service_name),
+                "scopeSpans": [{
+                    "scope": { "name": METRIC_PREFIX, "version": env!("CARGO_PKG_VERSION") },
+                    "spans": core::mem::take(&mut self.spans),
+                }],
+            }],
+        });
+        self.exporter.export(TRACES_PATH, &request);
+    }
+}
+
+#[cfg(feature = "introspection")]
+impl Drop for OtlpTracerInner {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Records spans in the fuzzer, and pushes them to a collector via OTLP/HTTP in batches.
+///
+/// Clones share the same batch, and nest their spans into each other: a span started while
+/// another one is open becomes its child. Spans without a parent start a new trace.
+/// Pending spans are exported once the export interval passed, and when the last clone is dropped.
+#[cfg(feature = "introspection")]
+#[derive(Debug, Clone)]
+pub struct OtlpTracer {
+    inner: Rc<RefCell<OtlpTracerInner>>,
+}
+
+#[cfg(feature = "introspection")]
+impl OtlpTracer {
+    /// Create a new tracer, which pushes spans to the collector at `endpoint`,
+    /// given as `host:port`, such as [`DEFAULT_OTLP_ENDPOINT`].
+    #[must_use]
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(OtlpTracerInner {
+                service_name: METRIC_PREFIX.to_string(),
+                export_interval: Duration::from_secs(1),
+                last_export: current_time(),
+                exporter: OtlpExporter::new(endpoint),
+                rand: StdRand::with_seed(current_nanos()),
+                open: Vec::new(),
+                spans: Vec::new(),
+                dropped: 0,
+            })),
+        }
+    }
+
+    /// Sets the `service.name` resource attribute of all exports, `libafl` by default.
+    #[must_use]
+    pub fn with_service_name(self, service_name: &str) -> Self {
+        self.inner.borrow_mut().service_name = service_name.to_string();
+        self
+    }
+
+    /// Sets the minimum time between two exports, 1 second by default.
+    #[must_use]
+    pub fn with_export_interval(self, export_interval: Duration) -> Self {
+        self.inner.borrow_mut().export_interval = export_interval;
+        self
+    }
+
+    /// Starts a new span, as a child of the innermost open span
+    pub(crate) fn start_span(&self) {
+        let mut inner = self.inner.borrow_mut();
+        let trace_id = match inner.open.last() {
+            Some(parent) => parent.trace_id.clone(),
+            None => format!("{:016x}{:016x}", inner.rand.next(), inner.rand.next()),
+        };
+        let span_id = format!("{:016x}", inner.rand.next());
+        inner.open.push(OpenSpan {
+            trace_id,
+            span_id,
+            start: current_time(),
+        });
+    }
+
+    /// Ends the innermost open span, and names it.
+    ///
+    /// The attributes are given as OTLP JSON values, `error` sets the status of the span.
+    pub(crate) fn end_span(&self, name: &str, attributes: &[Value], error: bool) {
+        let mut inner = self.inner.borrow_mut();
+        let Some(span) = inner.open.pop() else {
+            log::warn!("OTLP tracer ended span {name}, but none was started");
+            return;
+        };
+        let end = current_time();
+        if inner.spans.len() < MAX_BATCHED_SPANS {
+            let mut value = json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "name": name,
+                "kind": 1,
+                "startTimeUnixNano": span.start.as_nanos().to_string(),
+                "endTimeUnixNano": end.as_nanos().to_string(),
+                "attributes": attributes,
+                // Status codes: 1 is ok, 2 is an error
+                "status": { "code": if error { 2 } else { 1 } },
+            });
+            if let Some(parent) = inner.open.last() {
+                value["parentSpanId"] = parent.span_id.clone().into();
+            }
+            inner.spans.push(value);
+        } else {
+            inner.dropped += 1;
+        }
+        if end.saturating_sub(inner.last_export) >= inner.export_interval {
+            inner.flush();
+        }
+    }
+
+    /// Exports all ended spans now
+    pub fn flush(&self) {
+        self.inner.borrow_mut().flush();
+    }
+}
+
+/// OTLP JSON encodes 64-bit integers as strings
+pub(crate) fn int_value(value: u64) -> Value {
+    Value::String(value.to_string())
+}
+
+#[cfg(feature = "introspection")]
+pub(crate) fn int_attribute(key: &str, value: u64) -> Value {
+    json!({ "key": key, "value": { "intValue": int_value(value) } })
+}
+
+pub(crate) fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// The share of the elapsed time of each measured part of the fuzz loop, and of the rest
+#[cfg(feature = "introspection")]
+#[expect(clippy::cast_precision_loss)]
+fn perf_shares(perf: &ClientPerfStats) -> Vec<(String, f64)> {
+    let elapsed = perf.elapsed_cycles() as f64;
+    if elapsed == 0.0 {
+        return Vec::new();
+    }
+    let mut shares = vec![
+        (
+            "scheduler".to_string(),
+            perf.scheduler_cycles() as f64 / elapsed,
+        ),
+        (
+            "manager".to_string(),
+            perf.manager_cycles() as f64 / elapsed,
+        ),
+    ];
+    for (stage_index, features) in perf.used_stages() {
+        for (feature_index, cycles) in features.iter().enumerate() {
+            if *cycles != 0 {
+                let feature: PerfFeature = feature_index.into();
+                shares.push((
+                    format!("stage {stage_index} {feature:?}"),
+                    *cycles as f64 / elapsed,
+                ));
+            }
+        }
+    }
+    for (name, cycles) in perf.feedbacks() {
+        if *cycles != 0 {
+            shares.push((format!("feedback {name}"), *cycles as f64 / elapsed));
+        }
+    }
+    let measured: f64 = shares.iter().map(|(_, share)| share).sum();
+    shares.push(("not measured".to_string(), (1.0 - measured).max(0.0)));
+    shares
+}
+
+/// Posts `body` as JSON to `path` on `endpoint`, returning the HTTP status
+fn post_json(endpoint: &str, path: &str, body: &str) -> io::Result<u16> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no address for the endpoint");
+    let mut stream = None;
+    for addr in endpoint.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, COLLECTOR_TIMEOUT) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(err) => last_err = err,
+        }
+    }
+    let mut stream = stream.ok_or(last_err)?;
+    stream.set_read_timeout(Some(COLLECTOR_TIMEOUT))?;
+    stream.set_write_timeout(Some(COLLECTOR_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {endpoint}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, status_line))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        borrow::Cow,
+        string::{String, ToString},
+    };
+    use core::time::Duration;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    use libafl_bolts::{current_time, ClientId};
+    use serde_json::Value;
+
+    use crate::monitors::{
+        stats::{AggregatorOps, ClientStatsManager, UserStats, UserStatsValue},
+        Monitor, OtlpMonitor,
+    };
+
+    /// A stand-in for the collector, forwarding the path and body of each request
+    fn collector() -> (String, mpsc::Receiver<(String, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line.split_whitespace().nth(1).unwrap().to_string();
+                let mut len = 0;
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        len = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .unwrap();
+                if sender
+                    .send((path, serde_json::from_slice(&body).unwrap()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        (endpoint, receiver)
+    }
+
+    #[test]
+    fn test_otlp_export() {
+        let (endpoint, requests) = collector();
+        let mut monitor = OtlpMonitor::new(&endpoint).with_service_name("test");
+
+        let mut manager = ClientStatsManager::new();
+        manager.client_stats_insert(ClientId(0));
+        manager.update_client_stats_for(ClientId(0), |client| {
+            client.update_corpus_size(7);
+            client.update_executions(1000, current_time());
+            client.update_user_stats(
+                Cow::Borrowed("edges"),
+                UserStats::new(UserStatsValue::Ratio(3, 4), AggregatorOps::Avg),
+            );
+        });
+        monitor.display(&mut manager, "Testcase", ClientId(0));
+
+        let (path, request) = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(path, "/v1/metrics");
+        let resource_metrics = &request["resourceMetrics"][0];
+        assert_eq!(
+            resource_metrics["resource"]["attributes"][0]["value"]["stringValue"],
+            "test"
+        );
+        let metrics = resource_metrics["scopeMetrics"][0]["metrics"]
+            .as_array()
+            .unwrap();
+        let metric = |name: &str| {
+            metrics
+                .iter()
+                .find(|metric| metric["name"] == name)
+                .unwrap_or_else(|| panic!("missing {name}"))
+        };
+        let corpus = &metric("libafl.corpus")["gauge"]["dataPoints"];
+        assert_eq!(corpus[0]["asInt"], "7");
+        assert_eq!(corpus[1]["attributes"][0]["value"]["stringValue"], "0");
+        let executions = &metric("libafl.executions")["sum"];
+        assert!(executions["isMonotonic"].as_bool().unwrap());
+        assert_eq!(
+            executions["dataPoints"][0]["startTimeUnixNano"],
+            manager.start_time().as_nanos().to_string()
+        );
+        assert_eq!(
+            metric("libafl.edges.hit")["gauge"]["dataPoints"][0]["asInt"],
+            "3"
+        );
+        assert_eq!(
+            metric("libafl.user_stat")["gauge"]["dataPoints"][0]["asDouble"],
+            0.75
+        );
+
+        // Within the export interval, nothing is sent
+        monitor.display(&mut manager, "Testcase", ClientId(0));
+        assert!(requests.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "introspection")]
+    fn test_otlp_spans() {
+        use crate::monitors::OtlpTracer;
+
+        let (endpoint, requests) = collector();
+        let tracer = OtlpTracer::new(&endpoint)
+            .with_service_name("test")
+            .with_export_interval(Duration::from_secs(3600));
+
+        tracer.start_span();
+        tracer.start_span();
+        tracer.end_span("execution", &[], true);
+        tracer.end_span("stage", &[], false);
+        tracer.start_span();
+        tracer.end_span("other stage", &[], false);
+        // Dropping the last clone exports the pending spans
+        let clone = tracer.clone();
+        drop(tracer);
+        assert!(requests.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(clone);
+
+        let (path, request) = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(path, "/v1/traces");
+        let spans = request["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        assert_eq!(spans.len(), 3);
+        let (execution, stage, other) = (&spans[0], &spans[1], &spans[2]);
+        assert_eq!(execution["name"], "execution");
+        assert_eq!(execution["status"]["code"], 2);
+        assert_eq!(execution["parentSpanId"], stage["spanId"]);
+        assert_eq!(execution["traceId"], stage["traceId"]);
+        assert_eq!(stage["status"]["code"], 1);
+        assert!(stage.get("parentSpanId").is_none());
+        assert_ne!(other["traceId"], stage["traceId"]);
+    }
+}
@@ -0,0 +1,553 @@
+//! The [`WebMonitor`] serves a small live dashboard over HTTP, from the fuzzer process itself.
+//!
+//! ## Overview
+//!
+//! The monitor starts an HTTP server in a separate thread. Opening it in a browser shows
+//! the global and per-client stats, the corpus size and coverage over time, the objectives,
+//! and, with the `introspection` feature, where the clients spend their time.
+//! No other service, like `Prometheus` or `Grafana`, is needed.
+//!
+//! The server also exposes the raw data:
+//! - `/api/stats`: all stats, as JSON
+//! - `/api/objectives`: the files in the objectives directory, as JSON
+//! - `/objectives/<name>`: downloads a single objective
+//!
+//! ## How to use it
+//!
+//! ```rust,no_run
+//! use libafl::monitors::WebMonitor;
+//!
+//! let mon = WebMonitor::new("127.0.0.1:8000".to_string(), |s| log::info!("{s}"))
+//!     .with_objectives_dir("./crashes");
+//!
+//! // and, like with any other monitor, pass it into the event manager like so:
+//! // let mgr = SimpleEventManager::new(mon);
+//! ```
+//!
+//! The dashboard does not require authentication, so only listen on a public address
+//! in a trusted network.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Write},
+    time::Duration,
+};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    thread,
+};
+
+use futures::executor::block_on;
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, ClientId};
+use serde::Serialize;
+use tide::{http::mime, Request, Response, StatusCode};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::stats::perf_stats::{ClientPerfStats, PerfFeature};
+use crate::monitors::{
+    stats::{manager::ClientStatsManager, ClientStats, EdgeCoverage},
+    Monitor,
+};
+
+/// The dashboard page, polling `/api/stats` and `/api/objectives`
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// The maximum amount of points kept for the charts
+const MAX_HISTORY: usize = 4096;
+/// The minimum time between two points of the charts
+const HISTORY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A single point of the charts
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HistoryPoint {
+    /// Seconds since the start of the fuzzing campaign
+    pub time: u64,
+    /// The global corpus size
+    pub corpus: u64,
+    /// The global amount of objectives
+    pub objectives: u64,
+    /// The global executions per second
+    pub execs_per_sec: f64,
+    /// The hit edges, if an edges map reports them
+    pub edges_hit: Option<u64>,
+}
+
+/// The global stats shown in the dashboard
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GlobalWebContext {
+    /// Seconds since the start of the fuzzing campaign
+    pub run_time: u64,
+    /// The amount of clients
+    pub clients: usize,
+    /// The global corpus size
+    pub corpus: u64,
+    /// The global amount of objectives
+    pub objectives: u64,
+    /// The global executions
+    pub executions: u64,
+    /// The global executions per second
+    pub execs_per_sec: f64,
+    /// The hit edges of the client with the best coverage
+    pub edges_hit: Option<u64>,
+    /// The total edges of the client with the best coverage
+    pub edges_total: Option<u64>,
+    /// The aggregated user stats, formatted
+    pub user_stats: Vec<(String, String)>,
+}
+
+/// The stats of a single client shown in the dashboard
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClientWebContext {
+    /// The corpus size
+    pub corpus: u64,
+    /// The amount of objectives
+    pub objectives: u64,
+    /// The executions
+    pub executions: u64,
+    /// The executions per second
+    pub execs_per_sec: f64,
+    /// Seconds since this client started
+    pub run_time: u64,
+    /// Seconds from the start of this client to its last new corpus entry
+    pub last_new_entry: u64,
+    /// Seconds from the start of this client to its last objective
+    pub last_objective: u64,
+    /// The hit edges, if an edges map reports them
+    pub edges_hit: Option<u64>,
+    /// The total edges, if an edges map reports them
+    pub edges_total: Option<u64>,
+    /// The user stats of this client, formatted
+    pub user_stats: Vec<(String, String)>,
+    /// Where this client spends its time
+    #[cfg(feature = "introspection")]
+    pub perf: PerfWebContext,
+}
+
+impl ClientWebContext {
+    /// Grab data for a single client
+    pub fn grab_data(&mut self, client: &mut ClientStats) {
+        let now = current_time();
+        let process_timing = client.process_timing();
+
+        self.corpus = client.corpus_size();
+        self.objectives = client.objective_size();
+        self.executions = client.executions();
+        self.execs_per_sec = client.execs_per_sec(now);
+        self.run_time = now.saturating_sub(client.start_time()).as_secs();
+        self.last_new_entry = process_timing.last_new_entry.as_secs();
+        self.last_objective = process_timing.last_saved_solution.as_secs();
+        (self.edges_hit, self.edges_total) = match client.edges_coverage() {
+            Some(EdgeCoverage {
+                edges_hit,
+                edges_total,
+            }) => (Some(edges_hit), Some(edges_total)),
+            None => (None, None),
+        };
+
+        self.user_stats = client
+            .user_stats()
+            .iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect();
+        self.user_stats.sort_unstable();
+
+        #[cfg(feature = "introspection")]
+        self.perf.grab_data(&client.introspection_stats);
+    }
+}
+
+/// The share of the time spent in each part of the fuzzer, as the TUI shows it
+#[cfg(feature = "introspection")]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PerfWebContext {
+    /// Time spent in the scheduler
+    pub scheduler: f64,
+    /// Time spent in the event manager
+    pub manager: f64,
+    /// Additional time
+    pub unmeasured: f64,
+    /// Time spent in each individual stage
+    pub stages: Vec<Vec<(String, f64)>>,
+    /// Time spent in each individual feedback
+    pub feedbacks: Vec<(String, f64)>,
+}
+
+#[cfg(feature = "introspection")]
+impl PerfWebContext {
+    /// Get the data for performance metrics
+    #[expect(clippy::cast_precision_loss)]
+    pub fn grab_data(&mut self, m: &ClientPerfStats) {
+        let elapsed = m.elapsed_cycles() as f64;
+        if elapsed == 0.0 {
+            return;
+        }
+
+        self.scheduler = m.scheduler_cycles() as f64 / elapsed;
+        self.manager = m.manager_cycles() as f64 / elapsed;
+        let mut other_percent = 1.0 - self.scheduler - self.manager;
+
+        self.stages.clear();
+        for (_stage_index, features) in m.used_stages() {
+            let mut features_percentages = Vec::new();
+            for (feature_index, feature) in features.iter().enumerate() {
+                let feature_percent = *feature as f64 / elapsed;
+                if feature_percent == 0.0 {
+                    continue;
+                }
+                other_percent -= feature_percent;
+                let feature: PerfFeature = feature_index.into();
+                features_percentages.push((format!("{feature:?}"), feature_percent));
+            }
+            self.stages.push(features_percentages);
+        }
+
+        self.feedbacks.clear();
+        for (feedback_name, feedback_time) in m.feedbacks() {
+            let feedback_percent = *feedback_time as f64 / elapsed;
+            if feedback_percent == 0.0 {
+                continue;
+            }
+            other_percent -= feedback_percent;
+            self.feedbacks
+                .push((feedback_name.clone(), feedback_percent));
+        }
+
+        self.unmeasured = other_percent;
+    }
+}
+
+/// Everything the dashboard shows, shared between the [`WebMonitor`] and its server
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WebContext {
+    /// The global stats
+    pub global: GlobalWebContext,
+    /// The stats of each client, by client id
+    pub clients: HashMap<u32, ClientWebContext>,
+    /// The points of the charts, oldest first
+    pub history: VecDeque<HistoryPoint>,
+    /// The directory of the objectives, to list and download them
+    #[serde(skip)]
+    pub objectives_dir: Option<PathBuf>,
+}
+
+impl WebContext {
+    /// Add a point to the charts, unless the last one is too recent
+    fn add_history_point(&mut self) {
+        let time = self.global.run_time;
+        if self.history.back().is_some_and(|last| {
+            Duration::from_secs(time.saturating_sub(last.time)) < HISTORY_INTERVAL
+        }) {
+            return;
+        }
+        if self.history.len() >= MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(HistoryPoint {
+            time,
+            corpus: self.global.corpus,
+            objectives: self.global.objectives,
+            execs_per_sec: self.global.execs_per_sec,
+            edges_hit: self.global.edges_hit,
+        });
+    }
+}
+
+/// Tracking monitor during fuzzing, serving a live dashboard over HTTP.
+#[derive(Clone)]
+pub struct WebMonitor<F>
+where
+    F: FnMut(&str),
+{
+    print_fn: F,
+    context: Arc<RwLock<WebContext>>,
+}
+
+impl<F> fmt::Debug for WebMonitor<F>
+where
+    F: FnMut(&str),
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebMonitor")
+            .field("context", &self.context)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> Monitor for WebMonitor<F>
+where
+    F: FnMut(&str),
+{
+    fn display(
+        &mut self,
+        client_stats_manager: &mut ClientStatsManager,
+        event_msg: &str,
+        sender_id: ClientId,
+    ) {
+        let edges = client_stats_manager.edges_coverage();
+        let user_stats = client_stats_manager
+            .aggregated()
+            .iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect::<Vec<_>>();
+        let global_stats = client_stats_manager.global_stats();
+
+        (self.print_fn)(&format!(
+            "[Web] [{} #{}] run time: {}, clients: {}, corpus: {}, objectives: {}, executions: {}, exec/sec: {}",
+            event_msg,
+            sender_id.0,
+            global_stats.run_time_pretty,
+            global_stats.client_stats_count,
+            global_stats.corpus_size,
+            global_stats.objective_size,
+            global_stats.total_execs,
+            global_stats.execs_per_sec_pretty
+        ));
+
+        let mut ctx = self.context.write().unwrap();
+        ctx.global = GlobalWebContext {
+            run_time: global_stats.run_time.as_secs(),
+            clients: global_stats.client_stats_count,
+            corpus: global_stats.corpus_size,
+            objectives: global_stats.objective_size,
+            executions: global_stats.total_execs,
+            execs_per_sec: global_stats.execs_per_sec,
+            edges_hit: edges.as_ref().map(|edges| edges.edges_hit),
+            edges_total: edges.as_ref().map(|edges| edges.edges_total),
+            user_stats,
+        };
+        ctx.global.user_stats.sort_unstable();
+        ctx.add_history_point();
+
+        client_stats_manager.client_stats_insert(sender_id);
+        client_stats_manager.update_client_stats_for(sender_id, |client| {
+            ctx.clients
+                .entry(sender_id.0)
+                .or_default()
+                .grab_data(client);
+        });
+    }
+}
+
+impl<F> WebMonitor<F>
+where
+    F: FnMut(&str),
+{
+    /// Create a new [`WebMonitor`], serving the dashboard on `listener`, for example `127.0.0.1:8000`.
+    /// The `print_fn` is the printing function that can output the logs otherwise.
+    pub fn new(listener: String, print_fn: F) -> Self {
+        let context = Arc::new(RwLock::new(WebContext::default()));
+        let server_context = context.clone();
+
+        // Need to run the server in a different thread to avoid blocking
+        thread::spawn(move || {
+            block_on(serve_dashboard(listener, server_context))
+                .map_err(|err| log::error!("{err:?}"))
+                .ok();
+        });
+
+        Self { print_fn, context }
+    }
+
+    /// Lists the objectives in `objectives_dir` on the dashboard, and allows to download them.
+    /// This is usually the directory of the `OnDiskCorpus` of the objectives.
+    ///
+    /// # Panics
+    /// Panics if the dashboard server panicked while holding the context.
+    #[must_use]
+    pub fn with_objectives_dir<P>(self, objectives_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.context.write().unwrap().objectives_dir = Some(objectives_dir.into());
+        self
+    }
+}
+
+/// An objective, as listed by `/api/objectives`
+#[derive(Debug, Serialize)]
+struct ObjectiveEntry {
+    name: String,
+    size: u64,
+    /// Seconds since the unix epoch
+    modified: Option<u64>,
+}
+
+/// If `name` is a plain file name, which may be served from the objectives directory.
+/// Hidden files are the metadata and lock files of the `OnDiskCorpus`.
+fn is_objective_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// The `Content-Disposition` header value downloading the objective `name`.
+///
+/// The quoted `filename` is limited to printable ASCII, with quotes and backslashes escaped.
+/// Names with other characters are also passed percent-encoded in `filename*`.
+fn content_disposition(name: &str) -> String {
+    let mut quoted = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            ' '..='~' => quoted.push(c),
+            _ => quoted.push('_'),
+        }
+    }
+    if name.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        return format!("attachment; filename=\"{quoted}\"");
+    }
+
+    let mut encoded = String::with_capacity(name.len() * 3);
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+            encoded.push(char::from(b));
+        } else {
+            write!(encoded, "%{b:02X}").unwrap();
+        }
+    }
+    format!("attachment; filename=\"{quoted}\"; filename*=UTF-8''{encoded}")
+}
+
+/// The objectives in `dir`, newest first
+fn list_objectives(dir: &PathBuf) -> Result<Vec<ObjectiveEntry>, std::io::Error> {
+    let mut objectives = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() || !is_objective_name(&name) {
+            continue;
+        }
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|time| time.as_secs());
+        objectives.push(ObjectiveEntry {
+            name,
+            size: metadata.len(),
+            modified,
+        });
+    }
+    objectives.sort_by(|a, b| b.modified.cmp(&a.modified).then(a.name.cmp(&b.name)));
+    Ok(objectives)
+}
+
+/// Responds with `value` as JSON
+fn json_response<T>(value: &T) -> tide::Result
+where
+    T: Serialize,
+{
+    Ok(Response::builder(StatusCode::Ok)
+        .body(serde_json::to_string(value)?)
+        .content_type(mime::JSON)
+        .build())
+}
+
+/// The state of the dashboard server.
+#[derive(Clone)]
+struct State {
+    context: Arc<RwLock<WebContext>>,
+}
+
+/// Serve the dashboard and its data
+pub(crate) async fn serve_dashboard(
+    listener: String,
+    context: Arc<RwLock<WebContext>>,
+) -> Result<(), std::io::Error> {
+    let mut app = tide::with_state(State { context });
+
+    app.at("/").get(|_| async {
+        Ok(Response::builder(StatusCode::Ok)
+            .body(DASHBOARD_HTML)
+            .content_type(mime::HTML)
+            .build())
+    });
+    app.at("/api/stats").get(|req: Request<State>| async move {
+        let ctx = req.state().context.read().unwrap().clone();
+        json_response(&ctx)
+    });
+    app.at("/api/objectives")
+        .get(|req: Request<State>| async move {
+            let objectives_dir = req.state().context.read().unwrap().objectives_dir.clone();
+            let objectives = match objectives_dir {
+                Some(dir) => list_objectives(&dir)?,
+                None => Vec::new(),
+            };
+            json_response(&objectives)
+        });
+    app.at("/objectives/:name")
+        .get(|req: Request<State>| async move {
+            let name = req.param("name")?;
+            let objectives_dir = req.state().context.read().unwrap().objectives_dir.clone();
+            let (Some(dir), true) = (objectives_dir, is_objective_name(name)) else {
+                return Ok(Response::new(StatusCode::NotFound));
+            };
+            let Ok(objective) = fs::read(dir.join(name)) else {
+                return Ok(Response::new(StatusCode::NotFound));
+            };
+            Ok(Response::builder(StatusCode::Ok)
+                .body(objective)
+                .content_type(mime::BYTE_STREAM)
+                .header("Content-Disposition", content_disposition(name))
+                .build())
+        });
+    app.listen(listener).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monitors::web::{
+        content_disposition, is_objective_name, WebContext, HISTORY_INTERVAL,
+    };
+
+    #[test]
+    fn test_objective_names() {
+        assert!(is_objective_name("id_0_crash"));
+        assert!(!is_objective_name(".id_0_crash.metadata"));
+        assert!(!is_objective_name("../secret"));
+        assert!(!is_objective_name("a/b"));
+        assert!(!is_objective_name(""));
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("id_0_crash"),
+            "attachment; filename=\"id_0_crash\""
+        );
+        assert_eq!(
+            content_disposition("a\"b\\c"),
+            "attachment; filename=\"a\\\"b\\\\c\""
+        );
+        assert_eq!(
+            content_disposition("cr\u{e9}\r\nX: y"),
+            "attachment; filename=\"cr___X: y\"; filename*=UTF-8''cr%C3%A9%0D%0AX%3A%20y"
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let mut ctx = WebContext::default();
+        ctx.add_history_point();
+        ctx.add_history_point();
+        assert_eq!(ctx.history.len(), 1);
+        ctx.global.run_time += HISTORY_INTERVAL.as_secs();
+        ctx.global.corpus = 3;
+        ctx.add_history_point();
+        assert_eq!(ctx.history.len(), 2);
+        assert_eq!(ctx.history.back().unwrap().corpus, 3);
+    }
+}
@@ -160,6 +160,14 @@ where
     }
 }
 
+impl<MT> StdScheduledMutator<MT> {
+    /// The maximum stacking power: each mutation stacks up to `1 << max_stack_pow` mutations
+    #[must_use]
+    pub fn max_stack_pow(&self) -> usize {
+        self.max_stack_pow
+    }
+}
+
 impl<MT> StdScheduledMutator<MT>
 where
     MT: NamedTuple,
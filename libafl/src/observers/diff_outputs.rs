@@ -0,0 +1,142 @@
+//! An observer collecting one output per executor during an N-way differential execution.
+//!
+//! Use it together with [`crate::executors::MultiDiffExecutor`] and
+//! [`crate::feedbacks::differential::MultiDiffFeedback`].
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt::Debug;
+
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use crate::observers::stdio::OutputObserver;
+use crate::{
+    executors::ExitKind,
+    observers::{MultiDifferentialObserver, Observer, ValueObserver},
+    Error,
+};
+
+/// An [`Observer`] whose observation can be compared across executors by a [`DiffOutputsObserver`].
+pub trait DiffOutput {
+    /// The comparable output of the last execution
+    type Output: Clone + PartialEq + Debug + Serialize + DeserializeOwned;
+
+    /// Returns the output of the last execution
+    fn diff_output(&self) -> Self::Output;
+}
+
+#[cfg(feature = "std")]
+impl<T> DiffOutput for OutputObserver<T> {
+    type Output = Option<Vec<u8>>;
+
+    fn diff_output(&self) -> Self::Output {
+        self.output.clone()
+    }
+}
+
+impl<T> DiffOutput for ValueObserver<'_, T>
+where
+    T: Clone + PartialEq + Debug + Serialize + DeserializeOwned,
+{
+    type Output = T;
+
+    fn diff_output(&self) -> Self::Output {
+        self.get_ref().clone()
+    }
+}
+
+/// Collects the [`DiffOutput`] of the observer `O` of each executor wrapped by a
+/// [`crate::executors::MultiDiffExecutor`], together with the executor's [`ExitKind`].
+///
+/// The outputs are stored in the order of the executors.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound = "O::Output: Serialize + DeserializeOwned")]
+pub struct DiffOutputsObserver<O>
+where
+    O: DiffOutput,
+{
+    name: Cow<'static, str>,
+    observer: Handle<O>,
+    outputs: Vec<O::Output>,
+    exit_kinds: Vec<ExitKind>,
+}
+
+impl<O> DiffOutputsObserver<O>
+where
+    O: DiffOutput + Named,
+{
+    /// Creates a new [`DiffOutputsObserver`], collecting the outputs of all observers named like `observer`.
+    #[must_use]
+    pub fn new(name: &'static str, observer: &O) -> Self {
+        Self {
+            name: Cow::from(name),
+            observer: observer.handle(),
+            outputs: Vec::new(),
+            exit_kinds: Vec::new(),
+        }
+    }
+}
+
+impl<O> DiffOutputsObserver<O>
+where
+    O: DiffOutput,
+{
+    /// The outputs of the last execution, one per executor
+    #[must_use]
+    pub fn outputs(&self) -> &[O::Output] {
+        &self.outputs
+    }
+
+    /// The exit kinds of the last execution, one per executor
+    #[must_use]
+    pub fn exit_kinds(&self) -> &[ExitKind] {
+        &self.exit_kinds
+    }
+}
+
+impl<O> Named for DiffOutputsObserver<O>
+where
+    O: DiffOutput,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, O, S> Observer<I, S> for DiffOutputsObserver<O>
+where
+    O: DiffOutput,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.outputs.clear();
+        self.exit_kinds.clear();
+        Ok(())
+    }
+}
+
+impl<I, O, OT, S> MultiDifferentialObserver<OT, I, S> for DiffOutputsObserver<O>
+where
+    O: DiffOutput,
+    OT: MatchName,
+{
+    fn post_observe(
+        &mut self,
+        idx: usize,
+        observers: &mut OT,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        let observer = observers.get(&self.observer).ok_or_else(|| {
+            Error::illegal_state(format!(
+                "DiffOutputsObserver: observer {} not found for executor {idx}",
+                self.observer.name()
+            ))
+        })?;
+        self.outputs.push(observer.diff_output());
+        self.exit_kinds.push(*exit_kind);
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+//! The [`HangBacktraceObserver`] captures a backtrace of the target when it times out.
+//!
+//! For in-process executors, the backtrace is unwound from within the timeout signal handler,
+//! starting at the instruction that was interrupted.
+//! For executors running the target in another process (such as the
+//! [`crate::executors::ForkserverExecutor`] and the [`crate::executors::CommandExecutor`]),
+//! the executor samples the stack of the hung process with `ptrace` right before killing it.
+//! Out-of-process sampling walks the frame pointer chain, so the target should be compiled with
+//! `-fno-omit-frame-pointer` to get meaningful call stacks.
+//!
+//! Addresses are stored relative to the module they belong to, so that the hash stays the same
+//! across runs, even with ASLR.
+//!
+//! The in-process unwinding runs in the signal handler, so it does not allocate: the buffers are
+//! reserved in `pre_exec`, and the mappings of the fuzzer process are read once, on the first
+//! execution. Frames in modules loaded later keep their absolute address.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::fs;
+use std::sync::OnceLock;
+
+use libafl_bolts::{hasher_std, Named};
+#[cfg(all(
+    target_os = "linux",
+    target_env = "gnu",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+use nix::{
+    sys::{
+        ptrace,
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    executors::ExitKind,
+    observers::{Observer, ObserverWithHashField},
+    Error,
+};
+
+/// The maximum number of frames collected for a hang
+pub const HANG_BACKTRACE_MAX_FRAMES: usize = 64;
+
+/// The program counter that was interrupted by the last in-process timeout, 0 if none
+static HANG_PC: AtomicUsize = AtomicUsize::new(0);
+
+/// The file-backed mappings of the fuzzer process, read on the first execution
+static SELF_MODULES: OnceLock<Vec<MappedModule>> = OnceLock::new();
+
+/// Records the program counter interrupted by a timeout signal.
+///
+/// Called by the in-process timeout handlers, the next [`HangBacktraceObserver`] that
+/// observes an [`ExitKind::Timeout`] will unwind from there.
+pub fn record_hang_pc(pc: usize) {
+    HANG_PC.store(pc, Ordering::Relaxed);
+}
+
+/// A single frame of a hang backtrace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HangFrame {
+    /// The path of the module this frame belongs to, if it could be found
+    pub module: Option<Cow<'static, str>>,
+    /// The offset of the address in the module, or the absolute address if the module is unknown
+    pub offset: u64,
+}
+
+/// A memory mapping of a process, as listed in `/proc/<pid>/maps`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MappedModule {
+    start: u64,
+    end: u64,
+    file_offset: u64,
+    path: Cow<'static, str>,
+}
+
+/// Parses the content of a `/proc/<pid>/maps` file, keeping only file-backed mappings
+fn parse_maps(maps: &str) -> Vec<MappedModule> {
+    maps.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (start, end) = fields.next()?.split_once('-')?;
+            let _perms = fields.next()?;
+            let file_offset = fields.next()?;
+            let _dev = fields.next()?;
+            let _inode = fields.next()?;
+            let path = fields.collect::<Vec<_>>().join(" ");
+            if !path.starts_with('/') {
+                return None;
+            }
+            Some(MappedModule {
+                start: u64::from_str_radix(start, 16).ok()?,
+                end: u64::from_str_radix(end, 16).ok()?,
+                file_offset: u64::from_str_radix(file_offset, 16).ok()?,
+                path: path.into(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the file-backed mappings of the process `pid`, or of the current process if `None`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn read_maps(pid: Option<i32>) -> Vec<MappedModule> {
+    let path = match pid {
+        Some(pid) => format!("/proc/{pid}/maps"),
+        None => "/proc/self/maps".into(),
+    };
+    fs::read_to_string(path)
+        .map(|maps| parse_maps(&maps))
+        .unwrap_or_default()
+}
+
+/// Reads the file-backed mappings of the process `pid`, or of the current process if `None`
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn read_maps(_pid: Option<i32>) -> Vec<MappedModule> {
+    Vec::new()
+}
+
+/// The mappings of the fuzzer process. Their paths are borrowed, so that normalizing an address
+/// in the signal handler does not allocate.
+fn self_modules() -> &'static [MappedModule] {
+    SELF_MODULES.get_or_init(|| {
+        read_maps(None)
+            .into_iter()
+            .map(|m| MappedModule {
+                path: Cow::Borrowed(String::leak(m.path.into_owned())),
+                ..m
+            })
+            .collect()
+    })
+}
+
+/// Turns an absolute address into a [`HangFrame`], relative to its module
+fn normalize(modules: &[MappedModule], addr: u64) -> HangFrame {
+    match modules.iter().find(|m| m.start <= addr && addr < m.end) {
+        Some(m) => HangFrame {
+            module: Some(m.path.clone()),
+            offset: addr - m.start + m.file_offset,
+        },
+        None => HangFrame {
+            module: None,
+            offset: addr,
+        },
+    }
+}
+
+/// Hashes the frames of a hang, including the innermost frame, so that hangs are told apart by
+/// the location the target was looping at, not only by their callers.
+fn hash_frames(frames: &[HangFrame]) -> u64 {
+    let mut hasher = hasher_std();
+    frames.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Samples the stack of the (running) process `pid` with `ptrace`.
+///
+/// Returns the absolute program counter, followed by the return addresses found walking the
+/// frame pointer chain, innermost first. The process is resumed afterwards.
+#[cfg(all(
+    target_os = "linux",
+    target_env = "gnu",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[expect(clippy::cast_sign_loss)]
+pub fn sample_process_stack(pid: Pid, max_frames: usize) -> Result<Vec<u64>, Error> {
+    ptrace::seize(pid, ptrace::Options::empty())?;
+    let ret = (|| {
+        ptrace::interrupt(pid)?;
+        match waitpid(pid, Some(WaitPidFlag::__WALL))? {
+            WaitStatus::PtraceEvent(..) | WaitStatus::Stopped(..) => (),
+            status => {
+                return Err(Error::illegal_state(format!(
+                    "Process {pid} did not stop for sampling: {status:?}"
+                )));
+            }
+        }
+
+        let regs = ptrace::getregs(pid)?;
+        #[cfg(target_arch = "x86_64")]
+        let (pc, mut fp) = (regs.rip, regs.rbp);
+        #[cfg(target_arch = "aarch64")]
+        let (pc, mut fp) = (regs.pc, regs.regs[29]);
+
+        // Both architectures store the previous frame pointer at `fp`, followed by the return address.
+        let mut addrs = vec![pc];
+        while addrs.len() < max_frames && fp != 0 && fp % 8 == 0 {
+            let Ok(next) = ptrace::read(pid, fp as ptrace::AddressType) else {
+                break;
+            };
+            let Ok(ret_addr) = ptrace::read(pid, (fp + 8) as ptrace::AddressType) else {
+                break;
+            };
+            if ret_addr == 0 {
+                break;
+            }
+            addrs.push(ret_addr as u64);
+            // The stack grows down, callers live at higher addresses.
+            if (next as u64) <= fp {
+                break;
+            }
+            fp = next as u64;
+        }
+        Ok(addrs)
+    })();
+    let _ = ptrace::detach(pid, None);
+    ret
+}
+
+/// An observer collecting a backtrace of the target when it hangs.
+///
+/// Its hash can be used with a [`crate::feedbacks::NewHashFeedback`] to deduplicate timeouts by
+/// the location of the hang, and [`crate::feedbacks::HangBacktraceToMetadataFeedback`] stores the
+/// frames in the testcase metadata.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HangBacktraceObserver {
+    observer_name: Cow<'static, str>,
+    frames: Vec<HangFrame>,
+    hash: Option<u64>,
+    /// The instruction pointers unwound in the signal handler
+    #[serde(skip)]
+    ips: Vec<u64>,
+}
+
+impl HangBacktraceObserver {
+    /// Creates a new [`HangBacktraceObserver`] with the given name.
+    #[must_use]
+    pub fn new<S>(observer_name: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self {
+            observer_name: observer_name.into(),
+            frames: Vec::new(),
+            hash: None,
+            ips: Vec::new(),
+        }
+    }
+
+    /// The frames of the last hang, innermost first
+    #[must_use]
+    pub fn frames(&self) -> &[HangFrame] {
+        &self.frames
+    }
+
+    /// Sets the frames from absolute addresses, normalized using the mappings of `pid`
+    /// (or of the current process if `None`).
+    pub fn set_frames_from_addrs(&mut self, pid: Option<i32>, addrs: &[u64]) {
+        let modules = read_maps(pid);
+        self.frames = addrs
+            .iter()
+            .map(|addr| normalize(&modules, *addr))
+            .collect();
+        self.hash = if self.frames.is_empty() {
+            None
+        } else {
+            Some(hash_frames(&self.frames))
+        };
+    }
+
+    /// Samples the stack of the hung process `pid`, before the executor kills it.
+    #[cfg(all(
+        target_os = "linux",
+        target_env = "gnu",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    pub fn sample_process(&mut self, pid: Pid) -> Result<(), Error> {
+        let addrs = sample_process_stack(pid, HANG_BACKTRACE_MAX_FRAMES)?;
+        self.set_frames_from_addrs(Some(pid.as_raw()), &addrs);
+        Ok(())
+    }
+
+    /// Reserves what the in-process unwinding needs, as it cannot allocate in the signal handler.
+    fn prepare(&mut self) {
+        self_modules();
+        // The frames of the signal handler come first, and are skipped
+        self.ips.reserve(2 * HANG_BACKTRACE_MAX_FRAMES);
+        self.frames.reserve(HANG_BACKTRACE_MAX_FRAMES);
+    }
+
+    /// Unwinds the current stack, starting at the interrupted program counter `pc`.
+    ///
+    /// This runs in the timeout signal handler, and only uses the buffers reserved in [`Self::prepare`].
+    fn collect_in_process(&mut self, pc: usize) {
+        self.ips.clear();
+        let ips = &mut self.ips;
+        // # Safety
+        // The target is interrupted, no other thread of the fuzzer unwinds at the same time.
+        unsafe {
+            backtrace::trace_unsynchronized(|frame| {
+                ips.push(frame.ip() as u64);
+                ips.len() < ips.capacity()
+            });
+        }
+        // Skip the frames of the signal handler
+        let start = self.ips.iter().position(|ip| *ip == pc as u64).unwrap_or(0);
+        let end = self.ips.len().min(start + HANG_BACKTRACE_MAX_FRAMES);
+
+        let modules = self_modules();
+        self.frames.clear();
+        self.frames.extend(
+            self.ips[start..end]
+                .iter()
+                .map(|addr| normalize(modules, *addr)),
+        );
+        self.hash = if self.frames.is_empty() {
+            None
+        } else {
+            Some(hash_frames(&self.frames))
+        };
+    }
+
+    fn clear(&mut self) {
+        self.frames.clear();
+        self.hash = None;
+    }
+
+    fn observe_exit(&mut self, exit_kind: ExitKind) {
+        if exit_kind == ExitKind::Timeout {
+            let pc = HANG_PC.swap(0, Ordering::Relaxed);
+            if pc != 0 {
+                self.collect_in_process(pc);
+            }
+            // else, the executor already sampled the hung process.
+        } else {
+            self.clear();
+        }
+    }
+}
+
+impl Default for HangBacktraceObserver {
+    fn default() -> Self {
+        Self::new("HangBacktraceObserver")
+    }
+}
+
+impl ObserverWithHashField for HangBacktraceObserver {
+    fn hash(&self) -> Option<u64> {
+        self.hash
+    }
+}
+
+impl<I, S> Observer<I, S> for HangBacktraceObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.clear();
+        self.prepare();
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        self.observe_exit(*exit_kind);
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.clear();
+        Ok(())
+    }
+
+    fn post_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.observe_exit(*exit_kind);
+        Ok(())
+    }
+}
+
+impl Named for HangBacktraceObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.observer_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{hash_frames, normalize, parse_maps};
+
+    const MAPS: &str = "\
+55d0c0a00000-55d0c0a20000 r--p 00000000 fd:01 1234 /usr/bin/target
+55d0c0a20000-55d0c0a80000 r-xp 00020000 fd:01 1234 /usr/bin/target
+7f0000000000-7f0000021000 rw-p 00000000 00:00 0
+7ffd00000000-7ffd00021000 rw-p 00000000 00:00 0 [stack]
+";
+
+    #[test]
+    fn test_hang_frames_normalize() {
+        let modules = parse_maps(MAPS);
+        assert_eq!(modules.len(), 2);
+
+        let frame = normalize(&modules, 0x55d0_c0a2_1234);
+        assert_eq!(frame.module.as_deref(), Some("/usr/bin/target"));
+        assert_eq!(frame.offset, 0x21234);
+
+        let frame = normalize(&modules, 0x7ffd_0000_1000);
+        assert_eq!(frame.module, None);
+        assert_eq!(frame.offset, 0x7ffd_0000_1000);
+    }
+
+    #[test]
+    fn test_hang_hash_includes_innermost_frame() {
+        let modules = parse_maps(MAPS);
+        let a: Vec<_> = [0x55d0_c0a2_1234, 0x55d0_c0a2_2000, 0x55d0_c0a2_3000]
+            .iter()
+            .map(|addr| normalize(&modules, *addr))
+            .collect();
+        let b: Vec<_> = [0x55d0_c0a2_1240, 0x55d0_c0a2_2000, 0x55d0_c0a2_3000]
+            .iter()
+            .map(|addr| normalize(&modules, *addr))
+            .collect();
+        let c: Vec<_> = [0x55d0_c0a2_1234, 0x55d0_c0a2_2100, 0x55d0_c0a2_3000]
+            .iter()
+            .map(|addr| normalize(&modules, *addr))
+            .collect();
+        assert_ne!(hash_frames(&a), hash_frames(&b));
+        assert_ne!(hash_frames(&a), hash_frames(&c));
+        assert_eq!(hash_frames(&a), hash_frames(&a.clone()));
+    }
+}
@@ -12,7 +12,10 @@ use libafl_bolts::{ownedref::OwnedMutSizedSlice, HasLen, Named};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    observers::{map::MapObserver, ConstLenMapObserver, Observer},
+    observers::{
+        map::{sparse, MapObserver},
+        ConstLenMapObserver, Observer,
+    },
     Error,
 };
 
@@ -20,7 +23,12 @@ use crate::{
 /// know the size of the map at compile time.
 #[derive(Serialize, Deserialize, Debug)]
 #[expect(clippy::unsafe_derive_deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize + PartialEq",
+    deserialize = "T: Deserialize<'de> + Clone"
+))]
 pub struct ConstMapObserver<'a, T, const N: usize> {
+    #[serde(with = "sparse")]
     map: OwnedMutSizedSlice<'a, T, N>,
     initial: T,
     name: Cow<'static, str>,
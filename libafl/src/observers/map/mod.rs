@@ -31,6 +31,8 @@ pub use multi_map::*;
 pub mod owned_map;
 pub use owned_map::*;
 
+pub mod sparse;
+
 /// A trait indicating tracking of observed map values after testcase execution
 ///
 /// Trait marker which indicates that this [`MapObserver`] is tracked for indices or novelties.
@@ -434,7 +436,12 @@ where
 /// A well-known example is the AFL-Style coverage map.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[expect(clippy::unsafe_derive_deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize + PartialEq",
+    deserialize = "T: Deserialize<'de> + Clone"
+))]
 pub struct StdMapObserver<'a, T, const DIFFERENTIAL: bool> {
+    #[serde(with = "sparse")]
     map: OwnedMutSlice<'a, T>,
     initial: T,
     name: Cow<'static, str>,
@@ -11,13 +11,21 @@ use libafl_bolts::{AsSlice, AsSliceMut, HasLen, Named};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    observers::{map::MapObserver, Observer},
+    observers::{
+        map::{sparse, MapObserver},
+        Observer,
+    },
     Error,
 };
 
 /// Exact copy of `StdMapObserver` that owns its map
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(bound(
+    serialize = "T: Serialize + PartialEq",
+    deserialize = "T: Deserialize<'de> + Clone"
+))]
 pub struct OwnedMapObserver<T> {
+    #[serde(with = "sparse")]
     map: Vec<T>,
     initial: T,
     name: Cow<'static, str>,
@@ -0,0 +1,271 @@
+//! Sparse serialization of the maps of map observers.
+//!
+//! Observers are serialized into each [`crate::events::Event::NewTestcase`] sent to other clients,
+//! and coverage maps are mostly empty. With `#[serde(with = "sparse")]` on its map, an observer
+//! only serializes the most common value, and the entries that differ from it, as deltas:
+//! each entry is stored as its distance to the previous one, which is a single byte for most
+//! coverage maps, followed by its value.
+//! Maps with many set entries are still serialized densely, as this is smaller.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use libafl_bolts::ownedref::{OwnedMutSizedSlice, OwnedMutSlice};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A map with more than one in `DENSE_RATIO` set entries is serialized densely.
+/// A sparse entry costs its (varint) index on top of its value.
+const DENSE_RATIO: usize = 4;
+
+/// The largest map a sparse map is deserialized to, unless the storage has a fixed size.
+/// The length comes from another node, so it must not allocate arbitrary amounts of memory.
+pub const MAX_SPARSE_MAP_LEN: usize = 1 << 26;
+
+/// The storage of the map of an observer, that can be serialized sparsely
+pub trait SparseStorage<T>: Sized {
+    /// The entries of this map
+    fn entries(&self) -> &[T];
+
+    /// Rebuilds the map from its deserialized entries, `None` if their count does not fit
+    fn from_entries(entries: Vec<T>) -> Option<Self>;
+
+    /// The largest map accepted when deserializing a sparse map
+    #[must_use]
+    fn max_len() -> usize {
+        MAX_SPARSE_MAP_LEN
+    }
+}
+
+impl<T> SparseStorage<T> for Vec<T> {
+    fn entries(&self) -> &[T] {
+        self
+    }
+
+    fn from_entries(entries: Vec<T>) -> Option<Self> {
+        Some(entries)
+    }
+}
+
+impl<T> SparseStorage<T> for OwnedMutSlice<'_, T> {
+    fn entries(&self) -> &[T] {
+        self
+    }
+
+    fn from_entries(entries: Vec<T>) -> Option<Self> {
+        Some(entries.into())
+    }
+}
+
+impl<T, const N: usize> SparseStorage<T> for OwnedMutSizedSlice<'_, T, N> {
+    fn entries(&self) -> &[T] {
+        &**self
+    }
+
+    fn from_entries(entries: Vec<T>) -> Option<Self> {
+        let sized: Box<[T; N]> = entries.into_boxed_slice().try_into().ok()?;
+        Some(sized.into())
+    }
+
+    fn max_len() -> usize {
+        N
+    }
+}
+
+/// The serialized form of a map.
+/// The variants have to stay in sync with [`SparseMapOwned`].
+#[derive(Serialize)]
+enum SparseMapRef<'a, T> {
+    /// All entries
+    Dense(&'a [T]),
+    /// The length of the map, the most common value, and all entries that differ from it.
+    /// Each entry starts with the number of entries skipped since the previous one.
+    Sparse {
+        len: usize,
+        fill: &'a T,
+        entries: Vec<(usize, &'a T)>,
+    },
+}
+
+/// The deserialized form of a map, see [`SparseMapRef`]
+#[derive(Deserialize)]
+enum SparseMapOwned<T> {
+    Dense(Vec<T>),
+    Sparse {
+        len: usize,
+        fill: T,
+        entries: Vec<(usize, T)>,
+    },
+}
+
+/// Serializes the map sparsely, if it is worth it
+pub fn serialize<M, T, S>(map: &M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    M: SparseStorage<T>,
+    T: Serialize + PartialEq,
+    S: Serializer,
+{
+    let map = map.entries();
+    let Some(fill) = majority(map) else {
+        return SparseMapRef::Dense(map).serialize(serializer);
+    };
+    let set = map.iter().filter(|entry| *entry != fill).count();
+
+    if set * DENSE_RATIO > map.len() {
+        SparseMapRef::Dense(map).serialize(serializer)
+    } else {
+        SparseMapRef::Sparse {
+            len: map.len(),
+            fill,
+            entries: deltas(map, fill),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The entries differing from `fill`, each with the number of entries skipped since the previous one
+fn deltas<'a, T>(map: &'a [T], fill: &T) -> Vec<(usize, &'a T)>
+where
+    T: PartialEq,
+{
+    let mut next = 0;
+    map.iter()
+        .enumerate()
+        .filter(|(_, entry)| *entry != fill)
+        .map(|(idx, entry)| {
+            let skipped = idx - next;
+            next = idx + 1;
+            (skipped, entry)
+        })
+        .collect()
+}
+
+/// The value of more than half of the entries, if any (Boyer-Moore majority vote).
+/// If no value is in the majority, this returns some other value, which only costs space.
+fn majority<T>(map: &[T]) -> Option<&T>
+where
+    T: PartialEq,
+{
+    let mut candidate = map.first()?;
+    let mut count = 0_usize;
+    for entry in map {
+        if count == 0 {
+            candidate = entry;
+        }
+        if entry == candidate {
+            count += 1;
+        } else {
+            count -= 1;
+        }
+    }
+    Some(candidate)
+}
+
+/// Deserializes a map serialized by [`serialize`]
+pub fn deserialize<'de, M, T, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: SparseStorage<T>,
+    T: Deserialize<'de> + Clone,
+    D: Deserializer<'de>,
+{
+    let entries = match SparseMapOwned::<T>::deserialize(deserializer)? {
+        SparseMapOwned::Dense(entries) => entries,
+        SparseMapOwned::Sparse { len, fill, entries } => {
+            if len > M::max_len() {
+                return Err(D::Error::invalid_length(
+                    len,
+                    &"the length of the observer map",
+                ));
+            }
+            let mut map = vec![fill; len];
+            let mut next = 0_usize;
+            for (skipped, value) in entries {
+                let idx = next.saturating_add(skipped);
+                *map.get_mut(idx)
+                    .ok_or_else(|| D::Error::custom("Sparse map index out of bounds"))? = value;
+                next = idx + 1;
+            }
+            map
+        }
+    };
+    let len = entries.len();
+    M::from_entries(entries)
+        .ok_or_else(|| D::Error::invalid_length(len, &"the length of the observer map"))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::ownedref::{OwnedMutSizedSlice, OwnedMutSlice};
+    use serde::{Deserialize, Serialize};
+
+    use crate::observers::map::sparse::{self, SparseMapRef, MAX_SPARSE_MAP_LEN};
+
+    #[derive(Serialize, Deserialize)]
+    struct Map<'a> {
+        #[serde(with = "sparse")]
+        map: OwnedMutSlice<'a, u8>,
+    }
+
+    #[test]
+    fn test_sparse_map() {
+        let mut coverage = vec![0_u8; 65536];
+        coverage[3] = 1;
+        coverage[65535] = 128;
+        let map = Map {
+            map: OwnedMutSlice::from(coverage.clone()),
+        };
+        let serialized = postcard::to_allocvec(&map).unwrap();
+        assert!(serialized.len() < 16);
+        let deserialized: Map = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(&*deserialized.map, &coverage[..]);
+
+        let full: Vec<u8> = (0..=255).collect();
+        let map = Map {
+            map: OwnedMutSlice::from(full.clone()),
+        };
+        let serialized = postcard::to_allocvec(&map).unwrap();
+        assert!(serialized.len() < full.len() + 4);
+        let deserialized: Map = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(&*deserialized.map, &full[..]);
+    }
+
+    #[test]
+    fn test_sparse_map_deltas() {
+        // Close entries only cost a byte for their position
+        let mut coverage = vec![0_u8; 1 << 20];
+        coverage[1 << 19..(1 << 19) + 64].fill(1);
+        let map = Map {
+            map: OwnedMutSlice::from(coverage.clone()),
+        };
+        let serialized = postcard::to_allocvec(&map).unwrap();
+        assert!(serialized.len() < 2 * 64 + 16);
+        let deserialized: Map = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(&*deserialized.map, &coverage[..]);
+    }
+
+    #[test]
+    fn test_sparse_map_len_bound() {
+        #[derive(Serialize, Deserialize)]
+        struct SizedMap<'a> {
+            #[serde(with = "sparse")]
+            map: OwnedMutSizedSlice<'a, u8, 16>,
+        }
+
+        let huge = postcard::to_allocvec(&SparseMapRef::Sparse {
+            len: MAX_SPARSE_MAP_LEN + 1,
+            fill: &0_u8,
+            entries: vec![(3, &1)],
+        })
+        .unwrap();
+        assert!(postcard::from_bytes::<Map>(&huge).is_err());
+
+        // Sized maps only accept their own size
+        let larger = postcard::to_allocvec(&SparseMapRef::Sparse {
+            len: 17,
+            fill: &0_u8,
+            entries: vec![(3, &1)],
+        })
+        .unwrap();
+        assert!(postcard::from_bytes::<SizedMap>(&larger).is_err());
+    }
+}
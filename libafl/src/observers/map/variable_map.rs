@@ -14,14 +14,22 @@ use libafl_bolts::{
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    observers::{map::MapObserver, Observer, VarLenMapObserver},
+    observers::{
+        map::{sparse, MapObserver},
+        Observer, VarLenMapObserver,
+    },
     Error,
 };
 
 /// Overlooking a variable bitmap
 #[derive(Serialize, Deserialize, Debug)]
 #[expect(clippy::unsafe_derive_deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize + PartialEq",
+    deserialize = "T: Deserialize<'de> + Clone"
+))]
 pub struct VariableMapObserver<'a, T> {
+    #[serde(with = "sparse")]
     map: OwnedMutSlice<'a, T>,
     size: OwnedMutPtr<usize>,
     initial: T,
@@ -9,11 +9,26 @@ pub mod stdio;
 #[cfg(feature = "std")]
 pub use stdio::{StdErrObserver, StdOutObserver};
 
+#[cfg(feature = "std")]
+pub mod hang_backtrace;
+#[cfg(feature = "std")]
+pub use hang_backtrace::{HangBacktraceObserver, HangFrame};
+
+#[cfg(feature = "std")]
+pub mod sandbox;
+#[cfg(feature = "std")]
+pub use sandbox::SandboxViolationObserver;
+
 #[cfg(feature = "regex")]
 pub mod stacktrace;
 #[cfg(feature = "regex")]
 pub use stacktrace::*;
 
+#[cfg(feature = "regex")]
+pub mod sanitizer;
+#[cfg(feature = "regex")]
+pub use sanitizer::{SanitizerKind, SanitizerReport, SanitizerReportObserver};
+
 /// Profiler observer
 #[cfg(feature = "std")]
 pub mod profiling;
@@ -21,6 +36,10 @@ pub mod profiling;
 pub use profiling::*;
 
 pub mod concolic;
+
+pub mod diff_outputs;
+pub use diff_outputs::{DiffOutput, DiffOutputsObserver};
+
 pub mod map;
 pub use map::*;
 
@@ -287,6 +306,78 @@ where
     }
 }
 
+/// A trait for [`Observer`]`s` which observe over an N-way differential execution,
+/// as done by [`crate::executors::MultiDiffExecutor`].
+///
+/// The flow is the same as for [`DifferentialObserver`], except that instead of a first and a second
+/// execution, there is one execution per wrapped executor, identified by its index.
+/// `OT` is the observers type of the wrapped executors; when they are of different types, the observer
+/// is used with the observers type of each of them.
+#[expect(unused_variables)]
+pub trait MultiDifferentialObserver<OT, I, S>: Observer<I, S> {
+    /// Perform an operation with the observers of the executor at `idx` before they are `pre_exec`'d.
+    fn pre_observe(&mut self, idx: usize, observers: &mut OT) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Perform an operation with the observers of the executor at `idx` after they are `post_exec`'d.
+    fn post_observe(
+        &mut self,
+        idx: usize,
+        observers: &mut OT,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// N-way differential observers tuple, for when you're using multiple [`MultiDifferentialObserver`]s.
+pub trait MultiDifferentialObserversTuple<OT, I, S>: ObserversTuple<I, S> {
+    /// Perform an operation with the observers of the executor at `idx` before they are `pre_exec`'d
+    /// on all the differential observers in this tuple.
+    fn pre_observe_all(&mut self, idx: usize, observers: &mut OT) -> Result<(), Error>;
+
+    /// Perform an operation with the observers of the executor at `idx` after they are `post_exec`'d
+    /// on all the differential observers in this tuple.
+    fn post_observe_all(
+        &mut self,
+        idx: usize,
+        observers: &mut OT,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error>;
+}
+
+impl<OT, I, S> MultiDifferentialObserversTuple<OT, I, S> for () {
+    fn pre_observe_all(&mut self, _: usize, _: &mut OT) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn post_observe_all(&mut self, _: usize, _: &mut OT, _: &ExitKind) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<Head, Tail, OT, I, S> MultiDifferentialObserversTuple<OT, I, S> for (Head, Tail)
+where
+    Head: MultiDifferentialObserver<OT, I, S>,
+    Tail: MultiDifferentialObserversTuple<OT, I, S>,
+{
+    fn pre_observe_all(&mut self, idx: usize, observers: &mut OT) -> Result<(), Error> {
+        self.0.pre_observe(idx, observers)?;
+        self.1.pre_observe_all(idx, observers)
+    }
+
+    fn post_observe_all(
+        &mut self,
+        idx: usize,
+        observers: &mut OT,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.0.post_observe(idx, observers, exit_kind)?;
+        self.1.post_observe_all(idx, observers, exit_kind)
+    }
+}
+
 /// A simple observer, just overlooking the runtime of the target.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TimeObserver {
@@ -397,6 +488,8 @@ impl Named for TimeObserver {
 
 impl<OTA, OTB, I, S> DifferentialObserver<OTA, OTB, I, S> for TimeObserver {}
 
+impl<OT, I, S> MultiDifferentialObserver<OT, I, S> for TimeObserver {}
+
 #[cfg(feature = "std")]
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,68 @@
+//! The [`SandboxViolationObserver`] records if the target violated the policy of its sandbox.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{observers::Observer, Error};
+
+/// An observer recording if the target violated the `seccomp` policy of its sandbox, on Linux
+/// (see `crate::executors::Sandbox`), whether it was killed for it or not.
+///
+/// The executor running the target sets the violation, a
+/// [`crate::feedbacks::SandboxViolationFeedback`] turns it into an objective.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SandboxViolationObserver {
+    observer_name: Cow<'static, str>,
+    violation: bool,
+}
+
+impl SandboxViolationObserver {
+    /// Creates a new [`SandboxViolationObserver`] with the given name.
+    #[must_use]
+    pub fn new<S>(observer_name: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self {
+            observer_name: observer_name.into(),
+            violation: false,
+        }
+    }
+
+    /// If the target violated the sandbox policy in the last run
+    #[must_use]
+    pub fn violation(&self) -> bool {
+        self.violation
+    }
+
+    /// Records a violation of the sandbox policy, called by the executor
+    pub fn set_violation(&mut self) {
+        self.violation = true;
+    }
+}
+
+impl Default for SandboxViolationObserver {
+    fn default() -> Self {
+        Self::new("SandboxViolationObserver")
+    }
+}
+
+impl<I, S> Observer<I, S> for SandboxViolationObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.violation = false;
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.violation = false;
+        Ok(())
+    }
+}
+
+impl Named for SandboxViolationObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.observer_name
+    }
+}
@@ -0,0 +1,425 @@
+//! The [`SanitizerReportObserver`] parses the reports of `UBSan`, `MSan`, `TSan`, `LSan` and `ASan`.
+//!
+//! Contrary to the [`crate::observers::AsanBacktraceObserver`], it also keeps the reports of
+//! sanitizers that continue execution after reporting (`halt_on_error=0`), so findings of targets
+//! that exit normally are not lost.
+//! Use it with a [`crate::feedbacks::SanitizerReportFeedback`] to turn these reports into objectives,
+//! and with a [`crate::feedbacks::NewHashFeedback`] to deduplicate them.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::hash::{Hash, Hasher};
+use std::{fs, io, sync::LazyLock};
+
+use libafl_bolts::{hasher_std, Named};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    observers::{Observer, ObserverWithHashField},
+    Error,
+};
+
+/// The path prefix the sanitizers write their reports to, when run with [`sanitizer_runtime_envs`].
+///
+/// The sanitizers append the pid of the target to it.
+pub static SANITIZER_LOG_PATH: &str = "./sanitizerlog";
+
+static HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:==\d+==\s*)?(?:ERROR|WARNING): (AddressSanitizer|MemorySanitizer|ThreadSanitizer|LeakSanitizer|UndefinedBehaviorSanitizer): (.*)$",
+    )
+    .unwrap()
+});
+static UBSAN_HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\S+:\d+(?::\d+)?): runtime error: (.*)$").unwrap());
+static FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*#(\d+)\s+(?:0x([0-9a-fA-F]+)\s+)?(.*)$").unwrap());
+
+/// Returns the environment variables configuring `UBSan`, `MSan`, `TSan`, `LSan` and `ASan`
+/// to write their reports to [`SANITIZER_LOG_PATH`].
+///
+/// Use [`merge_sanitizer_options`] to keep the options the user already set.
+#[must_use]
+pub fn sanitizer_runtime_envs() -> Vec<(&'static str, String)> {
+    let log_path = format!("log_path={SANITIZER_LOG_PATH}");
+    vec![
+        ("UBSAN_OPTIONS", format!("print_stacktrace=1:{log_path}")),
+        ("ASAN_OPTIONS", log_path.clone()),
+        ("MSAN_OPTIONS", log_path.clone()),
+        ("TSAN_OPTIONS", log_path.clone()),
+        ("LSAN_OPTIONS", log_path),
+    ]
+}
+
+/// Appends the flags of `ours` to the sanitizer options `existing`, unless the user already set
+/// them there.
+#[must_use]
+pub fn merge_sanitizer_options(existing: Option<&str>, ours: &str) -> String {
+    let Some(existing) = existing.filter(|existing| !existing.trim().is_empty()) else {
+        return ours.into();
+    };
+    let is_set = |key: &str| {
+        existing
+            .split([':', ',', ' '])
+            .any(|flag| flag.split_once('=').is_some_and(|(k, _)| k == key))
+    };
+
+    let mut merged = String::from(existing);
+    for flag in ours.split(':') {
+        if flag.split_once('=').is_some_and(|(key, _)| !is_set(key)) {
+            merged.push(':');
+            merged.push_str(flag);
+        }
+    }
+    merged
+}
+
+/// The sanitizer that emitted a report
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SanitizerKind {
+    /// `AddressSanitizer`
+    Address,
+    /// `UndefinedBehaviorSanitizer`
+    UndefinedBehavior,
+    /// `MemorySanitizer`
+    Memory,
+    /// `ThreadSanitizer`
+    Thread,
+    /// `LeakSanitizer`
+    Leak,
+}
+
+impl SanitizerKind {
+    fn from_report_name(name: &str) -> Option<Self> {
+        match name {
+            "AddressSanitizer" => Some(Self::Address),
+            "UndefinedBehaviorSanitizer" => Some(Self::UndefinedBehavior),
+            "MemorySanitizer" => Some(Self::Memory),
+            "ThreadSanitizer" => Some(Self::Thread),
+            "LeakSanitizer" => Some(Self::Leak),
+            _ => None,
+        }
+    }
+}
+
+/// A stack frame of a sanitizer report
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SanitizerFrame {
+    /// The absolute address of the frame, if printed (`TSan` does not)
+    pub address: Option<u64>,
+    /// The function of the frame, if symbolized
+    pub function: Option<String>,
+    /// The source location, or the module and offset (`(module+0x1234)`) of the frame
+    pub location: Option<String>,
+}
+
+impl SanitizerFrame {
+    /// The part of the frame that stays the same across runs, even with ASLR
+    fn stable_key(&self) -> Option<&str> {
+        self.function.as_deref().or(self.location.as_deref())
+    }
+}
+
+/// A report emitted by a sanitizer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SanitizerReport {
+    /// The sanitizer that emitted this report
+    pub sanitizer: SanitizerKind,
+    /// The class of the bug, e.g. `heap-buffer-overflow`, `data race` or `signed integer overflow`
+    pub bug_class: String,
+    /// The source location of the bug, if given in the report header (`UBSan`)
+    pub location: Option<String>,
+    /// The frames of the first stack of the report, innermost first
+    pub frames: Vec<SanitizerFrame>,
+}
+
+impl SanitizerReport {
+    /// A hash of the report, based on the sanitizer, the bug class and the stack.
+    ///
+    /// Absolute addresses are left out, so the hash is stable across runs.
+    #[must_use]
+    pub fn hash(&self) -> u64 {
+        let mut hasher = hasher_std();
+        self.sanitizer.hash(&mut hasher);
+        self.bug_class.hash(&mut hasher);
+        if self.frames.is_empty() {
+            self.location.hash(&mut hasher);
+        }
+        for frame in &self.frames {
+            frame.stable_key().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// Reduces the description of a bug to its class, leaving out addresses and values.
+fn bug_class(description: &str) -> String {
+    let end = [":", " on ", " (pid=", " at pc ", " of type "]
+        .iter()
+        .filter_map(|sep| description.find(sep))
+        .min()
+        .unwrap_or(description.len());
+    description[..end]
+        .split(' ')
+        .map(|word| {
+            if word.bytes().all(|b| b.is_ascii_digit()) {
+                "N"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .into()
+}
+
+/// Parses a single stack frame line, such as `#0 0x4f6a3b in main /src/test.c:5:3`
+fn parse_frame(rest: &str, address: Option<&str>) -> SanitizerFrame {
+    let rest = rest.trim();
+    let rest = rest.strip_prefix("in ").unwrap_or(rest);
+    let (function, location) = if rest.starts_with('(') || rest.starts_with('/') {
+        (None, rest)
+    } else {
+        match rest.split_once(' ') {
+            Some((function, location)) => (Some(function), location.trim()),
+            None => (Some(rest), ""),
+        }
+    };
+    SanitizerFrame {
+        address: address.and_then(|addr| u64::from_str_radix(addr, 16).ok()),
+        function: function.map(String::from),
+        location: (!location.is_empty()).then(|| location.into()),
+    }
+}
+
+/// Parses all sanitizer reports found in `output`, in order.
+#[must_use]
+pub fn parse_sanitizer_reports(output: &str) -> Vec<SanitizerReport> {
+    let mut reports: Vec<SanitizerReport> = Vec::new();
+    // Only the first stack of each report is kept
+    let mut in_stack = false;
+    let mut stack_done = true;
+
+    for line in output.lines() {
+        if let Some((caps, sanitizer)) = HEADER.captures(line).and_then(|caps| {
+            let sanitizer = SanitizerKind::from_report_name(&caps[1])?;
+            Some((caps, sanitizer))
+        }) {
+            reports.push(SanitizerReport {
+                sanitizer,
+                bug_class: bug_class(&caps[2]),
+                location: None,
+                frames: Vec::new(),
+            });
+            in_stack = false;
+            stack_done = false;
+        } else if let Some(caps) = UBSAN_HEADER.captures(line) {
+            reports.push(SanitizerReport {
+                sanitizer: SanitizerKind::UndefinedBehavior,
+                bug_class: bug_class(&caps[2]),
+                location: Some(caps[1].into()),
+                frames: Vec::new(),
+            });
+            in_stack = false;
+            stack_done = false;
+        } else if let Some(caps) = FRAME.captures(line) {
+            if stack_done {
+                continue;
+            }
+            let Some(report) = reports.last_mut() else {
+                continue;
+            };
+            if in_stack && &caps[1] == "0" {
+                // A second stack starts
+                stack_done = true;
+                continue;
+            }
+            in_stack = true;
+            report
+                .frames
+                .push(parse_frame(&caps[3], caps.get(2).map(|m| m.as_str())));
+        } else if in_stack {
+            stack_done = true;
+            in_stack = false;
+        }
+    }
+    reports
+}
+
+/// An observer collecting the sanitizer reports of the last execution.
+///
+/// It is filled by the executor, with the `stderr` of the target for the
+/// [`crate::executors::CommandExecutor`], or with the log files written to [`SANITIZER_LOG_PATH`]
+/// for the [`crate::executors::ForkserverExecutor`].
+/// If the [`crate::executors::ForkserverExecutor`] also has an
+/// [`crate::observers::AsanBacktraceObserver`], the `ASan` reports go to that observer's log file instead.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SanitizerReportObserver {
+    observer_name: Cow<'static, str>,
+    reports: Vec<SanitizerReport>,
+}
+
+impl SanitizerReportObserver {
+    /// Creates a new [`SanitizerReportObserver`] with the given name.
+    #[must_use]
+    pub fn new<S>(observer_name: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self {
+            observer_name: observer_name.into(),
+            reports: Vec::new(),
+        }
+    }
+
+    /// The sanitizer reports of the last execution
+    #[must_use]
+    pub fn reports(&self) -> &[SanitizerReport] {
+        &self.reports
+    }
+
+    /// Parses the sanitizer reports in the given output and adds them to this observer.
+    pub fn parse_output(&mut self, output: &str) {
+        self.reports.extend(parse_sanitizer_reports(output));
+    }
+
+    /// Reads the sanitizer log file of the process `pid`, if any, and parses it.
+    ///
+    /// The file is removed after parsing, so a later process reusing the `pid` starts a new file,
+    /// and the reports are not parsed again.
+    pub fn parse_log_file(&mut self, pid: i32) -> Result<(), Error> {
+        let log_path = format!("{SANITIZER_LOG_PATH}.{pid}");
+        let buf = match fs::read(&log_path) {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        fs::remove_file(&log_path)?;
+
+        self.parse_output(&String::from_utf8_lossy(&buf));
+        Ok(())
+    }
+}
+
+impl Default for SanitizerReportObserver {
+    fn default() -> Self {
+        Self::new("SanitizerReportObserver")
+    }
+}
+
+impl ObserverWithHashField for SanitizerReportObserver {
+    /// The hash of the first report of the last execution
+    fn hash(&self) -> Option<u64> {
+        self.reports.first().map(SanitizerReport::hash)
+    }
+}
+
+impl<I, S> Observer<I, S> for SanitizerReportObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.reports.clear();
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.reports.clear();
+        Ok(())
+    }
+}
+
+impl Named for SanitizerReportObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.observer_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_sanitizer_options, parse_sanitizer_reports, SanitizerKind};
+
+    const UBSAN: &str = "\
+test.c:5:12: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+    #0 0x55d0c0a21234 in overflow /src/test.c:5:12
+    #1 0x55d0c0a22000 in main /src/test.c:10:3
+    #2 0x7f0000029d8f  (/lib/x86_64-linux-gnu/libc.so.6+0x29d8f)
+
+SUMMARY: UndefinedBehaviorSanitizer: undefined-behavior test.c:5:12 in
+";
+
+    const TSAN: &str = "\
+==================
+WARNING: ThreadSanitizer: data race (pid=1234)
+  Write of size 4 at 0x55d0c0a40010 by thread T1:
+    #0 Thread1 /src/race.c:5:10 (race+0xd1fb6)
+    #1 <null> <null> (libtsan.so.0+0x2d1af)
+
+  Previous write of size 4 at 0x55d0c0a40010 by main thread:
+    #0 main /src/race.c:12:10 (race+0xd2045)
+
+SUMMARY: ThreadSanitizer: data race /src/race.c:5:10 in Thread1
+";
+
+    const LSAN: &str = "\
+==4321==ERROR: LeakSanitizer: detected memory leaks
+
+Direct leak of 7 byte(s) in 1 object(s) allocated from:
+    #0 0x4af01b in __interceptor_malloc (/tmp/leak+0x4af01b)
+    #1 0x4da26a in main /src/leak.c:4:7
+";
+
+    #[test]
+    fn test_parse_sanitizer_reports() {
+        let reports = parse_sanitizer_reports(UBSAN);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].sanitizer, SanitizerKind::UndefinedBehavior);
+        assert_eq!(reports[0].bug_class, "signed integer overflow");
+        assert_eq!(reports[0].location.as_deref(), Some("test.c:5:12"));
+        assert_eq!(reports[0].frames.len(), 3);
+        assert_eq!(reports[0].frames[0].function.as_deref(), Some("overflow"));
+        assert_eq!(reports[0].frames[0].address, Some(0x55d0_c0a2_1234));
+        assert_eq!(reports[0].frames[2].function, None);
+
+        let reports = parse_sanitizer_reports(TSAN);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].sanitizer, SanitizerKind::Thread);
+        assert_eq!(reports[0].bug_class, "data race");
+        assert_eq!(reports[0].frames.len(), 2);
+        assert_eq!(reports[0].frames[0].function.as_deref(), Some("Thread1"));
+        assert_eq!(reports[0].frames[0].address, None);
+
+        let reports = parse_sanitizer_reports(LSAN);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].sanitizer, SanitizerKind::Leak);
+        assert_eq!(reports[0].bug_class, "detected memory leaks");
+        assert_eq!(reports[0].frames.len(), 2);
+    }
+
+    #[test]
+    fn test_sanitizer_report_hash() {
+        // The same bug at other addresses, e.g. with ASLR
+        let moved = UBSAN
+            .replace("0x55d0c0a2", "0x56aa00b2")
+            .replace("0x7f0000029d8f", "0x7f1234529d8f");
+        let a = parse_sanitizer_reports(UBSAN);
+        let b = parse_sanitizer_reports(&moved);
+        assert_eq!(a[0].hash(), b[0].hash());
+
+        let other = UBSAN.replace("in main", "in other_caller");
+        let c = parse_sanitizer_reports(&other);
+        assert_ne!(a[0].hash(), c[0].hash());
+    }
+
+    #[test]
+    fn test_merge_sanitizer_options() {
+        let ours = "print_stacktrace=1:log_path=./sanitizerlog";
+        assert_eq!(merge_sanitizer_options(None, ours), ours);
+        assert_eq!(
+            merge_sanitizer_options(Some("halt_on_error=1"), ours),
+            "halt_on_error=1:print_stacktrace=1:log_path=./sanitizerlog"
+        );
+        assert_eq!(
+            merge_sanitizer_options(Some("print_stacktrace=0,log_path=/tmp/x"), ours),
+            "print_stacktrace=0,log_path=/tmp/x"
+        );
+    }
+}
@@ -1,5 +1,5 @@
 //! Stage to compute and report AFL++ stats
-use alloc::{string::String, vec::Vec};
+use alloc::{collections::VecDeque, string::String, vec::Vec};
 use core::{marker::PhantomData, time::Duration};
 use std::{
     borrow::Cow,
@@ -12,6 +12,8 @@ use std::{
 
 #[cfg(unix)]
 use libafl_bolts::os::peak_rss_mb_child_processes;
+#[cfg(windows)]
+use libafl_bolts::os::peak_rss_mb_current_process;
 use libafl_bolts::{
     core_affinity::CoreId,
     current_time,
@@ -23,7 +25,7 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "track_hit_feedbacks")]
 use crate::feedbacks::{CRASH_FEEDBACK_NAME, TIMEOUT_FEEDBACK_NAME};
 use crate::{
-    corpus::{Corpus, HasCurrentCorpusId, SchedulerTestcaseMetadata, Testcase},
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId, SchedulerTestcaseMetadata, Testcase},
     events::{Event, EventFirer},
     executors::HasObservers,
     monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
@@ -39,7 +41,16 @@ use crate::{
 /// AFL++'s default stats update interval
 pub const AFL_FUZZER_STATS_UPDATE_INTERVAL_SECS: u64 = 60;
 
-/// `CalibrationTime` - Use in conjunction with `TimeTrackingFeedback`
+/// The window over which `execs_ps_last_min` is computed
+const EXECS_PER_SEC_WINDOW: Duration = Duration::from_secs(60);
+
+/// The minimum time between two samples of the executions, for `execs_ps_last_min`
+const EXECS_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// The maximum stacking power of AFL++'s havoc stage, before it is expanded
+const AFL_HAVOC_STACK_POW2: usize = 7;
+
+/// `CalibrationTime` - Time spent calibrating.
+/// Measured by a `TimeTrackingStageWrapper<CalibrationTime, _, _>` around the calibration stage.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CalibrationTime(pub Duration);
 impl From<Duration> for CalibrationTime {
@@ -50,7 +61,8 @@ impl From<Duration> for CalibrationTime {
 
 libafl_bolts::impl_serdeany!(CalibrationTime);
 
-/// `SyncTime` - Use in conjunction with `TimeTrackingFeedback`
+/// `SyncTime` - Time spent syncing.
+/// Measured by a `TimeTrackingStageWrapper<SyncTime, _, _>` around the sync stage.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SyncTime(pub Duration);
 impl From<Duration> for SyncTime {
@@ -61,7 +73,8 @@ impl From<Duration> for SyncTime {
 
 libafl_bolts::impl_serdeany!(SyncTime);
 
-/// `FuzzTime` - Use in conjunction with `TimeTrackingFeedback`
+/// `FuzzTime` - Time spent fuzzing.
+/// Measured by a `TimeTrackingStageWrapper<FuzzTime, _, _>` around the mutational stage.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FuzzTime(pub Duration);
 impl From<Duration> for FuzzTime {
@@ -72,18 +85,33 @@ impl From<Duration> for FuzzTime {
 
 libafl_bolts::impl_serdeany!(FuzzTime);
 
+/// `TrimTime` - Time spent trimming.
+/// Measured by a `TimeTrackingStageWrapper<TrimTime, _, _>` around the trimming stage,
+/// for example a [`crate::stages::StdTMinMutationalStage`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrimTime(pub Duration);
+impl From<Duration> for TrimTime {
+    fn from(value: Duration) -> Self {
+        Self(value)
+    }
+}
+
+libafl_bolts::impl_serdeany!(TrimTime);
+
 /// The [`AflStatsStage`] is a Stage that calculates and writes
 /// AFL++'s `fuzzer_stats` and `plot_data` information.
+///
+/// The time spent calibrating, syncing, fuzzing and trimming is only reported for the stages
+/// wrapped in a [`crate::stages::TimeTrackingStageWrapper`] for [`CalibrationTime`], [`SyncTime`],
+/// [`FuzzTime`] and [`TrimTime`], and is 0 otherwise.
 #[derive(Debug, Clone)]
 pub struct AflStatsStage<C, E, EM, I, O, S, Z> {
     map_observer_handle: Handle<C>,
     stats_file_path: Option<PathBuf>,
     plot_file_path: Option<PathBuf>,
     start_time: u64,
-    // the number of testcases that have been fuzzed
-    has_fuzzed_size: usize,
-    // the number of "favored" testcases
-    is_favored_size: usize,
+    // samples of the executions over the last minute
+    exec_samples: ExecSamples,
     // the last time that we report all stats
     last_report_time: Duration,
     // the interval at which we report all stats
@@ -112,6 +140,8 @@ pub struct AflStatsStage<C, E, EM, I, O, S, Z> {
     dict_count: usize,
     /// autotokens are enabled
     autotokens_enabled: bool,
+    /// How many powers of two the havoc stacking goes beyond AFL++'s default
+    havoc_expansion: usize,
     /// The core we are bound to
     core_id: CoreId,
     phantom_data: PhantomData<(E, EM, I, O, S, Z)>,
@@ -141,13 +171,13 @@ pub struct AFLFuzzerStats<'a> {
     /// Time spent syncing with foreign fuzzers
     /// NOTE: Syncing between our own instances is not counted.
     sync_time: u64,
-    /// TODO
+    /// Time spent trimming inputs
     trim_time: u64,
     /// number of fuzzer executions attempted (what does attempted mean here?)
     execs_done: u64,
     /// overall number of execs per second
     execs_per_sec: u64,
-    /// TODO
+    /// number of execs per second over the last minute
     execs_ps_last_min: u64,
     /// total number of entries in the queue
     corpus_count: usize,
@@ -189,7 +219,7 @@ pub struct AFLFuzzerStats<'a> {
     slowest_exec_ms: u128,
     /// max rss usage reached during fuzzing in MB
     peak_rss_mb: i64,
-    /// TODO
+    /// the core the fuzzer is bound to
     cpu_affinity: usize,
     /// how many edges have been found
     edges_found: u64,
@@ -197,15 +227,15 @@ pub struct AFLFuzzerStats<'a> {
     total_edges: u64,
     /// how many edges are non-deterministic
     var_byte_count: usize,
-    /// TODO:
+    /// the level of havoc stacking expansion
     havoc_expansion: usize,
     /// Amount of automatic dict entries found
     auto_dict_entries: usize,
-    /// TODO:
+    /// size of the testcase cache in bytes (the inputs are cached by the corpus, so always 0)
     testcache_size: usize,
-    /// TODO:
+    /// number of cached testcases (always 0)
     testcache_count: usize,
-    /// TODO:
+    /// number of testcases evicted from the cache (always 0)
     testcache_evict: usize,
     /// banner text (e.g., the target name)
     afl_banner: &'a Cow<'static, str>,
@@ -282,11 +312,11 @@ where
                 self.maybe_update_last_crash(&testcase, state);
                 self.maybe_update_last_hang(&testcase, state);
             }
-            self.update_has_fuzzed_size();
-            self.maybe_update_is_favored_size(&testcase);
         }
         self.maybe_update_slowest_exec(&testcase);
         self.maybe_update_max_depth(&testcase);
+        self.exec_samples
+            .sample(current_time(), *state.executions());
 
         // See if we actually need to run the stage, if not, avoid dynamic value computation.
         if !self.check_interval() {
@@ -295,6 +325,10 @@ where
 
         let corpus_size = state.corpus().count();
         let total_executions = *state.executions();
+        let run_time = self.last_report_time.as_secs() - self.start_time;
+
+        let (corpus_favored, pending_total, pending_favs) =
+            favored_and_pending(state.corpus(), corpus_idx, &testcase)?;
 
         let scheduler = fuzzer.scheduler();
         let queue_cycles = scheduler.queue_cycles();
@@ -325,7 +359,7 @@ where
         let stats = AFLFuzzerStats {
             start_time: self.start_time,
             last_update: self.last_report_time.as_secs(),
-            run_time: self.last_report_time.as_secs() - self.start_time,
+            run_time,
             fuzzer_pid: self.pid,
             cycles_done: queue_cycles,
             cycles_wo_find: self.cycles_wo_finds,
@@ -341,18 +375,21 @@ where
                 .metadata::<SyncTime>()
                 .map_or(Duration::from_secs(0), |d| d.0)
                 .as_secs(),
-            trim_time: 0, // TODO
+            trim_time: state
+                .metadata::<TrimTime>()
+                .map_or(Duration::from_secs(0), |d| d.0)
+                .as_secs(),
             execs_done: total_executions,
-            execs_per_sec: *state.executions(),     // TODO
-            execs_ps_last_min: *state.executions(), // TODO
+            execs_per_sec: total_executions / run_time.max(1),
+            execs_ps_last_min: self.exec_samples.per_sec(current_time(), total_executions),
             max_depth: self.max_depth,
             corpus_count: corpus_size,
-            corpus_favored: corpus_size - self.is_favored_size,
+            corpus_favored,
             corpus_found: corpus_size - state.imported(),
             corpus_imported: *state.imported(),
             cur_item: corpus_idx.into(),
-            pending_total: corpus_size - self.has_fuzzed_size,
-            pending_favs: 0, // TODO
+            pending_total,
+            pending_favs,
             time_wo_finds: (current_time() - self.last_find).as_secs(),
             corpus_variable: 0,
             stability: self.calculate_stability(unstable_entries_in_map, filled_entries_in_map),
@@ -369,13 +406,16 @@ where
             // TODO: getting rss_mb may take some extra millis, so might make sense to make this optional
             #[cfg(unix)]
             peak_rss_mb: peak_rss_mb_child_processes()?,
-            #[cfg(not(unix))]
-            peak_rss_mb: 0, // TODO for Windows
+            // Windows can't account child processes, only in-process targets are covered
+            #[cfg(windows)]
+            peak_rss_mb: peak_rss_mb_current_process()?,
+            #[cfg(not(any(unix, windows)))]
+            peak_rss_mb: 0,
             cpu_affinity: self.core_id.0,
             total_edges: map_size as u64,
             edges_found: filled_entries_in_map,
             var_byte_count: unstable_entries_in_map,
-            havoc_expansion: 0, // TODO
+            havoc_expansion: self.havoc_expansion,
             auto_dict_entries,
             testcache_size: 0,
             testcache_count: 0,
@@ -482,12 +522,6 @@ where
         Ok(())
     }
 
-    fn maybe_update_is_favored_size(&mut self, testcase: &Testcase<I>) {
-        if testcase.has_metadata::<IsFavoredMetadata>() {
-            self.is_favored_size += 1;
-        }
-    }
-
     fn maybe_update_slowest_exec(&mut self, testcase: &Testcase<I>) {
         if let Some(exec_time) = testcase.exec_time() {
             if exec_time > &self.slowest_exec {
@@ -496,10 +530,6 @@ where
         }
     }
 
-    fn update_has_fuzzed_size(&mut self) {
-        self.has_fuzzed_size += 1;
-    }
-
     fn maybe_update_max_depth(&mut self, testcase: &Testcase<I>) {
         if let Ok(metadata) = testcase.metadata::<SchedulerTestcaseMetadata>() {
             if metadata.depth() > self.max_depth {
@@ -562,6 +592,76 @@ where
     }
 }
 
+/// Samples of `(time, executions)`, to compute the executions per second over the last minute
+#[derive(Debug, Clone, Default)]
+struct ExecSamples {
+    samples: VecDeque<(Duration, u64)>,
+}
+
+impl ExecSamples {
+    /// Takes a sample at `now`, if the last one is old enough, and forgets the expired ones
+    fn sample(&mut self, now: Duration, executions: u64) {
+        if self
+            .samples
+            .back()
+            .is_none_or(|(time, _)| now.saturating_sub(*time) >= EXECS_SAMPLE_INTERVAL)
+        {
+            self.samples.push_back((now, executions));
+        }
+        while self
+            .samples
+            .front()
+            .is_some_and(|(time, _)| now.saturating_sub(*time) > EXECS_PER_SEC_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The executions per second since the oldest sample, at most a minute ago
+    fn per_sec(&self, now: Duration, executions: u64) -> u64 {
+        let Some((time, oldest_executions)) = self.samples.front() else {
+            return 0;
+        };
+        let elapsed = now.saturating_sub(*time).as_millis();
+        if elapsed == 0 {
+            return 0;
+        }
+        let per_sec = u128::from(executions.saturating_sub(*oldest_executions)) * 1000 / elapsed;
+        u64::try_from(per_sec).unwrap_or(u64::MAX)
+    }
+}
+
+/// Counts the favored entries, the entries not fuzzed yet, and the favored ones among them.
+///
+/// The `current` entry, borrowed as `current_testcase`, is being fuzzed, so it is never pending.
+fn favored_and_pending<C, I>(
+    corpus: &C,
+    current: CorpusId,
+    current_testcase: &Testcase<I>,
+) -> Result<(usize, usize, usize), Error>
+where
+    C: Corpus<I>,
+{
+    let mut favored_count = 0;
+    let mut pending_total = 0;
+    let mut pending_favs = 0;
+    for id in corpus.ids() {
+        let (favored, pending) = if id == current {
+            (current_testcase.has_metadata::<IsFavoredMetadata>(), false)
+        } else {
+            let entry = corpus.get(id)?.borrow();
+            (
+                entry.has_metadata::<IsFavoredMetadata>(),
+                entry.scheduled_count() == 0,
+            )
+        };
+        favored_count += usize::from(favored);
+        pending_total += usize::from(pending);
+        pending_favs += usize::from(favored && pending);
+    }
+    Ok((favored_count, pending_total, pending_favs))
+}
+
 impl Display for AFLPlotData<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{},", self.relative_time)?;
@@ -587,7 +687,6 @@ impl AFLPlotData<'_> {
 }
 impl Display for AFLFuzzerStats<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "start_time        : {}", &self.start_time)?;
         writeln!(f, "start_time        : {}", &self.start_time)?;
         writeln!(f, "last_update       : {}", &self.last_update)?;
         writeln!(f, "run_time          : {}", &self.run_time)?;
@@ -655,6 +754,7 @@ pub struct AflStatsStageBuilder<C, E, EM, I, O, S, Z> {
     uses_autotokens: bool,
     report_interval: Duration,
     dict_count: usize,
+    havoc_stack_pow: usize,
     exec_timeout: u64,
     banner: String,
     version: String,
@@ -679,6 +779,7 @@ where
             map_observer_handle: None,
             uses_autotokens: false,
             dict_count: 0,
+            havoc_stack_pow: AFL_HAVOC_STACK_POW2,
             exec_timeout: 0,
             banner: String::default(),
             version: String::default(),
@@ -747,6 +848,14 @@ where
         self.exec_timeout = timeout;
         self
     }
+    /// The maximum stacking power of the havoc mutator, see
+    /// [`crate::mutators::StdScheduledMutator::max_stack_pow`].
+    /// Reported as `havoc_expansion`, the powers of two it goes beyond AFL++'s default of 7.
+    #[must_use]
+    pub fn havoc_stack_pow(mut self, max_stack_pow: usize) -> Self {
+        self.havoc_stack_pow = max_stack_pow;
+        self
+    }
     /// Used in the UI (optional)
     /// default, persistent, qemu, unicorn, non-instrumented etc
     #[must_use]
@@ -797,8 +906,7 @@ where
             map_observer_handle: self.map_observer_handle.unwrap(),
             start_time: current_time().as_secs(),
             stats_report_interval: self.report_interval,
-            has_fuzzed_size: 0,
-            is_favored_size: 0,
+            exec_samples: ExecSamples::default(),
             cycles_done: 0,
             cycles_wo_finds: 0,
             execs_at_last_objective: 0,
@@ -819,7 +927,74 @@ where
             dict_count: self.dict_count,
             core_id: self.core_id.unwrap_or(CoreId(0)),
             autotokens_enabled: self.uses_autotokens,
+            havoc_expansion: self.havoc_stack_pow.saturating_sub(AFL_HAVOC_STACK_POW2),
             phantom_data: PhantomData,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::Error;
+
+    use super::{favored_and_pending, ExecSamples};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::NopInput,
+        schedulers::minimizer::IsFavoredMetadata,
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_execs_ps_last_min() {
+        let mut samples = ExecSamples::default();
+        assert_eq!(samples.per_sec(Duration::from_secs(1), 0), 0);
+
+        samples.sample(Duration::from_secs(10), 0);
+        // Samples closer than a second are skipped
+        samples.sample(Duration::from_millis(10_500), 1_000);
+        assert_eq!(samples.per_sec(Duration::from_secs(20), 10_000), 1_000);
+
+        // After a minute, only the later samples count
+        samples.sample(Duration::from_secs(30), 20_000);
+        samples.sample(Duration::from_secs(80), 70_000);
+        assert_eq!(samples.per_sec(Duration::from_secs(80), 70_000), 1_000);
+        samples.sample(Duration::from_secs(85), 170_000);
+        assert_eq!(samples.per_sec(Duration::from_secs(85), 170_000), 2_727);
+    }
+
+    #[test]
+    fn test_favored_and_pending() -> Result<(), Error> {
+        #[cfg(any(not(feature = "serdeany_autoreg"), miri))]
+        // # Safety
+        // No concurrency in tests
+        unsafe {
+            IsFavoredMetadata::register();
+        }
+
+        let mut corpus = InMemoryCorpus::<NopInput>::new();
+        let testcase = |favored: bool, scheduled_count: usize| {
+            let mut testcase = Testcase::new(NopInput {});
+            if favored {
+                testcase.add_metadata(IsFavoredMetadata {});
+            }
+            testcase.set_scheduled_count(scheduled_count);
+            testcase
+        };
+        let current = corpus.add(testcase(true, 0))?;
+        corpus.add(testcase(true, 0))?;
+        corpus.add(testcase(true, 2))?;
+        corpus.add(testcase(false, 0))?;
+        corpus.add(testcase(false, 1))?;
+
+        let current_testcase = corpus.get(current)?.borrow();
+        // The current entry is being fuzzed, so it is not pending anymore
+        assert_eq!(
+            favored_and_pending(&corpus, current, &current_testcase)?,
+            (3, 2, 1)
+        );
+        Ok(())
+    }
+}
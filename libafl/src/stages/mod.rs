@@ -13,7 +13,7 @@ use alloc::{
 use core::{fmt, marker::PhantomData};
 
 #[cfg(feature = "std")]
-pub use afl_stats::{AflStatsStage, CalibrationTime, FuzzTime, SyncTime};
+pub use afl_stats::{AflStatsStage, CalibrationTime, FuzzTime, SyncTime, TrimTime};
 pub use calibrate::CalibrationStage;
 pub use colorization::*;
 #[cfg(all(feature = "std", unix))]
@@ -31,6 +31,8 @@ use libafl_bolts::{
 };
 pub use logics::*;
 pub use mutational::{MutationalStage, StdMutationalStage};
+#[cfg(all(feature = "otlp_monitor", feature = "introspection"))]
+pub use otlp_span::OtlpSpanStage;
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
@@ -72,6 +74,8 @@ pub mod dump;
 pub mod generalization;
 pub mod generation;
 pub mod logics;
+#[cfg(all(feature = "otlp_monitor", feature = "introspection"))]
+pub mod otlp_span;
 pub mod power;
 #[cfg(feature = "std")]
 pub mod sync;
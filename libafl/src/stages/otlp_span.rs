@@ -0,0 +1,83 @@
+//! A stage wrapper recording a span around each run of a stage, for an [`OtlpTracer`].
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::{Error, Named};
+
+use crate::{
+    monitors::{
+        otlp::{int_attribute, string_attribute},
+        OtlpTracer,
+    },
+    stages::{Restartable, Stage},
+    state::HasExecutions,
+};
+
+/// Wraps a [`Stage`], recording a span named after the stage around each of its runs.
+///
+/// The span carries the number of executions the stage ran, and has an error status if the stage
+/// failed. Wrap the executor in a [`crate::executors::OtlpSpanExecutor`] sharing the same tracer
+/// to also get a child span for each execution.
+#[derive(Debug)]
+pub struct OtlpSpanStage<ST> {
+    name: Cow<'static, str>,
+    inner: ST,
+    tracer: OtlpTracer,
+}
+
+impl<ST> OtlpSpanStage<ST> {
+    /// Wraps `inner`, recording its runs as spans called `name` to `tracer`
+    pub fn new(name: Cow<'static, str>, inner: ST, tracer: OtlpTracer) -> Self {
+        Self {
+            name,
+            inner,
+            tracer,
+        }
+    }
+}
+
+impl<ST> Named for OtlpSpanStage<ST> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, M, Z, S, ST> Stage<E, M, S, Z> for OtlpSpanStage<ST>
+where
+    S: HasExecutions,
+    ST: Stage<E, M, S, Z>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut M,
+    ) -> Result<(), Error> {
+        let executions = *state.executions();
+        self.tracer.start_span();
+        let ret = self.inner.perform(fuzzer, executor, state, manager);
+        self.tracer.end_span(
+            &self.name,
+            &[
+                string_attribute("stage", &self.name),
+                int_attribute("executions", *state.executions() - executions),
+            ],
+            ret.is_err(),
+        );
+        ret
+    }
+}
+
+impl<S, ST> Restartable<S> for OtlpSpanStage<ST>
+where
+    ST: Restartable<S>,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        self.inner.should_restart(state)
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        self.inner.clear_progress(state)
+    }
+}
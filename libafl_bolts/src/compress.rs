@@ -1,16 +1,94 @@
 //! Compression of events passed between a broker and clients.
-//! Currently we use the gzip compression algorithm for its fast decompression performance.
+//! By default, we use the gzip compression algorithm for its fast decompression performance.
+//! With the `zstd` and `lz4` features, faster codecs are available through the [`Compressor`] trait.
 
 use alloc::vec::Vec;
 use core::fmt::Debug;
+#[cfg(feature = "zstd")]
+use std::io::Read;
 
 use miniz_oxide::{
     deflate::{compress_to_vec, CompressionLevel},
-    inflate::decompress_to_vec,
+    inflate::decompress_to_vec_with_limit,
 };
 
 use crate::Error;
 
+/// The compression level used by the [`ZstdCompressor`], tuned for speed
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 1;
+
+/// The largest buffer a [`Compressor`] decompresses to.
+/// Compressed buffers usually come from other nodes, which should not be able to make us
+/// allocate arbitrary amounts of memory.
+pub const MAX_DECOMPRESSED_SIZE: usize = 1 << 28;
+
+/// The compression algorithms available in this build
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionCodec {
+    /// Gzip, always available, and understood by all versions of `LibAFL`
+    #[default]
+    Gzip,
+    /// Zstd, a good tradeoff between speed and compression ratio
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// Lz4, the fastest codec, at the cost of a worse compression ratio
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl CompressionCodec {
+    /// Compress `buf` with this codec
+    #[must_use]
+    pub fn compress(self, buf: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Gzip => GzipCompressor::new().compress(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => ZstdCompressor::new().compress(buf),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Lz4Compressor::new().compress(buf),
+        }
+    }
+
+    /// Decompress `buf`, which was compressed with this codec
+    pub fn decompress(self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Gzip => GzipCompressor::new().decompress(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => ZstdCompressor::new().decompress(buf),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Lz4Compressor::new().decompress(buf),
+        }
+    }
+}
+
+/// A compression algorithm, with a threshold below which buffers are not worth compressing.
+pub trait Compressor: Debug {
+    /// The codec of this compressor, to tell the receiver how to decompress.
+    fn codec(&self) -> CompressionCodec;
+
+    /// Buffers smaller than this are not compressed by [`Compressor::maybe_compress`].
+    fn threshold(&self) -> usize;
+
+    /// Force compression.
+    /// Will ignore the threshold, and always compress.
+    fn compress(&self, buf: &[u8]) -> Vec<u8>;
+
+    /// Decompression.
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Compression.
+    /// If the buffer is smaller than the threshold of this compressor, `None` will be returned.
+    /// Else, the buffer is compressed.
+    fn maybe_compress(&self, buf: &[u8]) -> Option<Vec<u8>> {
+        if buf.len() >= self.threshold() {
+            Some(self.compress(buf))
+        } else {
+            None
+        }
+    }
+}
+
 /// Compression for your stream compression needs.
 #[derive(Debug)]
 pub struct GzipCompressor {
@@ -61,8 +139,14 @@ impl GzipCompressor {
     }
 
     /// Decompression.
+    /// Fails if the buffer decompresses to more than [`MAX_DECOMPRESSED_SIZE`] bytes.
     pub fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
-        let decompressed = decompress_to_vec(buf);
+        Self::decompress_with_limit(buf, MAX_DECOMPRESSED_SIZE)
+    }
+
+    /// Decompression, failing if the buffer decompresses to more than `limit` bytes.
+    fn decompress_with_limit(buf: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+        let decompressed = decompress_to_vec_with_limit(buf, limit);
 
         match decompressed {
             Ok(buf) => Ok(buf),
@@ -71,9 +155,179 @@ impl GzipCompressor {
     }
 }
 
+impl Compressor for GzipCompressor {
+    fn codec(&self) -> CompressionCodec {
+        CompressionCodec::Gzip
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn compress(&self, buf: &[u8]) -> Vec<u8> {
+        GzipCompressor::compress(self, buf)
+    }
+
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        GzipCompressor::decompress(self, buf)
+    }
+}
+
+/// A [`Compressor`] using zstd
+#[cfg(feature = "zstd")]
+#[derive(Debug, Default)]
+pub struct ZstdCompressor {
+    /// If less bytes than threshold are being passed to `maybe_compress`, the payload is not getting compressed.
+    threshold: usize,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCompressor {
+    /// If the buffer is at least as large as the `threshold` value, we compress the buffer.
+    #[must_use]
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self { threshold }
+    }
+
+    /// Create a [`ZstdCompressor`] that will always compress
+    #[must_use]
+    pub fn new() -> Self {
+        Self { threshold: 0 }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Compressor for ZstdCompressor {
+    fn codec(&self) -> CompressionCodec {
+        CompressionCodec::Zstd
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn compress(&self, buf: &[u8]) -> Vec<u8> {
+        // Compressing to a growable buffer only fails for invalid parameters.
+        zstd::stream::encode_all(buf, ZSTD_LEVEL).expect("Zstd compression failed")
+    }
+
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut decompressed = Vec::new();
+        zstd::stream::read::Decoder::new(buf)
+            .and_then(|decoder| {
+                decoder
+                    .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+                    .read_to_end(&mut decompressed)
+            })
+            .map_err(|_| Error::compression())?;
+        if decompressed.len() > MAX_DECOMPRESSED_SIZE {
+            return Err(Error::compression());
+        }
+        Ok(decompressed)
+    }
+}
+
+/// A [`Compressor`] using lz4
+#[cfg(feature = "lz4")]
+#[derive(Debug, Default)]
+pub struct Lz4Compressor {
+    /// If less bytes than threshold are being passed to `maybe_compress`, the payload is not getting compressed.
+    threshold: usize,
+}
+
+#[cfg(feature = "lz4")]
+impl Lz4Compressor {
+    /// If the buffer is at least as large as the `threshold` value, we compress the buffer.
+    #[must_use]
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self { threshold }
+    }
+
+    /// Create a [`Lz4Compressor`] that will always compress
+    #[must_use]
+    pub fn new() -> Self {
+        Self { threshold: 0 }
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl Compressor for Lz4Compressor {
+    fn codec(&self) -> CompressionCodec {
+        CompressionCodec::Lz4
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn compress(&self, buf: &[u8]) -> Vec<u8> {
+        lz4_flex::compress_prepend_size(buf)
+    }
+
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        // The size is prepended by the sender, don't allocate whatever it claims
+        let Some(size) = buf.first_chunk::<4>() else {
+            return Err(Error::compression());
+        };
+        if u32::from_le_bytes(*size) as usize > MAX_DECOMPRESSED_SIZE {
+            return Err(Error::compression());
+        }
+        lz4_flex::decompress_size_prepended(buf).map_err(|_| Error::compression())
+    }
+}
+
+/// A [`Compressor`] for a [`CompressionCodec`] picked at runtime.
+///
+/// Receivers should decompress with the codec the sender announced,
+/// using [`CompressionCodec::decompress`], not with their own codec.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CodecCompressor {
+    codec: CompressionCodec,
+    threshold: usize,
+}
+
+impl CodecCompressor {
+    /// Compress buffers of at least `threshold` bytes using `codec`
+    #[must_use]
+    pub fn with_threshold(codec: CompressionCodec, threshold: usize) -> Self {
+        Self { codec, threshold }
+    }
+
+    /// Create a [`CodecCompressor`] using `codec` that will always compress
+    #[must_use]
+    pub fn new(codec: CompressionCodec) -> Self {
+        Self::with_threshold(codec, 0)
+    }
+
+    /// Change the codec used for compression
+    pub fn set_codec(&mut self, codec: CompressionCodec) {
+        self.codec = codec;
+    }
+}
+
+impl Compressor for CodecCompressor {
+    fn codec(&self) -> CompressionCodec {
+        self.codec
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn compress(&self, buf: &[u8]) -> Vec<u8> {
+        self.codec.compress(buf)
+    }
+
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        self.codec.decompress(buf)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::compress::GzipCompressor;
+    use alloc::vec::Vec;
+
+    use crate::compress::{CodecCompressor, CompressionCodec, Compressor, GzipCompressor};
 
     #[test]
     fn test_compression() {
@@ -92,4 +346,46 @@ mod tests {
         assert!(compressor.maybe_compress(&[1u8; 1023]).is_none());
         assert!(compressor.maybe_compress(&[1u8; 1024]).is_some());
     }
+
+    #[test]
+    fn test_codecs() {
+        let codecs = [
+            CompressionCodec::Gzip,
+            #[cfg(feature = "zstd")]
+            CompressionCodec::Zstd,
+            #[cfg(feature = "lz4")]
+            CompressionCodec::Lz4,
+        ];
+
+        let buf: Vec<u8> = (0..4096_u32).map(|i| (i % 7) as u8).collect();
+        for codec in codecs {
+            let compressor = CodecCompressor::with_threshold(codec, 1024);
+            assert!(compressor.maybe_compress(&buf[..1023]).is_none());
+            let compressed = compressor.maybe_compress(&buf).unwrap();
+            assert!(compressed.len() < buf.len());
+            assert_eq!(codec.decompress(&compressed).unwrap(), buf);
+        }
+    }
+
+    #[test]
+    fn test_gzip_size_limit() {
+        let compressor = GzipCompressor::new();
+        let compressed = compressor.compress(&[0; 1025]);
+        assert!(GzipCompressor::decompress_with_limit(&compressed, 1024).is_err());
+        assert_eq!(
+            GzipCompressor::decompress_with_limit(&compressed, 1025).unwrap(),
+            [0; 1025]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_lz4_size_limit() {
+        use crate::compress::MAX_DECOMPRESSED_SIZE;
+
+        let mut compressed = CompressionCodec::Lz4.compress(&[1; 1024]);
+        let size = u32::try_from(MAX_DECOMPRESSED_SIZE + 1).unwrap();
+        compressed[..4].copy_from_slice(&size.to_le_bytes());
+        assert!(CompressionCodec::Lz4.decompress(&compressed).is_err());
+    }
 }
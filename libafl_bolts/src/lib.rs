@@ -103,6 +103,8 @@ pub mod os;
 #[cfg(feature = "alloc")]
 pub mod ownedref;
 pub mod rands;
+#[cfg(feature = "llmp_encryption")]
+pub mod secure_link;
 #[cfg(feature = "alloc")]
 pub mod serdeany;
 pub mod shmem;
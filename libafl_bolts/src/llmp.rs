@@ -60,7 +60,7 @@ Check out the `llmp_test` example in ./examples, or build it with `cargo run --e
 #[cfg(feature = "std")]
 use alloc::string::ToString;
 use alloc::{string::String, vec::Vec};
-#[cfg(not(target_pointer_width = "64"))]
+#[cfg(any(not(target_pointer_width = "64"), feature = "std"))]
 use core::sync::atomic::AtomicU32;
 #[cfg(target_pointer_width = "64")]
 use core::sync::atomic::AtomicU64;
@@ -81,25 +81,33 @@ use std::{
     env,
     io::{ErrorKind, Read, Write},
     net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
-    sync::mpsc::channel,
+    sync::{mpsc::channel, Arc},
     thread,
 };
+#[cfg(feature = "llmp_encryption")]
+use std::{net::IpAddr, sync::RwLock};
 
 #[cfg(all(debug_assertions, feature = "llmp_debug", feature = "std"))]
 use backtrace::Backtrace;
 #[cfg(all(unix, feature = "std"))]
 #[cfg(not(any(target_os = "solaris", target_os = "illumos")))]
 use nix::sys::socket::{self, sockopt::ReusePort};
+#[cfg(feature = "std")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
 use tuple_list::tuple_list;
 
+#[cfg(feature = "gzip")]
+use crate::compress::CompressionCodec;
 #[cfg(all(unix, not(miri)))]
 use crate::os::unix_signals::setup_signal_handler;
 #[cfg(unix)]
 use crate::os::unix_signals::{siginfo_t, ucontext_t, Signal, SignalHandler};
 #[cfg(all(windows, feature = "std"))]
 use crate::os::windows_exceptions::{setup_ctrl_handler, CtrlHandler};
+#[cfg(feature = "llmp_encryption")]
+use crate::secure_link::{LinkInitiator, LinkKey, LinkResponder, SecureChannel};
 #[cfg(feature = "std")]
 use crate::{current_time, IP_LOCALHOST};
 use crate::{
@@ -145,11 +153,30 @@ pub const LLMP_FLAG_COMPRESSED: Flags = Flags(0x1);
 pub const LLMP_FLAG_FROM_B2B: Flags = Flags(0x2);
 /// From another machine (with the `multi_machine` mode)
 pub const LLMP_FLAG_FROM_MM: Flags = Flags(0x4);
+/// Together with [`LLMP_FLAG_COMPRESSED`], this message was compressed using zstd instead of gzip
+pub const LLMP_FLAG_ZSTD: Flags = Flags(0x8);
+/// Together with [`LLMP_FLAG_COMPRESSED`], this message was compressed using lz4 instead of gzip
+pub const LLMP_FLAG_LZ4: Flags = Flags(0x10);
+/// The flags naming the codec of a compressed message, other than gzip
+const LLMP_CODEC_FLAGS: Flags = Flags(LLMP_FLAG_ZSTD.0 | LLMP_FLAG_LZ4.0);
+/// The codecs of a peer that did not announce any, see [`Flags::supported_codecs`].
+/// Older versions only decompress gzip.
+#[cfg(feature = "std")]
+const LLMP_LEGACY_CODECS: Flags = LLMP_FLAG_COMPRESSED;
 
 /// Timt the broker 2 broker connection waits for incoming data,
 /// before checking for own data to forward again.
 const _LLMP_B2B_BLOCK_TIME: Duration = Duration::from_millis(3_000);
 
+/// Time a new tcp connection has to complete its hello (and the `secure_link` handshake),
+/// so that a stalled peer cannot block the listener.
+#[cfg(feature = "std")]
+const LLMP_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest message accepted from a peer before it is authenticated
+#[cfg(feature = "std")]
+const LLMP_HANDSHAKE_MAX_MSG_SIZE: usize = 1 << 16;
+
 /// If broker2broker is enabled, bind to public IP
 #[cfg(feature = "llmp_bind_public")]
 const _LLMP_BIND_ADDR: &str = "0.0.0.0";
@@ -208,6 +235,12 @@ impl Debug for Flags {
         if *self & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
             f.write_str("COMPRESSED")?;
         }
+        if *self & LLMP_FLAG_ZSTD == LLMP_FLAG_ZSTD {
+            f.write_str("ZSTD")?;
+        }
+        if *self & LLMP_FLAG_LZ4 == LLMP_FLAG_LZ4 {
+            f.write_str("LZ4")?;
+        }
         if *self & LLMP_FLAG_FROM_B2B == LLMP_FLAG_FROM_B2B {
             f.write_str("FROM_B2B")?;
         }
@@ -215,6 +248,85 @@ impl Debug for Flags {
     }
 }
 
+impl Flags {
+    /// The codecs this build can decompress: [`LLMP_FLAG_COMPRESSED`] for gzip,
+    /// together with the flag of each other enabled codec.
+    ///
+    /// Peers append them to their handshake, and only send each other messages they can decompress.
+    #[must_use]
+    pub fn supported_codecs() -> Self {
+        let codecs = LLMP_FLAG_INITIALIZED;
+        #[cfg(feature = "gzip")]
+        let codecs = codecs | LLMP_FLAG_COMPRESSED;
+        #[cfg(feature = "zstd")]
+        let codecs = codecs | LLMP_FLAG_ZSTD;
+        #[cfg(feature = "lz4")]
+        let codecs = codecs | LLMP_FLAG_LZ4;
+        codecs
+    }
+
+    /// If a peer that decompresses `codecs` (see [`Flags::supported_codecs`]) can read a message with these flags
+    #[must_use]
+    pub fn readable_with(self, codecs: Self) -> bool {
+        self & LLMP_FLAG_COMPRESSED != LLMP_FLAG_COMPRESSED
+            || self & LLMP_CODEC_FLAGS & !codecs == LLMP_FLAG_INITIALIZED
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Flags {
+    /// The codec to compress messages with, for receivers decompressing these codecs
+    /// (see [`Flags::supported_codecs`]): `preferred` if they can, else gzip, which all versions can.
+    #[must_use]
+    pub fn pick_codec(self, preferred: CompressionCodec) -> CompressionCodec {
+        if Self::compressed_with(preferred).readable_with(self) {
+            preferred
+        } else {
+            CompressionCodec::Gzip
+        }
+    }
+
+    /// The flags for a message compressed with `codec`
+    #[must_use]
+    pub fn compressed_with(codec: CompressionCodec) -> Self {
+        match codec {
+            CompressionCodec::Gzip => LLMP_FLAG_COMPRESSED,
+            #[cfg(feature = "zstd")]
+            CompressionCodec::Zstd => LLMP_FLAG_COMPRESSED | LLMP_FLAG_ZSTD,
+            #[cfg(feature = "lz4")]
+            CompressionCodec::Lz4 => LLMP_FLAG_COMPRESSED | LLMP_FLAG_LZ4,
+        }
+    }
+
+    /// The codec a message with these flags was compressed with, or `None` if it is not compressed.
+    ///
+    /// Messages only flagged as [`LLMP_FLAG_COMPRESSED`] are gzip compressed, like all compressed
+    /// messages sent by older versions.
+    /// Fails if the sender used a codec that is not enabled in this build.
+    pub fn compression_codec(self) -> Result<Option<CompressionCodec>, Error> {
+        if self & LLMP_FLAG_COMPRESSED != LLMP_FLAG_COMPRESSED {
+            return Ok(None);
+        }
+        if self & LLMP_FLAG_ZSTD == LLMP_FLAG_ZSTD {
+            #[cfg(feature = "zstd")]
+            return Ok(Some(CompressionCodec::Zstd));
+            #[cfg(not(feature = "zstd"))]
+            return Err(Error::unsupported(
+                "Received a zstd compressed message, but the `zstd` feature is not enabled",
+            ));
+        }
+        if self & LLMP_FLAG_LZ4 == LLMP_FLAG_LZ4 {
+            #[cfg(feature = "lz4")]
+            return Ok(Some(CompressionCodec::Lz4));
+            #[cfg(not(feature = "lz4"))]
+            return Err(Error::unsupported(
+                "Received a lz4 compressed message, but the `lz4` feature is not enabled",
+            ));
+        }
+        Ok(Some(CompressionCodec::Gzip))
+    }
+}
+
 impl BitAnd for Flags {
     type Output = Self;
 
@@ -271,6 +383,17 @@ pub enum TcpRequest {
         /// Tell the broker that remove the client with this `client_id`. `client_id` is equal to the one of event restarter
         client_id: ClientId,
     },
+    /// We would like to establish an authenticated, encrypted, b2b connection.
+    /// All following messages are encrypted, once the handshake succeeded.
+    SecureLinkHello {
+        /// The hello of the `secure_link` handshake
+        hello: Vec<u8>,
+    },
+    /// The final message of the `secure_link` handshake, proving that we know the pre-shared key.
+    SecureLinkFinish {
+        /// The proof of the `secure_link` handshake
+        finish: Vec<u8>,
+    },
 }
 
 impl TryFrom<&Vec<u8>> for TcpRequest {
@@ -344,6 +467,11 @@ pub enum TcpResponse {
         /// Error description
         description: String,
     },
+    /// The answer to a [`TcpRequest::SecureLinkHello`], proving that we know the pre-shared key.
+    SecureLinkChallenge {
+        /// The challenge of the `secure_link` handshake
+        challenge: Vec<u8>,
+    },
 }
 
 impl TryFrom<&Vec<u8>> for TcpResponse {
@@ -460,6 +588,17 @@ fn tcp_bind(port: u16) -> Result<TcpListener, Error> {
     Ok(listener)
 }
 
+/// If the peer connected from this machine, also for IPv4-mapped IPv6 addresses
+#[cfg(feature = "llmp_encryption")]
+fn is_loopback(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback(),
+        IpAddr::V6(ip) => {
+            ip.is_loopback() || ip.to_ipv4_mapped().is_some_and(|ip| ip.is_loopback())
+        }
+    }
+}
+
 /// Send one message as `u32` len and `[u8;len]` bytes
 #[cfg(feature = "std")]
 pub fn send_tcp_msg<T>(stream: &mut TcpStream, msg: &T) -> Result<(), Error>
@@ -490,6 +629,12 @@ where
 /// Receive one message of `u32` len and `[u8; len]` bytes
 #[cfg(feature = "std")]
 pub fn recv_tcp_msg(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
+    recv_tcp_msg_capped(stream, usize::MAX)
+}
+
+/// Receive one message of `u32` len and `[u8; len]` bytes, failing if it is larger than `max_size`
+#[cfg(feature = "std")]
+fn recv_tcp_msg_capped(stream: &mut TcpStream, max_size: usize) -> Result<Vec<u8>, Error> {
     // Always receive one be u32 of size, then the command.
 
     #[cfg(feature = "llmp_debug")]
@@ -500,18 +645,135 @@ pub fn recv_tcp_msg(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
 
     let mut size_bytes = [0_u8; 4];
     stream.read_exact(&mut size_bytes)?;
-    let size = u32::from_be_bytes(size_bytes);
-    let mut bytes = vec![0; size.try_into().unwrap()];
+    let size = u32::from_be_bytes(size_bytes) as usize;
+    if size > max_size {
+        return Err(Error::illegal_state(format!(
+            "Received a tcp message of {size} bytes, the limit is {max_size} bytes"
+        )));
+    }
+    let mut bytes = vec![0; size];
 
     #[cfg(feature = "llmp_debug")]
     log::trace!("LLMP TCP: Receiving payload of size {size}");
 
-    stream
-        .read_exact(&mut bytes)
-        .expect("Failed to read message body");
+    stream.read_exact(&mut bytes)?;
     Ok(bytes)
 }
 
+/// Deserializes a handshake message, and the codecs the peer appended to it (see [`Flags::supported_codecs`]).
+/// Older peers neither append codecs, nor read them, as postcard ignores trailing bytes.
+#[cfg(feature = "std")]
+fn split_hello<T>(buf: &[u8]) -> Result<(T, Flags), Error>
+where
+    T: DeserializeOwned,
+{
+    let (msg, codecs) = postcard::take_from_bytes(buf)?;
+    Ok((
+        msg,
+        postcard::from_bytes(codecs).unwrap_or(LLMP_LEGACY_CODECS),
+    ))
+}
+
+/// Recompresses a message with gzip, if it was compressed with a codec
+/// that a receiver decompressing `codecs` can not read.
+/// Returns `None` if the message can be forwarded as is.
+#[cfg(feature = "std")]
+fn transcode_for(
+    codecs: Flags,
+    flags: Flags,
+    payload: &[u8],
+) -> Result<Option<(Flags, Vec<u8>)>, Error> {
+    if flags.readable_with(codecs) {
+        return Ok(None);
+    }
+    #[cfg(feature = "gzip")]
+    {
+        let Some(codec) = flags.compression_codec()? else {
+            return Ok(None);
+        };
+        let payload = CompressionCodec::Gzip.compress(&codec.decompress(payload)?);
+        Ok(Some((flags & !LLMP_CODEC_FLAGS, payload)))
+    }
+    #[cfg(not(feature = "gzip"))]
+    {
+        let _ = payload;
+        Err(Error::unsupported(
+            "Can not recompress a message without the `gzip` feature",
+        ))
+    }
+}
+
+/// Send one message, encrypted by the given [`SecureChannel`]
+#[cfg(feature = "llmp_encryption")]
+pub fn send_secure_tcp_msg<T>(
+    stream: &mut TcpStream,
+    channel: &mut SecureChannel,
+    msg: &T,
+) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    let sealed = channel.seal(&postcard::to_allocvec(msg)?)?;
+    send_tcp_msg(stream, &sealed)
+}
+
+/// Receive one message, encrypted by the given [`SecureChannel`]
+#[cfg(feature = "llmp_encryption")]
+pub fn recv_secure_tcp_msg(
+    stream: &mut TcpStream,
+    channel: &mut SecureChannel,
+) -> Result<Vec<u8>, Error> {
+    let sealed: Vec<u8> = postcard::from_bytes(&recv_tcp_msg(stream)?)?;
+    channel.open(&sealed)
+}
+
+/// A broker 2 broker connection, encrypted if a [`LinkKey`] was configured.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct LinkStream {
+    stream: TcpStream,
+    #[cfg(feature = "llmp_encryption")]
+    channel: Option<SecureChannel>,
+}
+
+#[cfg(feature = "std")]
+impl LinkStream {
+    fn plain(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            #[cfg(feature = "llmp_encryption")]
+            channel: None,
+        }
+    }
+
+    #[cfg(feature = "llmp_encryption")]
+    fn secure(stream: TcpStream, channel: SecureChannel) -> Self {
+        Self {
+            stream,
+            channel: Some(channel),
+        }
+    }
+
+    fn send<T>(&mut self, msg: &T) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        #[cfg(feature = "llmp_encryption")]
+        if let Some(channel) = &mut self.channel {
+            return send_secure_tcp_msg(&mut self.stream, channel, msg);
+        }
+        send_tcp_msg(&mut self.stream, msg)
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "llmp_encryption")]
+        if let Some(channel) = &mut self.channel {
+            return recv_secure_tcp_msg(&mut self.stream, channel);
+        }
+        recv_tcp_msg(&mut self.stream)
+    }
+}
+
 /// In case we don't have enough space, make sure the next page will be large
 /// enough. For now, we want to have at least enough space to store 2 of the
 /// largest messages we encountered (plus message one `new_page` message).
@@ -2074,6 +2336,14 @@ pub struct LlmpBrokerInner<SHM, SP> {
     clients_to_remove: Vec<ClientId>,
     /// The `ShMemProvider` to use
     shmem_provider: SP,
+    /// The key authenticating and encrypting broker 2 broker connections, shared with the listener thread
+    #[cfg(feature = "llmp_encryption")]
+    link_key: Arc<RwLock<Option<LinkKey>>>,
+    /// The codecs all local clients can decompress, see [`Flags::supported_codecs`].
+    /// Messages compressed with other codecs are recompressed with gzip when forwarded.
+    /// Shared with the listener thread, which narrows them down for each client attaching.
+    #[cfg(feature = "std")]
+    page_codecs: Arc<AtomicU32>,
 }
 
 /// The broker (node 0)
@@ -2491,6 +2761,16 @@ where
         &mut self.inner
     }
 
+    /// Get the hooks of the broker
+    pub fn hooks(&self) -> &HT {
+        &self.hooks
+    }
+
+    /// Get the hooks of the broker, mutably
+    pub fn hooks_mut(&mut self) -> &mut HT {
+        &mut self.hooks
+    }
+
     /// Loops unitl the last client quit,
     /// forwarding and handling all incoming messages from clients.
     /// 5 millis of sleep can't hurt to keep busywait not at 100%
@@ -2633,6 +2913,41 @@ where
         Ok(new_messages)
     }
 
+    /// Runs a message through the hooks as if client `client_id` had sent it, and broadcasts it
+    /// to the clients if the hooks let it through.
+    ///
+    /// This is useful to replay recorded traffic into a fresh broker.
+    pub fn inject_msg(
+        &mut self,
+        client_id: ClientId,
+        mut tag: Tag,
+        mut flags: Flags,
+        msg: &[u8],
+    ) -> Result<LlmpMsgHookResult, Error> {
+        let mut msg_buf = msg.to_vec();
+        let mut new_msgs: Vec<(Tag, Flags, Vec<u8>)> = Vec::new();
+        let res = self.hooks.on_new_message_all(
+            &mut self.inner,
+            client_id,
+            &mut tag,
+            &mut flags,
+            &mut msg_buf,
+            &mut new_msgs,
+        )?;
+        if let LlmpMsgHookResult::ForwardToClients = res {
+            self.inner
+                .llmp_out
+                .send_buf_with_flags(tag, flags, &msg_buf)?;
+        }
+
+        for (new_msg_tag, new_msg_flag, new_msg) in new_msgs {
+            self.inner
+                .llmp_out
+                .send_buf_with_flags(new_msg_tag, new_msg_flag, new_msg.as_ref())?;
+        }
+        Ok(res)
+    }
+
     /// Broker broadcast to its own page for all others to read
     /// Returns `true` if new messages were broker-ed
     /// It is supposed that the message is never unmapped.
@@ -2779,6 +3094,10 @@ where
 
                     log::debug!("New msg vector: {}", new_msgs.len());
                     for (new_msg_tag, new_msg_flag, new_msg) in new_msgs {
+                        #[cfg(feature = "std")]
+                        let (new_msg_flag, new_msg) =
+                            transcode_for(self.inner.page_codecs(), new_msg_flag, &new_msg)?
+                                .unwrap_or((new_msg_flag, new_msg));
                         self.inner.llmp_out.send_buf_with_flags(
                             new_msg_tag,
                             new_msg_flag,
@@ -2849,9 +3168,23 @@ where
             exit_cleanly_after: None,
             num_clients_seen: 0,
             shmem_provider,
+            #[cfg(feature = "llmp_encryption")]
+            link_key: Arc::new(RwLock::new(None)),
+            #[cfg(feature = "std")]
+            page_codecs: Arc::new(AtomicU32::new(Flags::supported_codecs().0)),
         })
     }
 
+    /// Authenticate and encrypt all broker 2 broker connections with the given pre-shared key,
+    /// see [`crate::secure_link`].
+    ///
+    /// Once set, this broker only accepts remote brokers knowing the key,
+    /// and only accepts local clients from the loopback interface.
+    #[cfg(feature = "llmp_encryption")]
+    pub fn set_link_key(&mut self, link_key: LinkKey) {
+        *self.link_key.write().unwrap() = Some(link_key);
+    }
+
     /// Gets the [`ClientId`] the next client attaching to this broker will get.
     /// In its current implementation, the inner value of the next [`ClientId`]
     /// is equal to `self.num_clients_seen`.
@@ -2946,8 +3279,10 @@ where
     {
         let mut stream = TcpStream::connect(addr)?;
         log::info!("B2B: Connected to {stream:?}");
+        stream.set_read_timeout(Some(LLMP_HANDSHAKE_TIMEOUT))?;
+        stream.set_write_timeout(Some(LLMP_HANDSHAKE_TIMEOUT))?;
 
-        match recv_tcp_msg(&mut stream)?.try_into()? {
+        match recv_tcp_msg_capped(&mut stream, LLMP_HANDSHAKE_MAX_MSG_SIZE)?.try_into()? {
             TcpResponse::BrokerConnectHello {
                 broker_shmem_description: _,
                 hostname,
@@ -2964,12 +3299,20 @@ where
             .to_string_lossy()
             .into();
 
-        send_tcp_msg(&mut stream, &TcpRequest::RemoteBrokerHello { hostname })?;
+        #[cfg(feature = "llmp_encryption")]
+        let mut stream = self.b2b_secure_connect(stream)?;
+        #[cfg(not(feature = "llmp_encryption"))]
+        let mut stream = LinkStream::plain(stream);
+
+        stream.send(&(
+            TcpRequest::RemoteBrokerHello { hostname },
+            Flags::supported_codecs(),
+        ))?;
 
-        let broker_id = match recv_tcp_msg(&mut stream)?.try_into()? {
-            TcpResponse::RemoteBrokerAccepted { broker_id } => {
-                log::info!("B2B: Got Connection Ack, broker_id {broker_id:?}");
-                broker_id
+        let (broker_id, codecs) = match split_hello(&stream.recv()?)? {
+            (TcpResponse::RemoteBrokerAccepted { broker_id }, codecs) => {
+                log::info!("B2B: Got Connection Ack, broker_id {broker_id:?}, codecs {codecs:?}");
+                (broker_id, codecs)
             }
             _ => {
                 return Err(Error::illegal_state(
@@ -2980,6 +3323,7 @@ where
 
         // TODO: use broker ids!
         log::info!("B2B: We are broker {broker_id:?}");
+        stream.stream.set_write_timeout(None)?;
 
         // TODO: handle broker_ids properly/at all.
         let map_description = Self::b2b_thread_on(
@@ -2992,6 +3336,7 @@ where
                 .unwrap()
                 .shmem
                 .description(),
+            codecs,
         )?;
 
         let new_shmem = LlmpSharedMap::existing(
@@ -3006,8 +3351,28 @@ where
         Ok(())
     }
 
+    /// The codecs all local clients can decompress, see [`Flags::supported_codecs`]
+    #[cfg(feature = "std")]
+    fn page_codecs(&self) -> Flags {
+        Flags(self.page_codecs.load(Ordering::Relaxed))
+    }
+
     /// For internal use: Forward the current message to the out map.
     unsafe fn forward_msg(&mut self, msg: *mut LlmpMsg) -> Result<(), Error> {
+        // Make sure all clients can read it
+        #[cfg(feature = "std")]
+        match transcode_for(self.page_codecs(), (*msg).flags, (*msg).as_slice_unsafe()) {
+            Ok(None) => (),
+            Ok(Some((flags, buf))) => return self.forward_msg_with(msg, flags, &buf),
+            Err(e) => {
+                log::warn!(
+                    "Dropping a message ({:?}) not all clients can decompress: {e}",
+                    (*msg).tag
+                );
+                return Ok(());
+            }
+        }
+
         let out: *mut LlmpMsg = self.alloc_next((*msg).buf_len_padded as usize)?;
 
         /* Copy over the whole message.
@@ -3025,6 +3390,27 @@ where
         Ok(())
     }
 
+    /// For internal use: Forward the current message to the out map, with another payload.
+    #[cfg(feature = "std")]
+    unsafe fn forward_msg_with(
+        &mut self,
+        msg: *const LlmpMsg,
+        flags: Flags,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        let out: *mut LlmpMsg = self.alloc_next(buf.len())?;
+        (*out).tag = (*msg).tag;
+        (*out).sender = (*msg).sender;
+        (*out).broker = (*msg).broker;
+        (*out).flags = flags;
+        buf.as_ptr()
+            .copy_to_nonoverlapping((*out).buf.as_mut_ptr(), buf.len());
+        if let Err(e) = self.llmp_out.send(out, false) {
+            panic!("Error sending msg: {e:?}");
+        }
+        Ok(())
+    }
+
     /// Internal function, returns true when shuttdown is requested by a `SIGINT` signal
     #[inline]
     #[cfg(any(unix, all(windows, feature = "std")))]
@@ -3110,17 +3496,125 @@ where
         }
     }
 
+    /// Runs the `secure_link` handshake on a new broker 2 broker connection, if a [`LinkKey`] is set.
+    #[cfg(feature = "llmp_encryption")]
+    fn b2b_secure_connect(&self, mut stream: TcpStream) -> Result<LinkStream, Error> {
+        let Some(link_key) = self.link_key.read().unwrap().clone() else {
+            return Ok(LinkStream::plain(stream));
+        };
+        let peer_address = stream.peer_addr()?;
+
+        let initiator = LinkInitiator::new(&link_key)?;
+        send_tcp_msg(
+            &mut stream,
+            &TcpRequest::SecureLinkHello {
+                hello: initiator.hello().to_vec(),
+            },
+        )?;
+        let challenge = match recv_tcp_msg_capped(&mut stream, LLMP_HANDSHAKE_MAX_MSG_SIZE)?
+            .try_into()?
+        {
+            TcpResponse::SecureLinkChallenge { challenge } => challenge,
+            TcpResponse::Error { description } => {
+                return Err(Error::illegal_state(format!(
+                    "B2B: Broker {peer_address} refused the secure link: {description}"
+                )));
+            }
+            _ => {
+                return Err(Error::illegal_state(format!(
+                    "B2B: Unexpected response from broker {peer_address} during the secure link handshake"
+                )));
+            }
+        };
+        let (finish, channel) = initiator.finish(&challenge)?;
+        send_tcp_msg(
+            &mut stream,
+            &TcpRequest::SecureLinkFinish {
+                finish: finish.to_vec(),
+            },
+        )?;
+
+        log::info!("B2B: Established a secure link to {peer_address}");
+        Ok(LinkStream::secure(stream, channel))
+    }
+
+    /// Checks a new connection to the listener against the [`LinkKey`], if one is set.
+    ///
+    /// Runs the `secure_link` handshake if requested, and refuses unauthenticated remote brokers
+    /// and unauthenticated clients from other machines.
+    /// Returns the (maybe encrypted) stream, and the first request sent over it with the codecs of the peer.
+    #[cfg(feature = "llmp_encryption")]
+    fn accept_secure_link(
+        mut stream: TcpStream,
+        addr: SocketAddr,
+        request: TcpRequest,
+        codecs: Flags,
+        link_key: &RwLock<Option<LinkKey>>,
+    ) -> Result<(LinkStream, TcpRequest, Flags), Error> {
+        let link_key = link_key.read().unwrap().clone();
+        match (request, link_key) {
+            (TcpRequest::SecureLinkHello { hello }, Some(link_key)) => {
+                let responder = LinkResponder::new(&link_key, &hello)?;
+                send_tcp_msg(
+                    &mut stream,
+                    &TcpResponse::SecureLinkChallenge {
+                        challenge: responder.challenge().to_vec(),
+                    },
+                )?;
+                let TcpRequest::SecureLinkFinish { finish } =
+                    recv_tcp_msg_capped(&mut stream, LLMP_HANDSHAKE_MAX_MSG_SIZE)?.try_into()?
+                else {
+                    return Err(Error::illegal_state(
+                        "Secure link handshake failed: unexpected request",
+                    ));
+                };
+                let mut stream = LinkStream::secure(stream, responder.finish(&finish)?);
+                let (request, codecs) = split_hello(&stream.recv()?)?;
+                log::info!("B2B: Established a secure link with {addr}");
+                Ok((stream, request, codecs))
+            }
+            (TcpRequest::SecureLinkHello { .. }, None) => {
+                let _ = send_tcp_msg(
+                    &mut stream,
+                    &TcpResponse::Error {
+                        description: "No link key is configured on this broker".into(),
+                    },
+                );
+                Err(Error::illegal_state(format!(
+                    "{addr} requested a secure link, but no link key is configured"
+                )))
+            }
+            (request, Some(_))
+                if matches!(request, TcpRequest::RemoteBrokerHello { .. })
+                    || !is_loopback(addr.ip()) =>
+            {
+                let _ = send_tcp_msg(
+                    &mut stream,
+                    &TcpResponse::Error {
+                        description: "This broker only accepts secure links".into(),
+                    },
+                );
+                Err(Error::illegal_state(format!(
+                    "Refused an unauthenticated connection from {addr}"
+                )))
+            }
+            (request, _) => Ok((LinkStream::plain(stream), request, codecs)),
+        }
+    }
+
     /// For broker to broker connections:
     /// Launches a proxy thread.
     /// It will read outgoing messages from the given broker map (and handle EOP by mapping a new page).
     /// This function returns the [`ShMemDescription`] the client uses to place incoming messages.
+    /// Messages the remote broker can not decompress with its `codecs` are recompressed with gzip.
     /// The thread exits, when the remote broker disconnects.
     #[cfg(feature = "std")]
     #[expect(clippy::too_many_lines)]
     fn b2b_thread_on(
-        mut stream: TcpStream,
+        mut stream: LinkStream,
         b2b_client_id: ClientId,
         broker_shmem_description: &ShMemDescription,
+        codecs: Flags,
     ) -> Result<ShMemDescription, Error> {
         let broker_shmem_description = *broker_shmem_description;
 
@@ -3137,6 +3631,7 @@ where
 
             // The background thread blocks on the incoming connection for 15 seconds (if no data is available), then checks if it should forward own messages, then blocks some more.
             stream
+                .stream
                 .set_read_timeout(Some(_LLMP_B2B_BLOCK_TIME))
                 .expect("Failed to set tcp stream timeout");
 
@@ -3164,7 +3659,7 @@ where
             #[cfg(feature = "llmp_debug")]
             log::info!("B2B: Starting proxy loop :)");
 
-            let peer_address = stream.peer_addr().unwrap();
+            let peer_address = stream.stream.peer_addr().unwrap();
 
             loop {
                 // first, forward all data we have.
@@ -3184,16 +3679,21 @@ where
                                 "Fowarding message ({} bytes) via broker2broker connection",
                                 payload.len()
                             );
+                            let (flags, payload) = match transcode_for(codecs, flags, payload) {
+                                Ok(Some(transcoded)) => transcoded,
+                                Ok(None) => (flags, payload.to_vec()),
+                                Err(e) => {
+                                    log::warn!("Not forwarding a message ({tag:?}) broker {peer_address} can not decompress: {e}");
+                                    continue;
+                                }
+                            };
                             // We got a new message! Forward...
-                            if let Err(e) = send_tcp_msg(
-                                &mut stream,
-                                &TcpRemoteNewMessage {
-                                    client_id,
-                                    tag,
-                                    flags,
-                                    payload: payload.to_vec(),
-                                },
-                            ) {
+                            if let Err(e) = stream.send(&TcpRemoteNewMessage {
+                                client_id,
+                                tag,
+                                flags,
+                                payload,
+                            }) {
                                 log::info!("Got error {e} while trying to forward a message to broker {peer_address}, exiting thread");
                                 return;
                             }
@@ -3212,7 +3712,7 @@ where
                 // Forwarding happens between each recv, too, as simplification.
                 // We ignore errors completely as they may be timeout, or stream closings.
                 // Instead, we catch stream close when/if we next try to send.
-                match recv_tcp_msg(&mut stream) {
+                match stream.recv() {
                     Ok(val) => {
                         let msg: TcpRemoteNewMessage = val.try_into().expect(
                             "Illegal message received from broker 2 broker connection - shutting down.",
@@ -3242,6 +3742,10 @@ where
                                 );
                                 return;
                             }
+                        } else {
+                            // The message could not be decrypted, the connection can't be trusted anymore.
+                            log::error!("Dropping the connection to broker {peer_address}: {e}");
+                            return;
                         }
 
                         #[cfg(feature = "llmp_debug")]
@@ -3262,14 +3766,20 @@ where
     }
 
     /// handles a single tcp request in the current context.
+    ///
+    /// `codecs` are the codecs the peer can decompress, narrowed down to ours, and sent back.
+    /// The peer only sends messages in these codecs, and local clients also narrow down `page_codecs`.
     #[cfg(feature = "std")]
     fn handle_tcp_request(
-        mut stream: TcpStream,
+        mut stream: LinkStream,
         request: &TcpRequest,
+        codecs: Flags,
+        page_codecs: &AtomicU32,
         current_client_id: &mut ClientId,
         sender: &mut LlmpSender<SHM, SP>,
         broker_shmem_description: &ShMemDescription,
     ) {
+        let codecs = codecs & Flags::supported_codecs();
         match request {
             TcpRequest::ClientQuit { client_id } => {
                 // todo search the ancestor_id and remove it.
@@ -3279,17 +3789,20 @@ where
                 }
             }
             TcpRequest::LocalClientHello { shmem_description } => {
+                // From now on, only forward messages this client can read
+                page_codecs.fetch_and(codecs.0, Ordering::Relaxed);
+
                 match Self::announce_new_client(sender, shmem_description) {
                     Ok(()) => (),
                     Err(e) => log::info!("Error forwarding client on map: {e:?}"),
                 }
 
-                if let Err(e) = send_tcp_msg(
-                    &mut stream,
-                    &TcpResponse::LocalClientAccepted {
+                if let Err(e) = stream.send(&(
+                    TcpResponse::LocalClientAccepted {
                         client_id: *current_client_id,
                     },
-                ) {
+                    codecs,
+                )) {
                     log::info!("An error occurred sending via tcp {e}");
                 }
                 current_client_id.0 += 1;
@@ -3298,27 +3811,37 @@ where
                 log::info!("B2B new client: {hostname}");
 
                 // TODO: Clean up broker ids.
-                if send_tcp_msg(
-                    &mut stream,
-                    &TcpResponse::RemoteBrokerAccepted {
-                        broker_id: BrokerId(current_client_id.0),
-                    },
-                )
-                .is_err()
+                if stream
+                    .send(&(
+                        TcpResponse::RemoteBrokerAccepted {
+                            broker_id: BrokerId(current_client_id.0),
+                        },
+                        codecs,
+                    ))
+                    .is_err()
                 {
                     log::info!("Error accepting broker, ignoring.");
                     return;
                 }
 
-                if let Ok(shmem_description) =
-                    Self::b2b_thread_on(stream, *current_client_id, broker_shmem_description)
-                {
+                if let Ok(shmem_description) = Self::b2b_thread_on(
+                    stream,
+                    *current_client_id,
+                    broker_shmem_description,
+                    codecs,
+                ) {
                     if Self::announce_new_client(sender, &shmem_description).is_err() {
                         log::info!("B2B: Error announcing client {shmem_description:?}");
                     }
                     current_client_id.0 += 1;
                 }
             }
+            TcpRequest::SecureLinkHello { .. } | TcpRequest::SecureLinkFinish { .. } => {
+                log::info!("Ignoring an unexpected secure link request");
+                let _ = stream.send(&TcpResponse::Error {
+                    description: "Unexpected secure link request".into(),
+                });
+            }
         }
     }
 
@@ -3350,6 +3873,9 @@ where
         );
         let tcp_out_shmem_description = tcp_out_shmem.shmem.description();
         let listener_id = self.register_client(tcp_out_shmem);
+        #[cfg(feature = "llmp_encryption")]
+        let link_key = self.link_key.clone();
+        let page_codecs = self.page_codecs.clone();
 
         let ret = thread::spawn(move || {
             // Create a new ShMemProvider for this background thread.
@@ -3381,6 +3907,15 @@ where
                             stream.peer_addr().unwrap()
                         );
 
+                        // Don't let a stalled peer block the listener.
+                        if let Err(e) = stream
+                            .set_read_timeout(Some(LLMP_HANDSHAKE_TIMEOUT))
+                            .and_then(|()| stream.set_write_timeout(Some(LLMP_HANDSHAKE_TIMEOUT)))
+                        {
+                            log::error!("Could not set the handshake timeout: {e:?}");
+                            continue;
+                        }
+
                         // Send initial information, without anyone asking.
                         // This makes it a tiny bit easier to map the broker map for new Clients.
                         match send_tcp_msg(&mut stream, &broker_hello) {
@@ -3391,26 +3926,50 @@ where
                             }
                         }
 
-                        let buf = match recv_tcp_msg(&mut stream) {
-                            Ok(buf) => buf,
-                            Err(e) => {
-                                log::error!("Error receving from tcp: {e:?}");
-                                continue;
-                            }
-                        };
+                        let buf =
+                            match recv_tcp_msg_capped(&mut stream, LLMP_HANDSHAKE_MAX_MSG_SIZE) {
+                                Ok(buf) => buf,
+                                Err(e) => {
+                                    log::error!("Error receving from tcp: {e:?}");
+                                    continue;
+                                }
+                            };
 
                         // log::info!("{:#?}", buf);
-                        let req = match buf.try_into() {
-                            Ok(req) => req,
+                        let (req, codecs) = match split_hello(&buf) {
+                            Ok(hello) => hello,
                             Err(e) => {
                                 log::error!("Could not deserialize tcp message: {e:?}");
                                 continue;
                             }
                         };
 
+                        #[cfg(feature = "llmp_encryption")]
+                        let (stream, req, codecs) =
+                            match Self::accept_secure_link(stream, addr, req, codecs, &link_key) {
+                                Ok(accepted) => accepted,
+                                Err(e) => {
+                                    log::error!("B2B: Rejected connection from {addr}: {e}");
+                                    continue;
+                                }
+                            };
+                        #[cfg(not(feature = "llmp_encryption"))]
+                        let stream = LinkStream::plain(stream);
+
+                        if let Err(e) = stream
+                            .stream
+                            .set_read_timeout(None)
+                            .and_then(|()| stream.stream.set_write_timeout(None))
+                        {
+                            log::error!("Could not reset the handshake timeout: {e:?}");
+                            continue;
+                        }
+
                         Self::handle_tcp_request(
                             stream,
                             &req,
+                            codecs,
+                            &page_codecs,
                             &mut current_client_id,
                             &mut tcp_incoming_sender,
                             &broker_shmem_description,
@@ -3434,6 +3993,8 @@ pub struct LlmpClientDescription {
     sender: LlmpDescription,
     /// Description of the receiver
     receiver: LlmpDescription,
+    /// The codecs agreed on with the broker
+    compression_codecs: Flags,
 }
 
 /// Client side of LLMP
@@ -3443,6 +4004,9 @@ pub struct LlmpClient<SHM, SP> {
     sender: LlmpSender<SHM, SP>,
     /// Incoming (broker) broadcast map
     receiver: LlmpReceiver<SHM, SP>,
+    /// The codecs the broker and this client can both decompress, see [`Flags::supported_codecs`].
+    /// Agreed on when attaching over tcp, else all codecs of this build.
+    compression_codecs: Flags,
 }
 
 /// `n` clients connect to a broker. They share an outgoing map with the broker,
@@ -3482,6 +4046,7 @@ where
                 #[cfg(feature = "std")]
                 last_msg_time: current_time(),
             },
+            compression_codecs: Flags::supported_codecs(),
         })
     }
 
@@ -3493,7 +4058,11 @@ where
             sender.out_shmems[0].shmem.clone(),
             None,
         )?;
-        Ok(Self { sender, receiver })
+        Ok(Self {
+            sender,
+            receiver,
+            compression_codecs: Flags::supported_codecs(),
+        })
     }
 
     /// Reattach to a vacant client map.
@@ -3518,6 +4087,7 @@ where
                 current_broker_shmem,
                 last_msg_recvd_offset,
             )?,
+            compression_codecs: Flags::supported_codecs(),
         })
     }
 
@@ -3533,6 +4103,10 @@ where
                 shmem_provider,
                 &format!("{env_name}_RECEIVER"),
             )?,
+            compression_codecs: env::var(format!("{env_name}_CODECS"))
+                .ok()
+                .and_then(|codecs| codecs.parse().ok())
+                .map_or_else(Flags::supported_codecs, Flags),
         })
     }
 
@@ -3550,6 +4124,7 @@ where
                 shmem_provider,
                 &description.receiver,
             )?,
+            compression_codecs: description.compression_codecs,
         })
     }
 
@@ -3670,17 +4245,21 @@ where
         let client_hello_req = TcpRequest::LocalClientHello {
             shmem_description: ret.sender.out_shmems.first().unwrap().shmem.description(),
         };
-        send_tcp_msg(&mut stream, &client_hello_req)?;
+        send_tcp_msg(&mut stream, &(client_hello_req, Flags::supported_codecs()))?;
 
-        // The broker accepted the client, and sent back an ID.
-        let TcpResponse::LocalClientAccepted {
-            client_id: client_sender_id,
-        } = recv_tcp_msg(&mut stream)?.try_into()?
+        // The broker accepted the client, and sent back an ID, and the codecs we agree on.
+        let (
+            TcpResponse::LocalClientAccepted {
+                client_id: client_sender_id,
+            },
+            codecs,
+        ) = split_hello(&recv_tcp_msg(&mut stream)?)?
         else {
             return Err(Error::illegal_state(
                 "Unexpected Response from Broker".to_string(),
             ));
         };
+        ret.compression_codecs = codecs;
 
         // Set our ID to the one the broker sent us.
         // This is mainly so we can filter out our own msgs later.
@@ -3731,7 +4310,12 @@ where
     #[cfg(feature = "std")]
     pub fn to_env(&self, env_name: &str) -> Result<(), Error> {
         self.sender.to_env(&format!("{env_name}_SENDER"))?;
-        self.receiver.to_env(&format!("{env_name}_RECEIVER"))
+        self.receiver.to_env(&format!("{env_name}_RECEIVER"))?;
+        env::set_var(
+            format!("{env_name}_CODECS"),
+            self.compression_codecs.0.to_string(),
+        );
+        Ok(())
     }
 
     /// Describe this client in a way that it can be recreated, for example after crash
@@ -3739,11 +4323,19 @@ where
         Ok(LlmpClientDescription {
             sender: self.sender.describe()?,
             receiver: self.receiver.describe()?,
+            compression_codecs: self.compression_codecs,
         })
     }
 }
 
 impl<SHM, SP> LlmpClient<SHM, SP> {
+    /// The codecs the broker and this client can both decompress, see [`Flags::supported_codecs`].
+    /// Pick the codec of outgoing messages with [`Flags::pick_codec`].
+    #[must_use]
+    pub fn compression_codecs(&self) -> Flags {
+        self.compression_codecs
+    }
+
     /// Outgoing channel to the broker
     #[must_use]
     pub fn sender(&self) -> &LlmpSender<SHM, SP> {
@@ -3831,4 +4423,130 @@ mod tests {
         // We want at least the tcp and sender clients.
         assert_eq!(broker.inner.llmp_clients.len(), 2);
     }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_compression_flags() {
+        use super::{Flags, LLMP_FLAG_COMPRESSED, LLMP_FLAG_FROM_B2B, LLMP_FLAG_ZSTD};
+        use crate::compress::CompressionCodec;
+
+        assert_eq!(LLMP_FLAG_FROM_B2B.compression_codec().unwrap(), None);
+        // Older versions only set the compressed flag for gzip
+        assert_eq!(
+            (LLMP_FLAG_COMPRESSED | LLMP_FLAG_FROM_B2B)
+                .compression_codec()
+                .unwrap(),
+            Some(CompressionCodec::Gzip)
+        );
+
+        let zstd_flags = LLMP_FLAG_COMPRESSED | LLMP_FLAG_ZSTD;
+        #[cfg(feature = "zstd")]
+        assert_eq!(
+            zstd_flags.compression_codec().unwrap(),
+            Some(CompressionCodec::Zstd)
+        );
+        #[cfg(not(feature = "zstd"))]
+        assert!(zstd_flags.compression_codec().is_err());
+
+        let codec = CompressionCodec::default();
+        assert_eq!(
+            Flags::compressed_with(codec).compression_codec().unwrap(),
+            Some(codec)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_codec_negotiation() {
+        use alloc::vec::Vec;
+
+        use super::{
+            split_hello, transcode_for, ClientId, Flags, TcpResponse, LLMP_FLAG_COMPRESSED,
+            LLMP_FLAG_FROM_B2B, LLMP_FLAG_INITIALIZED,
+        };
+        use crate::compress::CompressionCodec;
+
+        let accepted = TcpResponse::LocalClientAccepted {
+            client_id: ClientId(3),
+        };
+        // Older brokers don't append their codecs, and older clients ignore them
+        let (_, codecs): (TcpResponse, _) =
+            split_hello(&postcard::to_allocvec(&accepted).unwrap()).unwrap();
+        assert_eq!(codecs, LLMP_FLAG_COMPRESSED);
+        let hello = postcard::to_allocvec(&(&accepted, Flags::supported_codecs())).unwrap();
+        let TcpResponse::LocalClientAccepted { client_id } = hello.try_into().unwrap() else {
+            panic!("Unexpected response");
+        };
+        assert_eq!(client_id, ClientId(3));
+        let (_, codecs): (TcpResponse, _) =
+            split_hello(&postcard::to_allocvec(&(&accepted, Flags::supported_codecs())).unwrap())
+                .unwrap();
+        assert_eq!(codecs, Flags::supported_codecs());
+
+        // Uncompressed and gzip messages are readable by all
+        assert!(LLMP_FLAG_INITIALIZED.readable_with(LLMP_FLAG_COMPRESSED));
+        assert!(LLMP_FLAG_COMPRESSED.readable_with(LLMP_FLAG_COMPRESSED));
+        let buf: Vec<u8> = (0..4096_u32).map(|i| (i % 7) as u8).collect();
+        assert!(
+            transcode_for(LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED, &buf)
+                .unwrap()
+                .is_none()
+        );
+
+        for codec in [
+            CompressionCodec::Gzip,
+            #[cfg(feature = "zstd")]
+            CompressionCodec::Zstd,
+            #[cfg(feature = "lz4")]
+            CompressionCodec::Lz4,
+        ] {
+            assert_eq!(Flags::supported_codecs().pick_codec(codec), codec);
+            assert_eq!(
+                LLMP_FLAG_COMPRESSED.pick_codec(codec),
+                CompressionCodec::Gzip
+            );
+
+            // Peers only knowing gzip get gzip
+            let flags = Flags::compressed_with(codec) | LLMP_FLAG_FROM_B2B;
+            let compressed = codec.compress(&buf);
+            match transcode_for(LLMP_FLAG_COMPRESSED, flags, &compressed).unwrap() {
+                None => assert_eq!(codec, CompressionCodec::Gzip),
+                Some((flags, transcoded)) => {
+                    assert_eq!(flags, LLMP_FLAG_COMPRESSED | LLMP_FLAG_FROM_B2B);
+                    assert_eq!(CompressionCodec::Gzip.decompress(&transcoded).unwrap(), buf);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(feature = "llmp_encryption")]
+    fn test_llmp_secure_link_loopback() {
+        use std::{io::Write, net::TcpStream};
+
+        use super::{recv_tcp_msg, LlmpBrokerInner};
+        use crate::secure_link::LinkKey;
+
+        let port = 1339;
+        let key = LinkKey::from_passphrase("correct horse battery staple");
+
+        let mut listening = LlmpBrokerInner::new(StdShMemProvider::new().unwrap()).unwrap();
+        listening.set_link_key(key.clone());
+        listening.launch_tcp_listener_on(port).unwrap();
+
+        // An unauthenticated peer announcing a huge frame is dropped, without blocking the listener
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        recv_tcp_msg(&mut stream).unwrap();
+        stream.write_all(&u32::MAX.to_be_bytes()).unwrap();
+
+        let mut connecting = LlmpBrokerInner::new(StdShMemProvider::new().unwrap()).unwrap();
+        connecting.set_link_key(LinkKey::from_passphrase("incorrect horse battery staple"));
+        assert!(connecting.connect_b2b(("127.0.0.1", port)).is_err());
+
+        let mut connecting = LlmpBrokerInner::new(StdShMemProvider::new().unwrap()).unwrap();
+        connecting.set_link_key(key);
+        connecting.connect_b2b(("127.0.0.1", port)).unwrap();
+    }
 }
@@ -137,6 +137,32 @@ pub fn peak_rss_mb_child_processes() -> Result<i64, Error> {
     Ok(rss.ru_maxrss >> 10)
 }
 
+/// Get the peak working set of the current process, in MB.
+///
+/// `Windows` does not account the memory of child processes, so for targets run in child
+/// processes, this only covers the fuzzer itself.
+#[cfg(all(windows, feature = "std"))]
+#[expect(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+pub fn peak_rss_mb_current_process() -> Result<i64, Error> {
+    use core::mem;
+
+    use windows::Win32::System::{
+        ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+        Threading::GetCurrentProcess,
+    };
+
+    let mut counters = PROCESS_MEMORY_COUNTERS::default();
+    unsafe {
+        GetProcessMemoryInfo(
+            GetCurrentProcess(),
+            &raw mut counters,
+            mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+        )
+    }
+    .map_err(|err| Error::unknown(format!("Could not get the process memory info: {err}")))?;
+    Ok((counters.PeakWorkingSetSize >> 20) as i64)
+}
+
 /// "Safe" wrapper around dup2
 ///
 /// # Safety
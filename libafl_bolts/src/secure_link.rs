@@ -0,0 +1,316 @@
+//! Authenticated encryption for links between brokers and nodes on different machines.
+//!
+//! Both ends of a link share a secret [`LinkKey`]. When connecting, they run a short handshake:
+//! the connecting side (the [`LinkInitiator`]) sends a random nonce, the accepting side
+//! (the [`LinkResponder`]) answers with its own nonce and a proof that it knows the key, and the
+//! initiator finally proves that it knows the key, too.
+//! Both ends then derive fresh session keys from the shared key and the two nonces, and wrap every
+//! following message in a [`SecureChannel`], using `AES-256-GCM`.
+//!
+//! The transport is left to the user of this module, it only produces and consumes byte buffers.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Debug, Formatter};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::Error;
+
+/// The size of a [`LinkKey`]
+pub const LINK_KEY_SIZE: usize = 32;
+/// The size of the nonces exchanged in the handshake
+const LINK_NONCE_SIZE: usize = 32;
+/// The size of the proofs exchanged in the handshake
+const LINK_PROOF_SIZE: usize = 32;
+/// The magic starting a handshake, including the protocol version
+const LINK_MAGIC: [u8; 8] = *b"LAFLSEC1";
+
+/// The size of the hello sent by the [`LinkInitiator`]
+pub const LINK_HELLO_SIZE: usize = LINK_MAGIC.len() + LINK_NONCE_SIZE;
+/// The size of the challenge sent by the [`LinkResponder`]
+pub const LINK_CHALLENGE_SIZE: usize = LINK_NONCE_SIZE + LINK_PROOF_SIZE;
+/// The size of the final proof sent by the [`LinkInitiator`]
+pub const LINK_FINISH_SIZE: usize = LINK_PROOF_SIZE;
+
+/// The pre-shared key authenticating both ends of a link
+#[derive(Clone, PartialEq, Eq)]
+pub struct LinkKey([u8; LINK_KEY_SIZE]);
+
+impl LinkKey {
+    /// Creates a new [`LinkKey`] from raw bytes.
+    /// Those should be random, e.g. generated with `head -c 32 /dev/urandom`.
+    #[must_use]
+    pub fn new(key: [u8; LINK_KEY_SIZE]) -> Self {
+        Self(key)
+    }
+
+    /// Derives a [`LinkKey`] from a passphrase.
+    ///
+    /// There is no key stretching, so the passphrase should be long and random.
+    #[must_use]
+    pub fn from_passphrase(passphrase: &str) -> Self {
+        let mut key = [0; LINK_KEY_SIZE];
+        Hkdf::<Sha256>::new(Some(b"libafl link passphrase"), passphrase.as_bytes())
+            .expand(&[], &mut key)
+            .unwrap();
+        Self(key)
+    }
+
+    /// Derives the secrets for a session, identified by the nonces of both ends
+    fn derive(&self, initiator_nonce: &[u8], responder_nonce: &[u8], info: &[u8]) -> [u8; 32] {
+        let mut salt = Vec::with_capacity(initiator_nonce.len() + responder_nonce.len());
+        salt.extend_from_slice(initiator_nonce);
+        salt.extend_from_slice(responder_nonce);
+        let mut okm = [0; 32];
+        Hkdf::<Sha256>::new(Some(&salt), &self.0)
+            .expand(info, &mut okm)
+            .unwrap();
+        okm
+    }
+
+    fn channel(
+        &self,
+        initiator_nonce: &[u8],
+        responder_nonce: &[u8],
+        is_initiator: bool,
+    ) -> SecureChannel {
+        let to_responder = self.derive(initiator_nonce, responder_nonce, b"initiator to responder");
+        let to_initiator = self.derive(initiator_nonce, responder_nonce, b"responder to initiator");
+        let (seal_key, open_key) = if is_initiator {
+            (to_responder, to_initiator)
+        } else {
+            (to_initiator, to_responder)
+        };
+        SecureChannel {
+            sealer: Aes256Gcm::new(&seal_key.into()),
+            opener: Aes256Gcm::new(&open_key.into()),
+            sent: 0,
+            received: 0,
+        }
+    }
+}
+
+impl Debug for LinkKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("LinkKey(<redacted>)")
+    }
+}
+
+/// Compares two buffers in constant time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn random_nonce() -> Result<[u8; LINK_NONCE_SIZE], Error> {
+    let mut nonce = [0; LINK_NONCE_SIZE];
+    getrandom::getrandom(&mut nonce)
+        .map_err(|err| Error::unknown(format!("Could not generate a random nonce: {err}")))?;
+    Ok(nonce)
+}
+
+fn handshake_error<S>(reason: S) -> Error
+where
+    S: Into<String>,
+{
+    Error::illegal_state(format!("Secure link handshake failed: {}", reason.into()))
+}
+
+/// The connecting end of a link
+#[derive(Debug)]
+pub struct LinkInitiator {
+    key: LinkKey,
+    nonce: [u8; LINK_NONCE_SIZE],
+}
+
+impl LinkInitiator {
+    /// Starts a new handshake
+    pub fn new(key: &LinkKey) -> Result<Self, Error> {
+        Ok(Self {
+            key: key.clone(),
+            nonce: random_nonce()?,
+        })
+    }
+
+    /// The hello to send to the [`LinkResponder`]
+    #[must_use]
+    pub fn hello(&self) -> [u8; LINK_HELLO_SIZE] {
+        let mut hello = [0; LINK_HELLO_SIZE];
+        hello[..LINK_MAGIC.len()].copy_from_slice(&LINK_MAGIC);
+        hello[LINK_MAGIC.len()..].copy_from_slice(&self.nonce);
+        hello
+    }
+
+    /// Checks the challenge of the [`LinkResponder`].
+    /// Returns the proof to send back, and the [`SecureChannel`] to use from now on.
+    pub fn finish(
+        self,
+        challenge: &[u8],
+    ) -> Result<([u8; LINK_FINISH_SIZE], SecureChannel), Error> {
+        if challenge.len() != LINK_CHALLENGE_SIZE {
+            return Err(handshake_error(format!(
+                "expected a challenge of {LINK_CHALLENGE_SIZE} bytes, got {} bytes",
+                challenge.len()
+            )));
+        }
+        let (responder_nonce, proof) = challenge.split_at(LINK_NONCE_SIZE);
+        let expected = self
+            .key
+            .derive(&self.nonce, responder_nonce, b"responder proof");
+        if !constant_time_eq(proof, &expected) {
+            return Err(handshake_error("the peer does not know the pre-shared key"));
+        }
+        let finish = self
+            .key
+            .derive(&self.nonce, responder_nonce, b"initiator proof");
+        let channel = self.key.channel(&self.nonce, responder_nonce, true);
+        Ok((finish, channel))
+    }
+}
+
+/// The accepting end of a link
+#[derive(Debug)]
+pub struct LinkResponder {
+    key: LinkKey,
+    initiator_nonce: [u8; LINK_NONCE_SIZE],
+    nonce: [u8; LINK_NONCE_SIZE],
+}
+
+impl LinkResponder {
+    /// Answers the hello of a [`LinkInitiator`]
+    pub fn new(key: &LinkKey, hello: &[u8]) -> Result<Self, Error> {
+        if hello.len() != LINK_HELLO_SIZE || hello[..LINK_MAGIC.len()] != LINK_MAGIC {
+            return Err(handshake_error(
+                "the peer did not start a secure link handshake (is a key configured on both ends?)",
+            ));
+        }
+        let mut initiator_nonce = [0; LINK_NONCE_SIZE];
+        initiator_nonce.copy_from_slice(&hello[LINK_MAGIC.len()..]);
+        Ok(Self {
+            key: key.clone(),
+            initiator_nonce,
+            nonce: random_nonce()?,
+        })
+    }
+
+    /// The challenge to send to the [`LinkInitiator`], proving that we know the key
+    #[must_use]
+    pub fn challenge(&self) -> [u8; LINK_CHALLENGE_SIZE] {
+        let mut challenge = [0; LINK_CHALLENGE_SIZE];
+        challenge[..LINK_NONCE_SIZE].copy_from_slice(&self.nonce);
+        challenge[LINK_NONCE_SIZE..].copy_from_slice(&self.key.derive(
+            &self.initiator_nonce,
+            &self.nonce,
+            b"responder proof",
+        ));
+        challenge
+    }
+
+    /// Checks the final proof of the [`LinkInitiator`].
+    /// Returns the [`SecureChannel`] to use from now on.
+    pub fn finish(self, finish: &[u8]) -> Result<SecureChannel, Error> {
+        let expected = self
+            .key
+            .derive(&self.initiator_nonce, &self.nonce, b"initiator proof");
+        if !constant_time_eq(finish, &expected) {
+            return Err(handshake_error("the peer does not know the pre-shared key"));
+        }
+        Ok(self.key.channel(&self.initiator_nonce, &self.nonce, false))
+    }
+}
+
+/// An established, authenticated and encrypted link.
+///
+/// Messages are numbered, so that messages dropped, reordered or replayed by an attacker are
+/// detected when opening them.
+pub struct SecureChannel {
+    sealer: Aes256Gcm,
+    opener: Aes256Gcm,
+    sent: u64,
+    received: u64,
+}
+
+impl Debug for SecureChannel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureChannel")
+            .field("sent", &self.sent)
+            .field("received", &self.received)
+            .finish_non_exhaustive()
+    }
+}
+
+fn counter_nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+impl SecureChannel {
+    /// Encrypts and authenticates the next message to send
+    pub fn seal(&mut self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = counter_nonce(self.sent);
+        let sealed = self
+            .sealer
+            .encrypt(&nonce.into(), msg)
+            .map_err(|_| Error::illegal_state("Secure link: could not encrypt a message"))?;
+        self.sent += 1;
+        Ok(sealed)
+    }
+
+    /// Decrypts the next received message, failing if it was tampered with
+    pub fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = counter_nonce(self.received);
+        let msg = self.opener.decrypt(&nonce.into(), sealed).map_err(|_| {
+            Error::illegal_state(
+                "Secure link: received a message that was tampered with, replayed or reordered",
+            )
+        })?;
+        self.received += 1;
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::secure_link::{LinkInitiator, LinkKey, LinkResponder};
+
+    #[test]
+    fn test_secure_link() {
+        let key = LinkKey::from_passphrase("correct horse battery staple");
+
+        let initiator = LinkInitiator::new(&key).unwrap();
+        let responder = LinkResponder::new(&key, &initiator.hello()).unwrap();
+        let (finish, mut initiator_channel) = initiator.finish(&responder.challenge()).unwrap();
+        let mut responder_channel = responder.finish(&finish).unwrap();
+
+        let sealed = initiator_channel.seal(b"testcase").unwrap();
+        assert_ne!(&sealed[..8], b"testcase");
+        assert_eq!(responder_channel.open(&sealed).unwrap(), b"testcase");
+
+        // Replays are rejected
+        assert!(responder_channel.open(&sealed).is_err());
+
+        let sealed = responder_channel.seal(b"answer").unwrap();
+        assert_eq!(initiator_channel.open(&sealed).unwrap(), b"answer");
+    }
+
+    #[test]
+    fn test_secure_link_wrong_key() {
+        let key = LinkKey::from_passphrase("correct horse battery staple");
+        let other = LinkKey::from_passphrase("incorrect horse battery staple");
+
+        // The responder does not know the key
+        let initiator = LinkInitiator::new(&key).unwrap();
+        let responder = LinkResponder::new(&other, &initiator.hello()).unwrap();
+        assert!(initiator.finish(&responder.challenge()).is_err());
+
+        // A peer without the key cannot forge the final proof
+        let initiator = LinkInitiator::new(&other).unwrap();
+        let responder = LinkResponder::new(&key, &initiator.hello()).unwrap();
+        assert!(responder.finish(&[0; 32]).is_err());
+    }
+}
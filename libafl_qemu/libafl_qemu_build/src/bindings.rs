@@ -154,10 +154,13 @@ pub fn generate(
         .allowlist_type("Syx.*")
         .allowlist_type("libafl_mapinfo")
         .allowlist_type("IntervalTreeRoot")
+        .allowlist_type("MemoryRegion")
+        .allowlist_type("MemoryRegionOps")
         .allowlist_function("qemu_system_debug_request")
         .allowlist_function("target_mmap")
         .allowlist_function("target_mprotect")
         .allowlist_function("target_munmap")
+        .allowlist_function("force_sig")
         .allowlist_function("page_check_range")
         .allowlist_function("cpu_memory_rw_debug")
         .allowlist_function("cpu_physical_memory_rw")
@@ -177,6 +180,9 @@ pub fn generate(
         .allowlist_function("vm_start")
         .allowlist_function("qemu_main_loop")
         .allowlist_function("qemu_cleanup")
+        .allowlist_function("get_system_memory")
+        .allowlist_function("memory_region_init_io")
+        .allowlist_function("memory_region_add_subregion_overlap")
         .blocklist_function("main_loop_wait") // bindgen issue #1313
         .blocklist_type("siginfo_t")
         .raw_line("use libc::siginfo_t;")
@@ -311,70 +311,10 @@ where
             .unwrap();
         }
 
-        let mut call_addrs: Vec<(GuestAddr, usize)> = Vec::new();
-        let mut ret_addrs: Vec<GuestAddr> = Vec::new();
-
-        if let Some(h) = emulator_modules.modules().match_first_type::<Self>() {
-            #[allow(unused_mut)] // cfg dependent
-            let mut code = {
-                #[cfg(feature = "usermode")]
-                unsafe {
-                    std::slice::from_raw_parts(qemu.g2h(pc), 512)
-                }
-                #[cfg(feature = "systemmode")]
-                &mut [0; 512]
-            };
-            #[cfg(feature = "systemmode")]
-            if let Err(err) = qemu.read_mem(pc, code) {
-                // TODO handle faults
-                log::error!("gen_block_calls: Failed to read mem at pc {pc:#x}: {err:?}");
-                return None;
-            }
-
-            let mut iaddr = pc;
-
-            'disasm: while let Ok(insns) = h.cs.disasm_count(code, iaddr.into(), 1) {
-                if insns.is_empty() {
-                    break;
-                }
-                let insn = insns.first().unwrap();
-                let insn_detail: InsnDetail = h.cs.insn_detail(insn).unwrap();
-                for detail in insn_detail.groups() {
-                    match u32::from(detail.0) {
-                        capstone::InsnGroupType::CS_GRP_CALL => {
-                            let call_len = insn.bytes().len();
-                            call_addrs.push((insn.address() as GuestAddr, call_len));
-                        }
-                        capstone::InsnGroupType::CS_GRP_RET => {
-                            ret_addrs.push(insn.address() as GuestAddr);
-                            break 'disasm;
-                        }
-                        capstone::InsnGroupType::CS_GRP_INVALID
-                        | capstone::InsnGroupType::CS_GRP_JUMP
-                        | capstone::InsnGroupType::CS_GRP_IRET
-                        | capstone::InsnGroupType::CS_GRP_PRIVILEGE => {
-                            break 'disasm;
-                        }
-                        _ => {}
-                    }
-                }
-
-                iaddr += insn.bytes().len() as GuestAddr;
-
-                #[cfg(feature = "usermode")]
-                unsafe {
-                    code = std::slice::from_raw_parts(qemu.g2h(iaddr), 512);
-                }
-                #[cfg(feature = "systemmode")]
-                if let Err(err) = qemu.read_mem(pc, code) {
-                    // TODO handle faults
-                    log::error!(
-                        "gen_block_calls error 2: Failed to read mem at pc {pc:#x}: {err:?}"
-                    );
-                    return None;
-                }
-            }
-        }
+        let (call_addrs, ret_addrs) = emulator_modules
+            .modules()
+            .match_first_type::<Self>()
+            .and_then(|h| find_block_calls(qemu, &h.cs, pc))?;
 
         for (call_addr, call_len) in call_addrs {
             // TODO do not use a closure, find a more efficient way to pass call_len
@@ -410,6 +350,79 @@ where
     }
 }
 
+/// Disassembles the block at `pc`, returning its call instructions, with their length, and the
+/// return instruction ending it, if any.
+///
+/// Returns `None` if the block could not be read.
+pub(crate) fn find_block_calls(
+    qemu: Qemu,
+    cs: &Capstone,
+    pc: GuestAddr,
+) -> Option<(Vec<(GuestAddr, usize)>, Vec<GuestAddr>)> {
+    let mut call_addrs: Vec<(GuestAddr, usize)> = Vec::new();
+    let mut ret_addrs: Vec<GuestAddr> = Vec::new();
+
+    #[allow(unused_mut)] // cfg dependent
+    let mut code = {
+        #[cfg(feature = "usermode")]
+        unsafe {
+            std::slice::from_raw_parts(qemu.g2h(pc), 512)
+        }
+        #[cfg(feature = "systemmode")]
+        &mut [0; 512]
+    };
+    #[cfg(feature = "systemmode")]
+    if let Err(err) = qemu.read_mem(pc, code) {
+        // TODO handle faults
+        log::error!("gen_block_calls: Failed to read mem at pc {pc:#x}: {err:?}");
+        return None;
+    }
+
+    let mut iaddr = pc;
+
+    'disasm: while let Ok(insns) = cs.disasm_count(code, iaddr.into(), 1) {
+        if insns.is_empty() {
+            break;
+        }
+        let insn = insns.first().unwrap();
+        let insn_detail: InsnDetail = cs.insn_detail(insn).unwrap();
+        for detail in insn_detail.groups() {
+            match u32::from(detail.0) {
+                capstone::InsnGroupType::CS_GRP_CALL => {
+                    let call_len = insn.bytes().len();
+                    call_addrs.push((insn.address() as GuestAddr, call_len));
+                }
+                capstone::InsnGroupType::CS_GRP_RET => {
+                    ret_addrs.push(insn.address() as GuestAddr);
+                    break 'disasm;
+                }
+                capstone::InsnGroupType::CS_GRP_INVALID
+                | capstone::InsnGroupType::CS_GRP_JUMP
+                | capstone::InsnGroupType::CS_GRP_IRET
+                | capstone::InsnGroupType::CS_GRP_PRIVILEGE => {
+                    break 'disasm;
+                }
+                _ => {}
+            }
+        }
+
+        iaddr += insn.bytes().len() as GuestAddr;
+
+        #[cfg(feature = "usermode")]
+        unsafe {
+            code = std::slice::from_raw_parts(qemu.g2h(iaddr), 512);
+        }
+        #[cfg(feature = "systemmode")]
+        if let Err(err) = qemu.read_mem(pc, code) {
+            // TODO handle faults
+            log::error!("gen_block_calls error 2: Failed to read mem at pc {pc:#x}: {err:?}");
+            return None;
+        }
+    }
+
+    Some((call_addrs, ret_addrs))
+}
+
 impl<I, S, T> EmulatorModule<I, S> for CallTracerModule<T>
 where
     I: Input + Unpin,
@@ -13,7 +13,10 @@ pub use libafl_targets::{
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "systemmode")]
-use crate::modules::utils::filters::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::modules::{
+    address_space_active,
+    utils::filters::{NopPageFilter, NOP_PAGE_FILTER},
+};
 #[cfg(feature = "usermode")]
 use crate::{capstone, qemu::ArchExtras, CallingConvention};
 use crate::{
@@ -245,24 +248,44 @@ where
 }
 
 pub extern "C" fn trace_cmp1_cmplog(_: *const (), id: u64, v0: u8, v1: u8) {
+    #[cfg(feature = "systemmode")]
+    if !address_space_active() {
+        return;
+    }
+
     unsafe {
         __libafl_targets_cmplog_instructions(id as usize, 1, u64::from(v0), u64::from(v1));
     }
 }
 
 pub extern "C" fn trace_cmp2_cmplog(_: *const (), id: u64, v0: u16, v1: u16) {
+    #[cfg(feature = "systemmode")]
+    if !address_space_active() {
+        return;
+    }
+
     unsafe {
         __libafl_targets_cmplog_instructions(id as usize, 2, u64::from(v0), u64::from(v1));
     }
 }
 
 pub extern "C" fn trace_cmp4_cmplog(_: *const (), id: u64, v0: u32, v1: u32) {
+    #[cfg(feature = "systemmode")]
+    if !address_space_active() {
+        return;
+    }
+
     unsafe {
         __libafl_targets_cmplog_instructions(id as usize, 4, u64::from(v0), u64::from(v1));
     }
 }
 
 pub extern "C" fn trace_cmp8_cmplog(_: *const (), id: u64, v0: u64, v1: u64) {
+    #[cfg(feature = "systemmode")]
+    if !address_space_active() {
+        return;
+    }
+
     unsafe {
         __libafl_targets_cmplog_instructions(id as usize, 8, v0, v1);
     }
@@ -14,7 +14,11 @@ use serde::{Deserialize, Serialize};
 
 use super::utils::filters::HasAddressFilter;
 #[cfg(feature = "systemmode")]
-use crate::modules::utils::filters::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::modules::{
+    address_space_active,
+    utils::filters::{NopPageFilter, NOP_PAGE_FILTER},
+    AddressSpaceModule,
+};
 use crate::{
     emu::EmulatorModules,
     modules::{
@@ -203,6 +207,11 @@ pub fn exec_trace_block<ET, F, I, S>(
     I: Unpin,
     S: Unpin + HasMetadata,
 {
+    #[cfg(feature = "systemmode")]
+    if !address_space_active() {
+        return;
+    }
+
     DRCOV_IDS.lock().unwrap().as_mut().unwrap().push(id);
 }
 
@@ -330,6 +339,12 @@ where
             "DrCov should have a module mapping already set."
         );
 
+        // Blocks are shared between address spaces, only executions can be scoped.
+        if !self.full_trace && emulator_modules.get::<AddressSpaceModule>().is_some() {
+            log::info!("Address space scoping enabled, switching DrCov to full trace.");
+            self.full_trace = true;
+        }
+
         if self.full_trace {
             emulator_modules.blocks(
                 Hook::Function(gen_unique_block_ids::<ET, F, I, S>),
@@ -0,0 +1,223 @@
+use capstone::prelude::*;
+use libafl::HasMetadata;
+use libafl_qemu_sys::GuestAddr;
+
+use super::{
+    helpers::{
+        enter_call_context, gen_hashed_block_ids, leave_call_context, reset_call_context,
+        trace_block_transition_ctx_hitcount, trace_block_transition_ctx_single,
+    },
+    EdgeCoverageVariant,
+};
+use crate::{
+    capstone,
+    modules::{
+        calls::find_block_calls,
+        utils::filters::{StdAddressFilter, StdPageFilter},
+        AddressFilter, EdgeCoverageModule, EdgeCoverageModuleBuilder, EmulatorModuleTuple,
+        PageFilter,
+    },
+    qemu::ArchExtras,
+    EmulatorModules, Hook, Qemu,
+};
+
+/// Calling-context sensitive edge coverage, as `CtxHook` does for source-instrumented targets.
+///
+/// Edges are combined with a hash of the call sites of the current call stack. The call stack is
+/// shadowed by hooking call and return instructions, like the [`crate::modules::CallTracerModule`].
+#[derive(Debug)]
+pub struct EdgeCoverageCtxVariant {
+    cs: Capstone,
+}
+
+impl EdgeCoverageCtxVariant {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            cs: capstone().detail(true).build().unwrap(),
+        }
+    }
+}
+
+impl Default for EdgeCoverageCtxVariant {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type StdEdgeCoverageCtxModule =
+    EdgeCoverageModule<StdAddressFilter, StdPageFilter, EdgeCoverageCtxVariant, false, 0>;
+pub type StdEdgeCoverageCtxModuleBuilder = EdgeCoverageModuleBuilder<
+    StdAddressFilter,
+    StdPageFilter,
+    EdgeCoverageCtxVariant,
+    false,
+    false,
+    0,
+>;
+
+impl EdgeCoverageCtxVariant {
+    fn hook_calls<AF, ET, PF, I, S, const IS_CONST_MAP: bool, const MAP_SIZE: usize>(
+        emulator_modules: &mut EmulatorModules<ET, I, S>,
+    ) where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<I, S>,
+        PF: PageFilter,
+        I: Unpin,
+        S: HasMetadata + Unpin,
+    {
+        emulator_modules.blocks(
+            Hook::Function(gen_ctx_calls::<AF, ET, PF, I, S, IS_CONST_MAP, MAP_SIZE>),
+            Hook::Empty,
+            Hook::Empty,
+        );
+    }
+}
+
+impl<AF, PF, const IS_CONST_MAP: bool, const MAP_SIZE: usize>
+    EdgeCoverageVariant<AF, PF, IS_CONST_MAP, MAP_SIZE> for EdgeCoverageCtxVariant
+{
+    const DO_SIDE_EFFECTS: bool = false;
+
+    fn fn_hitcount<ET, I, S>(&mut self, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<I, S>,
+        PF: PageFilter,
+        I: Unpin,
+        S: HasMetadata + Unpin,
+    {
+        Self::hook_calls::<AF, ET, PF, I, S, IS_CONST_MAP, MAP_SIZE>(emulator_modules);
+        emulator_modules.blocks(
+            Hook::Function(gen_hashed_block_ids::<AF, ET, PF, I, S, Self, IS_CONST_MAP, MAP_SIZE>),
+            Hook::Empty,
+            Hook::Raw(trace_block_transition_ctx_hitcount),
+        );
+    }
+
+    fn fn_no_hitcount<ET, I, S>(&mut self, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<I, S>,
+        PF: PageFilter,
+        I: Unpin,
+        S: HasMetadata + Unpin,
+    {
+        Self::hook_calls::<AF, ET, PF, I, S, IS_CONST_MAP, MAP_SIZE>(emulator_modules);
+        emulator_modules.blocks(
+            Hook::Function(gen_hashed_block_ids::<AF, ET, PF, I, S, Self, IS_CONST_MAP, MAP_SIZE>),
+            Hook::Empty,
+            Hook::Raw(trace_block_transition_ctx_single),
+        );
+    }
+
+    fn pre_exec(&mut self) {
+        reset_call_context();
+    }
+}
+
+/// Hooks the calls and the return of each translated block, to keep track of the calling context.
+#[allow(clippy::needless_pass_by_value)] // no longer a problem with nightly
+fn gen_ctx_calls<AF, ET, PF, I, S, const IS_CONST_MAP: bool, const MAP_SIZE: usize>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    AF: AddressFilter,
+    ET: EmulatorModuleTuple<I, S>,
+    PF: PageFilter,
+    I: Unpin,
+    S: HasMetadata + Unpin,
+{
+    let (call_addrs, ret_addrs) = {
+        let module = emulator_modules.get_mut::<EdgeCoverageModule<
+            AF,
+            PF,
+            EdgeCoverageCtxVariant,
+            IS_CONST_MAP,
+            MAP_SIZE,
+        >>()?;
+
+        #[cfg(feature = "usermode")]
+        if !module.must_instrument(pc) {
+            return None;
+        }
+        #[cfg(feature = "systemmode")]
+        {
+            let paging_id = qemu.current_cpu().and_then(|cpu| cpu.current_paging_id());
+            if !module.must_instrument(pc, paging_id) {
+                return None;
+            }
+        }
+
+        #[cfg(cpu_target = "arm")]
+        module
+            .variant
+            .cs
+            .set_mode(if pc & 1 == 1 {
+                arch::arm::ArchMode::Thumb.into()
+            } else {
+                arch::arm::ArchMode::Arm.into()
+            })
+            .unwrap();
+
+        find_block_calls(qemu, &module.variant.cs, pc)?
+    };
+
+    for (call_addr, call_len) in call_addrs {
+        let call_cb = Box::new(
+            move |_qemu: Qemu,
+                  _emulator_modules: &mut EmulatorModules<ET, I, S>,
+                  _state: Option<&mut S>,
+                  pc: GuestAddr| {
+                // TODO handle Thumb
+                #[allow(clippy::unnecessary_cast)] // dependent on the target instruction size
+                enter_call_context(pc, pc + call_len as GuestAddr);
+            },
+        );
+        emulator_modules.instruction_closure(call_addr, call_cb, false);
+    }
+
+    for ret_addr in ret_addrs {
+        emulator_modules.instruction_function(ret_addr, on_ret_ctx::<ET, I, S>, false);
+    }
+
+    None
+}
+
+#[allow(clippy::needless_pass_by_value)] // no longer a problem with nightly
+fn on_ret_ctx<ET, I, S>(
+    qemu: Qemu,
+    _emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    if let Ok(ret_addr) = qemu.read_return_address() {
+        leave_call_context(ret_addr);
+    }
+}
+
+impl Default for StdEdgeCoverageCtxModuleBuilder {
+    fn default() -> Self {
+        Self {
+            variant: EdgeCoverageCtxVariant::new(),
+            address_filter: StdAddressFilter::default(),
+            page_filter: StdPageFilter::default(),
+            use_hitcounts: true,
+            use_jit: true,
+        }
+    }
+}
+
+impl StdEdgeCoverageCtxModule {
+    #[must_use]
+    pub fn builder() -> StdEdgeCoverageCtxModuleBuilder {
+        EdgeCoverageModuleBuilder::default().jit(false)
+    }
+}
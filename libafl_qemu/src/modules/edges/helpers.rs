@@ -7,8 +7,12 @@ use libafl_qemu_sys::GuestAddr;
 use serde::{Deserialize, Serialize};
 /// Tracers, responsible for propagating an ID in a map.
 pub use tracers::{
-    trace_block_transition_hitcount, trace_block_transition_single, trace_edge_hitcount,
-    trace_edge_hitcount_ptr, trace_edge_single, trace_edge_single_ptr,
+    enter_call_context, leave_call_context, reset_call_context, reset_ngram,
+    trace_block_transition_ctx_hitcount, trace_block_transition_ctx_single,
+    trace_block_transition_hitcount, trace_block_transition_ngram_hitcount,
+    trace_block_transition_ngram_single, trace_block_transition_single, trace_edge_hitcount,
+    trace_edge_hitcount_ptr, trace_edge_single, trace_edge_single_ptr, CALL_CTX_MAX_DEPTH,
+    CALL_CTX_MAX_UNWIND, NGRAM_MAX_SIZE,
 };
 
 // Constants used for variable-length maps
@@ -291,17 +295,43 @@ mod generators {
 mod tracers {
     use std::cell::UnsafeCell;
 
+    use libafl_bolts::hash_64_fast;
+    use libafl_qemu_sys::GuestAddr;
     use libafl_targets::EDGES_MAP;
 
     use super::{LIBAFL_QEMU_EDGES_MAP_MASK_MAX, LIBAFL_QEMU_EDGES_MAP_PTR};
+    #[cfg(feature = "systemmode")]
+    use crate::modules::address_space_active;
+
+    /// The maximum number of blocks in an n-gram.
+    pub const NGRAM_MAX_SIZE: usize = 16;
 
     thread_local!(static PREV_LOC : UnsafeCell<u64> = const { UnsafeCell::new(0) });
 
+    thread_local!(static PREV_LOCS : UnsafeCell<[u64; NGRAM_MAX_SIZE]> = const { UnsafeCell::new([0; NGRAM_MAX_SIZE]) });
+
+    thread_local!(static CALL_CTX : UnsafeCell<u64> = const { UnsafeCell::new(0) });
+
+    /// The maximum number of calls kept in the calling context. The oldest calls are dropped
+    /// first, e.g. on deep recursion or when their frames were left without a ret.
+    pub const CALL_CTX_MAX_DEPTH: usize = 256;
+
+    /// The maximum number of calls unwound by a single return.
+    pub const CALL_CTX_MAX_UNWIND: usize = 16;
+
+    /// The return addresses of the current calls, with the calling context to restore.
+    thread_local!(static CALL_CTX_STACK : UnsafeCell<Vec<(GuestAddr, u64)>> = const { UnsafeCell::new(Vec::new()) });
+
     /// # Safety
     ///
     /// - @id should be the one generated by a gen_* function from this module.
     /// - Calling this concurrently for the same id is racey and may lose updates.
     pub unsafe extern "C" fn trace_edge_hitcount(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
         unsafe {
             EDGES_MAP[id as usize] = EDGES_MAP[id as usize].wrapping_add(1);
         }
@@ -311,6 +341,11 @@ mod tracers {
     ///
     /// - @id should be the one generated by a gen_* function from this module.
     pub unsafe extern "C" fn trace_edge_single(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
         // # Safety
         // Worst case we set the byte to 1 multiple times..
         unsafe {
@@ -322,6 +357,11 @@ mod tracers {
     ///
     /// Increases id at `EDGES_MAP_PTR` - potentially racey if called concurrently.
     pub unsafe extern "C" fn trace_edge_hitcount_ptr(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
         unsafe {
             let ptr = LIBAFL_QEMU_EDGES_MAP_PTR.add(id as usize);
             *ptr = (*ptr).wrapping_add(1);
@@ -333,6 +373,11 @@ mod tracers {
     /// Fine.
     /// Worst case we set the byte to 1 multiple times.
     pub unsafe extern "C" fn trace_edge_single_ptr(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
         unsafe {
             let ptr = LIBAFL_QEMU_EDGES_MAP_PTR.add(id as usize);
             *ptr = 1;
@@ -343,6 +388,11 @@ mod tracers {
     ///
     /// Dereferences the global `PREV_LOC` variable. May not be called concurrently.
     pub unsafe extern "C" fn trace_block_transition_hitcount(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
         unsafe {
             PREV_LOC.with(|prev_loc| {
                 let x = ((*prev_loc.get() ^ id) as usize) & LIBAFL_QEMU_EDGES_MAP_MASK_MAX;
@@ -357,6 +407,11 @@ mod tracers {
     ///
     /// Dereferences the global `PREV_LOC` variable. May not be called concurrently.
     pub unsafe extern "C" fn trace_block_transition_single(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
         unsafe {
             PREV_LOC.with(|prev_loc| {
                 let x = ((*prev_loc.get() ^ id) as usize) & LIBAFL_QEMU_EDGES_MAP_MASK_MAX;
@@ -366,4 +421,259 @@ mod tracers {
             });
         }
     }
+
+    /// Forgets the previous blocks of the current n-gram.
+    pub fn reset_ngram() {
+        PREV_LOCS.with(|prev_locs| unsafe { *prev_locs.get() = [0; NGRAM_MAX_SIZE] });
+    }
+
+    /// Combines `id` with the `N - 1` previous blocks, and shifts it into the history.
+    ///
+    /// # Safety
+    ///
+    /// Dereferences the global `PREV_LOCS` variable. May not be called concurrently.
+    #[inline]
+    unsafe fn update_ngram<const N: usize>(id: u64) -> usize {
+        const {
+            assert!(
+                N >= 2 && N <= NGRAM_MAX_SIZE,
+                "The size of an n-gram should be between 2 and NGRAM_MAX_SIZE."
+            );
+        };
+
+        PREV_LOCS.with(|prev_locs| unsafe {
+            let prev_locs = &mut *prev_locs.get();
+            let history = &mut prev_locs[..N - 1];
+
+            let x = history.iter().fold(id, |acc, prev| acc ^ prev) as usize
+                & LIBAFL_QEMU_EDGES_MAP_MASK_MAX;
+
+            history.rotate_right(1);
+            for prev in history.iter_mut() {
+                *prev = prev.overflowing_shl(1).0;
+            }
+            history[0] = id.overflowing_shr(1).0;
+
+            x
+        })
+    }
+
+    /// # Safety
+    ///
+    /// Dereferences the global `PREV_LOCS` variable. May not be called concurrently.
+    pub unsafe extern "C" fn trace_block_transition_ngram_hitcount<const N: usize>(
+        _: *const (),
+        id: u64,
+    ) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
+        unsafe {
+            let entry = LIBAFL_QEMU_EDGES_MAP_PTR.add(update_ngram::<N>(id));
+            *entry = (*entry).wrapping_add(1);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Dereferences the global `PREV_LOCS` variable. May not be called concurrently.
+    pub unsafe extern "C" fn trace_block_transition_ngram_single<const N: usize>(
+        _: *const (),
+        id: u64,
+    ) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
+        unsafe {
+            let entry = LIBAFL_QEMU_EDGES_MAP_PTR.add(update_ngram::<N>(id));
+            *entry = 1;
+        }
+    }
+
+    /// Forgets the current calling context.
+    pub fn reset_call_context() {
+        PREV_LOC.with(|prev_loc| unsafe { *prev_loc.get() = 0 });
+        CALL_CTX.with(|ctx| unsafe { *ctx.get() = 0 });
+        CALL_CTX_STACK.with(|stack| unsafe { (*stack.get()).clear() });
+    }
+
+    /// Adds the call at `call_site`, returning to `ret_addr`, to the calling context.
+    pub fn enter_call_context(call_site: GuestAddr, ret_addr: GuestAddr) {
+        CALL_CTX.with(|ctx| {
+            CALL_CTX_STACK.with(|stack| unsafe {
+                let stack = &mut *stack.get();
+                if stack.len() >= CALL_CTX_MAX_DEPTH {
+                    stack.remove(0);
+                }
+                stack.push((ret_addr, *ctx.get()));
+                #[allow(clippy::unnecessary_cast)] // for GuestAddr -> u64
+                {
+                    *ctx.get() ^= hash_64_fast(call_site as u64);
+                }
+            });
+        });
+    }
+
+    /// Restores the calling context of the caller returned to.
+    ///
+    /// Up to [`CALL_CTX_MAX_UNWIND`] calls are searched, a return to none of them is ignored.
+    pub fn leave_call_context(ret_addr: GuestAddr) {
+        CALL_CTX.with(|ctx| {
+            CALL_CTX_STACK.with(|stack| unsafe {
+                let stack = &mut *stack.get();
+                // Unwind the frames left without a ret, e.g. by longjmp
+                if let Some(pos) = stack
+                    .iter()
+                    .rev()
+                    .take(CALL_CTX_MAX_UNWIND)
+                    .position(|(addr, _)| *addr == ret_addr)
+                {
+                    let idx = stack.len() - 1 - pos;
+                    *ctx.get() = stack[idx].1;
+                    stack.truncate(idx);
+                }
+            });
+        });
+    }
+
+    /// # Safety
+    ///
+    /// Dereferences the global `PREV_LOC` and `CALL_CTX` variables. May not be called concurrently.
+    pub unsafe extern "C" fn trace_block_transition_ctx_hitcount(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
+        unsafe {
+            PREV_LOC.with(|prev_loc| {
+                CALL_CTX.with(|ctx| {
+                    let x = ((*prev_loc.get() ^ *ctx.get() ^ id) as usize)
+                        & LIBAFL_QEMU_EDGES_MAP_MASK_MAX;
+                    let entry = LIBAFL_QEMU_EDGES_MAP_PTR.add(x);
+                    *entry = (*entry).wrapping_add(1);
+                    *prev_loc.get() = id.overflowing_shr(1).0;
+                });
+            });
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Dereferences the global `PREV_LOC` and `CALL_CTX` variables. May not be called concurrently.
+    pub unsafe extern "C" fn trace_block_transition_ctx_single(_: *const (), id: u64) {
+        #[cfg(feature = "systemmode")]
+        if !address_space_active() {
+            return;
+        }
+
+        unsafe {
+            PREV_LOC.with(|prev_loc| {
+                CALL_CTX.with(|ctx| {
+                    let x = ((*prev_loc.get() ^ *ctx.get() ^ id) as usize)
+                        & LIBAFL_QEMU_EDGES_MAP_MASK_MAX;
+                    let entry = LIBAFL_QEMU_EDGES_MAP_PTR.add(x);
+                    *entry = 1;
+                    *prev_loc.get() = id.overflowing_shr(1).0;
+                });
+            });
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use libafl_bolts::hash_64_fast;
+        use libafl_qemu_sys::GuestAddr;
+        use libafl_targets::EDGES_MAP_DEFAULT_SIZE;
+
+        use super::{
+            enter_call_context, leave_call_context, reset_call_context, reset_ngram, update_ngram,
+            CALL_CTX, CALL_CTX_MAX_DEPTH, CALL_CTX_MAX_UNWIND, CALL_CTX_STACK,
+            LIBAFL_QEMU_EDGES_MAP_MASK_MAX,
+        };
+
+        fn call_context() -> (u64, usize) {
+            CALL_CTX.with(|ctx| {
+                CALL_CTX_STACK.with(|stack| unsafe { (*ctx.get(), (*stack.get()).len()) })
+            })
+        }
+
+        fn ngram(ids: &[u64]) -> Vec<usize> {
+            // Same mask as the one set by the modules built in the other tests
+            unsafe {
+                LIBAFL_QEMU_EDGES_MAP_MASK_MAX = EDGES_MAP_DEFAULT_SIZE - 1;
+            }
+            reset_ngram();
+            ids.iter()
+                .map(|id| unsafe { update_ngram::<3>(*id) })
+                .collect()
+        }
+
+        #[test]
+        fn test_update_ngram() {
+            let a = ngram(&[0x100, 0x20, 0x30, 0x40]);
+            assert_eq!(a[0], 0x100);
+            assert_eq!(a[1], 0x20 ^ 0x80);
+            assert_eq!(a[2], 0x30 ^ 0x10 ^ 0x100);
+
+            // the first block changes the hash while it is in the window, then is forgotten
+            let b = ngram(&[0x200, 0x20, 0x30, 0x40]);
+            assert_ne!(a[1], b[1]);
+            assert_ne!(a[2], b[2]);
+            assert_eq!(a[3], b[3]);
+        }
+
+        #[test]
+        fn test_call_context() {
+            reset_call_context();
+            let site_a = hash_64_fast(0x1000);
+            let site_b = hash_64_fast(0x2000);
+
+            enter_call_context(0x1000, 0x1005);
+            enter_call_context(0x2000, 0x2005);
+            assert_eq!(call_context(), (site_a ^ site_b, 2));
+
+            leave_call_context(0x2005);
+            assert_eq!(call_context(), (site_a, 1));
+
+            // a ret to none of the calls is ignored
+            leave_call_context(0x9999);
+            assert_eq!(call_context(), (site_a, 1));
+
+            // the frames left without a ret are unwound
+            enter_call_context(0x2000, 0x2005);
+            enter_call_context(0x3000, 0x3005);
+            leave_call_context(0x1005);
+            assert_eq!(call_context(), (0, 0));
+        }
+
+        #[test]
+        fn test_call_context_bounds() {
+            reset_call_context();
+
+            // the oldest calls are dropped
+            for i in 0..=CALL_CTX_MAX_DEPTH as GuestAddr {
+                enter_call_context(0x1000 + i * 0x10, 0x1005 + i * 0x10);
+            }
+            assert_eq!(call_context().1, CALL_CTX_MAX_DEPTH);
+            CALL_CTX_STACK.with(|stack| unsafe { assert_eq!((*stack.get())[0].0, 0x1015) });
+            let ctx = call_context();
+            leave_call_context(0x1005);
+            assert_eq!(call_context(), ctx);
+
+            // only the most recent calls are unwound
+            reset_call_context();
+            enter_call_context(0x1000, 0x1005);
+            for i in 0..CALL_CTX_MAX_UNWIND as GuestAddr {
+                enter_call_context(0x2000 + i * 0x10, 0x2005 + i * 0x10);
+            }
+            let ctx = call_context();
+            leave_call_context(0x1005);
+            assert_eq!(call_context(), ctx);
+        }
+    }
 }
@@ -6,6 +6,8 @@ use libafl_qemu_sys::GuestAddr;
 #[cfg(feature = "systemmode")]
 use libafl_qemu_sys::GuestPhysAddr;
 
+#[cfg(feature = "systemmode")]
+use crate::modules::AddressSpaceModule;
 use crate::{
     emu::EmulatorModules,
     modules::{AddressFilter, EmulatorModule, EmulatorModuleTuple, PageFilter},
@@ -32,6 +34,16 @@ pub mod child;
 pub use child::{
     EdgeCoverageChildVariant, StdEdgeCoverageChildModule, StdEdgeCoverageChildModuleBuilder,
 };
+
+pub mod ngram;
+pub use ngram::{
+    EdgeCoverageNgramVariant, StdEdgeCoverageNgramModule, StdEdgeCoverageNgramModuleBuilder,
+};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod ctx;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use ctx::{EdgeCoverageCtxVariant, StdEdgeCoverageCtxModule, StdEdgeCoverageCtxModuleBuilder};
 use libafl::observers::ConstLenMapObserver;
 
 use super::utils::filters::HasAddressFilter;
@@ -94,6 +106,9 @@ trait EdgeCoverageVariant<AF, PF, const IS_CONST_MAP: bool, const MAP_SIZE: usiz
     {
         panic!("Func no hitcount is not supported.")
     }
+
+    /// Resets the per-run state of the variant, if any.
+    fn pre_exec(&mut self) {}
 }
 
 #[derive(Debug)]
@@ -348,18 +363,41 @@ where
     ) where
         ET: EmulatorModuleTuple<I, S>,
     {
+        // JIT tracers are inlined by QEMU and cannot check the active address space.
+        #[cfg(feature = "systemmode")]
+        let use_jit = if self.use_jit && emulator_modules.get::<AddressSpaceModule>().is_some() {
+            log::info!("Address space scoping enabled, falling back to non-JIT edge tracers.");
+            false
+        } else {
+            self.use_jit
+        };
+        #[cfg(feature = "usermode")]
+        let use_jit = self.use_jit;
+
         if self.use_hitcounts {
-            if self.use_jit {
+            if use_jit {
                 self.variant.jit_hitcount(emulator_modules);
             } else {
                 self.variant.fn_hitcount(emulator_modules);
             }
-        } else if self.use_jit {
+        } else if use_jit {
             self.variant.jit_no_hitcount(emulator_modules);
         } else {
             self.variant.fn_no_hitcount(emulator_modules);
         }
     }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.variant.pre_exec();
+    }
 }
 
 impl<AF, PF, V, const IS_CONST_MAP: bool, const MAP_SIZE: usize> HasAddressFilter
@@ -398,7 +436,9 @@ mod tests {
     use libafl_bolts::ownedref::OwnedMutSlice;
     use libafl_targets::{edges_map_mut_ptr, EDGES_MAP_DEFAULT_SIZE, MAX_EDGES_FOUND};
 
-    use crate::modules::StdEdgeCoverageModule;
+    use crate::modules::{
+        StdEdgeCoverageCtxModule, StdEdgeCoverageModule, StdEdgeCoverageNgramModule,
+    };
 
     /// The test is actually implemented as a doctest, since Rust does not
     /// permit tests that must not compile by default...
@@ -427,4 +467,38 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    pub fn does_build_ngram() {
+        let mut edges_observer = unsafe {
+            HitcountsMapObserver::new(VariableMapObserver::from_mut_slice(
+                "edges",
+                OwnedMutSlice::from_raw_parts_mut(edges_map_mut_ptr(), EDGES_MAP_DEFAULT_SIZE),
+                &raw mut MAX_EDGES_FOUND,
+            ))
+            .track_indices()
+        };
+
+        StdEdgeCoverageNgramModule::<4>::builder()
+            .map_observer(edges_observer.as_mut())
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    pub fn does_build_ctx() {
+        let mut edges_observer = unsafe {
+            HitcountsMapObserver::new(VariableMapObserver::from_mut_slice(
+                "edges",
+                OwnedMutSlice::from_raw_parts_mut(edges_map_mut_ptr(), EDGES_MAP_DEFAULT_SIZE),
+                &raw mut MAX_EDGES_FOUND,
+            ))
+            .track_indices()
+        };
+
+        StdEdgeCoverageCtxModule::builder()
+            .map_observer(edges_observer.as_mut())
+            .build()
+            .unwrap();
+    }
 }
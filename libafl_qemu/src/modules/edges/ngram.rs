@@ -0,0 +1,94 @@
+use libafl::HasMetadata;
+
+use super::{
+    helpers::{
+        gen_hashed_block_ids, reset_ngram, trace_block_transition_ngram_hitcount,
+        trace_block_transition_ngram_single,
+    },
+    EdgeCoverageVariant,
+};
+use crate::{
+    modules::{
+        utils::filters::{StdAddressFilter, StdPageFilter},
+        AddressFilter, EdgeCoverageModule, EdgeCoverageModuleBuilder, EmulatorModuleTuple,
+        PageFilter,
+    },
+    EmulatorModules, Hook,
+};
+
+/// N-gram edge coverage: each entry of the map is a sequence of `N` executed blocks, as
+/// `NgramHook` does for source-instrumented targets.
+///
+/// `N` should be between 2 and [`super::helpers::NGRAM_MAX_SIZE`]. 2 is the classic edge coverage.
+#[derive(Debug)]
+pub struct EdgeCoverageNgramVariant<const N: usize>;
+
+pub type StdEdgeCoverageNgramModule<const N: usize> =
+    EdgeCoverageModule<StdAddressFilter, StdPageFilter, EdgeCoverageNgramVariant<N>, false, 0>;
+pub type StdEdgeCoverageNgramModuleBuilder<const N: usize> = EdgeCoverageModuleBuilder<
+    StdAddressFilter,
+    StdPageFilter,
+    EdgeCoverageNgramVariant<N>,
+    false,
+    false,
+    0,
+>;
+
+impl<AF, PF, const IS_CONST_MAP: bool, const MAP_SIZE: usize, const N: usize>
+    EdgeCoverageVariant<AF, PF, IS_CONST_MAP, MAP_SIZE> for EdgeCoverageNgramVariant<N>
+{
+    const DO_SIDE_EFFECTS: bool = false;
+
+    fn fn_hitcount<ET, I, S>(&mut self, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<I, S>,
+        PF: PageFilter,
+        I: Unpin,
+        S: HasMetadata + Unpin,
+    {
+        emulator_modules.blocks(
+            Hook::Function(gen_hashed_block_ids::<AF, ET, PF, I, S, Self, IS_CONST_MAP, MAP_SIZE>),
+            Hook::Empty,
+            Hook::Raw(trace_block_transition_ngram_hitcount::<N>),
+        );
+    }
+
+    fn fn_no_hitcount<ET, I, S>(&mut self, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<I, S>,
+        PF: PageFilter,
+        I: Unpin,
+        S: HasMetadata + Unpin,
+    {
+        emulator_modules.blocks(
+            Hook::Function(gen_hashed_block_ids::<AF, ET, PF, I, S, Self, IS_CONST_MAP, MAP_SIZE>),
+            Hook::Empty,
+            Hook::Raw(trace_block_transition_ngram_single::<N>),
+        );
+    }
+
+    fn pre_exec(&mut self) {
+        reset_ngram();
+    }
+}
+
+impl<const N: usize> Default for StdEdgeCoverageNgramModuleBuilder<N> {
+    fn default() -> Self {
+        Self {
+            variant: EdgeCoverageNgramVariant,
+            address_filter: StdAddressFilter::default(),
+            page_filter: StdPageFilter::default(),
+            use_hitcounts: true,
+            use_jit: true,
+        }
+    }
+}
+
+impl<const N: usize> StdEdgeCoverageNgramModule<N> {
+    #[must_use]
+    pub fn builder() -> StdEdgeCoverageNgramModuleBuilder<N> {
+        EdgeCoverageModuleBuilder::default().jit(false)
+    }
+}
@@ -18,7 +18,6 @@ pub use usermode::*;
 #[cfg(feature = "systemmode")]
 pub mod systemmode;
 #[cfg(feature = "systemmode")]
-#[expect(unused_imports)]
 pub use systemmode::*;
 
 pub mod edges;
@@ -27,7 +26,10 @@ pub use edges::{
     StdEdgeCoverageChildModuleBuilder, StdEdgeCoverageClassicModule,
     StdEdgeCoverageClassicModuleBuilder, StdEdgeCoverageFullModule,
     StdEdgeCoverageFullModuleBuilder, StdEdgeCoverageModule, StdEdgeCoverageModuleBuilder,
+    StdEdgeCoverageNgramModule, StdEdgeCoverageNgramModuleBuilder,
 };
+#[cfg(not(cpu_target = "hexagon"))]
+pub use edges::{StdEdgeCoverageCtxModule, StdEdgeCoverageCtxModuleBuilder};
 
 #[cfg(not(cpu_target = "hexagon"))]
 pub mod calls;
@@ -39,6 +41,11 @@ pub mod cmplog;
 #[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
 pub use cmplog::CmpLogModule;
 
+#[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
+pub mod taint;
+#[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
+pub use taint::{TaintModule, TaintShadow};
+
 #[cfg(not(cpu_target = "hexagon"))]
 pub mod drcov;
 #[cfg(not(cpu_target = "hexagon"))]
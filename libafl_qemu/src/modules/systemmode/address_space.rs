@@ -0,0 +1,295 @@
+//! Address space module
+//!
+//! In full-system mode, translated blocks are shared by every process and by the kernel, so
+//! filtering at translation time cannot scope coverage to a single guest process.
+//! The [`AddressSpaceModule`] follows the paging id of the running CPU (CR3 on x86, TTBR on
+//! aarch64) at block execution, and tells whether the selected context is currently running.
+//!
+//! The tracers of [`crate::modules::EdgeCoverageModule`], [`crate::modules::CmpLogModule`] and
+//! [`crate::modules::DrCovModule`] check [`address_space_active`] before recording anything.
+//! Paging ids are selected either with [`AddressSpaceModule::paging_id`] or at runtime, with
+//! the `SubmitCR3Command` (or any command calling `allow_page_id_all` on the modules).
+//! As long as no paging id is registered, every address space is considered selected.
+//!
+//! On x86, the PCID bits of CR3 are ignored, and kernel code running on the KPTI sibling of a
+//! selected page directory is accounted to the selected address space.
+
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use libafl_qemu_sys::{GuestAddr, GuestPhysAddr};
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        utils::filters::{HasAddressFilter, NopAddressFilter, StdPageFilter},
+        EmulatorModule, EmulatorModuleTuple, PageFilter,
+    },
+    qemu::Hook,
+    Qemu,
+};
+
+/// The bits of a paging id not part of the page directory address (the PCID, on x86).
+#[cfg(any(feature = "x86_64", feature = "i386"))]
+const PAGING_ID_FLAGS_MASK: GuestPhysAddr = 0xfff;
+#[cfg(not(any(feature = "x86_64", feature = "i386")))]
+const PAGING_ID_FLAGS_MASK: GuestPhysAddr = 0;
+
+/// The offset between the kernel and the user page directories of a process with KPTI.
+#[cfg(any(feature = "x86_64", feature = "i386"))]
+const KPTI_PGD_OFFSET: Option<GuestPhysAddr> = Some(0x1000);
+#[cfg(not(any(feature = "x86_64", feature = "i386")))]
+const KPTI_PGD_OFFSET: Option<GuestPhysAddr> = None;
+
+/// Whether the context selected by the [`AddressSpaceModule`] is the one currently executing.
+///
+/// It stays `true` if no [`AddressSpaceModule`] is used.
+static ADDRESS_SPACE_ACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// Returns `true` if the guest is currently running in the context selected by the
+/// [`AddressSpaceModule`], or if there is no such module.
+///
+/// The state is updated at the beginning of each executed block, so an edge leading to the
+/// first block of a new context is still accounted to the previous one.
+#[must_use]
+#[inline]
+pub fn address_space_active() -> bool {
+    ADDRESS_SPACE_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// The execution contexts kept by the [`AddressSpaceModule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpaceContext {
+    /// Only the user-space code of the selected processes.
+    Process,
+    /// Only the kernel code running in the address space of the selected processes.
+    Kernel,
+    /// Both the user-space and the kernel code running in the selected address spaces.
+    ProcessAndKernel,
+}
+
+/// A page filter comparing paging ids without their flag bits, such as the PCID on x86.
+#[derive(Debug, Default)]
+pub struct PagingIdFilter(StdPageFilter);
+
+impl PageFilter for PagingIdFilter {
+    fn register(&mut self, page_id: GuestPhysAddr) {
+        self.0.register(page_id & !PAGING_ID_FLAGS_MASK);
+    }
+
+    fn allowed(&self, page_id: &GuestPhysAddr) -> bool {
+        self.0.allowed(&(page_id & !PAGING_ID_FLAGS_MASK))
+    }
+}
+
+/// Scopes the coverage modules to a set of guest address spaces, and to the user-space or
+/// kernel code running in them.
+///
+/// The selected context is checked by a block execution hook, and hooks run in the order they
+/// are registered. This module must thus be placed **before** the coverage modules in the
+/// modules tuple, otherwise they see the state of the previous block.
+#[derive(Debug)]
+pub struct AddressSpaceModule {
+    context: AddressSpaceContext,
+    kernel_range: Range<GuestAddr>,
+    address_filter: NopAddressFilter,
+    page_filter: PagingIdFilter,
+}
+
+impl AddressSpaceModule {
+    /// Creates a new [`AddressSpaceModule`] keeping the given `context`.
+    ///
+    /// By default, the kernel is considered to live in the upper half of the address space.
+    #[must_use]
+    pub fn new(context: AddressSpaceContext) -> Self {
+        Self {
+            context,
+            kernel_range: (1 << (GuestAddr::BITS - 1))..GuestAddr::MAX,
+            address_filter: NopAddressFilter,
+            page_filter: PagingIdFilter::default(),
+        }
+    }
+
+    /// Sets the range of guest addresses considered to be kernel code.
+    #[must_use]
+    pub fn kernel_range(mut self, kernel_range: Range<GuestAddr>) -> Self {
+        self.kernel_range = kernel_range;
+        self
+    }
+
+    /// Selects an address space, by its paging id.
+    ///
+    /// On x86, the PCID bits of the id are ignored.
+    #[must_use]
+    pub fn paging_id(mut self, paging_id: GuestPhysAddr) -> Self {
+        self.page_filter.register(paging_id);
+        self
+    }
+
+    /// The execution context kept by this module.
+    #[must_use]
+    pub fn context(&self) -> AddressSpaceContext {
+        self.context
+    }
+
+    /// Returns `true` if a block at `pc`, executed in the address space `paging_id`, is part of
+    /// the selected context.
+    #[must_use]
+    pub fn is_selected(&self, pc: GuestAddr, paging_id: Option<GuestPhysAddr>) -> bool {
+        let in_kernel = self.kernel_range.contains(&pc);
+
+        let space_selected = paging_id.is_none_or(|paging_id| {
+            self.page_filter.allowed(&paging_id)
+                || (in_kernel
+                    && self.context != AddressSpaceContext::Process
+                    && KPTI_PGD_OFFSET
+                        .is_some_and(|offset| self.page_filter.allowed(&(paging_id ^ offset))))
+        });
+        if !space_selected {
+            return false;
+        }
+
+        match self.context {
+            AddressSpaceContext::Process => !in_kernel,
+            AddressSpaceContext::Kernel => in_kernel,
+            AddressSpaceContext::ProcessAndKernel => true,
+        }
+    }
+}
+
+impl<I, S> EmulatorModule<I, S> for AddressSpaceModule
+where
+    I: Unpin,
+    S: Unpin,
+{
+    fn post_qemu_init<ET>(&mut self, _qemu: Qemu, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        // Only runs before the coverage modules hooks if the module is placed before them.
+        emulator_modules.blocks(
+            Hook::Function(gen_address_space_block::<ET, I, S>),
+            Hook::Empty,
+            Hook::Function(exec_address_space_block::<ET, I, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        ADDRESS_SPACE_ACTIVE.store(true, Ordering::Relaxed);
+    }
+}
+
+impl HasAddressFilter for AddressSpaceModule {
+    type ModuleAddressFilter = NopAddressFilter;
+    type ModulePageFilter = PagingIdFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.address_filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.address_filter
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &self.page_filter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        &mut self.page_filter
+    }
+}
+
+/// Uses the block pc as id, to get it back at execution.
+#[allow(clippy::needless_pass_by_value)] // no longer a problem with nightly
+pub fn gen_address_space_block<ET, I, S>(
+    _qemu: Qemu,
+    _emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    #[allow(clippy::unnecessary_cast)] // for GuestAddr -> u64
+    Some(pc as u64)
+}
+
+#[allow(clippy::needless_pass_by_value)] // no longer a problem with nightly
+pub fn exec_address_space_block<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let paging_id = qemu.current_cpu().and_then(|cpu| cpu.current_paging_id());
+    let module = emulator_modules.get::<AddressSpaceModule>().unwrap();
+
+    #[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)] // the id is a GuestAddr
+    let selected = module.is_selected(id as GuestAddr, paging_id);
+    ADDRESS_SPACE_ACTIVE.store(selected, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressSpaceContext, AddressSpaceModule};
+
+    #[test]
+    fn test_address_space_selection() {
+        let module = AddressSpaceModule::new(AddressSpaceContext::Process)
+            .kernel_range(0xc000_0000..0xffff_ffff)
+            .paging_id(0x1000);
+
+        assert!(module.is_selected(0x40_0000, Some(0x1000)));
+        assert!(!module.is_selected(0x40_0000, Some(0x2000)));
+        assert!(!module.is_selected(0xc010_0000, Some(0x1000)));
+        assert!(module.is_selected(0x40_0000, None));
+
+        let module = AddressSpaceModule::new(AddressSpaceContext::Kernel)
+            .kernel_range(0xc000_0000..0xffff_ffff)
+            .paging_id(0x1000);
+
+        assert!(module.is_selected(0xc010_0000, Some(0x1000)));
+        assert!(!module.is_selected(0xc010_0000, Some(0x2000)));
+        assert!(!module.is_selected(0x40_0000, Some(0x1000)));
+
+        // no registered paging id selects every address space
+        let module = AddressSpaceModule::new(AddressSpaceContext::ProcessAndKernel);
+        assert!(module.is_selected(0x40_0000, Some(0x2000)));
+    }
+
+    #[cfg(any(feature = "x86_64", feature = "i386"))]
+    #[test]
+    fn test_address_space_pcid_kpti() {
+        let module = AddressSpaceModule::new(AddressSpaceContext::ProcessAndKernel)
+            .kernel_range(0xc000_0000..0xffff_ffff)
+            .paging_id(0x1_1000 | 0x805);
+
+        // the PCID is ignored
+        assert!(module.is_selected(0x40_0000, Some(0x1_1000)));
+        assert!(module.is_selected(0x40_0000, Some(0x1_1000 | 0x3)));
+        // the kernel runs on the KPTI sibling of the user page directory
+        assert!(module.is_selected(0xc010_0000, Some(0x1_0000 | 0x5)));
+        assert!(!module.is_selected(0x40_0000, Some(0x1_0000)));
+        assert!(!module.is_selected(0xc010_0000, Some(0x2_0000)));
+
+        let module = AddressSpaceModule::new(AddressSpaceContext::Process).paging_id(0x1_1000);
+        assert!(!module.is_selected(0xc010_0000, Some(0x1_0000)));
+    }
+}
@@ -0,0 +1,1223 @@
+//! KASAN-style sanitizer for system mode
+//!
+//! Unlike the usermode [`AsanModule`](crate::modules::AsanModule), the [`KasanModule`] does not
+//! rely on syscalls or on a guest runtime. The allocator entry points of the target (kernel or
+//! bare-metal firmware) are registered by address or by symbol, and the shadow memory is kept
+//! host-side, one shadow byte per [`KASAN_GRANULE_SIZE`] guest bytes, with the KASAN encoding:
+//! `0` means the whole granule is accessible, `1..=7` means only the first bytes are accessible
+//! and a negative value means the granule is poisoned.
+//!
+//! Guest memory which has never been tracked is considered accessible. To catch out-of-bounds
+//! accesses between chunks, the heap ranges should be registered with [`KasanModuleBuilder::heap`]
+//! so that they start poisoned.
+//!
+//! Errors are reported with the allocation and free backtraces of the faulty chunk (if a
+//! [`crate::modules::CallTracerModule`] with a [`crate::modules::FullBacktraceCollector`] is
+//! used), and the run ends with [`ExitKind::Crash`].
+
+#![allow(clippy::cast_possible_truncation)] // shadow offsets and guest sizes fit in their casted types
+#![allow(clippy::needless_pass_by_value)] // default compiler complains about Option<&mut T> otherwise, and this is used extensively.
+
+use core::fmt;
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    ops::Range,
+};
+
+use hashbrown::{HashMap, HashSet};
+use libafl::{executors::ExitKind, observers::ObserversTuple, Error};
+use libafl_qemu_sys::GuestAddr;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::{
+    elf::EasyElf,
+    emu::EmulatorModules,
+    get_exit_arch_regs,
+    modules::{
+        calls::FullBacktraceCollector,
+        utils::filters::{HasAddressFilter, NopPageFilter, StdAddressFilter, NOP_PAGE_FILTER},
+        AddressFilter, EmulatorModule, EmulatorModuleTuple,
+    },
+    qemu::{Hook, MemAccessInfo},
+    sync_exit::ExitArgs,
+    sys::TCGTemp,
+    ArchExtras, CallingConvention, Qemu, Regs, CPU,
+};
+
+/// The number of guest bytes described by one shadow byte.
+pub const KASAN_GRANULE_SIZE: usize = 8;
+
+/// The number of guest bytes described by one shadow page.
+const KASAN_SHADOW_PAGE_SIZE: usize = 4096;
+
+/// The kind of poison stored in the shadow memory, with the same values as the Linux KASAN.
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i8)]
+pub enum KasanPoison {
+    /// Memory of a registered heap which has not been allocated yet.
+    Unallocated = -4, // 0xfc
+    /// Memory of a freed chunk.
+    Freed = -5, // 0xfb
+    /// Memory poisoned by the user.
+    User = -9, // 0xf7
+}
+
+/// The shadow memory, kept host-side.
+#[derive(Debug, Clone, Default)]
+pub struct KasanShadow {
+    pages: HashMap<GuestAddr, Box<[i8]>>,
+}
+
+impl KasanShadow {
+    fn granule_mut(&mut self, granule: GuestAddr) -> &mut i8 {
+        let page = granule & !(KASAN_SHADOW_PAGE_SIZE as GuestAddr - 1);
+        let idx = (granule - page) as usize / KASAN_GRANULE_SIZE;
+
+        &mut self.pages.entry(page).or_insert_with(|| {
+            vec![0; KASAN_SHADOW_PAGE_SIZE / KASAN_GRANULE_SIZE].into_boxed_slice()
+        })[idx]
+    }
+
+    /// The shadow byte of the granule containing `addr`.
+    #[must_use]
+    pub fn shadow(&self, addr: GuestAddr) -> i8 {
+        let page = addr & !(KASAN_SHADOW_PAGE_SIZE as GuestAddr - 1);
+        let idx = (addr - page) as usize / KASAN_GRANULE_SIZE;
+
+        self.pages
+            .get(&page)
+            .map_or(0, |shadow_page| shadow_page[idx])
+    }
+
+    /// Poisons `[addr, addr + size)`.
+    ///
+    /// As with KASAN, a granule can only be partially accessible from its start: if `addr` is not
+    /// aligned, the start of its granule stays accessible, and a poisoned granule prefix is left
+    /// accessible.
+    pub fn poison(&mut self, addr: GuestAddr, size: usize, poison: KasanPoison) {
+        let end = addr + size as GuestAddr;
+        let mut cur = addr;
+
+        while cur < end {
+            let granule = cur & !(KASAN_GRANULE_SIZE as GuestAddr - 1);
+            let granule_end = granule + KASAN_GRANULE_SIZE as GuestAddr;
+
+            if cur != granule {
+                *self.granule_mut(granule) = (cur - granule) as i8;
+            } else if end >= granule_end {
+                *self.granule_mut(granule) = poison.into();
+            }
+
+            cur = granule_end;
+        }
+    }
+
+    /// Unpoisons `[addr, addr + size)`.
+    pub fn unpoison(&mut self, addr: GuestAddr, size: usize) {
+        let end = addr + size as GuestAddr;
+        let mut cur = addr;
+
+        while cur < end {
+            let granule = cur & !(KASAN_GRANULE_SIZE as GuestAddr - 1);
+            let granule_end = granule + KASAN_GRANULE_SIZE as GuestAddr;
+
+            if end >= granule_end {
+                *self.granule_mut(granule) = 0;
+            } else {
+                let shadow = self.granule_mut(granule);
+                let accessible = (end - granule) as i8;
+                if *shadow < 0 || *shadow < accessible {
+                    *shadow = accessible;
+                }
+            }
+
+            cur = granule_end;
+        }
+    }
+
+    /// Checks an access of `size` bytes at `addr`.
+    ///
+    /// Returns the first invalid shadow byte met, if any.
+    #[must_use]
+    pub fn check(&self, addr: GuestAddr, size: usize) -> Option<i8> {
+        let end = addr + size as GuestAddr;
+        let mut cur = addr;
+
+        while cur < end {
+            let granule = cur & !(KASAN_GRANULE_SIZE as GuestAddr - 1);
+            let granule_end = granule + KASAN_GRANULE_SIZE as GuestAddr;
+            let shadow = self.shadow(granule);
+
+            if shadow < 0 {
+                return Some(shadow);
+            }
+            if shadow > 0 && end.min(granule_end) > granule + GuestAddr::from(shadow.unsigned_abs())
+            {
+                return Some(shadow);
+            }
+
+            cur = granule_end;
+        }
+
+        None
+    }
+}
+
+/// The addresses the [`KasanModule`] works on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KasanAddressKind {
+    /// Guest virtual addresses, as seen by the target.
+    Virtual,
+    /// Guest physical addresses, translated with the MMU of the current CPU.
+    ///
+    /// Chunks are tracked by their virtual address, and their shadow is kept page by page, so
+    /// they do not need to be physically contiguous.
+    Physical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KasanError {
+    /// Access to memory outside of any allocated chunk.
+    OutOfBounds {
+        addr: GuestAddr,
+        size: usize,
+        write: bool,
+    },
+    /// Access to a freed chunk.
+    UseAfterFree {
+        addr: GuestAddr,
+        size: usize,
+        write: bool,
+        chunk: Range<GuestAddr>,
+    },
+    /// Free of an already freed chunk.
+    DoubleFree {
+        addr: GuestAddr,
+        chunk: Range<GuestAddr>,
+    },
+    /// Free of a pointer which is not the start of an allocated chunk.
+    InvalidFree {
+        addr: GuestAddr,
+        chunk: Option<Range<GuestAddr>>,
+    },
+}
+
+impl KasanError {
+    /// The address of the chunk involved, if any.
+    fn chunk_addr(&self) -> Option<GuestAddr> {
+        match self {
+            KasanError::UseAfterFree { addr, .. }
+            | KasanError::DoubleFree { addr, .. }
+            | KasanError::InvalidFree {
+                addr,
+                chunk: Some(_),
+            } => Some(*addr),
+            KasanError::OutOfBounds { .. } | KasanError::InvalidFree { chunk: None, .. } => None,
+        }
+    }
+}
+
+impl Display for KasanError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let access = |write: &bool| if *write { "write" } else { "read" };
+
+        match self {
+            KasanError::OutOfBounds { addr, size, write } => {
+                write!(
+                    fmt,
+                    "Out-of-bounds {} of size {size} at {addr:#x}",
+                    access(write)
+                )
+            }
+            KasanError::UseAfterFree {
+                addr,
+                size,
+                write,
+                chunk,
+            } => write!(
+                fmt,
+                "Use-after-free {} of size {size} at {addr:#x} in the chunk {:#x}..{:#x}",
+                access(write),
+                chunk.start,
+                chunk.end
+            ),
+            KasanError::DoubleFree { addr, chunk } => write!(
+                fmt,
+                "Double free at {addr:#x} of the chunk {:#x}..{:#x}",
+                chunk.start, chunk.end
+            ),
+            KasanError::InvalidFree { addr, chunk } => match chunk {
+                Some(chunk) => write!(
+                    fmt,
+                    "Invalid free at {addr:#x} in the allocated chunk {:#x}..{:#x}",
+                    chunk.start, chunk.end
+                ),
+                None => write!(fmt, "Invalid free at {addr:#x} (wild pointer)"),
+            },
+        }
+    }
+}
+
+/// A [`KasanError`], with the context needed to investigate it.
+#[derive(Debug, Clone)]
+pub struct KasanReport {
+    pub pc: GuestAddr,
+    pub error: KasanError,
+    pub backtrace: Vec<GuestAddr>,
+    pub alloc_backtrace: Vec<GuestAddr>,
+    pub free_backtrace: Vec<GuestAddr>,
+}
+
+impl Display for KasanReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(fmt, "KASAN error: {} (pc {:#x})", self.error, self.pc)?;
+
+        for (title, backtrace) in [
+            ("Backtrace", &self.backtrace),
+            ("Allocated by", &self.alloc_backtrace),
+            ("Freed by", &self.free_backtrace),
+        ] {
+            if backtrace.is_empty() {
+                continue;
+            }
+            writeln!(fmt, "{title}:")?;
+            for (i, addr) in backtrace.iter().rev().enumerate() {
+                writeln!(fmt, "\t#{i} {addr:#x}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct KasanChunk {
+    end: GuestAddr,
+    /// The shadowed ranges of the chunk, one per page with [`KasanAddressKind::Physical`].
+    shadow: Vec<Range<GuestAddr>>,
+    alloc_backtrace: Vec<GuestAddr>,
+    free_backtrace: Option<Vec<GuestAddr>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum KasanCall {
+    Alloc(usize),
+    Free,
+}
+
+/// An allocator call which has not returned yet.
+///
+/// The allocator may touch poisoned memory while it runs, so the accesses made from its frame
+/// (on the same CPU, with a stack pointer not above `sp`) are not checked.
+#[derive(Debug, Clone, Copy)]
+struct PendingCall {
+    cpu: usize,
+    sp: GuestAddr,
+    ret_addr: GuestAddr,
+    kind: KasanCall,
+}
+
+type KasanErrorFn = Box<dyn FnMut(Qemu, &KasanReport)>;
+
+pub struct KasanErrorCallback(KasanErrorFn);
+
+impl KasanErrorCallback {
+    #[must_use]
+    pub fn new(error_callback: KasanErrorFn) -> Self {
+        Self(error_callback)
+    }
+
+    /// Logs the full report with the `error` level.
+    #[must_use]
+    pub fn report() -> Self {
+        Self::new(Box::new(|_qemu, report| {
+            log::error!("{report}");
+        }))
+    }
+
+    pub fn call(&mut self, qemu: Qemu, report: &KasanReport) {
+        self.0(qemu, report);
+    }
+}
+
+impl Debug for KasanErrorCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KasanErrorCallback").finish_non_exhaustive()
+    }
+}
+
+/// A builder for [`KasanModule`].
+#[derive(Debug)]
+pub struct KasanModuleBuilder {
+    allocators: Vec<(GuestAddr, u8)>,
+    deallocators: Vec<(GuestAddr, u8)>,
+    heaps: Vec<Range<GuestAddr>>,
+    address_kind: KasanAddressKind,
+    filter: StdAddressFilter,
+    error_callback: Option<KasanErrorCallback>,
+}
+
+impl KasanModuleBuilder {
+    /// Registers an allocator, called at `addr` and taking the size as argument `size_arg`.
+    ///
+    /// The allocated pointer is taken from the return value.
+    #[must_use]
+    pub fn allocator(mut self, addr: GuestAddr, size_arg: u8) -> Self {
+        self.allocators.push((addr, size_arg));
+        self
+    }
+
+    /// Registers a deallocator, called at `addr` and taking the pointer as argument `ptr_arg`.
+    #[must_use]
+    pub fn deallocator(mut self, addr: GuestAddr, ptr_arg: u8) -> Self {
+        self.deallocators.push((addr, ptr_arg));
+        self
+    }
+
+    /// Registers an allocator by symbol, see [`KasanModuleBuilder::allocator`].
+    pub fn allocator_symbol(
+        self,
+        elf: &EasyElf,
+        name: &str,
+        load_addr: GuestAddr,
+        size_arg: u8,
+    ) -> Result<Self, Error> {
+        let addr = elf
+            .resolve_symbol(name, load_addr)
+            .ok_or_else(|| Error::key_not_found(format!("Allocator symbol {name} not found")))?;
+        Ok(self.allocator(addr, size_arg))
+    }
+
+    /// Registers a deallocator by symbol, see [`KasanModuleBuilder::deallocator`].
+    pub fn deallocator_symbol(
+        self,
+        elf: &EasyElf,
+        name: &str,
+        load_addr: GuestAddr,
+        ptr_arg: u8,
+    ) -> Result<Self, Error> {
+        let addr = elf
+            .resolve_symbol(name, load_addr)
+            .ok_or_else(|| Error::key_not_found(format!("Deallocator symbol {name} not found")))?;
+        Ok(self.deallocator(addr, ptr_arg))
+    }
+
+    /// Registers a heap range. It is poisoned until allocated.
+    ///
+    /// With [`KasanAddressKind::Physical`], this is a range of physical addresses.
+    #[must_use]
+    pub fn heap(mut self, heap: Range<GuestAddr>) -> Self {
+        self.heaps.push(heap);
+        self
+    }
+
+    #[must_use]
+    pub fn address_kind(mut self, address_kind: KasanAddressKind) -> Self {
+        self.address_kind = address_kind;
+        self
+    }
+
+    #[must_use]
+    pub fn filter(mut self, filter: StdAddressFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    #[must_use]
+    pub fn error_callback(mut self, error_callback: KasanErrorCallback) -> Self {
+        self.error_callback = Some(error_callback);
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> KasanModule {
+        let mut shadow = KasanShadow::default();
+        for heap in &self.heaps {
+            shadow.poison(
+                heap.start,
+                (heap.end - heap.start) as usize,
+                KasanPoison::Unallocated,
+            );
+        }
+
+        KasanModule {
+            allocators: self.allocators,
+            deallocators: self.deallocators,
+            address_kind: self.address_kind,
+            filter: self.filter,
+            error_callback: self.error_callback,
+            shadow,
+            shadow_snapshot: None,
+            chunks: BTreeMap::new(),
+            pending: Vec::new(),
+            ret_hooks: HashSet::new(),
+            reports: Vec::new(),
+        }
+    }
+}
+
+impl Default for KasanModuleBuilder {
+    fn default() -> Self {
+        Self {
+            allocators: Vec::new(),
+            deallocators: Vec::new(),
+            heaps: Vec::new(),
+            address_kind: KasanAddressKind::Virtual,
+            filter: StdAddressFilter::default(),
+            error_callback: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct KasanModule {
+    allocators: Vec<(GuestAddr, u8)>,
+    deallocators: Vec<(GuestAddr, u8)>,
+    address_kind: KasanAddressKind,
+    filter: StdAddressFilter,
+    error_callback: Option<KasanErrorCallback>,
+    shadow: KasanShadow,
+    shadow_snapshot: Option<(KasanShadow, BTreeMap<GuestAddr, KasanChunk>)>,
+    chunks: BTreeMap<GuestAddr, KasanChunk>,
+    pending: Vec<PendingCall>,
+    ret_hooks: HashSet<GuestAddr>,
+    reports: Vec<KasanReport>,
+}
+
+impl KasanModule {
+    #[must_use]
+    pub fn builder() -> KasanModuleBuilder {
+        KasanModuleBuilder::default()
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    #[must_use]
+    pub fn shadow(&self) -> &KasanShadow {
+        &self.shadow
+    }
+
+    pub fn shadow_mut(&mut self) -> &mut KasanShadow {
+        &mut self.shadow
+    }
+
+    /// The errors found during the last run.
+    #[must_use]
+    pub fn reports(&self) -> &[KasanReport] {
+        &self.reports
+    }
+
+    /// The shadowed ranges of `[addr, addr + size)`, translated page by page with
+    /// [`KasanAddressKind::Physical`]. Unmapped pages are skipped.
+    fn shadow_ranges(&self, qemu: Qemu, addr: GuestAddr, size: usize) -> Vec<Range<GuestAddr>> {
+        let end = addr + size as GuestAddr;
+        if self.address_kind == KasanAddressKind::Virtual {
+            return vec![addr..end];
+        }
+
+        let page_size = qemu.target_page_size() as GuestAddr;
+        let mut ranges = Vec::new();
+        let mut cur = addr;
+        while cur < end {
+            let page_end = (cur & !(page_size - 1)).saturating_add(page_size);
+            let piece_end = end.min(page_end);
+            if let Some(shadow_addr) = self.translate(qemu, cur) {
+                ranges.push(shadow_addr..shadow_addr + (piece_end - cur));
+            }
+            cur = page_end;
+        }
+        ranges
+    }
+
+    #[allow(clippy::unnecessary_cast)] // for GuestPhysAddr -> GuestAddr
+    fn translate(&self, qemu: Qemu, addr: GuestAddr) -> Option<GuestAddr> {
+        match self.address_kind {
+            KasanAddressKind::Virtual => Some(addr),
+            // The physical page, with the offset in the page
+            KasanAddressKind::Physical => qemu
+                .current_cpu()
+                .and_then(|cpu| cpu.get_phys_addr(addr))
+                .map(|paddr| {
+                    paddr as GuestAddr | (addr & (qemu.target_page_size() as GuestAddr - 1))
+                }),
+        }
+    }
+
+    fn backtrace(pc: GuestAddr) -> Vec<GuestAddr> {
+        // # Safety
+        // Will access the global [`FullBacktraceCollector`].
+        // Calling this function concurrently might be racey.
+        FullBacktraceCollector::backtrace()
+            .map(|r| {
+                let mut v = r.to_vec();
+                v.push(pc);
+                v
+            })
+            .unwrap_or_default()
+    }
+
+    /// The chunk containing `addr`, allocated or freed.
+    fn chunk(&self, addr: GuestAddr) -> Option<(Range<GuestAddr>, &KasanChunk)> {
+        self.chunks
+            .range(..=addr)
+            .next_back()
+            .filter(|(start, chunk)| **start == addr || addr < chunk.end)
+            .map(|(start, chunk)| (*start..chunk.end, chunk))
+    }
+
+    fn report(&mut self, qemu: Qemu, pc: GuestAddr, error: KasanError) {
+        let (alloc_backtrace, free_backtrace) = error
+            .chunk_addr()
+            .and_then(|addr| self.chunk(addr))
+            .map(|(_, chunk)| {
+                (
+                    chunk.alloc_backtrace.clone(),
+                    chunk.free_backtrace.clone().unwrap_or_default(),
+                )
+            })
+            .unwrap_or_default();
+
+        let report = KasanReport {
+            pc,
+            error,
+            backtrace: Self::backtrace(pc),
+            alloc_backtrace,
+            free_backtrace,
+        };
+
+        if let Some(cb) = self.error_callback.as_mut() {
+            cb.call(qemu, &report);
+        }
+        self.reports.push(report);
+    }
+
+    pub fn alloc(&mut self, qemu: Qemu, pc: GuestAddr, start: GuestAddr, size: usize) {
+        let shadow = self.shadow_ranges(qemu, start, size);
+        self.alloc_chunk(pc, start, size, shadow);
+    }
+
+    /// Tracks the chunk `[start, start + size)`, unpoisoning its `shadow` ranges.
+    fn alloc_chunk(
+        &mut self,
+        pc: GuestAddr,
+        start: GuestAddr,
+        size: usize,
+        shadow: Vec<Range<GuestAddr>>,
+    ) {
+        let end = start + size as GuestAddr;
+
+        // Freed chunks overlapped by the new one are reused
+        let reused: Vec<GuestAddr> = self
+            .chunks
+            .range(..end)
+            .filter(|(_, chunk)| chunk.end > start)
+            .map(|(start, _)| *start)
+            .collect();
+        for reused_start in reused {
+            self.chunks.remove(&reused_start);
+        }
+
+        for range in &shadow {
+            self.shadow
+                .unpoison(range.start, (range.end - range.start) as usize);
+        }
+        self.chunks.insert(
+            start,
+            KasanChunk {
+                end,
+                shadow,
+                alloc_backtrace: Self::backtrace(pc),
+                free_backtrace: None,
+            },
+        );
+    }
+
+    pub fn dealloc(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
+        if let Some(error) = self.free(pc, addr) {
+            self.report(qemu, pc, error);
+        }
+    }
+
+    /// Marks the chunk at `addr` as freed, or returns the error of this free.
+    fn free(&mut self, pc: GuestAddr, addr: GuestAddr) -> Option<KasanError> {
+        let Some((range, chunk)) = self.chunk(addr) else {
+            return Some(KasanError::InvalidFree { addr, chunk: None });
+        };
+
+        if chunk.free_backtrace.is_some() {
+            return Some(KasanError::DoubleFree { addr, chunk: range });
+        }
+        if range.start != addr {
+            return Some(KasanError::InvalidFree {
+                addr,
+                chunk: Some(range),
+            });
+        }
+
+        let backtrace = Self::backtrace(pc);
+        let chunk = self.chunks.get_mut(&addr).unwrap();
+        chunk.free_backtrace = Some(backtrace);
+        for range in &chunk.shadow {
+            self.shadow.poison(
+                range.start,
+                (range.end - range.start) as usize,
+                KasanPoison::Freed,
+            );
+        }
+        None
+    }
+
+    pub fn access(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr, size: usize, write: bool) {
+        // The allocator itself manages the poisoned memory
+        if !self.pending.is_empty() && self.in_pending_call(qemu) {
+            return;
+        }
+
+        let shadow = self.shadow_ranges(qemu, addr, size);
+        if let Some(error) = self.check_access(addr, size, write, &shadow) {
+            self.report(qemu, pc, error);
+        }
+    }
+
+    /// Checks an access of `size` bytes at `addr`, shadowed by the `shadow` ranges.
+    fn check_access(
+        &self,
+        addr: GuestAddr,
+        size: usize,
+        write: bool,
+        shadow: &[Range<GuestAddr>],
+    ) -> Option<KasanError> {
+        let shadow = shadow.iter().find_map(|range| {
+            self.shadow
+                .check(range.start, (range.end - range.start) as usize)
+        })?;
+        let freed = matches!(KasanPoison::try_from(shadow), Ok(KasanPoison::Freed));
+        match self.chunk(addr).map(|(chunk, _)| chunk) {
+            Some(chunk) if freed => Some(KasanError::UseAfterFree {
+                addr,
+                size,
+                write,
+                chunk,
+            }),
+            _ => Some(KasanError::OutOfBounds { addr, size, write }),
+        }
+    }
+
+    /// Whether the current CPU runs in the frame of an allocator call.
+    fn in_pending_call(&self, qemu: Qemu) -> bool {
+        let Some(cpu) = qemu.current_cpu() else {
+            return false;
+        };
+        let Ok(sp) = cpu.read_reg(Regs::Sp) else {
+            return false;
+        };
+        #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+        let sp = sp as GuestAddr;
+        let cpu = cpu.index();
+
+        self.pending
+            .iter()
+            .any(|call| call.cpu == cpu && sp <= call.sp)
+    }
+
+    /// Drops the calls of `cpu` whose frame is gone, the stack pointer being above theirs.
+    ///
+    /// Their return was never seen, after a `longjmp` or an exception for instance.
+    fn drop_stale_calls(&mut self, cpu: usize, sp: GuestAddr) {
+        self.pending.retain(|call| call.cpu != cpu || call.sp >= sp);
+    }
+
+    fn on_call(&mut self, cpu: CPU, kind: KasanCall) -> Option<GuestAddr> {
+        #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+        let ret_addr = cpu.read_return_address().ok()? as GuestAddr;
+        #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+        let sp = cpu.read_reg(Regs::Sp).ok()? as GuestAddr;
+
+        self.drop_stale_calls(cpu.index(), sp);
+        self.pending.push(PendingCall {
+            cpu: cpu.index(),
+            sp,
+            ret_addr,
+            kind,
+        });
+        self.ret_hooks.insert(ret_addr).then_some(ret_addr)
+    }
+
+    /// Ends the pending call of `cpu` returning to `ret_addr`, with the stack pointer at `sp`.
+    fn on_ret(&mut self, cpu: usize, sp: GuestAddr, ret_addr: GuestAddr) -> Option<PendingCall> {
+        let idx = self
+            .pending
+            .iter()
+            .rposition(|call| call.cpu == cpu && call.ret_addr == ret_addr)?;
+        let call = self.pending.remove(idx);
+        self.drop_stale_calls(cpu, sp);
+        Some(call)
+    }
+}
+
+impl<I, S> EmulatorModule<I, S> for KasanModule
+where
+    I: Unpin,
+    S: Unpin,
+{
+    fn first_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        for (addr, _) in &self.allocators {
+            emulator_modules.instructions(*addr, Hook::Function(on_alloc_kasan::<ET, I, S>), true);
+        }
+
+        for (addr, _) in &self.deallocators {
+            emulator_modules.instructions(
+                *addr,
+                Hook::Function(on_dealloc_kasan::<ET, I, S>),
+                true,
+            );
+        }
+
+        emulator_modules.reads(
+            Hook::Function(gen_readwrite_kasan::<ET, I, S>),
+            Hook::Function(trace_read_kasan::<ET, I, S, 1>),
+            Hook::Function(trace_read_kasan::<ET, I, S, 2>),
+            Hook::Function(trace_read_kasan::<ET, I, S, 4>),
+            Hook::Function(trace_read_kasan::<ET, I, S, 8>),
+            Hook::Function(trace_read_n_kasan::<ET, I, S>),
+        );
+
+        emulator_modules.writes(
+            Hook::Function(gen_readwrite_kasan::<ET, I, S>),
+            Hook::Function(trace_write_kasan::<ET, I, S, 1>),
+            Hook::Function(trace_write_kasan::<ET, I, S, 2>),
+            Hook::Function(trace_write_kasan::<ET, I, S, 4>),
+            Hook::Function(trace_write_kasan::<ET, I, S, 8>),
+            Hook::Function(trace_write_n_kasan::<ET, I, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        // The guest memory is restored between runs, the shadow should follow
+        if self.shadow_snapshot.is_none() {
+            self.shadow_snapshot = Some((self.shadow.clone(), self.chunks.clone()));
+        }
+
+        self.reports.clear();
+        self.pending.clear();
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+        _observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<I, S>,
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        if !self.reports.is_empty() {
+            *exit_kind = ExitKind::Crash;
+        }
+
+        if let Some((shadow, chunks)) = &self.shadow_snapshot {
+            self.shadow.clone_from(shadow);
+            self.chunks.clone_from(chunks);
+        }
+    }
+}
+
+impl HasAddressFilter for KasanModule {
+    type ModuleAddressFilter = StdAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn on_alloc_kasan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let Some(cpu) = qemu.current_cpu() else {
+        return;
+    };
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+
+    let Some((_, size_arg)) = h.allocators.iter().find(|(addr, _)| *addr == pc) else {
+        return;
+    };
+    let Ok(size) = cpu.read_function_argument(CallingConvention::Cdecl, *size_arg) else {
+        log::warn!("KASAN: failed to read the allocation size at {pc:#x}");
+        return;
+    };
+
+    if let Some(ret_addr) = h.on_call(cpu, KasanCall::Alloc(size as usize)) {
+        emulator_modules.instructions(ret_addr, Hook::Function(on_ret_kasan::<ET, I, S>), true);
+    }
+}
+
+pub fn on_dealloc_kasan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let Some(cpu) = qemu.current_cpu() else {
+        return;
+    };
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+
+    let Some((_, ptr_arg)) = h.deallocators.iter().find(|(addr, _)| *addr == pc) else {
+        return;
+    };
+    let Ok(ptr) = cpu.read_function_argument(CallingConvention::Cdecl, *ptr_arg) else {
+        log::warn!("KASAN: failed to read the freed pointer at {pc:#x}");
+        return;
+    };
+
+    #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+    let ptr = ptr as GuestAddr;
+    if ptr != 0 {
+        h.dealloc(qemu, pc, ptr);
+    }
+
+    if let Some(ret_addr) = h.on_call(cpu, KasanCall::Free) {
+        emulator_modules.instructions(ret_addr, Hook::Function(on_ret_kasan::<ET, I, S>), true);
+    }
+}
+
+pub fn on_ret_kasan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let Some(cpu) = qemu.current_cpu() else {
+        return;
+    };
+    let Ok(sp) = cpu.read_reg(Regs::Sp) else {
+        return;
+    };
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+
+    #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+    let Some(call) = h.on_ret(cpu.index(), sp as GuestAddr, pc) else {
+        return;
+    };
+
+    if let KasanCall::Alloc(size) = call.kind {
+        let Ok(ptr) = qemu.read_reg(get_exit_arch_regs()[ExitArgs::Ret]) else {
+            return;
+        };
+
+        #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+        let ptr = ptr as GuestAddr;
+        if ptr != 0 {
+            h.alloc(qemu, pc, ptr, size);
+        }
+    }
+}
+
+pub fn gen_readwrite_kasan<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _addr: *mut TCGTemp,
+    _info: MemAccessInfo,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+    if h.must_instrument(pc) {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+pub fn trace_read_kasan<ET, I, S, const N: usize>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+    h.access(qemu, id as GuestAddr, addr, N, false);
+}
+
+pub fn trace_read_n_kasan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+    h.access(qemu, id as GuestAddr, addr, size, false);
+}
+
+pub fn trace_write_kasan<ET, I, S, const N: usize>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+    h.access(qemu, id as GuestAddr, addr, N, true);
+}
+
+pub fn trace_write_n_kasan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<KasanModule>().unwrap();
+    h.access(qemu, id as GuestAddr, addr, size, true);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KasanCall, KasanError, KasanModule, KasanPoison, KasanShadow, PendingCall};
+
+    #[test]
+    fn test_kasan_shadow() {
+        let mut shadow = KasanShadow::default();
+
+        // untracked memory is accessible
+        assert_eq!(shadow.check(0x1000, 8), None);
+
+        shadow.poison(0x1000, 0x100, KasanPoison::Unallocated);
+        assert_eq!(
+            shadow.check(0x1010, 1),
+            Some(KasanPoison::Unallocated.into())
+        );
+
+        shadow.unpoison(0x1000, 13);
+        assert_eq!(shadow.check(0x1000, 8), None);
+        assert_eq!(shadow.check(0x1008, 5), None);
+        assert_eq!(shadow.check(0x1008, 6), Some(5));
+        assert_eq!(shadow.check(0x100c, 2), Some(5));
+        assert_eq!(
+            shadow.check(0x1010, 1),
+            Some(KasanPoison::Unallocated.into())
+        );
+
+        shadow.poison(0x1000, 16, KasanPoison::Freed);
+        assert_eq!(shadow.check(0x1004, 4), Some(KasanPoison::Freed.into()));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_kasan_alloc_free() {
+        let mut kasan = KasanModule::builder().heap(0x1000..0x2000).build();
+
+        // the heap starts poisoned
+        assert_eq!(
+            kasan.check_access(0x1000, 1, false, &[0x1000..0x1001]),
+            Some(KasanError::OutOfBounds {
+                addr: 0x1000,
+                size: 1,
+                write: false
+            })
+        );
+
+        kasan.alloc_chunk(0, 0x1000, 20, vec![0x1000..0x1014]);
+        assert_eq!(
+            kasan.check_access(0x1010, 4, false, &[0x1010..0x1014]),
+            None
+        );
+        assert_eq!(
+            kasan.check_access(0x1012, 4, true, &[0x1012..0x1016]),
+            Some(KasanError::OutOfBounds {
+                addr: 0x1012,
+                size: 4,
+                write: true
+            })
+        );
+
+        assert_eq!(kasan.free(0, 0x1000), None);
+        assert_eq!(
+            kasan.check_access(0x1008, 8, true, &[0x1008..0x1010]),
+            Some(KasanError::UseAfterFree {
+                addr: 0x1008,
+                size: 8,
+                write: true,
+                chunk: 0x1000..0x1014
+            })
+        );
+        assert_eq!(
+            kasan.free(0, 0x1000),
+            Some(KasanError::DoubleFree {
+                addr: 0x1000,
+                chunk: 0x1000..0x1014
+            })
+        );
+
+        kasan.alloc_chunk(0, 0x1100, 16, vec![0x1100..0x1110]);
+        assert_eq!(
+            kasan.free(0, 0x1108),
+            Some(KasanError::InvalidFree {
+                addr: 0x1108,
+                chunk: Some(0x1100..0x1110)
+            })
+        );
+        assert_eq!(
+            kasan.free(0, 0x1800),
+            Some(KasanError::InvalidFree {
+                addr: 0x1800,
+                chunk: None
+            })
+        );
+
+        // a freed chunk is reused by the next allocation
+        kasan.alloc_chunk(0, 0x1000, 8, vec![0x1000..0x1008]);
+        assert_eq!(
+            kasan.check_access(0x1000, 8, false, &[0x1000..0x1008]),
+            None
+        );
+        assert_eq!(kasan.free(0, 0x1000), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_kasan_physical_pages() {
+        let mut kasan = KasanModule::builder()
+            .heap(0x5000..0x6000)
+            .heap(0x9000..0xa000)
+            .build();
+
+        // a chunk crossing a page boundary, mapped on two discontiguous physical pages
+        kasan.alloc_chunk(0, 0x1ff0, 0x20, vec![0x5ff0..0x6000, 0x9000..0x9010]);
+        assert_eq!(
+            kasan.check_access(0x1ff8, 8, false, &[0x5ff8..0x6000]),
+            None
+        );
+        assert_eq!(
+            kasan.check_access(0x2008, 8, false, &[0x9008..0x9010]),
+            None
+        );
+        assert_eq!(
+            kasan.check_access(0x2010, 1, false, &[0x9010..0x9011]),
+            Some(KasanError::OutOfBounds {
+                addr: 0x2010,
+                size: 1,
+                write: false
+            })
+        );
+
+        // both pages are poisoned when the chunk is freed
+        assert_eq!(kasan.free(0, 0x1ff0), None);
+        assert_eq!(
+            kasan.check_access(0x2000, 8, true, &[0x9000..0x9008]),
+            Some(KasanError::UseAfterFree {
+                addr: 0x2000,
+                size: 8,
+                write: true,
+                chunk: 0x1ff0..0x2010
+            })
+        );
+        assert!(kasan
+            .check_access(0x1ffc, 8, false, &[0x5ffc..0x6000, 0x9000..0x9004])
+            .is_some());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_kasan_pending_calls() {
+        let mut kasan = KasanModule::builder().build();
+        let call = |cpu, sp, ret_addr| PendingCall {
+            cpu,
+            sp,
+            ret_addr,
+            kind: KasanCall::Free,
+        };
+
+        // the returning call, a call of another CPU, and a nested call which was unwound
+        kasan.pending.push(call(0, 0x8000, 0x300));
+        kasan.pending.push(call(1, 0x7f00, 0x200));
+        kasan.pending.push(call(0, 0x7f00, 0x100));
+
+        assert!(kasan.on_ret(0, 0x8008, 0x300).is_some());
+        assert_eq!(kasan.pending.len(), 1);
+        assert_eq!(kasan.pending[0].cpu, 1);
+
+        assert!(kasan.on_ret(0, 0x7f08, 0x200).is_none());
+        assert!(kasan.on_ret(1, 0x7f08, 0x200).is_some());
+        assert!(kasan.pending.is_empty());
+    }
+}
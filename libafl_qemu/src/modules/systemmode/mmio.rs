@@ -0,0 +1,747 @@
+//! MMIO modeling for bare-metal firmware
+//!
+//! The [`MmioModule`] serves the reads from peripheral regions with values taken from the fuzz
+//! input, in the spirit of Fuzzware, so that firmware can be fuzzed without emulating the board.
+//! Each register can be modeled with a [`MmioModel`], and registers without an explicit model
+//! can have one inferred at runtime (see [`MmioModuleBuilder::infer_models`]).
+//!
+//! The peripheral regions are mapped as I/O memory regions over the system memory, with a higher
+//! priority than the RAM or the devices they overlap, so every access to them is served by the
+//! module. Values are little endian.
+//!
+//! Once the input is exhausted, the run is stopped and considered successful.
+
+#![allow(clippy::cast_possible_truncation)] // input chunks are at most 8 bytes long
+#![allow(clippy::needless_pass_by_value)] // default compiler complains about Option<&mut T> otherwise, and this is used extensively.
+
+use core::{
+    ffi::{c_uint, c_void},
+    fmt,
+    mem::MaybeUninit,
+    ptr,
+};
+use std::{ffi::CString, fmt::Debug, ops::Range};
+
+use hashbrown::{HashMap, HashSet};
+use libafl::{executors::ExitKind, inputs::HasTargetBytes, observers::ObserversTuple, HasMetadata};
+use libafl_bolts::AsSlice;
+use libafl_qemu_sys::{
+    device_endian_DEVICE_LITTLE_ENDIAN, get_system_memory, hwaddr, libafl_exit_request_timeout,
+    memory_region_add_subregion_overlap, memory_region_init_io, GuestAddr, MemoryRegion,
+    MemoryRegionOps,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        utils::filters::{HasAddressFilter, NopPageFilter, StdAddressFilter, NOP_PAGE_FILTER},
+        AddressFilter, EmulatorModule, EmulatorModuleTuple,
+    },
+    qemu::Hook,
+    Qemu,
+};
+
+/// Number of executed blocks after a read during which comparisons are attributed to it.
+const MMIO_CMP_WINDOW: u64 = 4;
+
+/// Priority of the MMIO regions over the memory and the devices they overlap.
+const MMIO_REGION_PRIORITY: i32 = 1000;
+
+/// The model of a MMIO register.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MmioModel {
+    /// Always reads the same value. No input is consumed.
+    Constant(u64),
+    /// Reads the last value written by the firmware. No input is consumed.
+    Passthrough,
+    /// Only the bits of the mask come from the input, the others read as 0.
+    Bitmask(u64),
+    /// Reads one of the values, chosen by one byte of input.
+    Set(Vec<u64>),
+    /// The whole register comes from the input.
+    Fuzzed,
+}
+
+type InterruptTriggerFn = Box<dyn FnMut(Qemu, u32)>;
+
+/// The models inferred by the [`MmioModule`], kept in the state so that they survive restarts.
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MmioModelsMetadata {
+    /// The inferred model of each register, by address.
+    pub models: HashMap<GuestAddr, MmioModel>,
+    /// The constants each register was compared to, from which its [`MmioModel::Set`] is built.
+    pub candidates: HashMap<GuestAddr, HashSet<u64>>,
+}
+
+libafl_bolts::impl_serdeany!(MmioModelsMetadata);
+
+/// Raises an interrupt in the guest.
+pub struct InterruptTrigger(InterruptTriggerFn);
+
+impl InterruptTrigger {
+    /// Creates a trigger from a function raising the given interrupt number.
+    #[must_use]
+    pub fn new(trigger: InterruptTriggerFn) -> Self {
+        Self(trigger)
+    }
+
+    /// Pends the interrupt in the NVIC of a Cortex-M, through its `ISPR` registers.
+    #[cfg(cpu_target = "arm")]
+    #[must_use]
+    pub fn nvic() -> Self {
+        const NVIC_ISPR: GuestAddr = 0xe000_e200;
+
+        Self::new(Box::new(|qemu, irq| {
+            let ispr = NVIC_ISPR + 4 * (irq / 32);
+            let val = (1u32 << (irq % 32)).to_le_bytes();
+            // # Safety
+            // The write is dispatched to the NVIC, it is skipped if there is none.
+            unsafe {
+                qemu.write_phys_mem(ispr.into(), &val);
+            }
+        }))
+    }
+
+    /// Raises the interrupt `irq`.
+    pub fn call(&mut self, qemu: Qemu, irq: u32) {
+        self.0(qemu, irq);
+    }
+}
+
+impl Debug for InterruptTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InterruptTrigger").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct MmioInterrupts {
+    irqs: Vec<u32>,
+    interval: u64,
+    trigger: InterruptTrigger,
+}
+
+/// A builder for [`MmioModule`].
+#[derive(Debug)]
+pub struct MmioModuleBuilder {
+    regions: Vec<Range<GuestAddr>>,
+    models: HashMap<GuestAddr, MmioModel>,
+    infer_models: bool,
+    interrupts: Option<MmioInterrupts>,
+    filter: StdAddressFilter,
+}
+
+impl MmioModuleBuilder {
+    /// Adds a peripheral region served from the input.
+    #[must_use]
+    pub fn region(mut self, region: Range<GuestAddr>) -> Self {
+        self.regions.push(region);
+        self
+    }
+
+    /// Sets the model of the register at `addr`.
+    #[must_use]
+    pub fn model(mut self, addr: GuestAddr, model: MmioModel) -> Self {
+        self.models.insert(addr, model);
+        self
+    }
+
+    /// Infers the model of the registers without an explicit one. Disabled by default.
+    ///
+    /// Registers written by the firmware become [`MmioModel::Passthrough`], and registers whose
+    /// value is compared to constants become a [`MmioModel::Set`] of these constants.
+    /// The inferred models persist across runs, in the [`MmioModelsMetadata`] of the state.
+    #[must_use]
+    pub fn infer_models(mut self, infer_models: bool) -> Self {
+        self.infer_models = infer_models;
+        self
+    }
+
+    /// Raises one of `irqs`, chosen by one byte of input, every `interval` executed blocks.
+    #[must_use]
+    pub fn interrupts(mut self, irqs: Vec<u32>, interval: u64, trigger: InterruptTrigger) -> Self {
+        self.interrupts = Some(MmioInterrupts {
+            irqs,
+            interval,
+            trigger,
+        });
+        self
+    }
+
+    /// Restricts the comparisons used to infer the models to the code in `filter`.
+    #[must_use]
+    pub fn filter(mut self, filter: StdAddressFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> MmioModule {
+        MmioModule {
+            regions: self.regions,
+            models: self.models,
+            infer_models: self.infer_models,
+            inferred: HashMap::new(),
+            candidates: HashMap::new(),
+            inferred_changed: false,
+            interrupts: self.interrupts,
+            filter: self.filter,
+            memory_regions: Vec::new(),
+            ops: None,
+            registers: HashMap::new(),
+            input: Vec::new(),
+            cursor: 0,
+            exhausted: false,
+            blocks: 0,
+            last_read: None,
+        }
+    }
+}
+
+impl Default for MmioModuleBuilder {
+    fn default() -> Self {
+        Self {
+            regions: Vec::new(),
+            models: HashMap::new(),
+            infer_models: false,
+            interrupts: None,
+            filter: StdAddressFilter::default(),
+        }
+    }
+}
+
+/// Serves the reads from the peripheral regions with values taken from the input, following the
+/// [`MmioModel`] of each register.
+///
+/// Built with [`MmioModule::builder`].
+#[derive(Debug)]
+pub struct MmioModule {
+    regions: Vec<Range<GuestAddr>>,
+    models: HashMap<GuestAddr, MmioModel>,
+    infer_models: bool,
+    inferred: HashMap<GuestAddr, MmioModel>,
+    candidates: HashMap<GuestAddr, HashSet<u64>>,
+    inferred_changed: bool,
+    interrupts: Option<MmioInterrupts>,
+    filter: StdAddressFilter,
+    /// The mapped regions, which QEMU refers to until the end
+    memory_regions: Vec<Box<MaybeUninit<MemoryRegion>>>,
+    ops: Option<Box<MemoryRegionOps>>,
+    /// The last bytes written by the firmware, for [`MmioModel::Passthrough`]
+    registers: HashMap<GuestAddr, u8>,
+    input: Vec<u8>,
+    cursor: usize,
+    exhausted: bool,
+    blocks: u64,
+    last_read: Option<(GuestAddr, u64, u64)>,
+}
+
+impl MmioModule {
+    #[must_use]
+    pub fn builder() -> MmioModuleBuilder {
+        MmioModuleBuilder::default()
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    #[must_use]
+    pub fn is_mmio(&self, addr: GuestAddr) -> bool {
+        self.regions.iter().any(|region| region.contains(&addr))
+    }
+
+    /// The model used for the register at `addr`.
+    #[must_use]
+    pub fn model(&self, addr: GuestAddr) -> &MmioModel {
+        self.models
+            .get(&addr)
+            .or_else(|| self.inferred.get(&addr))
+            .unwrap_or(&MmioModel::Fuzzed)
+    }
+
+    /// Whether the input was exhausted during the last run.
+    #[must_use]
+    pub fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    fn consume(&mut self, len: usize) -> Option<u64> {
+        if self.cursor + len > self.input.len() {
+            if !self.exhausted {
+                self.exhausted = true;
+                // # Safety
+                // Asks QEMU to stop at the end of the current block.
+                unsafe {
+                    libafl_exit_request_timeout();
+                }
+            }
+            return None;
+        }
+
+        let mut val = [0; 8];
+        val[..len].copy_from_slice(&self.input[self.cursor..self.cursor + len]);
+        self.cursor += len;
+
+        Some(u64::from_le_bytes(val))
+    }
+
+    /// Computes the value of a read of `size` bytes at `addr`.
+    ///
+    /// Returns `None` once the input is exhausted.
+    pub fn serve(&mut self, addr: GuestAddr, size: usize) -> Option<u64> {
+        let size = size.min(8);
+
+        let value = match self.model(addr) {
+            MmioModel::Passthrough => {
+                let mut value = [0; 8];
+                for (i, byte) in value[..size].iter_mut().enumerate() {
+                    *byte = self
+                        .registers
+                        .get(&(addr + i as GuestAddr))
+                        .copied()
+                        .unwrap_or_default();
+                }
+                return Some(u64::from_le_bytes(value));
+            }
+            MmioModel::Constant(value) => *value,
+            MmioModel::Bitmask(mask) => {
+                let mask = *mask;
+                let bits = self.consume(mask.count_ones().div_ceil(8) as usize)?;
+                deposit_bits(bits, mask)
+            }
+            MmioModel::Set(values) if !values.is_empty() => {
+                let values_len = values.len();
+                let idx = self.consume(1)? as usize % values_len;
+                let MmioModel::Set(values) = self.model(addr) else {
+                    unreachable!()
+                };
+                values[idx]
+            }
+            MmioModel::Set(_) | MmioModel::Fuzzed => self.consume(size)?,
+        };
+
+        self.last_read = Some((addr, value, self.blocks));
+        Some(value)
+    }
+
+    /// Serves a read of the firmware, reading 0 once the input is exhausted.
+    fn read(&mut self, addr: GuestAddr, size: usize) -> u64 {
+        self.serve(addr, size).unwrap_or_default()
+    }
+
+    /// Handles a write of `size` bytes of the firmware.
+    ///
+    /// Only the written bytes are updated, so a narrow write is merged into the register value.
+    fn write(&mut self, addr: GuestAddr, value: u64, size: usize) {
+        let size = size.min(8);
+        for (i, byte) in value.to_le_bytes()[..size].iter().enumerate() {
+            self.registers.insert(addr + i as GuestAddr, *byte);
+        }
+
+        if self.infer_models
+            && !self.models.contains_key(&addr)
+            && self.inferred.insert(addr, MmioModel::Passthrough) != Some(MmioModel::Passthrough)
+        {
+            log::info!("MMIO: register {addr:#x} inferred as passthrough");
+            self.inferred_changed = true;
+        }
+    }
+
+    fn on_cmp(&mut self, v0: u64, v1: u64) {
+        let Some((addr, value, blocks)) = self.last_read else {
+            return;
+        };
+        if !self.infer_models || self.models.contains_key(&addr) {
+            return;
+        }
+        if self.blocks - blocks > MMIO_CMP_WINDOW {
+            self.last_read = None;
+            return;
+        }
+
+        let constant = if v0 == value {
+            v1
+        } else if v1 == value {
+            v0
+        } else {
+            return;
+        };
+
+        if matches!(self.inferred.get(&addr), Some(MmioModel::Passthrough)) {
+            return;
+        }
+
+        let candidates = self.candidates.entry(addr).or_default();
+        if candidates.insert(constant) {
+            // Keep a way out of the compared values
+            let mut values: Vec<u64> = candidates.iter().copied().collect();
+            values.push(0);
+            values.sort_unstable();
+            values.dedup();
+
+            log::info!("MMIO: register {addr:#x} inferred as one of {values:x?}");
+            self.inferred.insert(addr, MmioModel::Set(values));
+            self.inferred_changed = true;
+        }
+    }
+
+    /// Maps the peripheral regions over the system memory.
+    #[allow(clippy::unnecessary_cast)] // for GuestAddr -> hwaddr
+    fn map_regions<ET, I, S>(&mut self)
+    where
+        ET: EmulatorModuleTuple<I, S>,
+        I: HasTargetBytes + Unpin,
+        S: HasMetadata + Unpin,
+    {
+        let mut ops = Box::new(MemoryRegionOps {
+            read: Some(read_mmio::<ET, I, S>),
+            write: Some(write_mmio::<ET, I, S>),
+            endianness: device_endian_DEVICE_LITTLE_ENDIAN,
+            ..MemoryRegionOps::default()
+        });
+        ops.valid.min_access_size = 1;
+        ops.valid.max_access_size = 8;
+        ops.impl_.min_access_size = 1;
+        ops.impl_.max_access_size = 8;
+
+        for region in &self.regions {
+            let mut memory_region = Box::new(MaybeUninit::<MemoryRegion>::zeroed());
+            let name = CString::new(format!("libafl-mmio-{:#x}", region.start)).unwrap();
+
+            // # Safety
+            // The region and its ops live as long as the module, the opaque is the region start.
+            unsafe {
+                memory_region_init_io(
+                    memory_region.as_mut_ptr(),
+                    ptr::null_mut(),
+                    &raw const *ops,
+                    region.start as usize as *mut c_void,
+                    name.as_ptr(),
+                    (region.end - region.start) as u64,
+                );
+                memory_region_add_subregion_overlap(
+                    get_system_memory(),
+                    region.start as hwaddr,
+                    memory_region.as_mut_ptr(),
+                    MMIO_REGION_PRIORITY,
+                );
+            }
+
+            self.memory_regions.push(memory_region);
+        }
+
+        self.ops = Some(ops);
+    }
+
+    fn on_block(&mut self, qemu: Qemu) {
+        self.blocks += 1;
+
+        let Some(interrupts) = self.interrupts.as_ref() else {
+            return;
+        };
+        if interrupts.irqs.is_empty()
+            || interrupts.interval == 0
+            || self.blocks % interrupts.interval != 0
+        {
+            return;
+        }
+
+        let irqs_len = interrupts.irqs.len();
+        let Some(idx) = self.consume(1) else {
+            return;
+        };
+
+        let interrupts = self.interrupts.as_mut().unwrap();
+        let irq = interrupts.irqs[idx as usize % irqs_len];
+        interrupts.trigger.call(qemu, irq);
+    }
+}
+
+/// Scatters the low bits of `bits` to the set bits of `mask`.
+fn deposit_bits(mut bits: u64, mut mask: u64) -> u64 {
+    let mut res = 0;
+
+    while mask != 0 {
+        let lowest = mask & mask.wrapping_neg();
+        if bits & 1 != 0 {
+            res |= lowest;
+        }
+        bits >>= 1;
+        mask &= mask - 1;
+    }
+
+    res
+}
+
+impl<I, S> EmulatorModule<I, S> for MmioModule
+where
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    fn first_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        emulator_modules: &mut EmulatorModules<ET, I, S>,
+        state: &mut S,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.map_regions::<ET, I, S>();
+
+        if self.infer_models {
+            if let Some(meta) = state.metadata_map().get::<MmioModelsMetadata>() {
+                self.inferred.clone_from(&meta.models);
+                self.candidates.clone_from(&meta.candidates);
+            }
+
+            emulator_modules.cmps(
+                Hook::Function(gen_cmp_mmio::<ET, I, S>),
+                Hook::Function(trace_cmp_mmio::<ET, I, S, u8>),
+                Hook::Function(trace_cmp_mmio::<ET, I, S, u16>),
+                Hook::Function(trace_cmp_mmio::<ET, I, S, u32>),
+                Hook::Function(trace_cmp_mmio::<ET, I, S, u64>),
+            );
+        }
+
+        emulator_modules.blocks(
+            Hook::Empty,
+            Hook::Empty,
+            Hook::Function(exec_block_mmio::<ET, I, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.input.clear();
+        self.input
+            .extend_from_slice(input.target_bytes().as_slice());
+        self.cursor = 0;
+        self.exhausted = false;
+        self.blocks = 0;
+        self.last_read = None;
+        self.registers.clear();
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        state: &mut S,
+        _input: &I,
+        _observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<I, S>,
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        // The stop requested when the input got exhausted is not a timeout
+        if self.exhausted && *exit_kind == ExitKind::Timeout {
+            *exit_kind = ExitKind::Ok;
+        }
+
+        if self.inferred_changed {
+            self.inferred_changed = false;
+            state.add_metadata(MmioModelsMetadata {
+                models: self.inferred.clone(),
+                candidates: self.candidates.clone(),
+            });
+        }
+    }
+}
+
+impl HasAddressFilter for MmioModule {
+    type ModuleAddressFilter = StdAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+extern "C" fn read_mmio<ET, I, S>(opaque: *mut c_void, offset: hwaddr, size: c_uint) -> u64
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    #[allow(clippy::unnecessary_cast)] // for hwaddr -> GuestAddr
+    let addr = opaque as usize as GuestAddr + offset as GuestAddr;
+
+    // # Safety
+    // The regions are mapped by the module, once the modules are initialized.
+    let Some(emulator_modules) = (unsafe { EmulatorModules::<ET, I, S>::emulator_modules_mut() })
+    else {
+        return 0;
+    };
+    let h = emulator_modules.get_mut::<MmioModule>().unwrap();
+    h.read(addr, size as usize)
+}
+
+extern "C" fn write_mmio<ET, I, S>(opaque: *mut c_void, offset: hwaddr, value: u64, size: c_uint)
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    #[allow(clippy::unnecessary_cast)] // for hwaddr -> GuestAddr
+    let addr = opaque as usize as GuestAddr + offset as GuestAddr;
+
+    // # Safety
+    // The regions are mapped by the module, once the modules are initialized.
+    let Some(emulator_modules) = (unsafe { EmulatorModules::<ET, I, S>::emulator_modules_mut() })
+    else {
+        return;
+    };
+    let h = emulator_modules.get_mut::<MmioModule>().unwrap();
+    h.write(addr, value, size as usize);
+}
+
+pub fn gen_cmp_mmio<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _size: usize,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get::<MmioModule>().unwrap();
+    h.must_instrument(pc).then_some(0)
+}
+
+pub fn trace_cmp_mmio<ET, I, S, SZ>(
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    v0: SZ,
+    v1: SZ,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+    SZ: Into<u64>,
+{
+    let h = emulator_modules.get_mut::<MmioModule>().unwrap();
+    h.on_cmp(v0.into(), v1.into());
+}
+
+pub fn exec_block_mmio<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<MmioModule>().unwrap();
+    h.on_block(qemu);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deposit_bits, MmioModel, MmioModule};
+
+    #[test]
+    fn test_deposit_bits() {
+        assert_eq!(deposit_bits(0b11, 0b1010), 0b1010);
+        assert_eq!(deposit_bits(0b01, 0b1010), 0b0010);
+        assert_eq!(deposit_bits(0xff, 0xf0f0), 0xf0f0);
+    }
+
+    #[test]
+    fn test_mmio_models() {
+        let mut module = MmioModule::builder()
+            .region(0x4000_0000..0x4000_1000)
+            .model(0x4000_0000, MmioModel::Constant(0x42))
+            .model(0x4000_0004, MmioModel::Set(vec![1, 2, 3]))
+            .model(0x4000_0008, MmioModel::Bitmask(0x30))
+            .build();
+        module.input = vec![0x04, 0xff, 0x12, 0x34];
+
+        assert_eq!(module.serve(0x4000_0000, 4), Some(0x42));
+        // 4 % 3
+        assert_eq!(module.serve(0x4000_0004, 4), Some(2));
+        assert_eq!(module.serve(0x4000_0008, 4), Some(0x30));
+        assert_eq!(module.serve(0x4000_000c, 2), Some(0x3412));
+    }
+
+    #[test]
+    fn test_mmio_inference() {
+        let mut module = MmioModule::builder()
+            .region(0x4000_0000..0x4000_1000)
+            .infer_models(true)
+            .build();
+        module.input = vec![0x7, 0x0, 0x0, 0x0];
+
+        assert_eq!(module.serve(0x4000_0010, 4), Some(7));
+        module.on_cmp(7, 0x80);
+        assert_eq!(module.model(0x4000_0010), &MmioModel::Set(vec![0, 0x80]));
+
+        module.write(0x4000_0014, 0x55, 4);
+        assert_eq!(module.model(0x4000_0014), &MmioModel::Passthrough);
+        assert_eq!(module.read(0x4000_0014, 4), 0x55);
+        assert!(module.inferred_changed);
+    }
+
+    #[test]
+    fn test_mmio_passthrough_narrow_writes() {
+        let mut module = MmioModule::builder()
+            .region(0x4000_0000..0x4000_1000)
+            .model(0x4000_0000, MmioModel::Passthrough)
+            .build();
+
+        module.write(0x4000_0000, 0x1122_3344, 4);
+        // only the written bytes are updated
+        module.write(0x4000_0000, 0xffff_ffaa, 1);
+        assert_eq!(module.read(0x4000_0000, 4), 0x1122_33aa);
+        module.write(0x4000_0002, 0xbbcc, 2);
+        assert_eq!(module.read(0x4000_0000, 4), 0xbbcc_33aa);
+        assert_eq!(module.read(0x4000_0000, 2), 0x33aa);
+    }
+
+    #[test]
+    fn test_mmio_no_inference() {
+        let mut module = MmioModule::builder()
+            .region(0x4000_0000..0x4000_1000)
+            .build();
+        module.input = vec![0x7];
+
+        assert_eq!(module.read(0x4000_0010, 1), 7);
+        module.on_cmp(7, 0x80);
+        module.write(0x4000_0014, 0x55, 4);
+        assert_eq!(module.model(0x4000_0010), &MmioModel::Fuzzed);
+        assert_eq!(module.model(0x4000_0014), &MmioModel::Fuzzed);
+        assert!(!module.inferred_changed);
+    }
+}
@@ -1 +1,16 @@
+pub mod address_space;
+pub use address_space::{
+    address_space_active, AddressSpaceContext, AddressSpaceModule, PagingIdFilter,
+};
 
+pub mod kasan;
+pub use kasan::{
+    KasanAddressKind, KasanError, KasanErrorCallback, KasanModule, KasanModuleBuilder, KasanPoison,
+    KasanReport, KasanShadow,
+};
+
+pub mod mmio;
+pub use mmio::{InterruptTrigger, MmioModel, MmioModelsMetadata, MmioModule, MmioModuleBuilder};
+
+pub mod record_replay;
+pub use record_replay::{RecordReplayMode, RecordReplayModule};
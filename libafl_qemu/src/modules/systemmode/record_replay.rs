@@ -0,0 +1,154 @@
+//! Record and replay of a full-system run
+//!
+//! In full-system mode, the nondeterministic inputs of the guest are interrupts, timers and device
+//! IO. QEMU records and replays them itself, in icount mode. The [`RecordReplayModule`] sets QEMU up
+//! to record a run into a trace file, or to replay one, so that a crash triaged under a debugger
+//! reproduces deterministically.
+//!
+//! QEMU replays a run from the state it started from: restoring snapshots between runs is not
+//! supported, so the crashing input should be run once in a fresh emulator, in record mode, then
+//! replayed in another one.
+
+use std::path::PathBuf;
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        utils::filters::{
+            HasAddressFilter, NopAddressFilter, NopPageFilter, NOP_ADDRESS_FILTER, NOP_PAGE_FILTER,
+        },
+        EmulatorModule, EmulatorModuleTuple,
+    },
+    QemuParams,
+};
+
+#[derive(Debug, Clone)]
+pub enum RecordReplayMode {
+    /// Records the run into the trace file.
+    Record { trace: PathBuf },
+    /// Replays the run recorded in the trace file.
+    Replay { trace: PathBuf },
+}
+
+#[derive(Debug)]
+pub struct RecordReplayModule {
+    mode: RecordReplayMode,
+    icount_shift: Option<u8>,
+}
+
+impl RecordReplayModule {
+    #[must_use]
+    pub fn record<P: Into<PathBuf>>(trace: P) -> Self {
+        Self::new(RecordReplayMode::Record {
+            trace: trace.into(),
+        })
+    }
+
+    #[must_use]
+    pub fn replay<P: Into<PathBuf>>(trace: P) -> Self {
+        Self::new(RecordReplayMode::Replay {
+            trace: trace.into(),
+        })
+    }
+
+    #[must_use]
+    pub fn new(mode: RecordReplayMode) -> Self {
+        Self {
+            mode,
+            icount_shift: None,
+        }
+    }
+
+    /// Sets a fixed icount shift, i.e. `2^shift` ns per guest instruction.
+    /// By default, it is adjusted to the host speed.
+    #[must_use]
+    pub fn icount_shift(mut self, shift: u8) -> Self {
+        self.icount_shift = Some(shift);
+        self
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> &RecordReplayMode {
+        &self.mode
+    }
+
+    /// The value of the QEMU `-icount` option.
+    #[must_use]
+    pub fn icount_option(&self) -> String {
+        let shift = self
+            .icount_shift
+            .map_or_else(|| "auto".to_string(), |shift| shift.to_string());
+        let (rr, trace) = match &self.mode {
+            RecordReplayMode::Record { trace } => ("record", trace),
+            RecordReplayMode::Replay { trace } => ("replay", trace),
+        };
+
+        format!("shift={shift},rr={rr},rrfile={}", trace.display())
+    }
+}
+
+impl<I, S> EmulatorModule<I, S> for RecordReplayModule
+where
+    I: Unpin,
+    S: Unpin,
+{
+    fn pre_qemu_init<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        qemu_params: &mut QemuParams,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        let mut args: Vec<String> = qemu_params.to_cli();
+
+        if let Some(i) = args.iter().position(|arg| arg == "-icount") {
+            log::warn!("Replacing the -icount option to record or replay the run.");
+            args.drain(i..(i + 2).min(args.len()));
+        }
+
+        args.push("-icount".to_string());
+        args.push(self.icount_option());
+
+        *qemu_params = QemuParams::Cli(args);
+    }
+}
+
+impl HasAddressFilter for RecordReplayModule {
+    type ModuleAddressFilter = NopAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordReplayModule;
+
+    #[test]
+    fn test_icount_option() {
+        assert_eq!(
+            RecordReplayModule::record("/tmp/run.rr").icount_option(),
+            "shift=auto,rr=record,rrfile=/tmp/run.rr"
+        );
+        assert_eq!(
+            RecordReplayModule::replay("/tmp/run.rr")
+                .icount_shift(7)
+                .icount_option(),
+            "shift=7,rr=replay,rrfile=/tmp/run.rr"
+        );
+    }
+}
@@ -0,0 +1,1340 @@
+//! Byte-level taint tracking of the fuzz input
+//!
+//! The [`TaintModule`] labels the guest bytes coming from the input with their offset in the
+//! input. Sources are the input location of the emulator driver and, in usermode, the reads of the
+//! input file descriptors.
+//!
+//! Labels are propagated by instruction, through memory and registers. Each translated
+//! instruction is disassembled to find its register operands, and hooked to attach the loads and
+//! stores it makes. A load labels the register it writes with the labels of the loaded bytes, a
+//! store labels the written bytes with the labels of the stored register, and any other
+//! instruction labels its destination with the labels of its sources. Registers keep one label by
+//! byte, and a computation keeps the label of the first tainted source of each byte. Comparisons
+//! also label the flags, for the targets whose conditional branches read them.
+//!
+//! After each run, the input offsets reaching each comparison and indirect jump are available
+//! with [`TaintModule::cmps`] and [`TaintModule::indirect_jumps`], and all of them are stored in
+//! the state as the ranges of a [`TaintMetadata`], for the mutators.
+
+#![allow(clippy::needless_pass_by_value)] // default compiler complains about Option<&mut T> otherwise, and this is used extensively.
+
+use core::mem;
+use std::ops::Range;
+#[cfg(feature = "usermode")]
+use std::path::PathBuf;
+
+use capstone::{
+    arch::{
+        arm::ArmOperandType, arm64::Arm64OperandType, ppc::PpcOperand, riscv::RiscVOperand,
+        x86::X86OperandType, ArchOperand,
+    },
+    prelude::*,
+    InsnGroupType, RegId,
+};
+use hashbrown::HashMap;
+use libafl::{
+    executors::ExitKind, inputs::HasTargetBytes, observers::ObserversTuple, stages::TaintMetadata,
+    HasMetadata,
+};
+use libafl_bolts::AsSlice;
+use libafl_qemu_sys::GuestAddr;
+
+#[cfg(feature = "systemmode")]
+use crate::modules::utils::filters::{NopPageFilter, NOP_PAGE_FILTER};
+#[cfg(all(feature = "usermode", not(cpu_target = "riscv32")))]
+use crate::SYS_lseek;
+#[cfg(all(
+    feature = "usermode",
+    any(
+        cpu_target = "x86_64",
+        cpu_target = "i386",
+        cpu_target = "arm",
+        cpu_target = "mips",
+        cpu_target = "ppc"
+    )
+))]
+use crate::SYS_open;
+use crate::{
+    capstone,
+    emu::EmulatorModules,
+    modules::{
+        utils::filters::{HasAddressFilter, StdAddressFilter},
+        AddressFilter, EmulatorModule, EmulatorModuleTuple,
+    },
+    qemu::{Hook, MemAccessInfo},
+    sys::TCGTemp,
+    GuestAddrKind, InputLocation, Qemu,
+};
+#[cfg(feature = "usermode")]
+use crate::{
+    modules::usermode::fs_snapshot::guest_path, SYS_close, SYS_openat, SYS_pread64, SYS_read,
+};
+
+const TAINT_SHADOW_PAGE_SIZE: usize = 4096;
+
+/// The number of bytes labeled in each register, enough for the vector registers.
+const TAINT_REG_SIZE: usize = 16;
+
+/// The maximum number of bytes of a block disassembled at once.
+const TAINT_BLOCK_MAX_SIZE: usize = 512;
+
+/// The guest's `AT_FDCWD`, the same on all targets
+#[cfg(feature = "usermode")]
+const AT_FDCWD: i32 = -100;
+
+/// The labels of the bytes of a register, or of a load, the least significant first.
+type RegLabels = [Option<usize>; TAINT_REG_SIZE];
+
+/// Ranges of the sorted input offsets.
+fn offset_ranges(offsets: impl IntoIterator<Item = usize>) -> Vec<Range<usize>> {
+    let mut offsets: Vec<usize> = offsets.into_iter().collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for offset in offsets {
+        match ranges.last_mut() {
+            Some(range) if range.end == offset => range.end += 1,
+            _ => ranges.push(offset..offset + 1),
+        }
+    }
+    ranges
+}
+
+fn add_offsets(map: &mut HashMap<GuestAddr, Vec<usize>>, addr: GuestAddr, offsets: &[usize]) {
+    let entry = map.entry(addr).or_default();
+    entry.extend_from_slice(offsets);
+    entry.sort_unstable();
+    entry.dedup();
+}
+
+/// Fills the untainted bytes of `labels` with the ones of `other`.
+fn merge_labels(labels: &mut RegLabels, other: &RegLabels) {
+    for (label, other) in labels.iter_mut().zip(other) {
+        if label.is_none() {
+            *label = *other;
+        }
+    }
+}
+
+/// A register operand: the index of the full register in the [`TaintModule`], and the size of
+/// the accessed part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TaintReg {
+    idx: usize,
+    size: usize,
+}
+
+/// The full register of a register name, and the size of the accessed part, e.g. `rax` and 4 for
+/// `eax`. `None` for the registers always reading as zero.
+#[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+fn canonical_reg(name: &str) -> Option<(String, usize)> {
+    const LEGACY_REGS: [(&str, [&str; 5]); 8] = [
+        ("rax", ["eax", "ax", "al", "ah", "rax"]),
+        ("rbx", ["ebx", "bx", "bl", "bh", "rbx"]),
+        ("rcx", ["ecx", "cx", "cl", "ch", "rcx"]),
+        ("rdx", ["edx", "dx", "dl", "dh", "rdx"]),
+        ("rsi", ["esi", "si", "sil", "sil", "rsi"]),
+        ("rdi", ["edi", "di", "dil", "dil", "rdi"]),
+        ("rbp", ["ebp", "bp", "bpl", "bpl", "rbp"]),
+        ("rsp", ["esp", "sp", "spl", "spl", "rsp"]),
+    ];
+
+    for (reg, [dword, word, byte, high_byte, qword]) in LEGACY_REGS {
+        let size = match name {
+            _ if name == qword => 8,
+            _ if name == dword => 4,
+            _ if name == word => 2,
+            _ if name == byte || name == high_byte => 1,
+            _ => continue,
+        };
+        return Some((reg.to_string(), size));
+    }
+
+    // r8 to r15, and their parts
+    if let Some(rest) = name.strip_prefix('r') {
+        let num = rest.trim_end_matches(['b', 'w', 'd']);
+        if !num.is_empty() && num.bytes().all(|c| c.is_ascii_digit()) {
+            let size = match &rest[num.len()..] {
+                "b" => 1,
+                "w" => 2,
+                "d" => 4,
+                _ => 8,
+            };
+            return Some((format!("r{num}"), size));
+        }
+    }
+
+    for vector in ["xmm", "ymm", "zmm"] {
+        if let Some(num) = name.strip_prefix(vector) {
+            return Some((format!("xmm{num}"), TAINT_REG_SIZE));
+        }
+    }
+
+    Some((name.to_string(), size_of::<GuestAddr>()))
+}
+
+/// The full register of a register name, and the size of the accessed part, e.g. `x0` and 4 for
+/// `w0`. `None` for the registers always reading as zero.
+#[cfg(cpu_target = "aarch64")]
+fn canonical_reg(name: &str) -> Option<(String, usize)> {
+    if matches!(name, "xzr" | "wzr") {
+        return None;
+    }
+    if name == "wsp" {
+        return Some(("sp".to_string(), 4));
+    }
+
+    let (prefix, num) = name.split_at(1);
+    if num.is_empty() || !num.bytes().all(|c| c.is_ascii_digit()) {
+        return Some((name.to_string(), 8));
+    }
+    match prefix {
+        "w" => Some((format!("x{num}"), 4)),
+        "x" => Some((name.to_string(), 8)),
+        "b" => Some((format!("v{num}"), 1)),
+        "h" => Some((format!("v{num}"), 2)),
+        "s" => Some((format!("v{num}"), 4)),
+        "d" => Some((format!("v{num}"), 8)),
+        _ => Some((format!("v{num}"), TAINT_REG_SIZE)),
+    }
+}
+
+/// The full register of a register name, and the size of the accessed part.
+/// `None` for the registers always reading as zero.
+#[cfg(not(any(cpu_target = "x86_64", cpu_target = "i386", cpu_target = "aarch64")))]
+fn canonical_reg(name: &str) -> Option<(String, usize)> {
+    (name != "zero").then(|| (name.to_string(), size_of::<GuestAddr>()))
+}
+
+/// How an instruction propagates the labels of its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaintInsnKind {
+    /// Copies its sources to its destination, e.g. `mov`, or to memory.
+    Move,
+    /// Computes its destination from its sources.
+    Compute,
+    /// Compares its operands, labeling only the flags.
+    Compare,
+    /// Writes a constant to its destination, e.g. `xor eax, eax`.
+    Clear,
+    /// A jump, call or return. Its operands label the block jumped to, unless it is direct.
+    Branch,
+}
+
+impl TaintInsnKind {
+    /// The kind of the instruction `mnemonic`, reading the registers `reads`.
+    fn new(mnemonic: &str, branch: bool, reads: &[TaintReg]) -> Self {
+        if branch {
+            return Self::Branch;
+        }
+
+        if (mnemonic.starts_with("cmp") && !mnemonic.starts_with("cmpxchg"))
+            || mnemonic.starts_with("test")
+            || mnemonic.contains("comis")
+            || matches!(mnemonic, "tst" | "teq" | "cmn" | "bt")
+        {
+            return Self::Compare;
+        }
+
+        if matches!(
+            mnemonic,
+            "xor" | "sub" | "sbb" | "eor" | "pxor" | "vpxor" | "xorps" | "xorpd"
+        ) && reads.len() >= 2
+            && reads.iter().all(|reg| reg.idx == reads[0].idx)
+        {
+            return Self::Clear;
+        }
+
+        if (mnemonic.starts_with("mov") && !matches!(mnemonic, "movk" | "movt"))
+            || ["cmov", "push", "pop", "set", "ld", "st"]
+                .iter()
+                .any(|prefix| mnemonic.starts_with(prefix))
+            || matches!(mnemonic, "lea" | "xchg")
+        {
+            return Self::Move;
+        }
+
+        Self::Compute
+    }
+}
+
+/// The data flow of a translated instruction.
+#[derive(Debug, Clone)]
+struct TaintInsn {
+    kind: TaintInsnKind,
+    /// The register operands, in order. The first one is the destination, unless the instruction
+    /// stores to memory or only reads its operands.
+    regs: Vec<TaintReg>,
+    /// Whether the destination is also a source, as with the two-operand instructions of x86.
+    reads_dst: bool,
+    /// Whether the instruction labels the flags with its result, as with the arithmetic of x86.
+    sets_flags: bool,
+    /// Whether the instruction has an immediate operand, e.g. the target of a direct branch.
+    has_imm: bool,
+}
+
+impl TaintInsn {
+    fn new(mnemonic: &str, branch: bool, regs: Vec<TaintReg>, has_imm: bool) -> Self {
+        let x86 = cfg!(any(cpu_target = "x86_64", cpu_target = "i386"));
+
+        // The first operand of x86 is read too, unless it is only written
+        let sources = usize::from(!x86).min(regs.len());
+        let kind = TaintInsnKind::new(mnemonic, branch, &regs[sources..]);
+        let computes = kind == TaintInsnKind::Compute;
+
+        Self {
+            kind,
+            regs,
+            reads_dst: x86 && computes,
+            sets_flags: x86 && computes,
+            has_imm,
+        }
+    }
+}
+
+/// A register or an immediate operand.
+enum TaintOperand {
+    Reg(RegId),
+    Imm,
+    Other,
+}
+
+fn taint_operand(operand: &ArchOperand) -> TaintOperand {
+    match operand {
+        ArchOperand::X86Operand(operand) => match &operand.op_type {
+            X86OperandType::Reg(reg) => TaintOperand::Reg(*reg),
+            X86OperandType::Imm(_) => TaintOperand::Imm,
+            _ => TaintOperand::Other,
+        },
+        ArchOperand::ArmOperand(operand) => match &operand.op_type {
+            ArmOperandType::Reg(reg) => TaintOperand::Reg(*reg),
+            ArmOperandType::Imm(_) => TaintOperand::Imm,
+            _ => TaintOperand::Other,
+        },
+        ArchOperand::Arm64Operand(operand) => match &operand.op_type {
+            Arm64OperandType::Reg(reg) => TaintOperand::Reg(*reg),
+            Arm64OperandType::Imm(_) => TaintOperand::Imm,
+            _ => TaintOperand::Other,
+        },
+        ArchOperand::PpcOperand(PpcOperand::Reg(reg))
+        | ArchOperand::RiscVOperand(RiscVOperand::Reg(reg)) => TaintOperand::Reg(*reg),
+        ArchOperand::PpcOperand(PpcOperand::Imm(_))
+        | ArchOperand::RiscVOperand(RiscVOperand::Imm(_)) => TaintOperand::Imm,
+        _ => TaintOperand::Other,
+    }
+}
+
+/// The labels of the registers of the guest.
+#[derive(Debug, Clone, Default)]
+struct TaintRegs {
+    /// The index of each full register, by name.
+    names: HashMap<String, usize>,
+    labels: Vec<RegLabels>,
+}
+
+impl TaintRegs {
+    /// The register operand named `name`, see [`canonical_reg`].
+    fn operand(&mut self, name: &str) -> Option<TaintReg> {
+        let (reg, size) = canonical_reg(name)?;
+        let next_idx = self.names.len();
+        let idx = *self.names.entry(reg).or_insert(next_idx);
+        if idx == self.labels.len() {
+            self.labels.push([None; TAINT_REG_SIZE]);
+        }
+
+        Some(TaintReg {
+            idx,
+            size: size.min(TAINT_REG_SIZE),
+        })
+    }
+
+    fn read(&self, reg: TaintReg) -> RegLabels {
+        let mut labels = [None; TAINT_REG_SIZE];
+        labels[..reg.size].copy_from_slice(&self.labels[reg.idx][..reg.size]);
+        labels
+    }
+
+    /// Writes the accessed part of `reg`. As on the hardware, the writes of 4 bytes or more clear
+    /// the rest of the register.
+    fn write(&mut self, reg: TaintReg, labels: &RegLabels) {
+        let reg_labels = &mut self.labels[reg.idx];
+        reg_labels[..reg.size].copy_from_slice(&labels[..reg.size]);
+        if reg.size >= 4 {
+            reg_labels[reg.size..].fill(None);
+        }
+    }
+
+    /// The input offsets of the register operands of `insn` and of its `loads`.
+    fn offsets(&self, insn: &TaintInsn, loads: &[RegLabels]) -> Vec<usize> {
+        let mut offsets: Vec<usize> = insn
+            .regs
+            .iter()
+            .map(|reg| self.read(*reg))
+            .chain(loads.iter().copied())
+            .flatten()
+            .flatten()
+            .collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+        offsets
+    }
+
+    fn clear(&mut self) {
+        for labels in &mut self.labels {
+            *labels = [None; TAINT_REG_SIZE];
+        }
+    }
+}
+
+/// The input offset of each tainted guest byte. Untracked memory is untainted.
+#[derive(Debug, Clone, Default)]
+pub struct TaintShadow {
+    /// The labels of each page, as the input offset + 1, 0 for untainted bytes.
+    pages: HashMap<GuestAddr, Box<[usize]>>,
+}
+
+impl TaintShadow {
+    fn locate(addr: GuestAddr) -> (GuestAddr, usize) {
+        let page = addr & !(TAINT_SHADOW_PAGE_SIZE as GuestAddr - 1);
+        (page, (addr - page) as usize)
+    }
+
+    fn set(&mut self, addr: GuestAddr, label: Option<usize>) {
+        let (page, offset) = Self::locate(addr);
+        let value = label.map_or(0, |label| label + 1);
+
+        if let Some(shadow_page) = self.pages.get_mut(&page) {
+            shadow_page[offset] = value;
+        } else if value != 0 {
+            let mut shadow_page = vec![0; TAINT_SHADOW_PAGE_SIZE].into_boxed_slice();
+            shadow_page[offset] = value;
+            self.pages.insert(page, shadow_page);
+        }
+    }
+
+    /// Taints `[addr, addr + len)` with the input offsets starting at `offset`.
+    pub fn taint(&mut self, addr: GuestAddr, len: usize, offset: usize) {
+        for i in 0..len {
+            self.set(addr + i as GuestAddr, Some(offset + i));
+        }
+    }
+
+    pub fn untaint(&mut self, addr: GuestAddr, len: usize) {
+        if self.pages.is_empty() {
+            return;
+        }
+        for i in 0..len as GuestAddr {
+            self.set(addr + i, None);
+        }
+    }
+
+    #[must_use]
+    pub fn label(&self, addr: GuestAddr) -> Option<usize> {
+        let (page, offset) = Self::locate(addr);
+
+        self.pages
+            .get(&page)
+            .and_then(|shadow_page| shadow_page[offset].checked_sub(1))
+    }
+
+    /// The labels of `[addr, addr + len)`, if any byte is tainted.
+    #[must_use]
+    pub fn labels(&self, addr: GuestAddr, len: usize) -> Option<Vec<Option<usize>>> {
+        if self.pages.is_empty() {
+            return None;
+        }
+        let labels: Vec<Option<usize>> = (0..len as GuestAddr)
+            .map(|i| self.label(addr + i))
+            .collect();
+        labels.iter().any(Option::is_some).then_some(labels)
+    }
+
+    /// Labels `[addr, addr + len)` with `labels`, the bytes past them untainted.
+    fn store(&mut self, addr: GuestAddr, len: usize, labels: &[Option<usize>]) {
+        if self.pages.is_empty() && labels.iter().all(Option::is_none) {
+            return;
+        }
+        for i in 0..len {
+            self.set(addr + i as GuestAddr, labels.get(i).copied().flatten());
+        }
+    }
+
+    /// Copies the labels of `[src, src + len)` to `[dst, dst + len)`.
+    pub fn copy(&mut self, dst: GuestAddr, src: GuestAddr, len: usize) {
+        if self.pages.is_empty() {
+            return;
+        }
+        let labels: Vec<Option<usize>> =
+            (0..len as GuestAddr).map(|i| self.label(src + i)).collect();
+        for (i, label) in labels.into_iter().enumerate() {
+            self.set(dst + i as GuestAddr, label);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct TaintModule {
+    filter: StdAddressFilter,
+    input_location: Option<InputLocation>,
+    #[cfg(feature = "usermode")]
+    input_fds: Vec<i32>,
+    #[cfg(feature = "usermode")]
+    input_files: Vec<PathBuf>,
+    /// The tainted fds of the current run, with their offset in the input.
+    #[cfg(feature = "usermode")]
+    fds: HashMap<i32, usize>,
+    shadow: TaintShadow,
+    cs: Capstone,
+    /// The translated instructions, by pc.
+    insns: HashMap<GuestAddr, TaintInsn>,
+    regs: TaintRegs,
+    /// The input offsets of the last comparison, or of the last result of an instruction setting
+    /// the flags.
+    flags: Vec<usize>,
+    /// The executing instruction, with its loads and its stores so far.
+    current: Option<GuestAddr>,
+    loads: Vec<RegLabels>,
+    stores: Vec<(GuestAddr, usize)>,
+    cmps: HashMap<GuestAddr, Vec<usize>>,
+    indirect_jumps: HashMap<GuestAddr, Vec<usize>>,
+}
+
+impl TaintModule {
+    #[must_use]
+    pub fn new(filter: StdAddressFilter) -> Self {
+        Self {
+            filter,
+            input_location: None,
+            #[cfg(feature = "usermode")]
+            input_fds: Vec::new(),
+            #[cfg(feature = "usermode")]
+            input_files: Vec::new(),
+            #[cfg(feature = "usermode")]
+            fds: HashMap::new(),
+            shadow: TaintShadow::default(),
+            cs: capstone().detail(true).build().unwrap(),
+            insns: HashMap::new(),
+            regs: TaintRegs::default(),
+            flags: Vec::new(),
+            current: None,
+            loads: Vec::new(),
+            stores: Vec::new(),
+            cmps: HashMap::new(),
+            indirect_jumps: HashMap::new(),
+        }
+    }
+
+    /// Taints the input written by the emulator driver at `input_location`.
+    #[must_use]
+    pub fn input_location(mut self, input_location: InputLocation) -> Self {
+        self.input_location = Some(input_location);
+        self
+    }
+
+    /// Taints the bytes read from the guest file descriptor `fd`, e.g. 0 for stdin.
+    #[cfg(feature = "usermode")]
+    #[must_use]
+    pub fn input_fd(mut self, fd: i32) -> Self {
+        self.input_fds.push(fd);
+        self
+    }
+
+    /// Taints the bytes read from the file at `path`, once opened by the guest.
+    #[cfg(feature = "usermode")]
+    #[must_use]
+    pub fn input_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.input_files.push(path.into());
+        self
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    #[must_use]
+    pub fn shadow(&self) -> &TaintShadow {
+        &self.shadow
+    }
+
+    /// The input offsets compared so far in this run, by comparison pc.
+    #[must_use]
+    pub fn cmps(&self) -> &HashMap<GuestAddr, Vec<usize>> {
+        &self.cmps
+    }
+
+    /// The input offsets of the targets of the indirect jumps so far in this run, by target.
+    #[must_use]
+    pub fn indirect_jumps(&self) -> &HashMap<GuestAddr, Vec<usize>> {
+        &self.indirect_jumps
+    }
+
+    /// Disassembles the block at `pc`, returning the instructions not translated yet.
+    #[allow(clippy::cast_possible_truncation)] // for u64 -> GuestAddr
+    fn translate_block(&mut self, qemu: Qemu, pc: GuestAddr) -> Vec<GuestAddr> {
+        #[cfg(cpu_target = "arm")]
+        self.cs
+            .set_mode(if pc & 1 == 1 {
+                arch::arm::ArchMode::Thumb.into()
+            } else {
+                arch::arm::ArchMode::Arm.into()
+            })
+            .unwrap();
+
+        // Read by chunks, the block can end just before an unmapped page
+        let mut code: Vec<u8> = Vec::with_capacity(TAINT_BLOCK_MAX_SIZE);
+        let mut chunk = [0; 16];
+        while code.len() < TAINT_BLOCK_MAX_SIZE
+            && qemu
+                .read_mem(pc + code.len() as GuestAddr, &mut chunk)
+                .is_ok()
+        {
+            code.extend_from_slice(&chunk);
+        }
+
+        let mut new_insns = Vec::new();
+        let mut offset = 0;
+        while let Ok(insns) =
+            self.cs
+                .disasm_count(&code[offset..], (pc + offset as GuestAddr).into(), 1)
+        {
+            let Some(insn) = insns.first() else {
+                break;
+            };
+            let insn_detail: InsnDetail = self.cs.insn_detail(insn).unwrap();
+
+            let mut branch = false;
+            for detail in insn_detail.groups() {
+                match u32::from(detail.0) {
+                    InsnGroupType::CS_GRP_JUMP
+                    | InsnGroupType::CS_GRP_CALL
+                    | InsnGroupType::CS_GRP_RET
+                    | InsnGroupType::CS_GRP_IRET => branch = true,
+                    InsnGroupType::CS_GRP_INVALID | InsnGroupType::CS_GRP_PRIVILEGE => {
+                        return new_insns;
+                    }
+                    _ => {}
+                }
+            }
+
+            let mut regs = Vec::new();
+            let mut has_imm = false;
+            for operand in insn_detail.arch_detail().operands() {
+                match taint_operand(&operand) {
+                    TaintOperand::Reg(reg) => {
+                        if let Some(reg) = self
+                            .cs
+                            .reg_name(reg)
+                            .and_then(|name| self.regs.operand(&name))
+                        {
+                            regs.push(reg);
+                        }
+                    }
+                    TaintOperand::Imm => has_imm = true,
+                    TaintOperand::Other => {}
+                }
+            }
+
+            let addr = insn.address() as GuestAddr;
+            let taint_insn =
+                TaintInsn::new(insn.mnemonic().unwrap_or_default(), branch, regs, has_imm);
+            if self.insns.insert(addr, taint_insn).is_none() {
+                new_insns.push(addr);
+            }
+
+            offset += insn.bytes().len();
+            if branch {
+                break;
+            }
+        }
+
+        new_insns
+    }
+
+    /// The instruction at `pc` is executed.
+    fn insn(&mut self, pc: GuestAddr) {
+        self.finish_insn(None);
+        self.current = Some(pc);
+    }
+
+    /// Propagates the labels through the executed instruction, with its loads and stores.
+    /// `next_block` is the block executed next, if it starts now.
+    fn finish_insn(&mut self, next_block: Option<GuestAddr>) {
+        let loads = mem::take(&mut self.loads);
+        let stores = mem::take(&mut self.stores);
+        let Some(insn) = self.current.take().and_then(|pc| self.insns.get(&pc)) else {
+            // Not instrumented code, nothing is known of the stored values
+            for (addr, size) in stores {
+                self.shadow.untaint(addr, size);
+            }
+            return;
+        };
+
+        match insn.kind {
+            TaintInsnKind::Branch => {
+                for (addr, size) in stores {
+                    self.shadow.untaint(addr, size);
+                }
+                if let Some(target) = next_block.filter(|_| !insn.has_imm) {
+                    let offsets = self.regs.offsets(insn, &loads);
+                    if !offsets.is_empty() {
+                        add_offsets(&mut self.indirect_jumps, target, &offsets);
+                    }
+                }
+            }
+            TaintInsnKind::Compare => {
+                self.flags = self.regs.offsets(insn, &loads);
+            }
+            TaintInsnKind::Clear => {
+                for (addr, size) in stores {
+                    self.shadow.untaint(addr, size);
+                }
+                if let Some(dst) = insn.regs.first() {
+                    self.regs.write(*dst, &[None; TAINT_REG_SIZE]);
+                }
+            }
+            TaintInsnKind::Move | TaintInsnKind::Compute if !stores.is_empty() => {
+                // e.g. the register pairs and lists of arm
+                if stores.len() > 1 && stores.len() == insn.regs.len() {
+                    for ((addr, size), reg) in stores.into_iter().zip(&insn.regs) {
+                        self.shadow.store(addr, size, &self.regs.read(*reg));
+                    }
+                    return;
+                }
+
+                let mut labels = [None; TAINT_REG_SIZE];
+                for reg in &insn.regs {
+                    merge_labels(&mut labels, &self.regs.read(*reg));
+                }
+                for load in &loads {
+                    merge_labels(&mut labels, load);
+                }
+                for (addr, size) in stores {
+                    self.shadow.store(addr, size, &labels);
+                }
+            }
+            TaintInsnKind::Move | TaintInsnKind::Compute => {
+                let Some((dst, srcs)) = insn.regs.split_first() else {
+                    return;
+                };
+
+                if loads.len() > 1 && loads.len() == insn.regs.len() {
+                    for (reg, load) in insn.regs.iter().zip(&loads) {
+                        self.regs.write(*reg, load);
+                    }
+                    return;
+                }
+
+                let mut labels = [None; TAINT_REG_SIZE];
+                for load in &loads {
+                    merge_labels(&mut labels, load);
+                }
+                for src in srcs {
+                    merge_labels(&mut labels, &self.regs.read(*src));
+                }
+                if insn.reads_dst {
+                    merge_labels(&mut labels, &self.regs.read(*dst));
+                }
+                self.regs.write(*dst, &labels);
+
+                if insn.sets_flags {
+                    self.flags = labels.iter().flatten().copied().collect();
+                }
+            }
+        }
+    }
+
+    /// The executing instruction loads `size` bytes at `addr`.
+    pub fn load(&mut self, addr: GuestAddr, size: usize) {
+        let mut labels = [None; TAINT_REG_SIZE];
+        if let Some(loaded) = self.shadow.labels(addr, size.min(TAINT_REG_SIZE)) {
+            labels[..loaded.len()].copy_from_slice(&loaded);
+        }
+        self.loads.push(labels);
+    }
+
+    /// The executing instruction stores `size` bytes at `addr`.
+    pub fn store(&mut self, addr: GuestAddr, size: usize) {
+        self.stores.push((addr, size));
+    }
+
+    /// The instruction at `pc` compares its operands, or branches on the flags.
+    pub fn cmp(&mut self, pc: GuestAddr) {
+        let offsets = match self.current.and_then(|current| self.insns.get(&current)) {
+            Some(insn) if !insn.regs.is_empty() || !self.loads.is_empty() => {
+                self.regs.offsets(insn, &self.loads)
+            }
+            _ => self.flags.clone(),
+        };
+
+        if !offsets.is_empty() {
+            add_offsets(&mut self.cmps, pc, &offsets);
+        }
+    }
+
+    /// The block at `pc` is executed.
+    fn block(&mut self, pc: GuestAddr) {
+        self.finish_insn(Some(pc));
+    }
+
+    #[cfg(feature = "usermode")]
+    fn is_input_file(&self, qemu: Qemu, dirfd: i32, path: GuestAddr) -> bool {
+        !self.input_files.is_empty()
+            && guest_path(qemu, dirfd, path).is_some_and(|path| self.input_files.contains(&path))
+    }
+
+    /// Follows the input file descriptors, and taints what is read from them.
+    #[cfg(feature = "usermode")]
+    #[allow(clippy::cast_possible_truncation)] // fds and counts are returned in GuestAddr
+    pub fn syscall(
+        &mut self,
+        qemu: Qemu,
+        result: GuestAddr,
+        sys_num: i64,
+        a0: GuestAddr,
+        a1: GuestAddr,
+        a3: GuestAddr,
+    ) {
+        // -4095..-1 are errors
+        if result > GuestAddr::MAX - 4095 {
+            return;
+        }
+
+        match sys_num {
+            SYS_openat if self.is_input_file(qemu, a0 as i32, a1) => {
+                self.fds.insert(result as i32, 0);
+            }
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_open if self.is_input_file(qemu, AT_FDCWD, a0) => {
+                self.fds.insert(result as i32, 0);
+            }
+            SYS_read => {
+                let len = result as usize;
+                if let Some(offset) = self.fds.get_mut(&(a0 as i32)) {
+                    self.shadow.taint(a1, len, *offset);
+                    *offset += len;
+                } else {
+                    self.shadow.untaint(a1, len);
+                }
+            }
+            SYS_pread64 => {
+                let len = result as usize;
+                if self.fds.contains_key(&(a0 as i32)) {
+                    self.shadow.taint(a1, len, a3 as usize);
+                } else {
+                    self.shadow.untaint(a1, len);
+                }
+            }
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_lseek => {
+                if let Some(offset) = self.fds.get_mut(&(a0 as i32)) {
+                    *offset = result as usize;
+                }
+            }
+            SYS_close => {
+                self.fds.remove(&(a0 as i32));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<I, S> EmulatorModule<I, S> for TaintModule
+where
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    #[cfg(feature = "usermode")]
+    fn post_qemu_init<ET>(&mut self, _qemu: Qemu, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        emulator_modules.post_syscalls(Hook::Function(syscall_taint::<ET, I, S>));
+    }
+
+    fn first_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        emulator_modules.reads(
+            Hook::Function(gen_readwrite_taint::<ET, I, S>),
+            Hook::Function(trace_read_taint::<ET, I, S, 1>),
+            Hook::Function(trace_read_taint::<ET, I, S, 2>),
+            Hook::Function(trace_read_taint::<ET, I, S, 4>),
+            Hook::Function(trace_read_taint::<ET, I, S, 8>),
+            Hook::Function(trace_read_n_taint::<ET, I, S>),
+        );
+
+        emulator_modules.writes(
+            Hook::Function(gen_readwrite_taint::<ET, I, S>),
+            Hook::Function(trace_write_taint::<ET, I, S, 1>),
+            Hook::Function(trace_write_taint::<ET, I, S, 2>),
+            Hook::Function(trace_write_taint::<ET, I, S, 4>),
+            Hook::Function(trace_write_taint::<ET, I, S, 8>),
+            Hook::Function(trace_write_n_taint::<ET, I, S>),
+        );
+
+        emulator_modules.cmps(
+            Hook::Function(gen_cmp_taint::<ET, I, S>),
+            Hook::Function(trace_cmp_taint::<ET, I, S, u8>),
+            Hook::Function(trace_cmp_taint::<ET, I, S, u16>),
+            Hook::Function(trace_cmp_taint::<ET, I, S, u32>),
+            Hook::Function(trace_cmp_taint::<ET, I, S, u64>),
+        );
+
+        emulator_modules.blocks(
+            Hook::Function(gen_block_taint::<ET, I, S>),
+            Hook::Empty,
+            Hook::Function(exec_block_taint::<ET, I, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.shadow.clear();
+        self.regs.clear();
+        self.flags.clear();
+        self.current = None;
+        self.loads.clear();
+        self.stores.clear();
+        self.cmps.clear();
+        self.indirect_jumps.clear();
+
+        #[cfg(feature = "usermode")]
+        {
+            self.fds = self.input_fds.iter().map(|fd| (*fd, 0)).collect();
+        }
+
+        if let Some(input_location) = &self.input_location {
+            let mem_chunk = input_location.mem_chunk();
+            let len = input
+                .target_bytes()
+                .as_slice()
+                .len()
+                .min(mem_chunk.size() as usize);
+            match mem_chunk.addr() {
+                #[allow(clippy::unnecessary_cast)] // for GuestVirtAddr -> GuestAddr
+                GuestAddrKind::Virtual(addr) => self.shadow.taint(addr as GuestAddr, len, 0),
+                GuestAddrKind::Physical(addr) => {
+                    log::warn!("Physical input location {addr:#x} cannot be tainted.");
+                }
+            }
+        }
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        state: &mut S,
+        input: &I,
+        _observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<I, S>,
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.finish_insn(None);
+
+        let offsets = self
+            .cmps
+            .values()
+            .chain(self.indirect_jumps.values())
+            .flatten()
+            .copied();
+        state.add_metadata(TaintMetadata::new(
+            input.target_bytes().as_slice().to_vec(),
+            offset_ranges(offsets),
+        ));
+    }
+}
+
+impl HasAddressFilter for TaintModule {
+    type ModuleAddressFilter = StdAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = NopPageFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+#[cfg(feature = "usermode")]
+#[expect(clippy::too_many_arguments)]
+pub fn syscall_taint<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    result: GuestAddr,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    _a2: GuestAddr,
+    a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> GuestAddr
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    h.syscall(qemu, result, i64::from(sys_num), a0, a1, a3);
+    result
+}
+
+pub fn gen_readwrite_taint<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _addr: *mut TCGTemp,
+    _info: MemAccessInfo,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get::<TaintModule>().unwrap();
+    h.must_instrument(pc).then_some(0)
+}
+
+pub fn trace_read_taint<ET, I, S, const N: usize>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    h.load(addr, N);
+}
+
+pub fn trace_read_n_taint<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    h.load(addr, size);
+}
+
+pub fn trace_write_taint<ET, I, S, const N: usize>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    h.store(addr, N);
+}
+
+pub fn trace_write_n_taint<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    h.store(addr, size);
+}
+
+/// Uses the comparison pc as id, to get it back at execution.
+
+pub fn gen_cmp_taint<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _size: usize,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get::<TaintModule>().unwrap();
+    #[allow(clippy::unnecessary_cast)] // for GuestAddr -> u64
+    h.must_instrument(pc).then_some(pc as u64)
+}
+
+/// The compared values are not needed, the operands are known from the instruction.
+pub fn trace_cmp_taint<ET, I, S, SZ>(
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    _v0: SZ,
+    _v1: SZ,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    #[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)] // the id is a GuestAddr
+    h.cmp(id as GuestAddr);
+}
+
+/// Translates the instructions of the block, and uses the block pc as id, to get it back at
+/// execution.
+pub fn gen_block_taint<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    if !h.must_instrument(pc) {
+        return None;
+    }
+
+    for addr in h.translate_block(qemu, pc) {
+        emulator_modules.instruction_function(addr, exec_insn_taint::<ET, I, S>, false);
+    }
+
+    #[allow(clippy::unnecessary_cast)] // for GuestAddr -> u64
+    Some(pc as u64)
+}
+
+pub fn exec_block_taint<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    #[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)] // the id is a GuestAddr
+    h.block(id as GuestAddr);
+}
+
+pub fn exec_insn_taint<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: HasMetadata + Unpin,
+{
+    let h = emulator_modules.get_mut::<TaintModule>().unwrap();
+    h.insn(pc);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        offset_ranges, StdAddressFilter, TaintInsn, TaintInsnKind, TaintModule, TaintShadow,
+    };
+
+    #[test]
+    fn test_taint_shadow() {
+        let mut shadow = TaintShadow::default();
+        assert_eq!(shadow.labels(0x1000, 4), None);
+
+        shadow.taint(0x1000, 4, 10);
+        assert_eq!(
+            shadow.labels(0x0ffe, 4),
+            Some(vec![None, None, Some(10), Some(11)])
+        );
+
+        shadow.copy(0x2000, 0x1002, 4);
+        assert_eq!(shadow.label(0x2000), Some(12));
+        assert_eq!(shadow.label(0x2002), None);
+
+        shadow.untaint(0x1000, 2);
+        assert_eq!(shadow.label(0x1001), None);
+        assert_eq!(shadow.label(0x1002), Some(12));
+
+        // across a shadow page
+        shadow.taint(0x2ffe, 4, 0);
+        assert_eq!(shadow.labels(0x2fff, 2), Some(vec![Some(1), Some(2)]));
+        shadow.clear();
+        assert!(shadow.is_empty());
+    }
+
+    #[test]
+    fn test_taint_ranges() {
+        assert_eq!(offset_ranges([3, 1, 2, 8, 4, 9, 2]), vec![1..5, 8..10]);
+        assert!(offset_ranges([]).is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_taint_insn_kind() {
+        let mut module = TaintModule::new(StdAddressFilter::default());
+        let a = module.regs.operand("a0").unwrap();
+        let b = module.regs.operand("a1").unwrap();
+
+        assert_eq!(TaintInsnKind::new("jmp", true, &[a]), TaintInsnKind::Branch);
+        assert_eq!(
+            TaintInsnKind::new("cmp", false, &[a, b]),
+            TaintInsnKind::Compare
+        );
+        assert_eq!(
+            TaintInsnKind::new("cmpxchg", false, &[a, b]),
+            TaintInsnKind::Compute
+        );
+        assert_eq!(
+            TaintInsnKind::new("xor", false, &[a, a]),
+            TaintInsnKind::Clear
+        );
+        assert_eq!(
+            TaintInsnKind::new("xor", false, &[a, b]),
+            TaintInsnKind::Compute
+        );
+        assert_eq!(
+            TaintInsnKind::new("movzx", false, &[a]),
+            TaintInsnKind::Move
+        );
+    }
+
+    #[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+    #[test]
+    fn test_taint_canonical_reg() {
+        use super::{canonical_reg, TAINT_REG_SIZE};
+
+        assert_eq!(canonical_reg("eax"), Some(("rax".to_string(), 4)));
+        assert_eq!(canonical_reg("ah"), Some(("rax".to_string(), 1)));
+        assert_eq!(canonical_reg("r8w"), Some(("r8".to_string(), 2)));
+        assert_eq!(canonical_reg("r15"), Some(("r15".to_string(), 8)));
+        assert_eq!(
+            canonical_reg("ymm3"),
+            Some(("xmm3".to_string(), TAINT_REG_SIZE))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_taint_propagation() {
+        let mut module = TaintModule::new(StdAddressFilter::default());
+        let a = module.regs.operand("a0").unwrap();
+        let b = module.regs.operand("a1").unwrap();
+        let size = a.size;
+
+        let insns = [
+            // load [0x1000] into a
+            (0x100, TaintInsn::new("mov", false, vec![a], false)),
+            // b = a
+            (0x104, TaintInsn::new("mov", false, vec![b, a], false)),
+            // store b to [0x2000]
+            (0x108, TaintInsn::new("mov", false, vec![b], false)),
+            // compare a with an immediate
+            (0x10c, TaintInsn::new("cmp", false, vec![a], true)),
+            // jump to b
+            (0x110, TaintInsn::new("jmp", true, vec![b], false)),
+        ];
+        module.insns.extend(insns);
+
+        module.shadow.taint(0x1000, size, 4);
+
+        module.insn(0x100);
+        module.load(0x1000, size);
+        module.insn(0x104);
+        module.insn(0x108);
+        module.store(0x2000, size);
+        module.insn(0x10c);
+        module.cmp(0x10c);
+        module.insn(0x110);
+        module.block(0x4000);
+
+        let offsets: Vec<usize> = (4..4 + size).collect();
+        assert_eq!(
+            module.shadow.labels(0x2000, size),
+            Some(offsets.iter().copied().map(Some).collect())
+        );
+        assert_eq!(module.cmps()[&0x10c], offsets);
+        assert_eq!(module.indirect_jumps()[&0x4000], offsets);
+        assert_eq!(module.flags, offsets);
+
+        // a store of an untainted register untaints memory
+        module
+            .insns
+            .insert(0x114, TaintInsn::new("xor", false, vec![b, b, b], false));
+        module.insn(0x114);
+        module.insn(0x108);
+        module.store(0x2000, size);
+        module.block(0x4004);
+        assert_eq!(module.shadow.labels(0x2000, size), None);
+    }
+}
@@ -0,0 +1,594 @@
+//! File descriptor and filesystem snapshots, for the [`super::SnapshotModule`].
+//!
+//! In usermode, guest file descriptors are host file descriptors, and guest paths are host paths.
+//! At snapshot time, we record the open fds and their offsets.
+//! During the execution, the syscalls of the guest are traced to:
+//! - remember the fds opened after the snapshot, to close them on reset,
+//! - keep a duplicate of the snapshot fds that the guest closes or replaces, to restore them on reset,
+//! - copy the files the guest creates, modifies, renames or removes before the first change (copy-on-write),
+//!   to roll them back on reset.
+//!
+//! The files are compared by path, as seen by the host. Paths remapped by QEMU (`-L`) are not translated.
+use std::{
+    env,
+    ffi::OsStr,
+    fs::{self, Permissions},
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::{HashMap, HashSet};
+use libafl_qemu_sys::GuestAddr;
+
+#[cfg(not(cpu_target = "i386"))]
+use crate::SYS_accept;
+#[cfg(not(any(cpu_target = "arm", cpu_target = "riscv32")))]
+use crate::SYS_mmap;
+#[cfg(not(any(cpu_target = "riscv32", cpu_target = "riscv64")))]
+use crate::SYS_renameat;
+use crate::{
+    Qemu, SYS_accept4, SYS_close, SYS_copy_file_range, SYS_dup, SYS_dup3, SYS_epoll_create1,
+    SYS_eventfd2, SYS_fallocate, SYS_inotify_init1, SYS_memfd_create, SYS_openat, SYS_pidfd_open,
+    SYS_pipe2, SYS_pwrite64, SYS_pwritev, SYS_pwritev2, SYS_read, SYS_readv, SYS_renameat2,
+    SYS_signalfd4, SYS_socket, SYS_socketpair, SYS_splice, SYS_timerfd_create, SYS_unlinkat,
+    SYS_write, SYS_writev,
+};
+#[cfg(any(
+    cpu_target = "x86_64",
+    cpu_target = "i386",
+    cpu_target = "arm",
+    cpu_target = "mips",
+    cpu_target = "ppc"
+))]
+use crate::{
+    SYS_creat, SYS_dup2, SYS_epoll_create, SYS_eventfd, SYS_inotify_init, SYS_open, SYS_pipe,
+    SYS_rename, SYS_signalfd, SYS_unlink,
+};
+#[cfg(not(cpu_target = "riscv32"))]
+use crate::{SYS_fcntl, SYS_ftruncate, SYS_lseek, SYS_sendfile, SYS_truncate};
+#[cfg(any(
+    cpu_target = "i386",
+    cpu_target = "arm",
+    cpu_target = "mips",
+    cpu_target = "ppc",
+    cpu_target = "riscv32"
+))]
+use crate::{SYS_ftruncate64, SYS_mmap2, SYS_truncate64};
+
+/// The guest's `AT_FDCWD`, the same on all targets
+const AT_FDCWD: i32 = -100;
+/// The guest's `AT_REMOVEDIR`, the same on all targets
+const AT_REMOVEDIR: GuestAddr = 0x200;
+/// The guest's access mode bits of the `open` flags
+const O_ACCMODE: GuestAddr = 0o3;
+/// The guest's `O_CREAT`, which differs on mips
+#[cfg(cpu_target = "mips")]
+const O_CREAT: GuestAddr = 0x100;
+#[cfg(not(cpu_target = "mips"))]
+const O_CREAT: GuestAddr = 0o100;
+/// The guest's `O_TRUNC`
+const O_TRUNC: GuestAddr = 0o1000;
+/// The guest's `F_DUPFD` and `F_DUPFD_CLOEXEC`
+#[cfg(not(cpu_target = "riscv32"))]
+const F_DUPFD: GuestAddr = 0;
+#[cfg(not(cpu_target = "riscv32"))]
+const F_DUPFD_CLOEXEC: GuestAddr = 1030;
+
+/// The guest's `MAP_SHARED`, the same on all targets
+const MAP_SHARED: GuestAddr = 0x1;
+
+/// The longest path the guest kernel accepts, `PATH_MAX`
+const GUEST_PATH_MAX: usize = 4096;
+
+/// The lowest fd used for the duplicates of the snapshot fds, to stay out of the way of the guest
+const BACKUP_FD_MIN: i32 = 512;
+
+/// The content of a file at snapshot time
+#[derive(Debug, Clone)]
+pub struct FileBackup {
+    pub content: Vec<u8>,
+    pub permissions: Permissions,
+}
+
+/// An fd open at snapshot time
+#[derive(Debug, Clone)]
+pub struct SnapshotFd {
+    /// The offset at snapshot time, if the fd is seekable
+    pub offset: Option<i64>,
+    /// The regular file behind the fd, if any
+    pub path: Option<PathBuf>,
+}
+
+/// The fds and files of the guest at snapshot time, and what changed since
+#[derive(Debug, Default)]
+pub struct FsSnapshot {
+    /// The fds open at snapshot time
+    pub fds: HashMap<i32, SnapshotFd>,
+    /// Duplicates of the snapshot fds that the guest closed or replaced
+    pub backup_fds: HashMap<i32, i32>,
+    /// The snapshot fds whose offset may have moved
+    pub touched_fds: HashSet<i32>,
+    /// The fds opened by the guest after the snapshot
+    pub new_fds: HashSet<i32>,
+    /// The files changed by the guest at any point, with their content at snapshot time,
+    /// or `None` if they did not exist
+    pub files: HashMap<PathBuf, Option<FileBackup>>,
+    /// The files changed by the guest since the last reset
+    pub dirty_files: HashSet<PathBuf>,
+}
+
+impl FsSnapshot {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the open fds and their offsets.
+    pub fn snapshot(&mut self) {
+        self.fds.clear();
+        self.backup_fds.clear();
+        self.touched_fds.clear();
+        self.new_fds.clear();
+        self.files.clear();
+        self.dirty_files.clear();
+
+        let Ok(entries) = fs::read_dir("/proc/self/fd") else {
+            log::warn!("Cannot list /proc/self/fd, fds will not be snapshotted");
+            return;
+        };
+        let fds: Vec<i32> = entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect();
+        // The fd used to list the directory is closed by now
+        for fd in fds {
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+                continue;
+            }
+            let offset = unsafe { libc::lseek(fd, 0, libc::SEEK_CUR) };
+            let path = fs::read_link(format!("/proc/self/fd/{fd}"))
+                .ok()
+                .filter(|path| path.is_absolute() && path.is_file());
+            self.fds.insert(
+                fd,
+                SnapshotFd {
+                    offset: (offset != -1).then_some(offset),
+                    path,
+                },
+            );
+        }
+        log::debug!("Snapshotted {} fds", self.fds.len());
+    }
+
+    /// Closes the fds opened since the snapshot, restores the snapshot fds and their offsets,
+    /// and rolls back the changed files.
+    pub fn reset(&mut self) {
+        for fd in self.new_fds.drain() {
+            unsafe { libc::close(fd) };
+        }
+
+        for (fd, backup) in self.backup_fds.drain() {
+            if unsafe { libc::dup2(backup, fd) } == -1 {
+                log::warn!("Cannot restore fd {fd}: {:?}", io::Error::last_os_error());
+            }
+            unsafe { libc::close(backup) };
+        }
+
+        for fd in self.touched_fds.drain() {
+            if let Some(SnapshotFd {
+                offset: Some(offset),
+                ..
+            }) = self.fds.get(&fd)
+            {
+                unsafe { libc::lseek(fd, *offset, libc::SEEK_SET) };
+            }
+        }
+
+        for path in self.dirty_files.drain() {
+            let restored = match &self.files[&path] {
+                None => match fs::remove_file(&path) {
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                    res => res,
+                },
+                Some(backup) => fs::write(&path, &backup.content)
+                    .and_then(|()| fs::set_permissions(&path, backup.permissions.clone())),
+            };
+            if let Err(err) = restored {
+                log::warn!("Cannot restore {}: {err:?}", path.display());
+            }
+        }
+    }
+
+    /// Saves the file at `path` before its first change, and marks it as dirty.
+    pub fn backup_file(&mut self, path: PathBuf) {
+        if !self.files.contains_key(&path) {
+            let backup = match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.is_file() => match fs::read(&path) {
+                    Ok(content) => Some(FileBackup {
+                        content,
+                        permissions: metadata.permissions(),
+                    }),
+                    Err(err) => {
+                        log::warn!("Cannot save {}: {err:?}", path.display());
+                        return;
+                    }
+                },
+                // Directories, devices, fifos and sockets are not rolled back
+                Ok(_) => return,
+                Err(_) => None,
+            };
+            self.files.insert(path.clone(), backup);
+        }
+        self.dirty_files.insert(path);
+    }
+
+    /// Saves the file behind the snapshot fd `fd`, if any, before it gets written.
+    fn backup_fd_file(&mut self, fd: i32) {
+        if let Some(path) = self.fds.get(&fd).and_then(|fd| fd.path.clone()) {
+            self.backup_file(path);
+        }
+    }
+
+    /// Keeps a duplicate of the snapshot fd `fd`, before the guest closes or replaces it.
+    fn backup_fd(&mut self, fd: i32) {
+        if !self.fds.contains_key(&fd) || self.backup_fds.contains_key(&fd) {
+            return;
+        }
+        let backup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, BACKUP_FD_MIN) };
+        if backup == -1 {
+            log::warn!(
+                "Cannot keep a duplicate of fd {fd}: {:?}",
+                io::Error::last_os_error()
+            );
+        } else {
+            self.backup_fds.insert(fd, backup);
+            self.touched_fds.insert(fd);
+        }
+    }
+
+    fn touch_fd(&mut self, fd: i32) {
+        if self.fds.contains_key(&fd) {
+            self.touched_fds.insert(fd);
+        }
+    }
+
+    /// Remembers an fd the guest got after the snapshot.
+    fn add_new_fd(&mut self, fd: i32) {
+        // A snapshot fd number reused after a close is restored from its duplicate
+        if fd >= 0 && !self.fds.contains_key(&fd) {
+            self.new_fds.insert(fd);
+        }
+    }
+
+    fn backup_open(&mut self, qemu: Qemu, dirfd: i32, path: GuestAddr, flags: GuestAddr) {
+        if flags & O_ACCMODE != 0 || flags & (O_CREAT | O_TRUNC) != 0 {
+            if let Some(path) = guest_path(qemu, dirfd, path) {
+                self.backup_file(path);
+            }
+        }
+    }
+
+    fn backup_path(&mut self, qemu: Qemu, dirfd: i32, path: GuestAddr) {
+        if let Some(path) = guest_path(qemu, dirfd, path) {
+            self.backup_file(path);
+        }
+    }
+
+    /// Called before each syscall of the guest, to save what it may change.
+    #[expect(clippy::too_many_arguments)]
+    pub fn pre_syscall(
+        &mut self,
+        qemu: Qemu,
+        sys_num: i64,
+        a0: GuestAddr,
+        a1: GuestAddr,
+        a2: GuestAddr,
+        a3: GuestAddr,
+        a4: GuestAddr,
+    ) {
+        match sys_num {
+            SYS_close => self.backup_fd(a0 as i32),
+            SYS_dup3 => self.backup_fd(a1 as i32),
+            SYS_read | SYS_readv => self.touch_fd(a0 as i32),
+            SYS_write | SYS_writev => {
+                self.touch_fd(a0 as i32);
+                self.backup_fd_file(a0 as i32);
+            }
+            SYS_pwrite64 | SYS_pwritev | SYS_pwritev2 | SYS_fallocate => {
+                self.backup_fd_file(a0 as i32);
+            }
+            // Both offsets move, unless given explicitly
+            SYS_copy_file_range | SYS_splice => {
+                self.touch_fd(a0 as i32);
+                self.touch_fd(a2 as i32);
+                self.backup_fd_file(a2 as i32);
+            }
+            SYS_openat => self.backup_open(qemu, a0 as i32, a1, a2),
+            SYS_unlinkat if a2 & AT_REMOVEDIR == 0 => self.backup_path(qemu, a0 as i32, a1),
+            SYS_renameat2 => {
+                self.backup_path(qemu, a0 as i32, a1);
+                self.backup_path(qemu, a2 as i32, a3);
+            }
+            #[cfg(not(any(cpu_target = "riscv32", cpu_target = "riscv64")))]
+            SYS_renameat => {
+                self.backup_path(qemu, a0 as i32, a1);
+                self.backup_path(qemu, a2 as i32, a3);
+            }
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_lseek => self.touch_fd(a0 as i32),
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_ftruncate => self.backup_fd_file(a0 as i32),
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_truncate => self.backup_path(qemu, AT_FDCWD, a0),
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_sendfile => {
+                self.touch_fd(a0 as i32);
+                self.touch_fd(a1 as i32);
+                self.backup_fd_file(a0 as i32);
+            }
+            #[cfg(any(
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc",
+                cpu_target = "riscv32"
+            ))]
+            SYS_ftruncate64 => self.backup_fd_file(a0 as i32),
+            #[cfg(any(
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc",
+                cpu_target = "riscv32"
+            ))]
+            SYS_truncate64 => self.backup_path(qemu, AT_FDCWD, a0),
+            // The guest may write to a shared mapping at any time, so save the file right away.
+            // The mapping itself is rolled back with the memory, not the file.
+            #[cfg(any(
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc",
+                cpu_target = "riscv32"
+            ))]
+            SYS_mmap2 if a3 & MAP_SHARED != 0 => self.backup_fd_file(a4 as i32),
+            // On i386, the old `mmap` takes its arguments in memory, so only `mmap2` is followed
+            #[cfg(not(any(cpu_target = "arm", cpu_target = "riscv32", cpu_target = "i386")))]
+            SYS_mmap if a3 & MAP_SHARED != 0 => self.backup_fd_file(a4 as i32),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_dup2 => self.backup_fd(a1 as i32),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_open => self.backup_open(qemu, AT_FDCWD, a0, a1),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_creat => self.backup_open(qemu, AT_FDCWD, a0, O_CREAT | O_TRUNC),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_unlink => self.backup_path(qemu, AT_FDCWD, a0),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_rename => {
+                self.backup_path(qemu, AT_FDCWD, a0);
+                self.backup_path(qemu, AT_FDCWD, a1);
+            }
+            _ => {}
+        }
+    }
+
+    /// Called after each syscall of the guest, to track the fds it gets.
+    #[cfg_attr(cpu_target = "riscv32", allow(unused_variables))]
+    pub fn post_syscall(
+        &mut self,
+        qemu: Qemu,
+        result: GuestAddr,
+        sys_num: i64,
+        a0: GuestAddr,
+        a1: GuestAddr,
+        a3: GuestAddr,
+    ) {
+        let fd = result as i32;
+        if fd < 0 {
+            return;
+        }
+        match sys_num {
+            SYS_close => {
+                self.new_fds.remove(&(a0 as i32));
+            }
+            // `signalfd4` on an existing signalfd returns that fd, which is then already known
+            SYS_openat | SYS_dup | SYS_dup3 | SYS_socket | SYS_accept4 | SYS_eventfd2
+            | SYS_memfd_create | SYS_epoll_create1 | SYS_inotify_init1 | SYS_timerfd_create
+            | SYS_signalfd4 | SYS_pidfd_open => self.add_new_fd(fd),
+            SYS_pipe2 => self.add_pipe_fds(qemu, a0),
+            SYS_socketpair => self.add_pipe_fds(qemu, a3),
+            // i386 only has `accept4`, besides `socketcall`
+            #[cfg(not(cpu_target = "i386"))]
+            SYS_accept => self.add_new_fd(fd),
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_fcntl if a1 == F_DUPFD || a1 == F_DUPFD_CLOEXEC => self.add_new_fd(fd),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_open | SYS_creat | SYS_dup2 | SYS_eventfd | SYS_epoll_create | SYS_inotify_init
+            | SYS_signalfd => self.add_new_fd(fd),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_pipe => self.add_pipe_fds(qemu, a0),
+            _ => {}
+        }
+    }
+
+    /// Remembers the two fds the guest got in the array at `fds`, from a pipe or a socket pair.
+    fn add_pipe_fds(&mut self, qemu: Qemu, fds: GuestAddr) {
+        let mut buf = [0; 8];
+        if qemu.read_mem(fds, &mut buf).is_ok() {
+            self.add_new_fd(i32::from_ne_bytes(buf[..4].try_into().unwrap()));
+            self.add_new_fd(i32::from_ne_bytes(buf[4..].try_into().unwrap()));
+        }
+    }
+}
+
+/// Reads the nul-terminated string at `addr` in the guest, at most `max_len` bytes long.
+///
+/// The string is read in small chunks, so a string right before an unmapped page is still found.
+/// Returns `None` if it runs into memory the guest can't access, where the kernel fails the
+/// syscall with `EFAULT`, or if it is too long.
+pub(crate) fn read_guest_cstr(qemu: Qemu, addr: GuestAddr, max_len: usize) -> Option<Vec<u8>> {
+    const CHUNK: GuestAddr = 64;
+    let mut bytes = Vec::new();
+    let mut addr = addr;
+    while bytes.len() <= max_len {
+        // Never read across a chunk boundary, so never across a page boundary
+        let len = CHUNK - addr % CHUNK;
+        let mut buf = [0; CHUNK as usize];
+        let buf = &mut buf[..len as usize];
+        qemu.read_mem(addr, buf).ok()?;
+        if let Some(end) = buf.iter().position(|&b| b == 0) {
+            bytes.extend_from_slice(&buf[..end]);
+            return (bytes.len() <= max_len).then_some(bytes);
+        }
+        bytes.extend_from_slice(buf);
+        addr = addr.checked_add(len)?;
+    }
+    None
+}
+
+/// Resolves the guest path at `path`, relative to `dirfd`, as the host sees it.
+///
+/// Returns `None` for a null or inaccessible `path`, which the syscall then fails on itself.
+pub(crate) fn guest_path(qemu: Qemu, dirfd: i32, path: GuestAddr) -> Option<PathBuf> {
+    if path == 0 {
+        return None;
+    }
+    let path = read_guest_cstr(qemu, path, GUEST_PATH_MAX)?;
+    resolve_path(dirfd, Path::new(OsStr::from_bytes(&path)))
+}
+
+/// Resolves `path`, relative to `dirfd`, as the host sees it.
+fn resolve_path(dirfd: i32, path: &Path) -> Option<PathBuf> {
+    if path.is_absolute() {
+        return Some(path.to_path_buf());
+    }
+    let base = if dirfd == AT_FDCWD {
+        env::current_dir().ok()?
+    } else {
+        fs::read_link(format!("/proc/self/fd/{dirfd}")).ok()?
+    };
+    Some(base.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        fs::{self, File},
+        os::fd::{AsRawFd, IntoRawFd},
+        path::Path,
+        process,
+    };
+
+    use super::{resolve_path, FsSnapshot, AT_FDCWD};
+
+    #[test]
+    fn test_fs_snapshot_reset() {
+        let dir = env::temp_dir().join(format!("libafl_qemu_test_fs_snapshot_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let existing = dir.join("existing");
+        let created = dir.join("created");
+        fs::write(&existing, b"snapshot").unwrap();
+
+        // An fd open at snapshot time, in the middle of the file
+        let fd = File::open(&existing).unwrap().into_raw_fd();
+        unsafe { libc::lseek(fd, 4, libc::SEEK_SET) };
+
+        let mut fs_snapshot = FsSnapshot::new();
+        fs_snapshot.snapshot();
+        assert_eq!(fs_snapshot.fds[&fd].offset, Some(4));
+        assert_eq!(
+            fs_snapshot.fds[&fd].path.as_deref(),
+            Some(existing.as_path())
+        );
+
+        // Each run changes the same things, each reset rolls them back
+        for _ in 0..2 {
+            fs_snapshot.backup_file(existing.clone());
+            fs::write(&existing, b"changed").unwrap();
+            fs_snapshot.backup_file(created.clone());
+            fs::write(&created, b"created").unwrap();
+
+            fs_snapshot.touch_fd(fd);
+            unsafe { libc::lseek(fd, 0, libc::SEEK_END) };
+            fs_snapshot.backup_fd(fd);
+            unsafe { libc::close(fd) };
+            let new_fd = File::open(&existing).unwrap().into_raw_fd();
+            fs_snapshot.add_new_fd(new_fd);
+
+            fs_snapshot.reset();
+
+            assert_eq!(fs::read(&existing).unwrap(), b"snapshot");
+            assert!(!created.exists());
+            assert_eq!(unsafe { libc::lseek(fd, 0, libc::SEEK_CUR) }, 4);
+            if new_fd != fd {
+                assert_eq!(unsafe { libc::fcntl(new_fd, libc::F_GETFD) }, -1);
+            }
+            assert!(fs_snapshot.dirty_files.is_empty());
+            assert!(fs_snapshot.backup_fds.is_empty());
+        }
+
+        unsafe { libc::close(fd) };
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let dir = env::temp_dir();
+        assert_eq!(
+            resolve_path(AT_FDCWD, Path::new("/etc/passwd")).unwrap(),
+            Path::new("/etc/passwd")
+        );
+        assert_eq!(
+            resolve_path(AT_FDCWD, Path::new("file")).unwrap(),
+            env::current_dir().unwrap().join("file")
+        );
+        let dirfd = File::open(&dir).unwrap();
+        assert_eq!(
+            resolve_path(dirfd.as_raw_fd(), Path::new("file")).unwrap(),
+            dir.canonicalize().unwrap().join("file")
+        );
+    }
+}
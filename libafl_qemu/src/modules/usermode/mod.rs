@@ -8,6 +8,16 @@ pub mod snapshot;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use snapshot::{IntervalSnapshotFilter, SnapshotModule};
 
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod fs_snapshot;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use fs_snapshot::FsSnapshot;
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod vfs;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use vfs::{VfsContent, VfsInput, VfsModule};
+
 #[cfg(not(cpu_target = "hexagon"))]
 pub mod asan;
 #[cfg(not(cpu_target = "hexagon"))]
@@ -17,3 +27,23 @@ pub use asan::AsanModule;
 pub mod asan_guest;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use asan_guest::AsanGuestModule;
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod msan;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use msan::{MsanError, MsanErrorCallback, MsanModule, MsanShadow};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod record_replay;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use record_replay::{
+    RecordReplayMode, RecordReplayModule, RecordedEvent, RecordedTrace, RecordedTraceFeedback,
+};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod syscall_fault;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use syscall_fault::{
+    split_syscall_faults, syscall_faults_map_mut_ptr, SyscallFault, SyscallFaultModule,
+    SyscallFaultModuleBuilder, SYSCALL_FAULTS_MAP, SYSCALL_FAULTS_MAP_SIZE,
+};
@@ -0,0 +1,1134 @@
+//! Uninitialized memory detection, in the spirit of MSan
+//!
+//! The [`MsanModule`] keeps a host-side shadow bitmap of the undefined guest bytes:
+//! - heap chunks returned by `malloc` and `realloc` start undefined, and become undefined again
+//!   once freed. `calloc` and `memset` define their buffers;
+//! - stack memory becomes undefined when it is released, i.e. when the stack pointer goes up;
+//! - `memcpy` and `memmove` copy the definedness of their source, and so do stores of a value
+//!   loaded earlier in the same block. Any other store defines the written bytes.
+//!
+//! Without tracking registers, the uses of undefined values are found by matching values:
+//! a comparison, or a syscall argument, equal to a recently loaded undefined value is reported.
+//! Zeroes and the immediate operands of the comparisons are too common to be matched.
+//! Buffers written to a file descriptor are checked byte by byte.
+//! This is an approximation: it can miss uses going through computations, and report uses of a
+//! defined value equal to an undefined one.
+
+#![allow(clippy::needless_pass_by_value)] // default compiler complains about Option<&mut T> otherwise, and this is used extensively.
+
+use core::fmt;
+use std::fmt::{Debug, Display};
+
+use capstone::{
+    arch::{
+        arm::ArmOperandType, arm64::Arm64OperandType, mips::MipsOperand, ppc::PpcOperand,
+        riscv::RiscVOperand, x86::X86OperandType, ArchOperand,
+    },
+    prelude::*,
+};
+use hashbrown::{HashMap, HashSet};
+use libafl::{executors::ExitKind, observers::ObserversTuple};
+use libafl_bolts::os::unix_signals::Signal;
+use libafl_qemu_sys::GuestAddr;
+
+use crate::{
+    capstone,
+    elf::EasyElf,
+    emu::EmulatorModules,
+    get_exit_arch_regs,
+    modules::{
+        utils::{
+            filters::{HasAddressFilter, StdAddressFilter},
+            loads::{load_value, value_mask, RecentLoads},
+        },
+        AddressFilter, EmulatorModule, EmulatorModuleTuple,
+    },
+    qemu::{Hook, MemAccessInfo, SyscallHookResult},
+    sync_exit::ExitArgs,
+    sys::TCGTemp,
+    ArchExtras, CallingConvention, Qemu, Regs, SYS_pwrite64, SYS_sendto, SYS_write, SYS_writev,
+};
+
+/// The number of pending libc calls kept, the oldest ones never returned, e.g. after a `longjmp`.
+const MSAN_MAX_CALLS: usize = 32;
+
+/// Stack pointer moves bigger than this are not considered as frames being released.
+const MSAN_MAX_STACK_RELEASE: GuestAddr = 0x10_0000;
+
+const MSAN_SHADOW_PAGE_SIZE: usize = 4096;
+
+/// The shadow bitmap of the undefined guest bytes. Untracked memory is defined.
+#[derive(Debug, Clone, Default)]
+pub struct MsanShadow {
+    pages: HashMap<GuestAddr, Box<[u8]>>,
+}
+
+impl MsanShadow {
+    fn locate(addr: GuestAddr) -> (GuestAddr, usize) {
+        let page = addr & !(MSAN_SHADOW_PAGE_SIZE as GuestAddr - 1);
+        (page, (addr - page) as usize)
+    }
+
+    fn set(&mut self, addr: GuestAddr, undefined: bool) {
+        let (page, offset) = Self::locate(addr);
+
+        if let Some(shadow_page) = self.pages.get_mut(&page) {
+            if undefined {
+                shadow_page[offset / 8] |= 1 << (offset % 8);
+            } else {
+                shadow_page[offset / 8] &= !(1 << (offset % 8));
+            }
+        } else if undefined {
+            let mut shadow_page = vec![0; MSAN_SHADOW_PAGE_SIZE / 8].into_boxed_slice();
+            shadow_page[offset / 8] |= 1 << (offset % 8);
+            self.pages.insert(page, shadow_page);
+        }
+    }
+
+    #[must_use]
+    pub fn is_undefined(&self, addr: GuestAddr) -> bool {
+        let (page, offset) = Self::locate(addr);
+
+        self.pages
+            .get(&page)
+            .is_some_and(|shadow_page| shadow_page[offset / 8] & (1 << (offset % 8)) != 0)
+    }
+
+    pub fn set_undefined(&mut self, addr: GuestAddr, len: usize) {
+        for i in 0..len as GuestAddr {
+            self.set(addr + i, true);
+        }
+    }
+
+    pub fn set_defined(&mut self, addr: GuestAddr, len: usize) {
+        if self.pages.is_empty() {
+            return;
+        }
+        for i in 0..len as GuestAddr {
+            self.set(addr + i, false);
+        }
+    }
+
+    /// The first undefined byte of `[addr, addr + len)`, if any.
+    #[must_use]
+    pub fn first_undefined(&self, addr: GuestAddr, len: usize) -> Option<GuestAddr> {
+        if self.pages.is_empty() {
+            return None;
+        }
+        (0..len as GuestAddr)
+            .map(|i| addr + i)
+            .find(|addr| self.is_undefined(*addr))
+    }
+
+    /// Copies the definedness of `[src, src + len)` to `[dst, dst + len)`.
+    pub fn copy(&mut self, dst: GuestAddr, src: GuestAddr, len: usize) {
+        let undefined: Vec<bool> = (0..len as GuestAddr)
+            .map(|i| self.is_undefined(src + i))
+            .collect();
+        for (i, undefined) in undefined.into_iter().enumerate() {
+            self.set(dst + i as GuestAddr, undefined);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MsanError {
+    /// A comparison used a value loaded from undefined memory.
+    Branch { addr: GuestAddr, size: usize },
+    /// A syscall argument was a value loaded from undefined memory.
+    SyscallArgument {
+        sys_num: i32,
+        idx: usize,
+        addr: GuestAddr,
+    },
+    /// Undefined bytes were written to a file descriptor.
+    Output {
+        fd: GuestAddr,
+        buf: GuestAddr,
+        addr: GuestAddr,
+    },
+}
+
+impl Display for MsanError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            MsanError::Branch { addr, size } => write!(
+                fmt,
+                "Conditional branch depends on the uninitialized {size} bytes at {addr:#x}"
+            ),
+            MsanError::SyscallArgument { sys_num, idx, addr } => write!(
+                fmt,
+                "Argument {idx} of syscall {sys_num} is the uninitialized value at {addr:#x}"
+            ),
+            MsanError::Output { fd, buf, addr } => write!(
+                fmt,
+                "Uninitialized byte at {addr:#x} written to fd {fd} (buffer at {buf:#x})"
+            ),
+        }
+    }
+}
+
+type MsanErrorFn = Box<dyn FnMut(Qemu, GuestAddr, &MsanError)>;
+
+pub struct MsanErrorCallback(MsanErrorFn);
+
+impl MsanErrorCallback {
+    #[must_use]
+    pub fn new(error_callback: MsanErrorFn) -> Self {
+        Self(error_callback)
+    }
+
+    pub fn call(&mut self, qemu: Qemu, pc: GuestAddr, error: &MsanError) {
+        self.0(qemu, pc, error);
+    }
+}
+
+impl Debug for MsanErrorCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MsanErrorCallback").finish_non_exhaustive()
+    }
+}
+
+/// The libc functions handled by the [`MsanModule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MsanFunction {
+    Malloc,
+    Calloc,
+    Realloc,
+    Free,
+    Memcpy,
+    Memmove,
+    Memset,
+}
+
+impl MsanFunction {
+    const ALL: [MsanFunction; 7] = [
+        MsanFunction::Malloc,
+        MsanFunction::Calloc,
+        MsanFunction::Realloc,
+        MsanFunction::Free,
+        MsanFunction::Memcpy,
+        MsanFunction::Memmove,
+        MsanFunction::Memset,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            MsanFunction::Malloc => "malloc",
+            MsanFunction::Calloc => "calloc",
+            MsanFunction::Realloc => "realloc",
+            MsanFunction::Free => "free",
+            MsanFunction::Memcpy => "memcpy",
+            MsanFunction::Memmove => "memmove",
+            MsanFunction::Memset => "memset",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MsanCall {
+    function: MsanFunction,
+    ret_addr: GuestAddr,
+    /// The stack pointer at the call.
+    sp: GuestAddr,
+    args: [GuestAddr; 3],
+}
+
+#[derive(Debug)]
+pub struct MsanModule {
+    filter: StdAddressFilter,
+    error_callback: Option<MsanErrorCallback>,
+    crash_on_error: bool,
+    shadow: MsanShadow,
+    snapshot: Option<(MsanShadow, HashMap<GuestAddr, usize>)>,
+    allocs: HashMap<GuestAddr, usize>,
+    functions: HashMap<GuestAddr, MsanFunction>,
+    calls: Vec<MsanCall>,
+    ret_hooks: HashSet<GuestAddr>,
+    /// The recent loads, and whether they read undefined bytes.
+    loads: RecentLoads<bool>,
+    /// The immediate operands of the comparison instructions, by instruction start and end.
+    cmp_imms: HashMap<GuestAddr, Vec<u64>>,
+    cs: Capstone,
+    last_sp: Option<GuestAddr>,
+    reported: HashSet<GuestAddr>,
+    error_found: bool,
+}
+
+impl MsanModule {
+    #[must_use]
+    pub fn new(filter: StdAddressFilter) -> Self {
+        Self {
+            filter,
+            error_callback: None,
+            crash_on_error: true,
+            shadow: MsanShadow::default(),
+            snapshot: None,
+            allocs: HashMap::new(),
+            functions: HashMap::new(),
+            calls: Vec::new(),
+            ret_hooks: HashSet::new(),
+            loads: RecentLoads::new(),
+            cmp_imms: HashMap::new(),
+            cs: capstone().detail(true).build().unwrap(),
+            last_sp: None,
+            reported: HashSet::new(),
+            error_found: false,
+        }
+    }
+
+    #[must_use]
+    pub fn error_callback(mut self, error_callback: MsanErrorCallback) -> Self {
+        self.error_callback = Some(error_callback);
+        self
+    }
+
+    /// Whether to crash the target on the first error. Otherwise, the run ends as a crash.
+    #[must_use]
+    pub fn crash_on_error(mut self, crash_on_error: bool) -> Self {
+        self.crash_on_error = crash_on_error;
+        self
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    #[must_use]
+    pub fn shadow(&self) -> &MsanShadow {
+        &self.shadow
+    }
+
+    pub fn shadow_mut(&mut self) -> &mut MsanShadow {
+        &mut self.shadow
+    }
+
+    /// Checks are disabled while running the libc functions handled by the module.
+    fn enabled(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    fn report(&mut self, qemu: Qemu, pc: GuestAddr, error: MsanError) {
+        if !self.reported.insert(pc) {
+            return;
+        }
+        self.error_found = true;
+
+        log::error!("MSan error at {pc:#x}: {error}");
+        if let Some(cb) = self.error_callback.as_mut() {
+            cb.call(qemu, pc, &error);
+        }
+
+        if self.crash_on_error {
+            unsafe {
+                qemu.target_signal(Signal::SigAbort);
+            }
+        }
+    }
+
+    /// The recent undefined load which loaded the `size` bytes of `value`.
+    fn undefined_load(&self, value: u64, size: usize) -> Option<(GuestAddr, usize)> {
+        self.loads
+            .recent()
+            .find(|load| load.shadow && load.matches(value, size))
+            .map(|load| (load.addr, load.size))
+    }
+
+    pub fn load(&mut self, qemu: Qemu, addr: GuestAddr, size: usize) {
+        if !self.enabled() {
+            return;
+        }
+
+        let undefined = self.shadow.first_undefined(addr, size).is_some();
+        self.loads
+            .push(addr, size, load_value(qemu, addr, size), undefined);
+    }
+
+    pub fn store(&mut self, addr: GuestAddr, size: usize) {
+        if !self.enabled() {
+            return;
+        }
+
+        match self.loads.store(size) {
+            Some(src) => self.shadow.copy(addr, src, size),
+            None => self.shadow.set_defined(addr, size),
+        }
+    }
+
+    /// Records the immediate operands of the instructions of the block at `pc`.
+    fn find_cmp_imms(&mut self, qemu: Qemu, pc: GuestAddr) {
+        #[cfg(cpu_target = "arm")]
+        self.cs
+            .set_mode(if pc & 1 == 1 {
+                arch::arm::ArchMode::Thumb.into()
+            } else {
+                arch::arm::ArchMode::Arm.into()
+            })
+            .unwrap();
+
+        let mut code = unsafe { std::slice::from_raw_parts(qemu.g2h(pc), 512) };
+        let mut iaddr = pc;
+
+        'disasm: while let Ok(insns) = self.cs.disasm_count(code, iaddr.into(), 1) {
+            let Some(insn) = insns.first() else {
+                break;
+            };
+            let insn_detail: InsnDetail = self.cs.insn_detail(insn).unwrap();
+
+            let imms: Vec<u64> = insn_detail
+                .arch_detail()
+                .operands()
+                .iter()
+                .filter_map(immediate)
+                .collect();
+            if !imms.is_empty() {
+                let start = insn.address() as GuestAddr;
+                let end = start + insn.bytes().len() as GuestAddr;
+                for addr in [start, end] {
+                    self.cmp_imms
+                        .entry(addr)
+                        .or_default()
+                        .extend_from_slice(&imms);
+                }
+            }
+
+            for detail in insn_detail.groups() {
+                match u32::from(detail.0) {
+                    capstone::InsnGroupType::CS_GRP_CALL
+                    | capstone::InsnGroupType::CS_GRP_RET
+                    | capstone::InsnGroupType::CS_GRP_INVALID
+                    | capstone::InsnGroupType::CS_GRP_JUMP
+                    | capstone::InsnGroupType::CS_GRP_IRET
+                    | capstone::InsnGroupType::CS_GRP_PRIVILEGE => {
+                        break 'disasm;
+                    }
+                    _ => {}
+                }
+            }
+
+            iaddr += insn.bytes().len() as GuestAddr;
+            if (iaddr - pc) as usize >= code.len() {
+                break;
+            }
+            code = &code[insn.bytes().len()..];
+        }
+    }
+
+    pub fn cmp(&mut self, qemu: Qemu, pc: GuestAddr, size: usize, v0: u64, v1: u64) {
+        if !self.enabled() {
+            return;
+        }
+
+        let mask = value_mask(size);
+        let imms = self.cmp_imms.get(&pc);
+        // Too many defined zeroes and constants around to match them
+        let load = [v0, v1]
+            .into_iter()
+            .filter(|value| {
+                value & mask != 0
+                    && !imms.is_some_and(|imms| imms.iter().any(|imm| imm & mask == value & mask))
+            })
+            .find_map(|value| self.undefined_load(value, size));
+
+        if let Some((addr, size)) = load {
+            self.report(qemu, pc, MsanError::Branch { addr, size });
+        }
+    }
+
+    fn check_output(&mut self, qemu: Qemu, fd: GuestAddr, buf: GuestAddr, len: usize) {
+        if let Some(addr) = self.shadow.first_undefined(buf, len) {
+            let pc: GuestAddr = qemu.read_reg(Regs::Pc).unwrap_or_default();
+            self.report(qemu, pc, MsanError::Output { fd, buf, addr });
+        }
+    }
+
+    #[expect(clippy::too_many_arguments)]
+    pub fn syscall(
+        &mut self,
+        qemu: Qemu,
+        sys_num: i32,
+        a0: GuestAddr,
+        a1: GuestAddr,
+        a2: GuestAddr,
+        a3: GuestAddr,
+        a4: GuestAddr,
+        a5: GuestAddr,
+    ) {
+        if !self.enabled() {
+            return;
+        }
+
+        let pc: GuestAddr = qemu.read_reg(Regs::Pc).unwrap_or_default();
+        for (idx, arg) in [a0, a1, a2, a3, a4, a5].into_iter().enumerate() {
+            // Too many defined zeroes around to match them
+            if arg == 0 {
+                continue;
+            }
+            #[allow(clippy::unnecessary_cast)] // for GuestAddr -> u64
+            if let Some((addr, _)) = self.undefined_load(arg as u64, 8) {
+                self.report(qemu, pc, MsanError::SyscallArgument { sys_num, idx, addr });
+            }
+        }
+
+        match i64::from(sys_num) {
+            SYS_write | SYS_pwrite64 | SYS_sendto => {
+                self.check_output(qemu, a0, a1, a2 as usize);
+            }
+            SYS_writev => {
+                let ptr_size = size_of::<GuestAddr>() as GuestAddr;
+                for i in 0..a2 {
+                    let iov = a1 + i * 2 * ptr_size;
+                    let mut base = [0u8; size_of::<GuestAddr>()];
+                    let mut len = [0u8; size_of::<GuestAddr>()];
+                    if qemu.read_mem(iov, &mut base).is_err()
+                        || qemu.read_mem(iov + ptr_size, &mut len).is_err()
+                    {
+                        break;
+                    }
+                    self.check_output(
+                        qemu,
+                        a0,
+                        GuestAddr::from_ne_bytes(base),
+                        GuestAddr::from_ne_bytes(len) as usize,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, qemu: Qemu) {
+        self.loads.block();
+
+        // The stack released by the target is undefined
+        let Ok(sp): Result<GuestAddr, _> = qemu.read_reg(Regs::Sp) else {
+            return;
+        };
+        self.expire_calls(sp);
+
+        if let Some(last_sp) = self.last_sp {
+            if sp > last_sp && sp - last_sp < MSAN_MAX_STACK_RELEASE && self.enabled() {
+                self.shadow.set_undefined(last_sp, (sp - last_sp) as usize);
+            }
+        }
+        self.last_sp = Some(sp);
+    }
+
+    /// Forgets the calls whose frame was released without returning, e.g. by a `longjmp`.
+    ///
+    /// The return address popped by the return itself is tolerated, so that the call is still
+    /// pending when its return hook runs.
+    fn expire_calls(&mut self, sp: GuestAddr) {
+        self.calls
+            .retain(|call| sp <= call.sp + size_of::<GuestAddr>() as GuestAddr);
+    }
+
+    fn on_call(&mut self, qemu: Qemu, function: MsanFunction) -> Option<GuestAddr> {
+        let cpu = qemu.current_cpu()?;
+        #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+        let ret_addr = cpu.read_return_address().ok()? as GuestAddr;
+        let sp: GuestAddr = qemu.read_reg(Regs::Sp).ok()?;
+
+        let mut args = [0; 3];
+        for (idx, arg) in args.iter_mut().enumerate() {
+            #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+            let val = cpu
+                .read_function_argument(CallingConvention::Cdecl, idx as u8)
+                .unwrap_or_default() as GuestAddr;
+            *arg = val;
+        }
+
+        if function == MsanFunction::Free && args[0] != 0 {
+            if let Some(size) = self.allocs.remove(&args[0]) {
+                self.shadow.set_undefined(args[0], size);
+            }
+        }
+
+        if self.calls.len() >= MSAN_MAX_CALLS {
+            self.calls.remove(0);
+        }
+        self.calls.push(MsanCall {
+            function,
+            ret_addr,
+            sp,
+            args,
+        });
+        self.ret_hooks.insert(ret_addr).then_some(ret_addr)
+    }
+
+    fn on_ret(&mut self, qemu: Qemu, pc: GuestAddr) {
+        let Some(idx) = self.calls.iter().rposition(|call| call.ret_addr == pc) else {
+            return;
+        };
+        let call = self.calls.remove(idx);
+
+        let ret: GuestAddr = qemu
+            .read_reg(get_exit_arch_regs()[ExitArgs::Ret])
+            .unwrap_or_default();
+        self.returned(call, ret);
+    }
+
+    /// Applies the effects of `call`, which returned `ret`, on the shadow.
+    fn returned(&mut self, call: MsanCall, ret: GuestAddr) {
+        let [a0, a1, a2] = call.args;
+
+        match call.function {
+            MsanFunction::Malloc if ret != 0 => {
+                self.allocs.insert(ret, a0 as usize);
+                self.shadow.set_undefined(ret, a0 as usize);
+            }
+            MsanFunction::Calloc if ret != 0 => {
+                let size = a0.saturating_mul(a1) as usize;
+                self.allocs.insert(ret, size);
+                self.shadow.set_defined(ret, size);
+            }
+            MsanFunction::Realloc if ret != 0 => {
+                let old_size = if a0 == 0 {
+                    0
+                } else {
+                    self.allocs.remove(&a0).unwrap_or_default()
+                };
+                let size = a1 as usize;
+                let kept = old_size.min(size);
+
+                if ret != a0 && kept > 0 {
+                    self.shadow.copy(ret, a0, kept);
+                    self.shadow.set_undefined(a0, old_size);
+                }
+                self.shadow
+                    .set_undefined(ret + kept as GuestAddr, size - kept);
+                self.allocs.insert(ret, size);
+            }
+            MsanFunction::Memcpy | MsanFunction::Memmove => {
+                self.shadow.copy(a0, a1, a2 as usize);
+            }
+            MsanFunction::Memset => {
+                self.shadow.set_defined(a0, a2 as usize);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The value of an immediate operand, sign-extended.
+// The immediate type depends on the arch
+#[allow(
+    clippy::unnecessary_cast,
+    clippy::cast_lossless,
+    clippy::cast_sign_loss
+)]
+fn immediate(operand: &ArchOperand) -> Option<u64> {
+    let imm = match operand {
+        ArchOperand::X86Operand(operand) => match operand.op_type {
+            X86OperandType::Imm(imm) => imm as i64,
+            _ => return None,
+        },
+        ArchOperand::ArmOperand(operand) => match operand.op_type {
+            ArmOperandType::Imm(imm) => imm as i64,
+            _ => return None,
+        },
+        ArchOperand::Arm64Operand(operand) => match operand.op_type {
+            Arm64OperandType::Imm(imm) => imm as i64,
+            _ => return None,
+        },
+        ArchOperand::MipsOperand(MipsOperand::Imm(imm)) => *imm as i64,
+        ArchOperand::PpcOperand(PpcOperand::Imm(imm)) => *imm as i64,
+        ArchOperand::RiscVOperand(RiscVOperand::Imm(imm)) => *imm as i64,
+        _ => return None,
+    };
+    Some(imm as u64)
+}
+
+/// Resolves the handled libc functions in every mapped file.
+fn resolve_functions(qemu: Qemu) -> HashMap<GuestAddr, MsanFunction> {
+    let mut files: Vec<(String, GuestAddr)> = Vec::new();
+    for region in qemu.mappings() {
+        if let Some(path) = region.path() {
+            // skip [heap], [vdso] and friends
+            if !path.is_empty()
+                && !path.starts_with('[')
+                && !files.iter().any(|(file, _)| file == path)
+            {
+                files.push((path.clone(), region.start()));
+            }
+        }
+    }
+
+    let mut functions = HashMap::new();
+    for (file, load_addr) in files {
+        let mut elf_buffer = Vec::new();
+        let Ok(elf) = EasyElf::from_file(&file, &mut elf_buffer) else {
+            continue;
+        };
+        let goblin = elf.goblin();
+
+        let symtabs = [
+            (&goblin.syms, &goblin.strtab),
+            (&goblin.dynsyms, &goblin.dynstrtab),
+        ];
+
+        for function in MsanFunction::ALL {
+            // Only the symbols defined in this file, not the imports
+            let sym = symtabs.iter().find_map(|(syms, strtab)| {
+                syms.iter().find(|sym| {
+                    sym.st_shndx != 0
+                        && sym.st_value != 0
+                        && strtab.get_at(sym.st_name) == Some(function.name())
+                })
+            });
+
+            if let Some(sym) = sym {
+                #[allow(clippy::unnecessary_cast)] // for u64 -> GuestAddr
+                let addr = if elf.is_pic() {
+                    sym.st_value as GuestAddr + load_addr
+                } else {
+                    sym.st_value as GuestAddr
+                };
+                log::info!("MSan: {} found at {addr:#x} in {file}", function.name());
+                functions.insert(addr, function);
+            }
+        }
+    }
+
+    functions
+}
+
+impl<I, S> EmulatorModule<I, S> for MsanModule
+where
+    I: Unpin,
+    S: Unpin,
+{
+    fn post_qemu_init<ET>(&mut self, _qemu: Qemu, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        emulator_modules.pre_syscalls(Hook::Function(syscall_msan::<ET, I, S>));
+    }
+
+    fn first_exec<ET>(
+        &mut self,
+        qemu: Qemu,
+        emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.functions = resolve_functions(qemu);
+        for addr in self.functions.keys() {
+            emulator_modules.instructions(*addr, Hook::Function(on_call_msan::<ET, I, S>), true);
+        }
+
+        // The stack below the current frame is undefined
+        if let Ok(sp) = qemu.read_reg(Regs::Sp) {
+            if let Some(stack) = qemu
+                .mappings()
+                .find(|region| region.start() <= sp && sp < region.end())
+            {
+                self.shadow
+                    .set_undefined(stack.start(), (sp - stack.start()) as usize);
+            }
+            self.last_sp = Some(sp);
+        }
+
+        emulator_modules.reads(
+            Hook::Function(gen_readwrite_msan::<ET, I, S>),
+            Hook::Function(trace_read_msan::<ET, I, S, 1>),
+            Hook::Function(trace_read_msan::<ET, I, S, 2>),
+            Hook::Function(trace_read_msan::<ET, I, S, 4>),
+            Hook::Function(trace_read_msan::<ET, I, S, 8>),
+            Hook::Function(trace_read_n_msan::<ET, I, S>),
+        );
+
+        emulator_modules.writes(
+            Hook::Function(gen_readwrite_msan::<ET, I, S>),
+            Hook::Function(trace_write_msan::<ET, I, S, 1>),
+            Hook::Function(trace_write_msan::<ET, I, S, 2>),
+            Hook::Function(trace_write_msan::<ET, I, S, 4>),
+            Hook::Function(trace_write_msan::<ET, I, S, 8>),
+            Hook::Function(trace_write_n_msan::<ET, I, S>),
+        );
+
+        emulator_modules.cmps(
+            Hook::Function(gen_cmp_msan::<ET, I, S>),
+            Hook::Function(trace_cmp_msan::<ET, I, S, u8, 1>),
+            Hook::Function(trace_cmp_msan::<ET, I, S, u16, 2>),
+            Hook::Function(trace_cmp_msan::<ET, I, S, u32, 4>),
+            Hook::Function(trace_cmp_msan::<ET, I, S, u64, 8>),
+        );
+
+        emulator_modules.blocks(
+            Hook::Function(gen_block_msan::<ET, I, S>),
+            Hook::Empty,
+            Hook::Function(exec_block_msan::<ET, I, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        // The guest memory is restored between runs, the shadow should follow
+        if self.snapshot.is_none() {
+            self.snapshot = Some((self.shadow.clone(), self.allocs.clone()));
+        }
+
+        self.calls.clear();
+        self.loads.clear();
+        self.reported.clear();
+        self.error_found = false;
+        self.last_sp = qemu.read_reg(Regs::Sp).ok();
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+        _observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<I, S>,
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        if self.error_found {
+            *exit_kind = ExitKind::Crash;
+        }
+
+        if let Some((shadow, allocs)) = &self.snapshot {
+            self.shadow.clone_from(shadow);
+            self.allocs.clone_from(allocs);
+        }
+    }
+}
+
+impl HasAddressFilter for MsanModule {
+    type ModuleAddressFilter = StdAddressFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+}
+
+pub fn on_call_msan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    let Some(function) = h.functions.get(&pc).copied() else {
+        return;
+    };
+
+    if let Some(ret_addr) = h.on_call(qemu, function) {
+        emulator_modules.instructions(ret_addr, Hook::Function(on_ret_msan::<ET, I, S>), true);
+    }
+}
+
+pub fn on_ret_msan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    h.on_ret(qemu, pc);
+}
+
+#[expect(clippy::too_many_arguments)]
+pub fn syscall_msan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    a3: GuestAddr,
+    a4: GuestAddr,
+    a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    h.syscall(qemu, sys_num, a0, a1, a2, a3, a4, a5);
+    SyscallHookResult::new(None)
+}
+
+pub fn gen_readwrite_msan<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _addr: *mut TCGTemp,
+    _info: MemAccessInfo,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get::<MsanModule>().unwrap();
+    h.must_instrument(pc).then_some(0)
+}
+
+pub fn trace_read_msan<ET, I, S, const N: usize>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    h.load(qemu, addr, N);
+}
+
+pub fn trace_read_n_msan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    h.load(qemu, addr, size);
+}
+
+pub fn trace_write_msan<ET, I, S, const N: usize>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    h.store(addr, N);
+}
+
+pub fn trace_write_n_msan<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    _pc: GuestAddr,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    h.store(addr, size);
+}
+
+pub fn gen_cmp_msan<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _size: usize,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get::<MsanModule>().unwrap();
+    // The pc is the id, to find the immediates of the comparison
+    #[allow(clippy::unnecessary_cast)] // for GuestAddr -> u64
+    let id = pc as u64;
+    h.must_instrument(pc).then_some(id)
+}
+
+pub fn trace_cmp_msan<ET, I, S, SZ, const N: usize>(
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    v0: SZ,
+    v1: SZ,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+    SZ: Into<u64>,
+{
+    let qemu = Qemu::get().unwrap();
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    #[allow(clippy::unnecessary_cast)] // for u64 -> GuestAddr
+    h.cmp(qemu, id as GuestAddr, N, v0.into(), v1.into());
+}
+
+pub fn gen_block_msan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    if h.must_instrument(pc) {
+        h.find_cmp_imms(qemu, pc);
+    }
+    // The stack is followed in every block
+    Some(0)
+}
+
+pub fn exec_block_msan<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<MsanModule>().unwrap();
+    h.block(qemu);
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_qemu_sys::GuestAddr;
+
+    use super::{MsanCall, MsanFunction, MsanModule, MsanShadow};
+    use crate::modules::utils::filters::StdAddressFilter;
+
+    fn call(function: MsanFunction, sp: GuestAddr, args: [GuestAddr; 3]) -> MsanCall {
+        MsanCall {
+            function,
+            ret_addr: 0x40_0000,
+            sp,
+            args,
+        }
+    }
+
+    #[test]
+    fn test_msan_shadow() {
+        let mut shadow = MsanShadow::default();
+        assert_eq!(shadow.first_undefined(0x1000, 0x100), None);
+
+        shadow.set_undefined(0x1000, 0x10);
+        assert_eq!(shadow.first_undefined(0x0ff0, 0x20), Some(0x1000));
+
+        shadow.set_defined(0x1000, 4);
+        assert_eq!(shadow.first_undefined(0x1000, 0x10), Some(0x1004));
+
+        // across a shadow page
+        shadow.copy(0x1ffe, 0x1000, 8);
+        assert!(!shadow.is_undefined(0x1fff));
+        assert!(shadow.is_undefined(0x2000));
+        assert_eq!(shadow.first_undefined(0x1ffe, 2), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_msan_realloc() {
+        let mut msan = MsanModule::new(StdAddressFilter::default());
+
+        msan.returned(call(MsanFunction::Malloc, 0, [0x10, 0, 0]), 0x1000);
+        assert_eq!(msan.shadow.first_undefined(0x1000, 0x10), Some(0x1000));
+        msan.shadow.set_defined(0x1000, 8);
+
+        // moved: the defined bytes follow the chunk, the old chunk and the growth are undefined
+        msan.returned(call(MsanFunction::Realloc, 0, [0x1000, 0x20, 0]), 0x2000);
+        assert_eq!(msan.shadow.first_undefined(0x2000, 0x20), Some(0x2008));
+        assert_eq!(msan.shadow.first_undefined(0x1000, 0x10), Some(0x1000));
+        assert_eq!(msan.allocs.get(&0x2000), Some(&0x20));
+        assert!(!msan.allocs.contains_key(&0x1000));
+
+        // shrunk in place
+        msan.shadow.set_defined(0x2000, 0x20);
+        msan.returned(call(MsanFunction::Realloc, 0, [0x2000, 0x4, 0]), 0x2000);
+        assert_eq!(msan.shadow.first_undefined(0x2000, 0x20), None);
+        assert_eq!(msan.allocs.get(&0x2000), Some(&0x4));
+
+        // realloc(NULL) behaves as malloc
+        msan.returned(call(MsanFunction::Realloc, 0, [0, 0x8, 0]), 0x3000);
+        assert_eq!(msan.shadow.first_undefined(0x3000, 0x8), Some(0x3000));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_msan_memcpy_memset() {
+        let mut msan = MsanModule::new(StdAddressFilter::default());
+        msan.shadow.set_undefined(0x1000, 0x10);
+        msan.shadow.set_defined(0x1000, 4);
+
+        msan.returned(call(MsanFunction::Memcpy, 0, [0x2000, 0x1000, 8]), 0x2000);
+        assert_eq!(msan.shadow.first_undefined(0x2000, 8), Some(0x2004));
+
+        msan.returned(call(MsanFunction::Memset, 0, [0x2000, 0, 8]), 0x2000);
+        assert_eq!(msan.shadow.first_undefined(0x2000, 8), None);
+
+        msan.returned(call(MsanFunction::Memmove, 0, [0x1002, 0x2000, 4]), 0x1002);
+        assert_eq!(msan.shadow.first_undefined(0x1000, 8), Some(0x1006));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_msan_expire_calls() {
+        let mut msan = MsanModule::new(StdAddressFilter::default());
+        msan.calls.push(call(MsanFunction::Malloc, 0x8000, [0; 3]));
+        msan.calls.push(call(MsanFunction::Memcpy, 0x7f00, [0; 3]));
+
+        // running in the callee, then back at the return address of the inner call
+        msan.expire_calls(0x7e00);
+        msan.expire_calls(0x7f00 + size_of::<GuestAddr>() as GuestAddr);
+        assert_eq!(msan.calls.len(), 2);
+
+        // the inner frame was released without returning
+        msan.expire_calls(0x7f80);
+        assert_eq!(msan.calls.len(), 1);
+        assert_eq!(msan.calls[0].function, MsanFunction::Malloc);
+        assert!(!msan.enabled());
+
+        msan.expire_calls(0x9000);
+        assert!(msan.enabled());
+    }
+}
@@ -0,0 +1,796 @@
+//! Record and replay of the nondeterministic inputs of a usermode run
+//!
+//! When a crash does not reproduce (thread timing, signals, `getrandom`, clocks, ...), the
+//! [`RecordReplayModule`] can record the results of the nondeterministic syscalls of each run,
+//! the signals and the thread creations. The [`RecordedTraceFeedback`] attaches the trace of the
+//! crashing and timing out runs to the objective, as metadata.
+//! In replay mode, the same module forces the recorded results, and the bytes the kernel wrote to
+//! the guest memory, instead of running the syscalls, so that the crash reproduces deterministically,
+//! e.g. under a debugger.
+//!
+//! Guest threads are numbered in creation order, and syscalls are replayed per thread, in the
+//! order they were recorded. Asynchronous signals, seen when a handler of the target is entered,
+//! are injected again after the same syscall of their thread. The signals the target sends to
+//! itself are sent again by the replayed run.
+//! The scheduling of the guest threads itself cannot be forced: thread creations and crashing
+//! signals are only compared with the trace, and a divergence is reported.
+
+#![allow(clippy::needless_pass_by_value)] // default compiler complains about Option<&mut T> otherwise, and this is used extensively.
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use hashbrown::{HashMap, HashSet};
+use libafl::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::Input,
+    observers::ObserversTuple,
+    Error, HasMetadata,
+};
+use libafl_bolts::Named;
+use libafl_qemu_sys::{CPUArchStatePtr, GuestAddr};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        utils::filters::{HasAddressFilter, NopAddressFilter, NOP_ADDRESS_FILTER},
+        EmulatorModule, EmulatorModuleTuple,
+    },
+    qemu::{Hook, SyscallHookResult},
+    CallingConvention, Qemu, Regs, SYS_getpid, SYS_getppid, SYS_gettid, SYS_kill, SYS_pread64,
+    SYS_read, SYS_recvfrom, SYS_rt_sigaction, SYS_rt_sigqueueinfo, SYS_rt_tgsigqueueinfo,
+    SYS_tgkill, SYS_tkill,
+};
+#[cfg(not(cpu_target = "riscv32"))]
+use crate::{SYS_clock_gettime, SYS_getrandom, SYS_gettimeofday};
+
+/// The extension of the trace files.
+pub const RECORDED_TRACE_EXTENSION: &str = "replay";
+
+/// The syscalls a thread sends signals to itself with.
+const SIGNAL_SYSCALLS: [i64; 5] = [
+    SYS_kill,
+    SYS_tkill,
+    SYS_tgkill,
+    SYS_rt_sigqueueinfo,
+    SYS_rt_tgsigqueueinfo,
+];
+
+/// A nondeterministic event of a recorded run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    /// The result of a syscall, with the guest memory written by the kernel.
+    Syscall {
+        thread: usize,
+        sys_num: i32,
+        result: GuestAddr,
+        writes: Vec<(GuestAddr, Vec<u8>)>,
+    },
+    /// An asynchronous signal, delivered to a handler of the target once the thread ran
+    /// `syscalls` syscalls.
+    AsyncSignal {
+        thread: usize,
+        signal: i32,
+        syscalls: usize,
+    },
+    /// A signal crashing the target.
+    Signal {
+        thread: usize,
+        signal: i32,
+        pc: GuestAddr,
+    },
+    /// The creation of a guest thread.
+    ThreadCreation { thread: usize },
+}
+
+/// The nondeterministic events of a run, in order.
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedTrace {
+    pub events: Vec<RecordedEvent>,
+}
+
+libafl_bolts::impl_serdeany!(RecordedTrace);
+
+impl RecordedTrace {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        Ok(postcard::from_bytes(&bytes)?)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, postcard::to_allocvec(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordReplayMode {
+    /// Records every run. The trace of the crashing and timing out runs is kept for the
+    /// [`RecordedTraceFeedback`].
+    Record,
+    /// Forces the events of the trace.
+    Replay { trace: RecordedTrace },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GuestThread {
+    /// The creation order of the thread.
+    id: usize,
+    /// The number of syscalls run by the thread so far.
+    syscalls: usize,
+    last_sys_num: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct RecordReplayModule {
+    mode: RecordReplayMode,
+    syscalls: Vec<i64>,
+    recording: RecordedTrace,
+    /// The trace of the last crashing or timing out run, until attached to the objective.
+    objective_trace: Arc<Mutex<Option<RecordedTrace>>>,
+    /// The guest threads of the run, by host tid. Guest threads are host threads in usermode.
+    threads: HashMap<u32, GuestThread>,
+    /// The signal handlers registered by the target.
+    handlers: HashSet<GuestAddr>,
+    /// The syscalls left to replay, by guest thread.
+    pending_syscalls: HashMap<usize, VecDeque<RecordedEvent>>,
+    /// The asynchronous signals left to inject, by guest thread.
+    pending_signals: HashMap<usize, VecDeque<(usize, i32)>>,
+    /// The injected signals not delivered to their handler yet, with their thread.
+    injected_signals: Vec<(usize, i32)>,
+    /// The other events left to replay.
+    pending_events: VecDeque<RecordedEvent>,
+    diverged: bool,
+}
+
+impl RecordReplayModule {
+    /// Records the runs.
+    #[must_use]
+    pub fn record() -> Self {
+        Self::new(RecordReplayMode::Record)
+    }
+
+    /// Replays `trace`, e.g. the [`RecordedTrace`] metadata of an objective.
+    #[must_use]
+    pub fn replay(trace: RecordedTrace) -> Self {
+        Self::new(RecordReplayMode::Replay { trace })
+    }
+
+    /// Replays the trace saved in `path`.
+    pub fn replay_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(Self::replay(RecordedTrace::from_file(path)?))
+    }
+
+    #[must_use]
+    pub fn new(mode: RecordReplayMode) -> Self {
+        #[allow(unused_mut)] // cfg dependent
+        let mut syscalls = vec![
+            SYS_read,
+            SYS_pread64,
+            SYS_recvfrom,
+            SYS_getpid,
+            SYS_getppid,
+            SYS_gettid,
+        ];
+        #[cfg(not(cpu_target = "riscv32"))]
+        syscalls.extend([SYS_getrandom, SYS_clock_gettime, SYS_gettimeofday]);
+
+        Self {
+            mode,
+            syscalls,
+            recording: RecordedTrace::default(),
+            objective_trace: Arc::new(Mutex::new(None)),
+            threads: HashMap::new(),
+            handlers: HashSet::new(),
+            pending_syscalls: HashMap::new(),
+            pending_signals: HashMap::new(),
+            injected_signals: Vec::new(),
+            pending_events: VecDeque::new(),
+            diverged: false,
+        }
+    }
+
+    /// Also records the results of the syscall `sys_num`.
+    ///
+    /// Only the return value is replayed for the syscalls without a known output buffer.
+    #[must_use]
+    pub fn syscall(mut self, sys_num: i64) -> Self {
+        if !self.syscalls.contains(&sys_num) {
+            self.syscalls.push(sys_num);
+        }
+        self
+    }
+
+    /// The feedback attaching the traces of this module to the objectives.
+    #[must_use]
+    pub fn feedback(&self) -> RecordedTraceFeedback {
+        RecordedTraceFeedback {
+            trace: self.objective_trace.clone(),
+        }
+    }
+
+    /// The trace of the last run, in record mode.
+    #[must_use]
+    pub fn recording(&self) -> &RecordedTrace {
+        &self.recording
+    }
+
+    /// Whether the last replayed run diverged from the trace.
+    #[must_use]
+    pub fn diverged(&self) -> bool {
+        self.diverged
+    }
+
+    #[must_use]
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, RecordReplayMode::Replay { .. })
+    }
+
+    /// Keeps the trace of the run for the objective, in record mode.
+    fn save_objective_trace(&mut self) {
+        if !self.is_replaying() {
+            *self.objective_trace.lock().unwrap() = Some(self.recording.clone());
+        }
+    }
+
+    /// The host tid of the current guest thread.
+    fn current_tid() -> u32 {
+        u32::try_from(unsafe { libc::gettid() }).unwrap_or_default()
+    }
+
+    /// The current guest thread, numbered on first sight if created outside of the run.
+    fn current_thread(&mut self) -> &mut GuestThread {
+        let id = self.threads.len();
+        self.threads
+            .entry(Self::current_tid())
+            .or_insert(GuestThread {
+                id,
+                syscalls: 0,
+                last_sys_num: None,
+            })
+    }
+
+    fn divergence(&mut self, expected: Option<&RecordedEvent>, got: &RecordedEvent) {
+        log::warn!("Replay diverged: expected {expected:?}, got {got:?}");
+        self.diverged = true;
+    }
+
+    /// The guest memory the kernel writes for `sys_num`, as `(addr, len)`.
+    #[cfg_attr(cpu_target = "riscv32", allow(unused_variables))]
+    fn output_buffers(
+        sys_num: i32,
+        result: GuestAddr,
+        a0: GuestAddr,
+        a1: GuestAddr,
+    ) -> Vec<(GuestAddr, usize)> {
+        // -4095..-1 are errors
+        if result > GuestAddr::MAX - 4095 {
+            return Vec::new();
+        }
+
+        match i64::from(sys_num) {
+            SYS_read | SYS_pread64 | SYS_recvfrom => vec![(a1, result as usize)],
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_getrandom => vec![(a0, result as usize)],
+            // struct timespec and struct timeval, two longs
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_clock_gettime => vec![(a1, 2 * size_of::<GuestAddr>())],
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_gettimeofday if a0 != 0 => vec![(a0, 2 * size_of::<GuestAddr>())],
+            _ => Vec::new(),
+        }
+    }
+
+    fn record_syscall(
+        &mut self,
+        qemu: Qemu,
+        thread: usize,
+        result: GuestAddr,
+        sys_num: i32,
+        a0: GuestAddr,
+        a1: GuestAddr,
+    ) {
+        if !self.syscalls.contains(&i64::from(sys_num)) {
+            return;
+        }
+
+        let writes = Self::output_buffers(sys_num, result, a0, a1)
+            .into_iter()
+            .filter(|(_, len)| *len > 0)
+            .filter_map(|(addr, len)| qemu.read_mem_vec(addr, len).ok().map(|buf| (addr, buf)))
+            .collect();
+
+        self.recording.events.push(RecordedEvent::Syscall {
+            thread,
+            sys_num,
+            result,
+            writes,
+        });
+    }
+
+    /// Returns the recorded result of the syscall, if it should not be run.
+    fn replay_syscall(&mut self, qemu: Qemu, thread: usize, sys_num: i32) -> Option<GuestAddr> {
+        if !self.syscalls.contains(&i64::from(sys_num)) {
+            return None;
+        }
+
+        let event = self
+            .pending_syscalls
+            .get_mut(&thread)
+            .and_then(VecDeque::pop_front);
+
+        match event {
+            Some(RecordedEvent::Syscall {
+                sys_num: recorded,
+                result,
+                writes,
+                ..
+            }) if recorded == sys_num => {
+                for (addr, buf) in writes {
+                    if let Err(err) = qemu.write_mem(addr, &buf) {
+                        log::warn!(
+                            "Replay failed to write {} bytes at {addr:#x}: {err:?}",
+                            buf.len()
+                        );
+                        self.diverged = true;
+                    }
+                }
+                Some(result)
+            }
+            event => {
+                // Let the syscall run, nothing can be forced from now on for this thread
+                self.divergence(
+                    event.as_ref(),
+                    &RecordedEvent::Syscall {
+                        thread,
+                        sys_num,
+                        result: 0,
+                        writes: Vec::new(),
+                    },
+                );
+                self.pending_syscalls.remove(&thread);
+                None
+            }
+        }
+    }
+
+    fn pre_syscall(&mut self, qemu: Qemu, sys_num: i32) -> Option<GuestAddr> {
+        let thread = self.current_thread();
+        thread.syscalls += 1;
+        thread.last_sys_num = Some(sys_num);
+        let thread = thread.id;
+
+        if self.is_replaying() {
+            self.replay_syscall(qemu, thread, sys_num)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the new signal handler registered by the syscall, if any.
+    fn post_syscall(
+        &mut self,
+        qemu: Qemu,
+        result: GuestAddr,
+        sys_num: i32,
+        a0: GuestAddr,
+        a1: GuestAddr,
+    ) -> Option<GuestAddr> {
+        let GuestThread { id, syscalls, .. } = *self.current_thread();
+
+        if self.is_replaying() {
+            // The signals delivered once this syscall returned
+            if let Some(signals) = self.pending_signals.get_mut(&id) {
+                while signals.front().is_some_and(|(after, _)| *after <= syscalls) {
+                    let (_, signal) = signals.pop_front().unwrap();
+                    unsafe {
+                        qemu.queue_target_signal(signal);
+                    }
+                    self.injected_signals.push((id, signal));
+                }
+            }
+        } else {
+            self.record_syscall(qemu, id, result, sys_num, a0, a1);
+        }
+
+        if i64::from(sys_num) != SYS_rt_sigaction || result != 0 || a1 == 0 {
+            return None;
+        }
+
+        // struct sigaction starts with the handler, after the flags on mips
+        #[cfg(cpu_target = "mips")]
+        let a1 = a1 + 4;
+        let mut handler = [0u8; size_of::<GuestAddr>()];
+        qemu.read_mem(a1, &mut handler).ok()?;
+        let handler = GuestAddr::from_ne_bytes(handler);
+        #[cfg(cpu_target = "arm")]
+        let handler = handler & !1;
+
+        // SIG_DFL and SIG_IGN
+        if handler <= 1 {
+            return None;
+        }
+        self.handlers.insert(handler).then_some(handler)
+    }
+
+    /// A signal handler of the target is entered.
+    fn on_signal(&mut self, qemu: Qemu) {
+        let Some(cpu) = qemu.current_cpu() else {
+            return;
+        };
+        let Some(signal) = cpu
+            .read_function_argument(CallingConvention::Cdecl, 0)
+            .ok()
+            .and_then(|signal| i32::try_from(signal).ok())
+        else {
+            return;
+        };
+
+        let GuestThread {
+            id,
+            syscalls,
+            last_sys_num,
+        } = *self.current_thread();
+        let sent_by_thread =
+            last_sys_num.is_some_and(|sys_num| SIGNAL_SYSCALLS.contains(&i64::from(sys_num)));
+
+        if self.is_replaying() {
+            if let Some(idx) = self
+                .injected_signals
+                .iter()
+                .position(|injected| *injected == (id, signal))
+            {
+                self.injected_signals.remove(idx);
+            } else if !sent_by_thread {
+                self.divergence(
+                    None,
+                    &RecordedEvent::AsyncSignal {
+                        thread: id,
+                        signal,
+                        syscalls,
+                    },
+                );
+            }
+        } else if !sent_by_thread {
+            self.recording.events.push(RecordedEvent::AsyncSignal {
+                thread: id,
+                signal,
+                syscalls,
+            });
+        }
+    }
+
+    fn on_thread_creation(&mut self, tid: u32) {
+        let id = self.threads.len();
+        let thread = self.threads.entry(tid).or_insert(GuestThread {
+            id,
+            syscalls: 0,
+            last_sys_num: None,
+        });
+        let event = RecordedEvent::ThreadCreation { thread: thread.id };
+        self.on_event(event);
+    }
+
+    fn on_event(&mut self, event: RecordedEvent) {
+        match &self.mode {
+            RecordReplayMode::Record => self.recording.events.push(event),
+            RecordReplayMode::Replay { .. } => {
+                let expected = self.pending_events.pop_front();
+                if expected.as_ref() != Some(&event) {
+                    self.divergence(expected.as_ref(), &event);
+                }
+            }
+        }
+    }
+}
+
+impl<I, S> EmulatorModule<I, S> for RecordReplayModule
+where
+    I: Input + Unpin,
+    S: Unpin,
+{
+    fn post_qemu_init<ET>(&mut self, _qemu: Qemu, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        emulator_modules.pre_syscalls(Hook::Function(pre_syscall_record_replay::<ET, I, S>));
+        emulator_modules.post_syscalls(Hook::Function(post_syscall_record_replay::<ET, I, S>));
+        emulator_modules.thread_creation(Hook::Function(thread_creation_record_replay::<ET, I, S>));
+        emulator_modules.crash_function(oncrash_record_replay::<ET, I, S>);
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.recording.events.clear();
+        *self.objective_trace.lock().unwrap() = None;
+        self.diverged = false;
+
+        // The run starts in the main thread, the first one
+        self.threads.clear();
+        self.current_thread();
+
+        self.pending_syscalls.clear();
+        self.pending_signals.clear();
+        self.injected_signals.clear();
+        self.pending_events.clear();
+        if let RecordReplayMode::Replay { trace } = &self.mode {
+            for event in &trace.events {
+                match event {
+                    RecordedEvent::Syscall { thread, .. } => {
+                        self.pending_syscalls
+                            .entry(*thread)
+                            .or_default()
+                            .push_back(event.clone());
+                    }
+                    RecordedEvent::AsyncSignal {
+                        thread,
+                        signal,
+                        syscalls,
+                    } => {
+                        self.pending_signals
+                            .entry(*thread)
+                            .or_default()
+                            .push_back((*syscalls, *signal));
+                    }
+                    _ => self.pending_events.push_back(event.clone()),
+                }
+            }
+        }
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+        _observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<I, S>,
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        match &self.mode {
+            RecordReplayMode::Record => {
+                if matches!(*exit_kind, ExitKind::Crash | ExitKind::Timeout) {
+                    self.save_objective_trace();
+                }
+            }
+            RecordReplayMode::Replay { .. } => {
+                if self.diverged
+                    || !self.pending_events.is_empty()
+                    || self
+                        .pending_syscalls
+                        .values()
+                        .any(|events| !events.is_empty())
+                    || self
+                        .pending_signals
+                        .values()
+                        .any(|signals| !signals.is_empty())
+                {
+                    log::warn!("The run did not follow the replayed trace until its end.");
+                }
+            }
+        }
+    }
+
+    unsafe fn on_crash(&mut self) {
+        self.save_objective_trace();
+    }
+
+    unsafe fn on_timeout(&mut self) {
+        self.save_objective_trace();
+    }
+}
+
+impl HasAddressFilter for RecordReplayModule {
+    type ModuleAddressFilter = NopAddressFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+/// Nop feedback that attaches the [`RecordedTrace`] of the [`RecordReplayModule`] to the new
+/// objective. The testcase is never interesting (use with an OR).
+#[derive(Debug, Clone)]
+pub struct RecordedTraceFeedback {
+    trace: Arc<Mutex<Option<RecordedTrace>>>,
+}
+
+impl<S> StateInitializer<S> for RecordedTraceFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for RecordedTraceFeedback {
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Append to the testcase the trace of the run.
+    #[inline]
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        if let Some(trace) = self.trace.lock().unwrap().take() {
+            testcase.metadata_map_mut().insert(trace);
+        }
+        Ok(())
+    }
+}
+
+impl Named for RecordedTraceFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RecordedTraceFeedback");
+        &NAME
+    }
+}
+
+#[expect(clippy::too_many_arguments)]
+pub fn pre_syscall_record_replay<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    _a0: GuestAddr,
+    _a1: GuestAddr,
+    _a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Input + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<RecordReplayModule>().unwrap();
+    SyscallHookResult::new(h.pre_syscall(qemu, sys_num))
+}
+
+#[expect(clippy::too_many_arguments)]
+pub fn post_syscall_record_replay<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    result: GuestAddr,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    _a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> GuestAddr
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Input + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<RecordReplayModule>().unwrap();
+    if let Some(handler) = h.post_syscall(qemu, result, sys_num, a0, a1) {
+        emulator_modules.instructions(
+            handler,
+            Hook::Function(on_signal_record_replay::<ET, I, S>),
+            true,
+        );
+    }
+    result
+}
+
+pub fn on_signal_record_replay<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Input + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<RecordReplayModule>().unwrap();
+    h.on_signal(qemu);
+}
+
+pub fn thread_creation_record_replay<ET, I, S>(
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    _env: CPUArchStatePtr,
+    tid: u32,
+) -> bool
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Input + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<RecordReplayModule>().unwrap();
+    h.on_thread_creation(tid);
+    true
+}
+
+/// Records the crashing signal, and keeps the trace: `post_exec` does not run after a crash.
+pub fn oncrash_record_replay<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    target_sig: i32,
+) where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Input + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<RecordReplayModule>().unwrap();
+    let pc: GuestAddr = qemu.read_reg(Regs::Pc).unwrap_or_default();
+    let thread = h.current_thread().id;
+    h.on_event(RecordedEvent::Signal {
+        thread,
+        signal: target_sig,
+        pc,
+    });
+    h.save_objective_trace();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordedEvent, RecordedTrace};
+
+    #[test]
+    fn test_recorded_trace_roundtrip() {
+        let trace = RecordedTrace {
+            events: vec![
+                RecordedEvent::Syscall {
+                    thread: 0,
+                    sys_num: 0,
+                    result: 4,
+                    writes: vec![(0x1000, b"AAAA".to_vec())],
+                },
+                RecordedEvent::ThreadCreation { thread: 1 },
+                RecordedEvent::AsyncSignal {
+                    thread: 1,
+                    signal: 14,
+                    syscalls: 3,
+                },
+                RecordedEvent::Signal {
+                    thread: 1,
+                    signal: 11,
+                    pc: 0x40_1000,
+                },
+            ],
+        };
+
+        let path = std::env::temp_dir().join("libafl_qemu_test_recorded_trace.replay");
+        trace.to_file(&path).unwrap();
+        assert_eq!(RecordedTrace::from_file(&path).unwrap(), trace);
+        std::fs::remove_file(path).unwrap();
+    }
+}
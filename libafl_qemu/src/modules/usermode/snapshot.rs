@@ -24,6 +24,7 @@ use crate::{
     emu::EmulatorModules,
     modules::{
         asan::AsanModule,
+        usermode::fs_snapshot::FsSnapshot,
         utils::filters::{HasAddressFilter, NopAddressFilter, NOP_ADDRESS_FILTER},
         EmulatorModule, EmulatorModuleTuple,
     },
@@ -98,6 +99,8 @@ pub struct SnapshotModule {
     pub empty: bool,
     pub accurate_unmap: bool,
     pub interval_filter: Vec<IntervalSnapshotFilter>,
+    /// The fds and files of the guest, if they are snapshotted too
+    pub fs: Option<FsSnapshot>,
     auto_reset: bool,
 }
 
@@ -111,6 +114,7 @@ impl core::fmt::Debug for SnapshotModule {
             .field("mmap_start", &self.mmap_start)
             .field("mmap_limit", &self.mmap_limit)
             .field("empty", &self.empty)
+            .field("fs", &self.fs)
             .finish_non_exhaustive()
     }
 }
@@ -131,6 +135,7 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter: Vec::<IntervalSnapshotFilter>::new(),
+            fs: None,
             auto_reset: true,
         }
     }
@@ -150,6 +155,7 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter,
+            fs: None,
             auto_reset: true,
         }
     }
@@ -169,6 +175,7 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter: Vec::<IntervalSnapshotFilter>::new(),
+            fs: None,
             auto_reset: true,
         }
     }
@@ -181,6 +188,13 @@ impl SnapshotModule {
         self.auto_reset = false;
     }
 
+    /// Also snapshot the file descriptors of the guest, their offsets, and the files it changes.
+    ///
+    /// Must be called before the module is initialized, to install the syscall hooks.
+    pub fn use_fs_snapshot(&mut self) {
+        self.fs = Some(FsSnapshot::new());
+    }
+
     pub fn to_skip(&self, addr: GuestAddr) -> bool {
         for filter in &self.interval_filter {
             match filter {
@@ -239,6 +253,9 @@ impl SnapshotModule {
             );
             self.maps.size += (map.end() - map.start()) as usize;
         }
+        if let Some(fs) = &mut self.fs {
+            fs.snapshot();
+        }
         self.empty = false;
         *self.new_maps.lock().unwrap() = self.maps.clone();
         log::info!("End snapshot");
@@ -519,6 +536,10 @@ impl SnapshotModule {
         qemu.set_brk(self.brk);
         qemu.set_mmap_start(self.mmap_start);
 
+        if let Some(fs) = &mut self.fs {
+            fs.reset();
+        }
+
         #[cfg(feature = "paranoid_debug")]
         self.check_snapshot(qemu);
 
@@ -749,6 +770,11 @@ where
             emulator_modules.pre_syscalls(Hook::Function(filter_mmap_snapshot::<ET, I, S>));
         }
         emulator_modules.post_syscalls(Hook::Function(trace_mmap_snapshot::<ET, I, S>));
+
+        if self.fs.is_some() {
+            emulator_modules.pre_syscalls(Hook::Function(filter_fs_snapshot::<ET, I, S>));
+            emulator_modules.post_syscalls(Hook::Function(trace_fs_snapshot::<ET, I, S>));
+        }
     }
 
     fn pre_exec<ET>(
@@ -841,6 +867,61 @@ where
     SyscallHookResult::new(None)
 }
 
+#[expect(clippy::too_many_arguments)]
+pub fn filter_fs_snapshot<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    a3: GuestAddr,
+    a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<SnapshotModule>().unwrap();
+    if let Some(fs) = &mut h.fs {
+        fs.pre_syscall(qemu, i64::from(sys_num), a0, a1, a2, a3, a4);
+    }
+    SyscallHookResult::new(None)
+}
+
+#[expect(clippy::too_many_arguments)]
+pub fn trace_fs_snapshot<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    result: GuestAddr,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    _a2: GuestAddr,
+    a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> GuestAddr
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<SnapshotModule>().unwrap();
+    if let Some(fs) = &mut h.fs {
+        fs.post_syscall(qemu, result, i64::from(sys_num), a0, a1, a3);
+    }
+    result
+}
+
 #[expect(non_upper_case_globals, clippy::too_many_arguments)]
 pub fn trace_mmap_snapshot<ET, I, S>(
     _qemu: Qemu,
@@ -0,0 +1,459 @@
+//! Syscall-level fault injection driven by the fuzz input
+//!
+//! Some bugs only show up when a syscall fails, is interrupted, or reads or writes less than
+//! requested. For a configurable set of syscalls, the [`SyscallFaultModule`] takes the result of
+//! each call from the last bytes of the input, reserved for it: each call consumes a decision
+//! byte, to let the syscall run normally or to inject one of the faults configured for it
+//! (an error such as `ENOMEM` or `EINTR`, a short read, a partial write, ...).
+//!
+//! The harness must only pass the rest of the input to the target, see [`split_syscall_faults`].
+//! Each injected fault, by call site, is reported in [`SYSCALL_FAULTS_MAP`], so that a map observer
+//! tells the fuzzer which error paths were reached. The call site is the return address of the
+//! libc wrapper issuing the syscall, the same for all the wrappers of the program otherwise.
+//!
+//! The arguments of a syscall are read before the hooks run, so a short read or write is injected
+//! by reducing the count argument in its register, and restarting the syscall as after a signal.
+
+#![allow(clippy::needless_pass_by_value)] // default compiler complains about Option<&mut T> otherwise, and this is used extensively.
+
+use hashbrown::HashMap;
+use libafl::inputs::HasTargetBytes;
+use libafl_bolts::{hash_64_fast, AsSlice};
+use libafl_qemu_sys::GuestAddr;
+
+#[cfg(not(any(cpu_target = "arm", cpu_target = "riscv32")))]
+use crate::SYS_mmap;
+#[cfg(any(cpu_target = "arm", cpu_target = "mips", cpu_target = "riscv32"))]
+use crate::SYS_mmap2;
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        utils::filters::{HasAddressFilter, NopAddressFilter, NOP_ADDRESS_FILTER},
+        EmulatorModule, EmulatorModuleTuple,
+    },
+    qemu::{ArchExtras, Hook, SyscallHookResult},
+    Qemu, Regs, SYS_openat, SYS_pread64, SYS_pwrite64, SYS_read, SYS_recvfrom, SYS_sendto,
+    SYS_write,
+};
+
+/// The register of the count argument of the shortened syscalls, their third argument.
+#[cfg(cpu_target = "x86_64")]
+const SYSCALL_COUNT_REG: Regs = Regs::Rdx;
+#[cfg(cpu_target = "i386")]
+const SYSCALL_COUNT_REG: Regs = Regs::Edx;
+#[cfg(cpu_target = "aarch64")]
+const SYSCALL_COUNT_REG: Regs = Regs::X2;
+#[cfg(cpu_target = "arm")]
+const SYSCALL_COUNT_REG: Regs = Regs::R2;
+#[cfg(any(cpu_target = "mips", cpu_target = "riscv32", cpu_target = "riscv64"))]
+const SYSCALL_COUNT_REG: Regs = Regs::A2;
+#[cfg(cpu_target = "ppc")]
+const SYSCALL_COUNT_REG: Regs = Regs::R5;
+
+/// The index of the count argument of the shortened syscalls.
+const SYSCALL_COUNT_ARG: usize = 2;
+
+/// QEMU's `ERESTARTSYS`: the syscall is executed again, with the arguments in the registers.
+const QEMU_ERESTARTSYS: GuestAddr = 512;
+
+/// The size of [`SYSCALL_FAULTS_MAP`]
+pub const SYSCALL_FAULTS_MAP_SIZE: usize = 1 << 12;
+
+/// The coverage of the injected faults, by call site and fault.
+pub static mut SYSCALL_FAULTS_MAP: [u8; SYSCALL_FAULTS_MAP_SIZE] = [0; SYSCALL_FAULTS_MAP_SIZE];
+
+/// A pointer to [`SYSCALL_FAULTS_MAP`], to build a map observer.
+#[must_use]
+pub fn syscall_faults_map_mut_ptr() -> *mut u8 {
+    (&raw mut SYSCALL_FAULTS_MAP).cast()
+}
+
+/// Splits the input bytes into the bytes of the target and the last `reserved_len` bytes,
+/// driving the [`SyscallFaultModule`].
+#[must_use]
+pub fn split_syscall_faults(bytes: &[u8], reserved_len: usize) -> (&[u8], &[u8]) {
+    bytes.split_at(bytes.len().saturating_sub(reserved_len))
+}
+
+/// A fault injected in a syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallFault {
+    /// Fails with the errno, without running the syscall.
+    Errno(i32),
+    /// Runs the syscall with its count argument, the third one, reduced to a part taken from the
+    /// input.
+    Short,
+    /// Returns a value taken from the input, without running the syscall.
+    Value,
+    /// Fills the buffer of the argument `buf`, up to the length of the argument `count`, with bytes
+    /// taken from the input, and returns their number, without running the syscall.
+    Data { buf: usize, count: usize },
+}
+
+/// The decisions taken from the reserved bytes of the input.
+#[derive(Debug, Default)]
+struct FaultBytes {
+    bytes: Vec<u8>,
+    cursor: usize,
+}
+
+impl FaultBytes {
+    fn reset(&mut self, bytes: &[u8]) {
+        self.bytes.clear();
+        self.bytes.extend_from_slice(bytes);
+        self.cursor = 0;
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = self.bytes.get(self.cursor).copied()?;
+        self.cursor += 1;
+        Some(byte)
+    }
+
+    fn next_bytes(&mut self, len: usize) -> &[u8] {
+        let start = self.cursor.min(self.bytes.len());
+        let end = (start + len).min(self.bytes.len());
+        self.cursor = end;
+        &self.bytes[start..end]
+    }
+
+    /// The fault to inject among `faults`, or `None` to run the syscall normally.
+    /// Once the bytes are exhausted, syscalls run normally.
+    fn decide(&mut self, faults: &[SyscallFault]) -> Option<(usize, SyscallFault)> {
+        let byte = usize::from(self.next_byte()?);
+        match byte % (faults.len() + 1) {
+            0 => None,
+            idx => Some((idx - 1, faults[idx - 1])),
+        }
+    }
+
+    fn value(&mut self) -> GuestAddr {
+        let mut value = [0; size_of::<GuestAddr>()];
+        let bytes = self.next_bytes(value.len());
+        value[..bytes.len()].copy_from_slice(bytes);
+        GuestAddr::from_le_bytes(value)
+    }
+
+    /// How to inject `fault` in a syscall called with `args`, taking its parameters from the
+    /// input.
+    #[allow(clippy::cast_sign_loss, clippy::unnecessary_cast)] // errnos are positive
+    fn action(&mut self, fault: SyscallFault, args: &[GuestAddr; 8]) -> FaultAction {
+        match fault {
+            SyscallFault::Errno(errno) => FaultAction::Return((errno as GuestAddr).wrapping_neg()),
+            SyscallFault::Short => {
+                let count = args[SYSCALL_COUNT_ARG];
+                let byte = GuestAddr::from(self.next_byte().unwrap_or_default());
+                if count == 0 {
+                    FaultAction::Run
+                } else {
+                    FaultAction::Shorten(byte % count)
+                }
+            }
+            SyscallFault::Value => FaultAction::Return(self.value()),
+            SyscallFault::Data { buf, count } => {
+                let len = usize::from(self.next_byte().unwrap_or_default());
+                let len = len.min(args[count] as usize);
+                FaultAction::Fill(args[buf], self.next_bytes(len).to_vec())
+            }
+        }
+    }
+}
+
+/// What the pre-syscall hook does to inject a fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FaultAction {
+    /// Runs the syscall normally.
+    Run,
+    /// Returns the value, without running the syscall.
+    Return(GuestAddr),
+    /// Restarts the syscall with this count argument.
+    Shorten(GuestAddr),
+    /// Writes the bytes at the address, and returns their number, without running the syscall.
+    Fill(GuestAddr, Vec<u8>),
+}
+
+/// The entry of [`SYSCALL_FAULTS_MAP`] of a fault, by call site.
+#[allow(clippy::cast_sign_loss, clippy::unnecessary_cast)] // for GuestAddr -> u64
+fn fault_id(call_site: GuestAddr, sys_num: i64, fault_idx: usize) -> usize {
+    hash_64_fast(call_site as u64 ^ ((sys_num as u64) << 8) ^ fault_idx as u64) as usize
+        & (SYSCALL_FAULTS_MAP_SIZE - 1)
+}
+
+#[derive(Debug)]
+pub struct SyscallFaultModuleBuilder {
+    faults: HashMap<i64, Vec<SyscallFault>>,
+    reserved_len: usize,
+}
+
+impl SyscallFaultModuleBuilder {
+    /// Adds faults to inject in the syscall.
+    #[must_use]
+    pub fn syscall(mut self, sys_num: i64, faults: &[SyscallFault]) -> Self {
+        self.faults
+            .entry(sys_num)
+            .or_default()
+            .extend_from_slice(faults);
+        self
+    }
+
+    /// Adds the usual errors and short reads and writes of the IO and memory syscalls.
+    #[must_use]
+    pub fn default_faults(self) -> Self {
+        let io = [
+            SyscallFault::Errno(libc::EINTR),
+            SyscallFault::Errno(libc::EIO),
+            SyscallFault::Short,
+        ];
+
+        let builder = self
+            .syscall(SYS_read, &io)
+            .syscall(SYS_write, &io)
+            .syscall(SYS_pread64, &io)
+            .syscall(SYS_pwrite64, &io)
+            .syscall(SYS_recvfrom, &io)
+            .syscall(SYS_sendto, &io)
+            .syscall(
+                SYS_openat,
+                &[
+                    SyscallFault::Errno(libc::ENOENT),
+                    SyscallFault::Errno(libc::EACCES),
+                    SyscallFault::Errno(libc::EMFILE),
+                ],
+            );
+        #[cfg(not(any(cpu_target = "arm", cpu_target = "riscv32")))]
+        let builder = builder.syscall(SYS_mmap, &[SyscallFault::Errno(libc::ENOMEM)]);
+        #[cfg(any(cpu_target = "arm", cpu_target = "mips", cpu_target = "riscv32"))]
+        let builder = builder.syscall(SYS_mmap2, &[SyscallFault::Errno(libc::ENOMEM)]);
+        builder
+    }
+
+    /// The number of bytes at the end of the input driving the faults.
+    #[must_use]
+    pub fn reserved_len(mut self, reserved_len: usize) -> Self {
+        self.reserved_len = reserved_len;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> SyscallFaultModule {
+        SyscallFaultModule {
+            faults: self.faults,
+            reserved_len: self.reserved_len,
+            bytes: FaultBytes::default(),
+            restarting: None,
+        }
+    }
+}
+
+impl Default for SyscallFaultModuleBuilder {
+    fn default() -> Self {
+        Self {
+            faults: HashMap::new(),
+            reserved_len: 64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SyscallFaultModule {
+    faults: HashMap<i64, Vec<SyscallFault>>,
+    reserved_len: usize,
+    bytes: FaultBytes,
+    /// The shortened syscall, restarted with its reduced count
+    restarting: Option<i64>,
+}
+
+impl SyscallFaultModule {
+    #[must_use]
+    pub fn builder() -> SyscallFaultModuleBuilder {
+        SyscallFaultModuleBuilder::default()
+    }
+
+    #[must_use]
+    pub fn reserved_len(&self) -> usize {
+        self.reserved_len
+    }
+
+    fn hit(call_site: GuestAddr, sys_num: i64, fault_idx: usize) {
+        unsafe {
+            let entry =
+                &mut (*(&raw mut SYSCALL_FAULTS_MAP))[fault_id(call_site, sys_num, fault_idx)];
+            *entry = entry.wrapping_add(1);
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)] // errnos are positive
+    fn pre_syscall(&mut self, qemu: Qemu, sys_num: i64, args: [GuestAddr; 8]) -> Option<GuestAddr> {
+        // The restart of a shortened syscall runs with the reduced count
+        if self.restarting.take() == Some(sys_num) {
+            return None;
+        }
+
+        let faults = self.faults.get(&sys_num)?;
+        let (idx, fault) = self.bytes.decide(faults)?;
+
+        #[allow(clippy::unnecessary_cast)] // for GuestReg -> GuestAddr
+        let call_site = qemu.read_return_address().unwrap_or_default() as GuestAddr;
+        Self::hit(call_site, sys_num, idx);
+
+        match self.bytes.action(fault, &args) {
+            FaultAction::Run => None,
+            FaultAction::Return(value) => Some(value),
+            FaultAction::Shorten(count) => {
+                qemu.write_reg(SYSCALL_COUNT_REG, count).ok()?;
+                self.restarting = Some(sys_num);
+                Some(QEMU_ERESTARTSYS.wrapping_neg())
+            }
+            FaultAction::Fill(addr, data) => {
+                if qemu.write_mem(addr, &data).is_err() {
+                    return Some((libc::EFAULT as GuestAddr).wrapping_neg());
+                }
+                Some(data.len() as GuestAddr)
+            }
+        }
+    }
+}
+
+impl<I, S> EmulatorModule<I, S> for SyscallFaultModule
+where
+    I: HasTargetBytes + Unpin,
+    S: Unpin,
+{
+    fn post_qemu_init<ET>(&mut self, _qemu: Qemu, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        emulator_modules.pre_syscalls(Hook::Function(pre_syscall_fault::<ET, I, S>));
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        let target_bytes = input.target_bytes();
+        let (_, fault_bytes) = split_syscall_faults(target_bytes.as_slice(), self.reserved_len);
+        self.bytes.reset(fault_bytes);
+        self.restarting = None;
+    }
+}
+
+impl HasAddressFilter for SyscallFaultModule {
+    type ModuleAddressFilter = NopAddressFilter;
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+#[expect(clippy::too_many_arguments)]
+pub fn pre_syscall_fault<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    a3: GuestAddr,
+    a4: GuestAddr,
+    a5: GuestAddr,
+    a6: GuestAddr,
+    a7: GuestAddr,
+) -> SyscallHookResult
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: HasTargetBytes + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<SyscallFaultModule>().unwrap();
+    SyscallHookResult::new(h.pre_syscall(
+        qemu,
+        i64::from(sys_num),
+        [a0, a1, a2, a3, a4, a5, a6, a7],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_qemu_sys::GuestAddr;
+
+    use super::{
+        fault_id, split_syscall_faults, FaultAction, FaultBytes, SYS_read, SYS_write, SyscallFault,
+    };
+
+    #[test]
+    fn test_fault_bytes() {
+        let faults = [SyscallFault::Errno(libc::EINTR), SyscallFault::Short];
+
+        let (target, reserved) = split_syscall_faults(&[9, 9, 0, 1, 5, 2, 1, 2], 6);
+        assert_eq!(target, &[9, 9]);
+
+        let mut bytes = FaultBytes::default();
+        bytes.reset(reserved);
+        assert_eq!(bytes.decide(&faults), None);
+        assert_eq!(
+            bytes.decide(&faults),
+            Some((0, SyscallFault::Errno(libc::EINTR)))
+        );
+        assert_eq!(bytes.decide(&faults), Some((1, SyscallFault::Short)));
+        assert_eq!(bytes.decide(&faults), Some((1, SyscallFault::Short)));
+        assert_eq!(bytes.value(), 0x201);
+        assert_eq!(bytes.decide(&faults), None);
+    }
+
+    #[test]
+    fn test_fault_sites() {
+        let id = fault_id(0x40_1000, SYS_read, 0);
+        assert_eq!(id, fault_id(0x40_1000, SYS_read, 0));
+
+        // Each call site, syscall and fault is its own entry
+        assert_ne!(id, fault_id(0x40_2000, SYS_read, 0));
+        assert_ne!(id, fault_id(0x40_1000, SYS_write, 0));
+        assert_ne!(id, fault_id(0x40_1000, SYS_read, 1));
+    }
+
+    #[test]
+    fn test_fault_actions() {
+        let args: [GuestAddr; 8] = [3, 0x1000, 100, 0, 0, 0, 0, 0];
+
+        let mut bytes = FaultBytes::default();
+        bytes.reset(&[42, 7, 3, 0xaa, 0xbb, 0xcc, 0x01, 0x02]);
+
+        assert_eq!(
+            bytes.action(SyscallFault::Errno(libc::EINTR), &args),
+            FaultAction::Return((libc::EINTR as GuestAddr).wrapping_neg())
+        );
+
+        // the count is reduced, the syscall runs with the same buffer
+        assert_eq!(
+            bytes.action(SyscallFault::Short, &args),
+            FaultAction::Shorten(42)
+        );
+        let empty: [GuestAddr; 8] = [3, 0x1000, 0, 0, 0, 0, 0, 0];
+        assert_eq!(bytes.action(SyscallFault::Short, &empty), FaultAction::Run);
+
+        assert_eq!(
+            bytes.action(SyscallFault::Data { buf: 1, count: 2 }, &args),
+            FaultAction::Fill(0x1000, vec![0xaa, 0xbb, 0xcc])
+        );
+        assert_eq!(
+            bytes.action(SyscallFault::Value, &args),
+            FaultAction::Return(0x201)
+        );
+
+        // exhausted bytes shorten to nothing
+        assert_eq!(
+            bytes.action(SyscallFault::Short, &args),
+            FaultAction::Shorten(0)
+        );
+    }
+}
@@ -0,0 +1,655 @@
+//! A virtual filesystem overlay for usermode targets.
+//!
+//! Selected paths are served from memory instead of the host filesystem:
+//! fixed contents, the current input, or a named part of a [`MultipartInput`].
+//! The guest may also write to them, and the writes stay in memory.
+//!
+//! Each file is backed by a host `memfd`. Opening an overlaid path hands out a new description of the
+//! `memfd` to the guest, so reads, writes, seeks, `fstat` and `mmap` work as for a regular file.
+//! The descriptions handed out, and their duplicates, are closed at the end of each run.
+//!
+//! Stats by path are served for `statx` on all targets, and for `newfstatat` (and `stat` and `lstat`
+//! on `x86_64`) on the 64-bit targets. The 32-bit targets have a `stat64` layout of their own,
+//! and are served through `statx` only, which glibc uses for all stats since 2.33.
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::fs::FileExt,
+    },
+    path::{Path, PathBuf},
+};
+
+use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "multipart_inputs")]
+use libafl::inputs::MultipartInput;
+use libafl::{
+    executors::ExitKind,
+    inputs::{BytesInput, HasTargetBytes},
+    observers::ObserversTuple,
+    Error,
+};
+use libafl_bolts::AsSlice;
+use libafl_qemu_sys::GuestAddr;
+
+#[cfg(not(cpu_target = "riscv32"))]
+use crate::SYS_fcntl;
+#[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64"))]
+use crate::SYS_newfstatat;
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        usermode::fs_snapshot::guest_path,
+        utils::filters::{HasAddressFilter, NopAddressFilter, NOP_ADDRESS_FILTER},
+        EmulatorModule, EmulatorModuleTuple,
+    },
+    qemu::{Hook, SyscallHookResult},
+    Qemu, SYS_close, SYS_dup, SYS_dup3, SYS_faccessat, SYS_faccessat2, SYS_openat, SYS_statx,
+};
+#[cfg(any(
+    cpu_target = "x86_64",
+    cpu_target = "i386",
+    cpu_target = "arm",
+    cpu_target = "mips",
+    cpu_target = "ppc"
+))]
+use crate::{SYS_access, SYS_creat, SYS_dup2, SYS_open};
+#[cfg(cpu_target = "x86_64")]
+use crate::{SYS_lstat, SYS_stat};
+
+/// The guest's `AT_FDCWD`, the same on all targets
+const AT_FDCWD: i32 = -100;
+/// The guest's access mode bits of the `open` flags
+const O_ACCMODE: GuestAddr = 0o3;
+/// The guest's `O_TRUNC`, the same on all targets
+const O_TRUNC: GuestAddr = 0o1000;
+/// The guest's `O_APPEND`, which differs on mips
+#[cfg(cpu_target = "mips")]
+const O_APPEND: GuestAddr = 0x8;
+#[cfg(not(cpu_target = "mips"))]
+const O_APPEND: GuestAddr = 0o2000;
+/// The guest's `F_DUPFD` and `F_DUPFD_CLOEXEC`
+#[cfg(not(cpu_target = "riscv32"))]
+const F_DUPFD: GuestAddr = 0;
+#[cfg(not(cpu_target = "riscv32"))]
+const F_DUPFD_CLOEXEC: GuestAddr = 1030;
+
+/// Inputs that can back the files of a [`VfsModule`]
+pub trait VfsInput {
+    /// The bytes of the whole input, if `part` is `None`, or of the part named `part`.
+    fn vfs_bytes(&self, part: Option<&str>) -> Option<Vec<u8>>;
+}
+
+impl VfsInput for BytesInput {
+    fn vfs_bytes(&self, part: Option<&str>) -> Option<Vec<u8>> {
+        part.is_none()
+            .then(|| self.target_bytes().as_slice().to_vec())
+    }
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<I> VfsInput for MultipartInput<I>
+where
+    I: HasTargetBytes,
+{
+    /// The first part named `part`. A [`MultipartInput`] has no bytes as a whole.
+    fn vfs_bytes(&self, part: Option<&str>) -> Option<Vec<u8>> {
+        let (_, part) = self.parts_by_name(part?).next()?;
+        Some(part.target_bytes().as_slice().to_vec())
+    }
+}
+
+/// Where the content of a file of the [`VfsModule`] comes from, at the start of each run
+#[derive(Debug, Clone)]
+pub enum VfsContent {
+    /// Fixed bytes
+    Bytes(Vec<u8>),
+    /// The current input
+    Input,
+    /// The part of the current input with this name
+    InputPart(String),
+}
+
+#[derive(Debug)]
+struct VfsFile {
+    content: VfsContent,
+    memfd: File,
+}
+
+/// Serves selected paths from memory, see the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct VfsModule {
+    files: HashMap<PathBuf, VfsFile>,
+    /// The descriptions of the memfds handed out to the guest in this run, and their duplicates
+    open_fds: HashSet<i32>,
+}
+
+impl VfsModule {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves `path` with the given `content`, reset at the start of each run.
+    pub fn file<P: AsRef<Path>>(mut self, path: P, content: VfsContent) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let name = CString::new(format!("libafl_vfs:{}", path.display()))
+            .map_err(|_| Error::illegal_argument("The path contains a NUL byte"))?;
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+        if fd == -1 {
+            return Err(Error::last_os_error(format!(
+                "Cannot create the memfd for {}",
+                path.display()
+            )));
+        }
+        let memfd = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        self.files
+            .insert(path.to_path_buf(), VfsFile { content, memfd });
+        Ok(self)
+    }
+
+    /// Serves `path` with fixed `bytes`.
+    pub fn bytes_file<P: AsRef<Path>>(self, path: P, bytes: Vec<u8>) -> Result<Self, Error> {
+        self.file(path, VfsContent::Bytes(bytes))
+    }
+
+    /// Serves `path` with the current input.
+    pub fn input_file<P: AsRef<Path>>(self, path: P) -> Result<Self, Error> {
+        self.file(path, VfsContent::Input)
+    }
+
+    /// Serves `path` with the part named `part` of the current input.
+    pub fn input_part_file<P: AsRef<Path>>(self, path: P, part: &str) -> Result<Self, Error> {
+        self.file(path, VfsContent::InputPart(part.to_string()))
+    }
+
+    /// Captures the writes of the guest to `path`, which starts empty at each run.
+    pub fn capture_file<P: AsRef<Path>>(self, path: P) -> Result<Self, Error> {
+        self.file(path, VfsContent::Bytes(Vec::new()))
+    }
+
+    /// The current content of `path`, including the writes of the guest in this run.
+    pub fn content<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        let file = self.files.get(path.as_ref()).ok_or_else(|| {
+            Error::key_not_found(format!("{} is not in the VFS", path.as_ref().display()))
+        })?;
+        let mut memfd = &file.memfd;
+        let mut content = Vec::new();
+        memfd.seek(SeekFrom::Start(0))?;
+        memfd.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    /// Stops tracking `fd`, which the guest closes itself.
+    fn forget_fd(&mut self, fd: i32) {
+        self.open_fds.remove(&fd);
+    }
+
+    /// Closes the fds the guest got for overlaid files, and did not close itself.
+    fn close_open_fds(&mut self) {
+        for fd in self.open_fds.drain() {
+            unsafe { libc::close(fd) };
+        }
+    }
+
+    fn reset<I>(&mut self, input: &I) -> io::Result<()>
+    where
+        I: VfsInput,
+    {
+        for (path, file) in &mut self.files {
+            let bytes = match &file.content {
+                VfsContent::Bytes(bytes) => Some(bytes.clone()),
+                VfsContent::Input => input.vfs_bytes(None),
+                VfsContent::InputPart(part) => input.vfs_bytes(Some(part)),
+            };
+            let bytes = bytes.unwrap_or_else(|| {
+                log::debug!("No content for {} in this input", path.display());
+                Vec::new()
+            });
+            file.memfd.set_len(0)?;
+            file.memfd.write_all_at(&bytes, 0)?;
+        }
+        Ok(())
+    }
+
+    /// The memfd serving the guest path at `path`, relative to `dirfd`, if it is overlaid
+    fn memfd(&self, qemu: Qemu, dirfd: i32, path: GuestAddr) -> Option<&File> {
+        let path = guest_path(qemu, dirfd, path)?;
+        self.files.get(&path).map(|file| &file.memfd)
+    }
+
+    /// Opens a new description of the memfd for `path`, as the guest asked for.
+    fn open(
+        &mut self,
+        qemu: Qemu,
+        dirfd: i32,
+        path: GuestAddr,
+        flags: GuestAddr,
+    ) -> Option<GuestAddr> {
+        let memfd = self.memfd(qemu, dirfd, path)?.as_raw_fd();
+        Some(self.open_memfd(memfd, flags))
+    }
+
+    /// Opens a new description of `memfd` with the guest's open `flags`, and tracks it.
+    fn open_memfd(&mut self, memfd: i32, flags: GuestAddr) -> GuestAddr {
+        let mut host_flags = match flags & O_ACCMODE {
+            0 => libc::O_RDONLY,
+            1 => libc::O_WRONLY,
+            _ => libc::O_RDWR,
+        };
+        if flags & O_TRUNC != 0 {
+            host_flags |= libc::O_TRUNC;
+        }
+        if flags & O_APPEND != 0 {
+            host_flags |= libc::O_APPEND;
+        }
+        let proc_path = CString::new(format!("/proc/self/fd/{memfd}")).unwrap();
+        let fd = unsafe { libc::open(proc_path.as_ptr(), host_flags) };
+        if fd >= 0 {
+            self.open_fds.insert(fd);
+        }
+        syscall_result(fd.into())
+    }
+
+    /// Writes the host `statx` of the memfd for `path` to the guest's `statx` buffer at `buf`.
+    fn statx(
+        &self,
+        qemu: Qemu,
+        dirfd: i32,
+        path: GuestAddr,
+        mask: GuestAddr,
+        buf: GuestAddr,
+    ) -> Option<GuestAddr> {
+        let memfd = self.memfd(qemu, dirfd, path)?;
+        let res = memfd_statx(memfd, mask as u32).and_then(|statx| {
+            // The layout of `statx` is the same on all targets
+            let bytes = unsafe {
+                core::slice::from_raw_parts(
+                    (&raw const statx).cast::<u8>(),
+                    size_of::<libc::statx>(),
+                )
+            };
+            qemu.write_mem(buf, bytes)
+                .map_err(|_| -i64::from(libc::EFAULT))
+        });
+        Some(syscall_result(res.err().unwrap_or(0)))
+    }
+
+    /// Writes the `stat` of the memfd for `path` to the guest's `stat` buffer at `buf`.
+    #[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64"))]
+    fn stat(&self, qemu: Qemu, dirfd: i32, path: GuestAddr, buf: GuestAddr) -> Option<GuestAddr> {
+        let memfd = self.memfd(qemu, dirfd, path)?;
+        let res = memfd_statx(memfd, libc::STATX_BASIC_STATS).and_then(|statx| {
+            qemu.write_mem(buf, &guest_stat(&statx))
+                .map_err(|_| -i64::from(libc::EFAULT))
+        });
+        Some(syscall_result(res.err().unwrap_or(0)))
+    }
+
+    /// All overlaid files can be accessed in any mode.
+    fn access(&self, qemu: Qemu, dirfd: i32, path: GuestAddr) -> Option<GuestAddr> {
+        self.memfd(qemu, dirfd, path).map(|_| 0)
+    }
+
+    /// Serves the syscalls on overlaid paths, or `None` to let the syscall go to the host.
+    #[expect(clippy::too_many_arguments)]
+    fn syscall(
+        &mut self,
+        qemu: Qemu,
+        sys_num: i64,
+        a0: GuestAddr,
+        a1: GuestAddr,
+        a2: GuestAddr,
+        a3: GuestAddr,
+        a4: GuestAddr,
+    ) -> Option<GuestAddr> {
+        match sys_num {
+            SYS_close => {
+                self.forget_fd(a0 as i32);
+                None
+            }
+            SYS_openat => self.open(qemu, a0 as i32, a1, a2),
+            SYS_statx => self.statx(qemu, a0 as i32, a1, a3, a4),
+            SYS_faccessat | SYS_faccessat2 => self.access(qemu, a0 as i32, a1),
+            #[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64"))]
+            SYS_newfstatat => self.stat(qemu, a0 as i32, a1, a2),
+            #[cfg(cpu_target = "x86_64")]
+            SYS_stat | SYS_lstat => self.stat(qemu, AT_FDCWD, a0, a1),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_open => self.open(qemu, AT_FDCWD, a0, a1),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_creat => self.open(qemu, AT_FDCWD, a0, 1 | O_TRUNC),
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_access => self.access(qemu, AT_FDCWD, a0),
+            _ => None,
+        }
+    }
+
+    /// Tracks the duplicates of the fds handed out to the guest, after the syscall.
+    #[cfg_attr(cpu_target = "riscv32", allow(unused_variables))]
+    fn post_syscall(&mut self, result: GuestAddr, sys_num: i64, a0: GuestAddr, a1: GuestAddr) {
+        let fd = result as i32;
+        if fd < 0 {
+            return;
+        }
+        let duplicate = match sys_num {
+            SYS_dup | SYS_dup3 => true,
+            #[cfg(any(
+                cpu_target = "x86_64",
+                cpu_target = "i386",
+                cpu_target = "arm",
+                cpu_target = "mips",
+                cpu_target = "ppc"
+            ))]
+            SYS_dup2 => true,
+            #[cfg(not(cpu_target = "riscv32"))]
+            SYS_fcntl => a1 == F_DUPFD || a1 == F_DUPFD_CLOEXEC,
+            _ => false,
+        };
+        if !duplicate {
+            return;
+        }
+        if self.open_fds.contains(&(a0 as i32)) {
+            self.open_fds.insert(fd);
+        } else {
+            // `dup2` and `dup3` closed what was there before
+            self.open_fds.remove(&fd);
+        }
+    }
+}
+
+/// The `statx` of `memfd`, or the negated `errno`
+fn memfd_statx(memfd: &File, mask: u32) -> Result<libc::statx, i64> {
+    let mut statx: libc::statx = unsafe { core::mem::zeroed() };
+    let res = unsafe {
+        libc::statx(
+            memfd.as_raw_fd(),
+            c"".as_ptr(),
+            libc::AT_EMPTY_PATH,
+            mask,
+            &raw mut statx,
+        )
+    };
+    if res == 0 {
+        Ok(statx)
+    } else {
+        Err(-i64::from(
+            io::Error::last_os_error()
+                .raw_os_error()
+                .unwrap_or(libc::EIO),
+        ))
+    }
+}
+
+/// The kernel's encoding of a device number in `stat`
+#[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64"))]
+fn encode_dev(major: u32, minor: u32) -> u64 {
+    u64::from(minor & 0xff) | (u64::from(major) << 8) | (u64::from(minor & !0xff) << 12)
+}
+
+/// The guest's `struct stat` for `statx`, built field by field, as the host layout may differ.
+/// All these targets are little endian.
+#[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64"))]
+fn guest_stat(statx: &libc::statx) -> Vec<u8> {
+    let dev = encode_dev(statx.stx_dev_major, statx.stx_dev_minor);
+    let rdev = encode_dev(statx.stx_rdev_major, statx.stx_rdev_minor);
+    let mode = u32::from(statx.stx_mode);
+    let mut bytes = Vec::with_capacity(144);
+    #[cfg(cpu_target = "x86_64")]
+    {
+        for field in [dev, statx.stx_ino, statx.stx_nlink.into()] {
+            bytes.extend(field.to_le_bytes());
+        }
+        for field in [mode, statx.stx_uid, statx.stx_gid, 0] {
+            bytes.extend(field.to_le_bytes());
+        }
+        for field in [
+            rdev,
+            statx.stx_size,
+            statx.stx_blksize.into(),
+            statx.stx_blocks,
+        ] {
+            bytes.extend(field.to_le_bytes());
+        }
+    }
+    // The generic layout of `asm-generic/stat.h`
+    #[cfg(any(cpu_target = "aarch64", cpu_target = "riscv64"))]
+    {
+        for field in [dev, statx.stx_ino] {
+            bytes.extend(field.to_le_bytes());
+        }
+        for field in [mode, statx.stx_nlink, statx.stx_uid, statx.stx_gid] {
+            bytes.extend(field.to_le_bytes());
+        }
+        for field in [rdev, 0, statx.stx_size] {
+            bytes.extend(field.to_le_bytes());
+        }
+        for field in [statx.stx_blksize, 0] {
+            bytes.extend(field.to_le_bytes());
+        }
+        bytes.extend(statx.stx_blocks.to_le_bytes());
+    }
+    for time in [statx.stx_atime, statx.stx_mtime, statx.stx_ctime] {
+        bytes.extend(time.tv_sec.to_le_bytes());
+        bytes.extend(u64::from(time.tv_nsec).to_le_bytes());
+    }
+    // The unused fields at the end
+    #[cfg(cpu_target = "x86_64")]
+    bytes.extend([0; 24]);
+    #[cfg(any(cpu_target = "aarch64", cpu_target = "riscv64"))]
+    bytes.extend([0; 8]);
+    bytes
+}
+
+/// The return value of a syscall, from a host result (`-1` and `errno` on errors)
+/// or an already negated `errno`
+fn syscall_result(res: i64) -> GuestAddr {
+    let res = if res == -1 {
+        -i64::from(
+            io::Error::last_os_error()
+                .raw_os_error()
+                .unwrap_or(libc::EIO),
+        )
+    } else {
+        res
+    };
+    res as GuestAddr
+}
+
+impl<I, S> EmulatorModule<I, S> for VfsModule
+where
+    I: VfsInput + Unpin,
+    S: Unpin,
+{
+    fn post_qemu_init<ET>(&mut self, _qemu: Qemu, emulator_modules: &mut EmulatorModules<ET, I, S>)
+    where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        emulator_modules.pre_syscalls(Hook::Function(vfs_syscall_hook::<ET, I, S>));
+        emulator_modules.post_syscalls(Hook::Function(vfs_trace_syscall::<ET, I, S>));
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        input: &I,
+    ) where
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        // Left over if the last run did not end with `post_exec`, for example on a timeout
+        self.close_open_fds();
+        self.reset(input)
+            .expect("Cannot reset the files of the VFS");
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _qemu: Qemu,
+        _emulator_modules: &mut EmulatorModules<ET, I, S>,
+        _state: &mut S,
+        _input: &I,
+        _observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<I, S>,
+        ET: EmulatorModuleTuple<I, S>,
+    {
+        self.close_open_fds();
+    }
+}
+
+impl HasAddressFilter for VfsModule {
+    type ModuleAddressFilter = NopAddressFilter;
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+#[expect(clippy::too_many_arguments)]
+pub fn vfs_syscall_hook<ET, I, S>(
+    qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    a3: GuestAddr,
+    a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: VfsInput + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<VfsModule>().unwrap();
+    SyscallHookResult::new(h.syscall(qemu, i64::from(sys_num), a0, a1, a2, a3, a4))
+}
+
+#[expect(clippy::too_many_arguments)]
+pub fn vfs_trace_syscall<ET, I, S>(
+    _qemu: Qemu,
+    emulator_modules: &mut EmulatorModules<ET, I, S>,
+    _state: Option<&mut S>,
+    result: GuestAddr,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    _a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> GuestAddr
+where
+    ET: EmulatorModuleTuple<I, S>,
+    I: VfsInput + Unpin,
+    S: Unpin,
+{
+    let h = emulator_modules.get_mut::<VfsModule>().unwrap();
+    h.post_syscall(result, i64::from(sys_num), a0, a1);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::{fd::AsRawFd, unix::fs::FileExt};
+
+    use libafl::inputs::BytesInput;
+
+    use super::{SYS_dup, VfsModule};
+
+    #[test]
+    fn test_vfs_reset() {
+        let mut vfs = VfsModule::new()
+            .bytes_file("/fixed", b"fixed".to_vec())
+            .unwrap()
+            .input_file("/input")
+            .unwrap()
+            .capture_file("/output")
+            .unwrap();
+        assert!(vfs.content("/missing").is_err());
+
+        for input in [&b"first"[..], b"second"] {
+            vfs.reset(&BytesInput::new(input.to_vec())).unwrap();
+            assert_eq!(vfs.content("/fixed").unwrap(), b"fixed");
+            assert_eq!(vfs.content("/input").unwrap(), input);
+            // The writes of the guest go to the memfd, and are gone at the next run
+            assert_eq!(vfs.content("/output").unwrap(), b"");
+            vfs.files["/output"]
+                .memfd
+                .write_all_at(b"written", 0)
+                .unwrap();
+            assert_eq!(vfs.content("/output").unwrap(), b"written");
+        }
+    }
+
+    #[test]
+    fn test_vfs_open_fds() {
+        let mut vfs = VfsModule::new().capture_file("/output").unwrap();
+        let memfd = vfs.files["/output"].memfd.as_raw_fd();
+
+        // Read-write, each open gets its own description
+        let fd = vfs.open_memfd(memfd, 2) as i32;
+        assert!(fd >= 0);
+        let dup = unsafe { libc::dup(fd) };
+        vfs.post_syscall(dup as _, SYS_dup, fd as _, 0);
+        assert!(vfs.open_fds.contains(&fd) && vfs.open_fds.contains(&dup));
+
+        // Closed by the guest, so not closed again
+        vfs.forget_fd(dup);
+        unsafe { libc::close(dup) };
+        assert!(!vfs.open_fds.contains(&dup));
+
+        vfs.close_open_fds();
+        assert!(vfs.open_fds.is_empty());
+        assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+    }
+
+    #[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64"))]
+    #[test]
+    fn test_guest_stat() {
+        let vfs = VfsModule::new()
+            .bytes_file("/fixed", b"fixed".to_vec())
+            .unwrap();
+        let statx =
+            super::memfd_statx(&vfs.files["/fixed"].memfd, libc::STATX_BASIC_STATS).unwrap();
+        let stat = super::guest_stat(&statx);
+        #[cfg(cpu_target = "x86_64")]
+        assert_eq!(stat.len(), 144);
+        #[cfg(any(cpu_target = "aarch64", cpu_target = "riscv64"))]
+        assert_eq!(stat.len(), 128);
+        // `st_size` is at the same offset in both layouts
+        assert_eq!(stat[48..56], 5_u64.to_le_bytes());
+    }
+}
@@ -0,0 +1,152 @@
+//! The recent loads of the target, for the modules following values through memory
+//!
+//! It is not a module by itself, but instead used as helper by the modules keeping a shadow of
+//! the guest memory. They cannot instrument the TCG ops computing on registers, so a store of a
+//! value loaded earlier in the same block is seen as a copy of the loaded bytes, and a value used
+//! later is matched with the recent loads holding it.
+
+use libafl_qemu_sys::GuestAddr;
+
+use crate::Qemu;
+
+/// The number of executed blocks during which a load can be matched with a use.
+pub const LOAD_WINDOW: u64 = 2;
+
+/// The mask of the `size` first bytes of a value.
+#[must_use]
+pub fn value_mask(size: usize) -> u64 {
+    if size >= 8 {
+        u64::MAX
+    } else {
+        (1 << (size * 8)) - 1
+    }
+}
+
+/// Reads the value of a load of `size` bytes at `addr`, for loads up to 8 bytes.
+/// Guest values are considered little-endian.
+#[must_use]
+pub fn load_value(qemu: Qemu, addr: GuestAddr, size: usize) -> Option<u64> {
+    if size > 8 {
+        return None;
+    }
+    let mut buf = [0u8; 8];
+    qemu.read_mem(addr, &mut buf[..size])
+        .ok()
+        .map(|()| u64::from_le_bytes(buf))
+}
+
+#[derive(Debug, Clone)]
+pub struct RecentLoad<T> {
+    pub addr: GuestAddr,
+    pub size: usize,
+    pub value: Option<u64>,
+    /// The shadow of the loaded bytes, as tracked by the module.
+    pub shadow: T,
+    block: u64,
+    consumed: bool,
+}
+
+impl<T> RecentLoad<T> {
+    /// Whether the `size` first bytes of the load hold `value`.
+    #[must_use]
+    pub fn matches(&self, value: u64, size: usize) -> bool {
+        let mask = value_mask(size);
+        self.value
+            .is_some_and(|loaded| loaded & mask == value & mask)
+    }
+}
+
+/// The loads of the last [`LOAD_WINDOW`] executed blocks.
+#[derive(Debug)]
+pub struct RecentLoads<T> {
+    loads: Vec<RecentLoad<T>>,
+    blocks: u64,
+}
+
+impl<T> Default for RecentLoads<T> {
+    fn default() -> Self {
+        Self {
+            loads: Vec::new(),
+            blocks: 0,
+        }
+    }
+}
+
+impl<T> RecentLoads<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, addr: GuestAddr, size: usize, value: Option<u64>, shadow: T) {
+        self.loads.push(RecentLoad {
+            addr,
+            size,
+            value,
+            shadow,
+            block: self.blocks,
+            consumed: false,
+        });
+    }
+
+    /// The source of a store of `size` bytes: the first load of the same size in the current
+    /// block, not stored yet. `None` if the store writes a new value.
+    pub fn store(&mut self, size: usize) -> Option<GuestAddr> {
+        let blocks = self.blocks;
+        let load = self
+            .loads
+            .iter_mut()
+            .find(|load| !load.consumed && load.block == blocks && load.size == size)?;
+        load.consumed = true;
+        Some(load.addr)
+    }
+
+    /// A new block is executed, the loads out of the window are forgotten.
+    pub fn block(&mut self) {
+        self.blocks += 1;
+
+        let blocks = self.blocks;
+        self.loads.retain(|load| blocks - load.block <= LOAD_WINDOW);
+    }
+
+    /// The recent loads, the most recent first.
+    pub fn recent(&self) -> impl Iterator<Item = &RecentLoad<T>> {
+        self.loads.iter().rev()
+    }
+
+    pub fn clear(&mut self) {
+        self.loads.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecentLoads, LOAD_WINDOW};
+
+    #[test]
+    fn test_recent_loads() {
+        let mut loads = RecentLoads::new();
+        loads.push(0x1000, 4, Some(0x4141_4141), 'a');
+        loads.push(0x2000, 4, Some(0x4242), 'b');
+        loads.push(0x3000, 8, None, 'c');
+
+        // stores match the loads of their block, once
+        assert_eq!(loads.store(4), Some(0x1000));
+        assert_eq!(loads.store(4), Some(0x2000));
+        assert_eq!(loads.store(4), None);
+        assert_eq!(loads.store(2), None);
+
+        let found = loads.recent().find(|load| load.matches(0x4242, 2)).unwrap();
+        assert_eq!(found.shadow, 'b');
+        assert!(loads.recent().all(|load| !load.matches(0x4343_4242, 4)));
+
+        loads.block();
+        assert_eq!(loads.store(8), None);
+        assert_eq!(loads.recent().count(), 3);
+
+        for _ in 0..LOAD_WINDOW {
+            loads.block();
+        }
+        assert_eq!(loads.recent().count(), 0);
+    }
+}
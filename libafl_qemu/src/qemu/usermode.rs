@@ -405,6 +405,17 @@ impl Qemu {
         libafl_qemu_sys::libafl_set_in_target_signal_ctx();
         libc::raise(signal.into());
     }
+
+    /// Queues the guest signal `signal` to the current guest thread, as if sent by the kernel.
+    /// It is delivered once the current syscall returns.
+    ///
+    /// # Safety
+    ///
+    /// Should only be called from a hook running in a guest thread.
+    /// Blocked and ignored signals get their default action.
+    pub unsafe fn queue_target_signal(&self, signal: i32) {
+        libafl_qemu_sys::force_sig(signal);
+    }
 }
 
 #[cfg(feature = "python")]
@@ -0,0 +1,159 @@
+//! Minimal SVG line charts, so that reports need no scripts or external resources
+
+use core::fmt::Write;
+use std::time::Duration;
+
+use libafl_bolts::format_duration_hms;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 240.0;
+const PADDING: f64 = 48.0;
+
+/// The colors of the series, in order
+const COLORS: [&str; 8] = [
+    "#2a6fdb", "#d9480f", "#2b8a3e", "#862e9c", "#e67700", "#0b7285", "#c2255c", "#5c940d",
+];
+
+/// A named series of points, with the time on the x axis
+#[derive(Debug, Clone)]
+pub struct Series {
+    /// The name in the legend
+    pub name: String,
+    /// The points, sorted by time
+    pub points: Vec<(Duration, f64)>,
+}
+
+impl Series {
+    /// Creates a new [`Series`]
+    #[must_use]
+    pub fn new(name: &str, points: Vec<(Duration, f64)>) -> Self {
+        Self {
+            name: name.to_string(),
+            points,
+        }
+    }
+}
+
+/// Escapes `text` for HTML and SVG
+#[must_use]
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Formats large values compactly, like `1.20M`
+#[must_use]
+pub fn pretty_number(value: f64) -> String {
+    match value {
+        value if value >= 1_000_000_000.0 => format!("{:.2}G", value / 1_000_000_000.0),
+        value if value >= 1_000_000.0 => format!("{:.2}M", value / 1_000_000.0),
+        value if value >= 1_000.0 => format!("{:.2}k", value / 1_000.0),
+        value if value.fract() == 0.0 => format!("{value}"),
+        value => format!("{value:.2}"),
+    }
+}
+
+/// Renders the `series` as an SVG line chart, or `None` if there is nothing to show
+#[must_use]
+pub fn line_chart(title: &str, series: &[Series]) -> Option<String> {
+    let points = || series.iter().flat_map(|series| series.points.iter());
+    if points().count() < 2 {
+        return None;
+    }
+    let max_time = points()
+        .map(|(time, _)| time.as_secs_f64())
+        .fold(0.0, f64::max)
+        .max(1.0);
+    let max_value = points()
+        .map(|(_, value)| *value)
+        .fold(0.0, f64::max)
+        .max(1.0);
+
+    let x = |time: &Duration| PADDING + time.as_secs_f64() / max_time * (WIDTH - 2.0 * PADDING);
+    let y = |value: f64| HEIGHT - PADDING - value / max_value * (HEIGHT - 2.0 * PADDING);
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="11">"#
+    )
+    .unwrap();
+    write!(
+        svg,
+        r##"<rect width="100%" height="100%" fill="#fff" stroke="#ddd"/><text x="{PADDING}" y="18" font-size="13">{}</text>"##,
+        escape(title)
+    )
+    .unwrap();
+    // Axes, with the maximum values
+    write!(
+        svg,
+        r##"<path d="M{PADDING},{top}V{bottom}H{right}" fill="none" stroke="#888"/>"##,
+        top = PADDING,
+        bottom = HEIGHT - PADDING,
+        right = WIDTH - PADDING
+    )
+    .unwrap();
+    write!(
+        svg,
+        r#"<text x="4" y="{}">{}</text><text x="{}" y="{}" text-anchor="end">{}</text><text x="{PADDING}" y="{}">0s</text>"#,
+        PADDING + 4.0,
+        pretty_number(max_value),
+        WIDTH - PADDING,
+        HEIGHT - PADDING + 16.0,
+        format_duration_hms(&Duration::from_secs_f64(max_time)),
+        HEIGHT - PADDING + 16.0,
+    )
+    .unwrap();
+
+    for (idx, series) in series.iter().enumerate() {
+        let color = COLORS[idx % COLORS.len()];
+        let mut path = String::new();
+        for (point_idx, (time, value)) in series.points.iter().enumerate() {
+            let command = if point_idx == 0 { 'M' } else { 'L' };
+            write!(path, "{command}{:.1},{:.1}", x(time), y(*value)).unwrap();
+        }
+        write!(
+            svg,
+            r#"<path d="{path}" fill="none" stroke="{color}" stroke-width="1.5"/>"#
+        )
+        .unwrap();
+        if !series.name.is_empty() {
+            // The legend, in a row below the title
+            #[expect(clippy::cast_precision_loss)]
+            let legend_x = PADDING + 120.0 * idx as f64;
+            write!(
+                svg,
+                r#"<text x="{legend_x}" y="34" fill="{color}">{}</text>"#,
+                escape(&series.name)
+            )
+            .unwrap();
+        }
+    }
+    svg.push_str("</svg>");
+    Some(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::chart::{escape, line_chart, pretty_number, Series};
+
+    #[test]
+    fn test_line_chart() {
+        let series = Series::new(
+            "a<b",
+            vec![(Duration::ZERO, 1.0), (Duration::from_secs(10), 5.0)],
+        );
+        let svg = line_chart("edges", &[series]).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("a&lt;b"));
+        assert!(line_chart("empty", &[]).is_none());
+
+        assert_eq!(escape("\"&\""), "&quot;&amp;&quot;");
+        assert_eq!(pretty_number(1_500.0), "1.50k");
+        assert_eq!(pretty_number(12.0), "12");
+    }
+}
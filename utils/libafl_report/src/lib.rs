@@ -0,0 +1,526 @@
+//! Generate a report of a finished (or running) fuzzing campaign.
+//!
+//! A [`Campaign`] collects the output of a fuzzer:
+//! - the `plot_data` of the `AflStatsStage`, one file per client
+//! - the JSON lines of the `OnDiskJsonMonitor` or `OnDiskJsonAggregateMonitor`
+//! - the corpus and objectives directories of an `OnDiskCorpus`, including their metadata
+//!
+//! It then renders a self-contained HTML or Markdown report, with edges, executions and the corpus
+//! over time, the objectives grouped into buckets of duplicates, and the throughput of each client.
+//!
+//! ```rust,no_run
+//! use libafl_report::Campaign;
+//!
+//! let mut campaign = Campaign::new();
+//! campaign.add_plot_data("client 0", "./out/plot_data").unwrap();
+//! campaign.add_objectives_dir("./crashes").unwrap();
+//! std::fs::write("report.html", campaign.to_html()).unwrap();
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use libafl_bolts::{hash_std, Error};
+use serde_json::Value;
+
+pub mod chart;
+pub mod render;
+
+/// The stats of a client, or of all clients, at a point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Sample {
+    /// Time since the start of the campaign
+    pub time: Duration,
+    /// The corpus size
+    pub corpus: u64,
+    /// The amount of objectives
+    pub objectives: u64,
+    /// The total executions
+    pub executions: u64,
+    /// The executions per second
+    pub execs_per_sec: f64,
+    /// The hit edges, if known
+    pub edges_hit: Option<u64>,
+    /// The total edges, if known
+    pub edges_total: Option<u64>,
+}
+
+/// The samples of a single client
+#[derive(Debug, Clone, Default)]
+pub struct ClientSeries {
+    /// The name of this client in the report
+    pub name: String,
+    /// The samples, oldest first
+    pub samples: Vec<Sample>,
+}
+
+impl ClientSeries {
+    /// The average executions per second of this client, over its whole run time
+    #[must_use]
+    #[expect(clippy::cast_precision_loss)]
+    pub fn average_execs_per_sec(&self) -> f64 {
+        match self.samples.last() {
+            Some(last) if !last.time.is_zero() => last.executions as f64 / last.time.as_secs_f64(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// A file in the corpus or in the objectives
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    /// The file name
+    pub name: String,
+    /// The size of the file
+    pub size: u64,
+    /// When the file was written, usually when the testcase was found
+    pub modified: Option<SystemTime>,
+}
+
+/// An objective, with the bucket of duplicates it belongs to
+#[derive(Debug, Clone)]
+pub struct Objective {
+    /// The file of this objective
+    pub file: FileEntry,
+    /// The key of the bucket. Objectives with the same key are considered duplicates.
+    pub bucket: String,
+}
+
+/// A bucket of duplicate objectives
+#[derive(Debug, Clone)]
+pub struct Bucket<'a> {
+    /// The key of this bucket
+    pub key: &'a str,
+    /// The objectives in this bucket, oldest first
+    pub objectives: Vec<&'a Objective>,
+}
+
+/// The collected output of a fuzzing campaign
+#[derive(Debug, Clone, Default)]
+pub struct Campaign {
+    /// The start of the campaign. If `None`, the oldest file in the corpus is the start.
+    pub start: Option<SystemTime>,
+    /// The stats of all clients together, from the monitor logs
+    pub global: Vec<Sample>,
+    /// The stats of each client
+    pub clients: Vec<ClientSeries>,
+    /// The corpus
+    pub corpus: Vec<FileEntry>,
+    /// The objectives
+    pub objectives: Vec<Objective>,
+    /// The metadata field objectives are grouped by, see [`Campaign::with_bucket_key`]
+    bucket_key: Option<String>,
+}
+
+impl Campaign {
+    /// Creates an empty [`Campaign`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Groups the objectives by the value of the field `bucket_key` in their metadata,
+    /// for example a stack hash stored by a custom feedback.
+    /// Objectives without this field, and all objectives by default, are grouped by their content.
+    #[must_use]
+    pub fn with_bucket_key(mut self, bucket_key: &str) -> Self {
+        self.bucket_key = Some(bucket_key.to_string());
+        self
+    }
+
+    /// Adds the `plot_data` written by the `AflStatsStage` of one client
+    pub fn add_plot_data<P>(&mut self, name: &str, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = BufReader::new(File::open(path)?);
+        let mut samples = vec![];
+        for line in file.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            samples.push(parse_plot_line(line)?);
+        }
+        self.clients.push(ClientSeries {
+            name: name.to_string(),
+            samples,
+        });
+        Ok(())
+    }
+
+    /// Adds the JSON lines written by the `OnDiskJsonMonitor` or the `OnDiskJsonAggregateMonitor`.
+    /// The per-client stats of the `OnDiskJsonMonitor` are added as clients.
+    pub fn add_json_log<P>(&mut self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = BufReader::new(File::open(path)?);
+        let mut clients: Vec<ClientSeries> = vec![];
+        for line in file.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(&line)
+                .map_err(|err| Error::serialize(format!("Invalid monitor log line: {err}")))?;
+            let time = json_duration(&value["run_time"]);
+            let mut sample = Sample {
+                time,
+                corpus: value["corpus"].as_u64().unwrap_or_default(),
+                objectives: value["objectives"].as_u64().unwrap_or_default(),
+                executions: value["executions"].as_u64().unwrap_or_default(),
+                execs_per_sec: value["exec_sec"].as_f64().unwrap_or_default(),
+                edges_hit: None,
+                edges_total: None,
+            };
+            // The aggregated user stats of the `OnDiskJsonAggregateMonitor`
+            if let Some((hit, total)) = json_ratio(&value["edges"]) {
+                sample.edges_hit = Some(hit);
+                sample.edges_total = Some(total);
+            }
+
+            // The client stats of the `OnDiskJsonMonitor`
+            if let Some(client_stats) = value["client_stats"].as_array() {
+                if clients.len() < client_stats.len() {
+                    clients.resize_with(client_stats.len(), ClientSeries::default);
+                }
+                for (id, (client, series)) in client_stats.iter().zip(&mut clients).enumerate() {
+                    if !client["enabled"].as_bool().unwrap_or(true) {
+                        continue;
+                    }
+                    series.name = format!("client #{id}");
+                    let client_sample = client_sample(client, time);
+                    if client_sample.edges_hit > sample.edges_hit {
+                        sample.edges_hit = client_sample.edges_hit;
+                        sample.edges_total = client_sample.edges_total;
+                    }
+                    series.samples.push(client_sample);
+                }
+            }
+
+            self.global.push(sample);
+        }
+        self.clients.extend(
+            clients
+                .into_iter()
+                .filter(|client| !client.samples.is_empty()),
+        );
+        Ok(())
+    }
+
+    /// Adds the testcases in the corpus directory `dir`
+    pub fn add_corpus_dir<P>(&mut self, dir: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.corpus.extend(read_testcases(dir.as_ref())?);
+        Ok(())
+    }
+
+    /// Adds the objectives in `dir`, and sorts them into buckets
+    pub fn add_objectives_dir<P>(&mut self, dir: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        for file in read_testcases(dir)? {
+            let bucket = self
+                .bucket_key
+                .as_ref()
+                .and_then(|key| metadata_bucket(dir, &file.name, key))
+                .map_or_else(
+                    || {
+                        let content = fs::read(dir.join(&file.name)).unwrap_or_default();
+                        format!("content:{:016x}", hash_std(&content))
+                    },
+                    |value| format!("metadata:{value}"),
+                );
+            self.objectives.push(Objective { file, bucket });
+        }
+        Ok(())
+    }
+
+    /// The start of the campaign, see [`Campaign::start`]
+    #[must_use]
+    pub fn start_time(&self) -> Option<SystemTime> {
+        self.start.or_else(|| {
+            self.corpus
+                .iter()
+                .chain(self.objectives.iter().map(|objective| &objective.file))
+                .filter_map(|file| file.modified)
+                .min()
+        })
+    }
+
+    /// The time since the start of the campaign, when `file` was written
+    #[must_use]
+    pub fn relative_time(&self, file: &FileEntry) -> Option<Duration> {
+        let start = self.start_time()?;
+        Some(file.modified?.duration_since(start).unwrap_or_default())
+    }
+
+    /// The global stats. If no monitor log was added, they are merged from the clients.
+    #[must_use]
+    pub fn global_samples(&self) -> Vec<Sample> {
+        if self.global.is_empty() {
+            merge_clients(&self.clients)
+        } else {
+            self.global.clone()
+        }
+    }
+
+    /// The objectives, grouped into buckets of duplicates, by first appearance
+    #[must_use]
+    pub fn buckets(&self) -> Vec<Bucket<'_>> {
+        let mut objectives: Vec<&Objective> = self.objectives.iter().collect();
+        objectives.sort_by_key(|objective| objective.file.modified);
+
+        let mut buckets: Vec<Bucket<'_>> = vec![];
+        let mut indices: HashMap<&str, usize> = HashMap::new();
+        for objective in objectives {
+            if let Some(&idx) = indices.get(objective.bucket.as_str()) {
+                buckets[idx].objectives.push(objective);
+            } else {
+                indices.insert(&objective.bucket, buckets.len());
+                buckets.push(Bucket {
+                    key: &objective.bucket,
+                    objectives: vec![objective],
+                });
+            }
+        }
+        buckets
+    }
+
+    /// Renders the report as a self-contained HTML page
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        render::html(self)
+    }
+
+    /// Renders the report as Markdown
+    #[must_use]
+    pub fn to_markdown(&self) -> String {
+        render::markdown(self)
+    }
+}
+
+/// Parses a line of AFL++'s `plot_data`, as written by the `AflStatsStage`:
+/// `relative_time, cycles_done, cur_item, corpus_count, pending_total, pending_favs, total_edges,
+/// saved_crashes, saved_hangs, max_depth, execs_per_sec, execs_done, edges_found`
+fn parse_plot_line(line: &str) -> Result<Sample, Error> {
+    let fields = line
+        .split(',')
+        .map(|field| field.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::illegal_argument(format!("Invalid plot_data line {line}: {err}")))?;
+    if fields.len() < 13 {
+        return Err(Error::illegal_argument(format!(
+            "Expected 13 fields in plot_data line {line}"
+        )));
+    }
+    #[expect(clippy::cast_sign_loss)]
+    let field = |idx: usize| fields[idx] as u64;
+    Ok(Sample {
+        time: Duration::from_secs(field(0)),
+        corpus: field(3),
+        objectives: field(7) + field(8),
+        executions: field(11),
+        execs_per_sec: fields[10],
+        edges_hit: Some(field(12)),
+        edges_total: Some(field(6)),
+    })
+}
+
+/// A serialized [`Duration`], either in seconds, or as written by serde
+fn json_duration(value: &Value) -> Duration {
+    match value {
+        Value::Object(duration) => Duration::new(
+            duration
+                .get("secs")
+                .and_then(Value::as_u64)
+                .unwrap_or_default(),
+            duration
+                .get("nanos")
+                .and_then(Value::as_u64)
+                .unwrap_or_default() as u32,
+        ),
+        value => Duration::from_secs(value.as_u64().unwrap_or_default()),
+    }
+}
+
+/// A serialized `UserStatsValue::Ratio`
+fn json_ratio(value: &Value) -> Option<(u64, u64)> {
+    let ratio = value.get("Ratio")?.as_array()?;
+    Some((ratio.first()?.as_u64()?, ratio.get(1)?.as_u64()?))
+}
+
+/// A sample from a serialized `ClientStats`
+#[expect(clippy::cast_precision_loss)]
+fn client_sample(client: &Value, time: Duration) -> Sample {
+    let start = json_duration(&client["start_time"]);
+    let executions = client["executions"].as_u64().unwrap_or_default();
+    // The client stats contain absolute times
+    let run_time = client["last_window_time"]
+        .as_object()
+        .map(|_| json_duration(&client["last_window_time"]).saturating_sub(start))
+        .unwrap_or_default();
+    let execs_per_sec = if run_time.is_zero() {
+        0.0
+    } else {
+        executions as f64 / run_time.as_secs_f64()
+    };
+    let edges = json_ratio(&client["user_stats"]["edges"]["value"]);
+    Sample {
+        time,
+        corpus: client["corpus_size"].as_u64().unwrap_or_default(),
+        objectives: client["objective_size"].as_u64().unwrap_or_default(),
+        executions,
+        execs_per_sec,
+        edges_hit: edges.map(|(hit, _)| hit),
+        edges_total: edges.map(|(_, total)| total),
+    }
+}
+
+/// The testcases in `dir`, skipping the hidden metadata and lock files of the `OnDiskCorpus`
+fn read_testcases(dir: &Path) -> Result<Vec<FileEntry>, Error> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let metadata = entry.metadata()?;
+        if name.starts_with('.') || !metadata.is_file() {
+            continue;
+        }
+        files.push(FileEntry {
+            name,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    files.sort_by_key(|file| file.modified);
+    Ok(files)
+}
+
+/// The value of the first field named `key` in the JSON metadata of the testcase `name`
+fn metadata_bucket(dir: &Path, name: &str, key: &str) -> Option<String> {
+    let metadata = fs::read(dir.join(format!(".{name}.metadata"))).ok()?;
+    let metadata: Value = serde_json::from_slice(&metadata).ok()?;
+    find_key(&metadata, key).map(|value| match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    })
+}
+
+/// Searches `value` recursively for a field named `key`
+fn find_key<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => map
+            .get(key)
+            .or_else(|| map.values().find_map(|value| find_key(value, key))),
+        Value::Array(values) => values.iter().find_map(|value| find_key(value, key)),
+        _ => None,
+    }
+}
+
+/// Merges the samples of all clients into global samples.
+/// Executions, their rate and objectives add up, the corpus and edges are shared between clients.
+/// The samples of each client are ordered by time.
+fn merge_clients(clients: &[ClientSeries]) -> Vec<Sample> {
+    let mut times: Vec<Duration> = clients
+        .iter()
+        .flat_map(|client| client.samples.iter().map(|sample| sample.time))
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+
+    // The number of samples of each client up to the current time
+    let mut cursors = vec![0; clients.len()];
+    times
+        .into_iter()
+        .map(|time| {
+            let mut merged = Sample {
+                time,
+                ..Sample::default()
+            };
+            for (client, cursor) in clients.iter().zip(&mut cursors) {
+                while client
+                    .samples
+                    .get(*cursor)
+                    .is_some_and(|sample| sample.time <= time)
+                {
+                    *cursor += 1;
+                }
+                let Some(sample) = cursor.checked_sub(1).map(|last| &client.samples[last]) else {
+                    continue;
+                };
+                merged.corpus = merged.corpus.max(sample.corpus);
+                merged.objectives += sample.objectives;
+                merged.executions += sample.executions;
+                merged.execs_per_sec += sample.execs_per_sec;
+                if sample.edges_hit > merged.edges_hit {
+                    merged.edges_hit = sample.edges_hit;
+                    merged.edges_total = sample.edges_total;
+                }
+            }
+            merged
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{merge_clients, parse_plot_line, ClientSeries, Sample};
+
+    #[test]
+    fn test_plot_data() {
+        let sample = parse_plot_line("5, 1, 2, 30, 4, 1, 65536, 2, 1, 3, 1000, 5000, 420").unwrap();
+        assert_eq!(sample.time, Duration::from_secs(5));
+        assert_eq!(sample.corpus, 30);
+        assert_eq!(sample.objectives, 3);
+        assert_eq!(sample.executions, 5000);
+        assert_eq!(sample.edges_hit, Some(420));
+        assert_eq!(sample.edges_total, Some(65536));
+        assert!(parse_plot_line("5, 1, 2").is_err());
+    }
+
+    #[test]
+    fn test_merge_clients() {
+        let sample = |secs, executions| Sample {
+            time: Duration::from_secs(secs),
+            executions,
+            ..Sample::default()
+        };
+        let clients = [
+            ClientSeries {
+                name: "a".into(),
+                samples: vec![sample(1, 10), sample(3, 30)],
+            },
+            ClientSeries {
+                name: "b".into(),
+                samples: vec![sample(2, 5)],
+            },
+        ];
+        let merged = merge_clients(&clients);
+        let executions: Vec<u64> = merged.iter().map(|sample| sample.executions).collect();
+        assert_eq!(executions, [10, 15, 35]);
+    }
+
+    #[test]
+    fn test_json_ratio() {
+        assert_eq!(super::json_ratio(&json!({"Ratio": [3, 10]})), Some((3, 10)));
+        assert_eq!(super::json_ratio(&json!({"Number": 3})), None);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{fs, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use libafl_report::Campaign;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[command(
+    name = "libafl_report",
+    about,
+    long_about = "Generates a self-contained HTML or Markdown report of a fuzzing campaign"
+)]
+pub struct Opt {
+    #[arg(
+        short,
+        long,
+        help = "The plot_data files of the AflStatsStage, one per client"
+    )]
+    pub plot_data: Vec<PathBuf>,
+    #[arg(
+        short,
+        long,
+        help = "The logs of the OnDiskJsonMonitor or OnDiskJsonAggregateMonitor"
+    )]
+    pub json_log: Vec<PathBuf>,
+    #[arg(short, long, help = "The corpus directory")]
+    pub corpus: Vec<PathBuf>,
+    #[arg(short = 'x', long, help = "The objectives (crashes) directory")]
+    pub objectives: Vec<PathBuf>,
+    #[arg(
+        short,
+        long,
+        help = "Group objectives by this field of their metadata, instead of by their content"
+    )]
+    pub bucket_key: Option<String>,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "html",
+        help = "The report format"
+    )]
+    pub format: Format,
+    #[arg(short, long, help = "The report file")]
+    pub output: PathBuf,
+}
+
+fn main() {
+    env_logger::init();
+    let opts = Opt::parse();
+
+    let mut campaign = Campaign::new();
+    if let Some(bucket_key) = &opts.bucket_key {
+        campaign = campaign.with_bucket_key(bucket_key);
+    }
+
+    for (idx, plot_data) in opts.plot_data.iter().enumerate() {
+        campaign
+            .add_plot_data(&format!("client {idx}"), plot_data)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "Failed to read plot_data at {}: {err:?}",
+                    plot_data.display()
+                )
+            });
+    }
+    for json_log in &opts.json_log {
+        campaign.add_json_log(json_log).unwrap_or_else(|err| {
+            panic!(
+                "Failed to read monitor log at {}: {err:?}",
+                json_log.display()
+            )
+        });
+    }
+    for corpus in &opts.corpus {
+        campaign
+            .add_corpus_dir(corpus)
+            .unwrap_or_else(|err| panic!("Failed to read corpus at {}: {err:?}", corpus.display()));
+    }
+    for objectives in &opts.objectives {
+        campaign
+            .add_objectives_dir(objectives)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "Failed to read objectives at {}: {err:?}",
+                    objectives.display()
+                )
+            });
+    }
+
+    let report = match opts.format {
+        Format::Html => campaign.to_html(),
+        Format::Markdown => campaign.to_markdown(),
+    };
+    fs::write(&opts.output, report).expect("Failed to write the report");
+    println!("Wrote the report to {}", opts.output.display());
+}
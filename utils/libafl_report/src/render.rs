@@ -0,0 +1,390 @@
+//! Render a [`Campaign`] as HTML or Markdown
+
+use core::fmt::Write;
+use std::time::Duration;
+
+use libafl_bolts::format_duration_hms;
+
+use crate::{
+    chart::{escape, line_chart, pretty_number, Series},
+    Campaign, Sample,
+};
+
+/// The amount of rows of the timeline in Markdown reports
+const MARKDOWN_TIMELINE_ROWS: usize = 12;
+
+/// The rows of the table of each client
+struct ClientRow {
+    name: String,
+    run_time: Duration,
+    executions: u64,
+    execs_per_sec: f64,
+    corpus: u64,
+    objectives: u64,
+}
+
+/// The rows of the objective timeline
+struct BucketRow {
+    first_seen: Option<Duration>,
+    count: usize,
+    first: String,
+    size: u64,
+    key: String,
+}
+
+/// The numbers shown at the top of a report
+#[expect(clippy::cast_precision_loss)]
+fn summary(campaign: &Campaign, global: &[Sample]) -> Vec<(&'static str, String)> {
+    let last = global.last().copied().unwrap_or_default();
+    let buckets = campaign.buckets().len();
+    let objectives = campaign.objectives.len().max(last.objectives as usize);
+    let mut summary = vec![
+        ("Run time", format_duration_hms(&last.time)),
+        ("Executions", pretty_number(last.executions as f64)),
+        ("Exec/sec (last)", pretty_number(last.execs_per_sec)),
+        (
+            "Corpus",
+            last.corpus.max(campaign.corpus.len() as u64).to_string(),
+        ),
+        ("Objectives", objectives.to_string()),
+        ("Unique objectives", buckets.to_string()),
+        ("Clients", campaign.clients.len().to_string()),
+    ];
+    if let (Some(hit), Some(total)) = (last.edges_hit, last.edges_total) {
+        let percent = if total == 0 {
+            0.0
+        } else {
+            hit as f64 * 100.0 / total as f64
+        };
+        summary.push(("Edges", format!("{hit}/{total} ({percent:.2}%)")));
+    }
+    summary
+}
+
+fn client_rows(campaign: &Campaign) -> Vec<ClientRow> {
+    campaign
+        .clients
+        .iter()
+        .map(|client| {
+            let last = client.samples.last().copied().unwrap_or_default();
+            ClientRow {
+                name: client.name.clone(),
+                run_time: last.time,
+                executions: last.executions,
+                execs_per_sec: client.average_execs_per_sec(),
+                corpus: last.corpus,
+                objectives: last.objectives,
+            }
+        })
+        .collect()
+}
+
+fn bucket_rows(campaign: &Campaign) -> Vec<BucketRow> {
+    campaign
+        .buckets()
+        .into_iter()
+        .map(|bucket| {
+            let first = bucket.objectives[0];
+            BucketRow {
+                first_seen: campaign.relative_time(&first.file),
+                count: bucket.objectives.len(),
+                first: first.file.name.clone(),
+                size: first.file.size,
+                key: bucket.key.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn format_time(time: Option<Duration>) -> String {
+    time.map_or_else(|| "-".to_string(), |time| format_duration_hms(&time))
+}
+
+/// The charts of the HTML report
+#[expect(clippy::cast_precision_loss)]
+fn charts(campaign: &Campaign, global: &[Sample]) -> Vec<String> {
+    let series = |name: &str, value: fn(&Sample) -> Option<f64>, samples: &[Sample]| {
+        Series::new(
+            name,
+            samples
+                .iter()
+                .filter_map(|sample| Some((sample.time, value(sample)?)))
+                .collect(),
+        )
+    };
+
+    let mut charts = vec![];
+    charts.extend(line_chart(
+        "Edges over time",
+        &[series(
+            "",
+            |sample| sample.edges_hit.map(|hit| hit as f64),
+            global,
+        )],
+    ));
+    charts.extend(line_chart(
+        "Executions over time",
+        &[series("", |sample| Some(sample.executions as f64), global)],
+    ));
+
+    // The corpus growth, from the stats and from the corpus directory
+    let mut corpus_series = vec![series("stats", |sample| Some(sample.corpus as f64), global)];
+    let corpus_dir: Vec<(Duration, f64)> = campaign
+        .corpus
+        .iter()
+        .filter_map(|file| campaign.relative_time(file))
+        .enumerate()
+        .map(|(idx, time)| (time, (idx + 1) as f64))
+        .collect();
+    if !corpus_dir.is_empty() {
+        corpus_series.push(Series::new("corpus directory", corpus_dir));
+    }
+    charts.extend(line_chart("Corpus growth", &corpus_series));
+
+    // The objective timeline, with and without duplicates
+    let mut all = vec![];
+    let mut unique = vec![];
+    let mut seen = vec![];
+    let mut objectives: Vec<_> = campaign.objectives.iter().collect();
+    objectives.sort_by_key(|objective| objective.file.modified);
+    for objective in objectives {
+        let Some(time) = campaign.relative_time(&objective.file) else {
+            continue;
+        };
+        all.push((time, (all.len() + 1) as f64));
+        if !seen.contains(&&objective.bucket) {
+            seen.push(&objective.bucket);
+            unique.push((time, seen.len() as f64));
+        }
+    }
+    charts.extend(line_chart(
+        "Objectives over time",
+        &[Series::new("all", all), Series::new("unique", unique)],
+    ));
+
+    let clients: Vec<Series> = campaign
+        .clients
+        .iter()
+        .map(|client| {
+            series(
+                &client.name,
+                |sample| Some(sample.execs_per_sec),
+                &client.samples,
+            )
+        })
+        .collect();
+    charts.extend(line_chart("Exec/sec per client", &clients));
+
+    charts
+}
+
+/// Renders `campaign` as a self-contained HTML page
+#[must_use]
+#[expect(clippy::cast_precision_loss)]
+pub fn html(campaign: &Campaign) -> String {
+    let global = campaign.global_samples();
+    let mut out = String::new();
+    out.push_str(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>LibAFL Campaign Report</title>
+<style>
+  body { font-family: sans-serif; margin: 1em 2em; color: #222; }
+  table { border-collapse: collapse; margin-bottom: 1em; }
+  th, td { border: 1px solid #ddd; padding: 0.3em 0.6em; text-align: right; }
+  th { background: #eee; }
+  td.name { text-align: left; font-family: monospace; }
+  .charts { display: flex; flex-wrap: wrap; gap: 1em; }
+</style>
+</head>
+<body>
+<h1>LibAFL Campaign Report</h1>
+"#,
+    );
+
+    out.push_str("<h2>Summary</h2>\n<table>\n");
+    for (name, value) in summary(campaign, &global) {
+        writeln!(out, "<tr><th>{name}</th><td>{}</td></tr>", escape(&value)).unwrap();
+    }
+    out.push_str("</table>\n");
+
+    let charts = charts(campaign, &global);
+    if !charts.is_empty() {
+        out.push_str("<h2>Over time</h2>\n<div class=\"charts\">\n");
+        for chart in charts {
+            writeln!(out, "<div>{chart}</div>").unwrap();
+        }
+        out.push_str("</div>\n");
+    }
+
+    let clients = client_rows(campaign);
+    if !clients.is_empty() {
+        out.push_str("<h2>Clients</h2>\n<table>\n<tr><th>Client</th><th>Run time</th><th>Executions</th><th>Avg exec/sec</th><th>Corpus</th><th>Objectives</th></tr>\n");
+        for client in clients {
+            writeln!(
+                out,
+                "<tr><td class=\"name\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&client.name),
+                format_duration_hms(&client.run_time),
+                pretty_number(client.executions as f64),
+                pretty_number(client.execs_per_sec),
+                client.corpus,
+                client.objectives
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n");
+    }
+
+    let buckets = bucket_rows(campaign);
+    if !buckets.is_empty() {
+        out.push_str("<h2>Objectives</h2>\n<table>\n<tr><th>First seen</th><th>Duplicates</th><th>First objective</th><th>Size</th><th>Bucket</th></tr>\n");
+        for bucket in buckets {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td class=\"name\">{}</td><td>{}</td><td class=\"name\">{}</td></tr>",
+                format_time(bucket.first_seen),
+                bucket.count,
+                escape(&bucket.first),
+                bucket.size,
+                escape(&bucket.key)
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Escapes `text` for a Markdown table cell
+fn md(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// Renders `campaign` as Markdown
+#[must_use]
+#[expect(clippy::cast_precision_loss)]
+pub fn markdown(campaign: &Campaign) -> String {
+    let global = campaign.global_samples();
+    let mut out = String::from("# LibAFL Campaign Report\n\n## Summary\n\n| | |\n|---|---|\n");
+    for (name, value) in summary(campaign, &global) {
+        writeln!(out, "| {name} | {} |", md(&value)).unwrap();
+    }
+
+    if !global.is_empty() {
+        out.push_str("\n## Timeline\n\n| Time | Corpus | Edges | Executions | Exec/sec | Objectives |\n|---|---|---|---|---|---|\n");
+        let step = global.len().div_ceil(MARKDOWN_TIMELINE_ROWS).max(1);
+        let mut rows: Vec<&Sample> = global.iter().step_by(step).collect();
+        if let Some(last) = global.last() {
+            if !core::ptr::eq(*rows.last().unwrap(), last) {
+                rows.push(last);
+            }
+        }
+        for sample in rows {
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                format_duration_hms(&sample.time),
+                sample.corpus,
+                sample
+                    .edges_hit
+                    .map_or_else(|| "-".to_string(), |hit| hit.to_string()),
+                pretty_number(sample.executions as f64),
+                pretty_number(sample.execs_per_sec),
+                sample.objectives
+            )
+            .unwrap();
+        }
+    }
+
+    let clients = client_rows(campaign);
+    if !clients.is_empty() {
+        out.push_str("\n## Clients\n\n| Client | Run time | Executions | Avg exec/sec | Corpus | Objectives |\n|---|---|---|---|---|---|\n");
+        for client in clients {
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                md(&client.name),
+                format_duration_hms(&client.run_time),
+                pretty_number(client.executions as f64),
+                pretty_number(client.execs_per_sec),
+                client.corpus,
+                client.objectives
+            )
+            .unwrap();
+        }
+    }
+
+    let buckets = bucket_rows(campaign);
+    if !buckets.is_empty() {
+        out.push_str("\n## Objectives\n\n| First seen | Duplicates | First objective | Size | Bucket |\n|---|---|---|---|---|\n");
+        for bucket in buckets {
+            writeln!(
+                out,
+                "| {} | {} | `{}` | {} | `{}` |",
+                format_time(bucket.first_seen),
+                bucket.count,
+                md(&bucket.first),
+                bucket.size,
+                md(&bucket.key)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use crate::{Campaign, ClientSeries, FileEntry, Objective, Sample};
+
+    #[test]
+    fn test_render() {
+        let start = SystemTime::UNIX_EPOCH;
+        let objective = |name: &str, secs, bucket: &str| Objective {
+            file: FileEntry {
+                name: name.to_string(),
+                size: 4,
+                modified: Some(start + Duration::from_secs(secs)),
+            },
+            bucket: bucket.to_string(),
+        };
+        let mut campaign = Campaign::new();
+        campaign.start = Some(start);
+        campaign.clients.push(ClientSeries {
+            name: "client 0".to_string(),
+            samples: (1..=20)
+                .map(|secs| Sample {
+                    time: Duration::from_secs(secs),
+                    executions: secs * 100,
+                    edges_hit: Some(secs),
+                    edges_total: Some(100),
+                    ..Sample::default()
+                })
+                .collect(),
+        });
+        campaign.objectives = vec![
+            objective("crash_b", 8, "b"),
+            objective("crash_a", 5, "a"),
+            objective("crash_a2", 9, "a"),
+        ];
+
+        let buckets = campaign.buckets();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].key, "a");
+        assert_eq!(buckets[0].objectives.len(), 2);
+
+        let html = campaign.to_html();
+        assert!(html.contains("<svg"));
+        assert!(html.contains("crash_a"));
+        let markdown = campaign.to_markdown();
+        assert!(markdown.contains("| Edges | 20/100 (20.00%) |"));
+        assert!(markdown.contains("| 0h-0m-20s |"));
+    }
+}
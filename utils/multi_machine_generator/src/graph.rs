@@ -17,6 +17,8 @@ pub struct MultiMachineNode {
 pub struct MultiMachineNodeConfig {
     addr: String,
     parent: Option<String>,
+    /// The ancestors beyond the parent, closest first, to re-attach to if the parent is lost
+    fallback_parents: Vec<String>,
     port: u16,
 }
 
@@ -120,9 +122,19 @@ impl MultiMachineTree {
                 .get_parent(node_idx)
                 .map(|parent_idx| self.graph[parent_idx].addr.clone());
 
+            let mut fallback_parents = Vec::new();
+            let mut ancestor = self
+                .get_parent(node_idx)
+                .and_then(|parent_idx| self.get_parent(parent_idx));
+            while let Some(ancestor_idx) = ancestor {
+                fallback_parents.push(self.graph[ancestor_idx].addr.clone());
+                ancestor = self.get_parent(ancestor_idx);
+            }
+
             node_configs.push(MultiMachineNodeConfig {
                 addr: node.addr.clone(),
                 parent,
+                fallback_parents,
                 port: default_port,
             });
         }